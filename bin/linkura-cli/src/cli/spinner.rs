@@ -1,18 +1,111 @@
 use indicatif::{ProgressBar, ProgressStyle};
+use linkura_i18n::t;
+use std::sync::Mutex;
+use std::time::Duration;
 
 #[allow(dead_code)]
 pub const TICK_CLOCK: [&str; 12] = [
     "🕐 ", "🕑 ", "🕒 ", "🕓 ", "🕔 ", "🕕 ", "🕖 ", "🕗 ", "🕘 ", "🕙 ", "🕚 ", "🕛 ",
 ];
 
-#[derive(Debug)]
+/// Where `SpinnerManager` draws its shared spinner
+pub trait SpinnerBackend: Send + Sync {
+    /// Show the spinner with `message`, reusing the visible one if any
+    fn show(&self, message: &str);
+    /// Hide the spinner
+    fn clear(&self);
+}
+
+/// Terminal spinner drawn by indicatif
+#[derive(Debug, Default)]
+pub struct TerminalSpinner {
+    bar: Mutex<Option<ProgressBar>>,
+}
+
+impl SpinnerBackend for TerminalSpinner {
+    fn show(&self, message: &str) {
+        let mut bar = self.bar.lock().unwrap();
+        let bar = bar.get_or_insert_with(|| {
+            let pb = ProgressBar::new_spinner();
+            pb.set_style(
+                ProgressStyle::default_spinner()
+                    .template("{spinner:.green} {msg}")
+                    .unwrap(),
+            );
+            pb.enable_steady_tick(Duration::from_millis(100));
+            pb
+        });
+        bar.set_message(message.to_string());
+    }
+
+    fn clear(&self) {
+        if let Some(bar) = self.bar.lock().unwrap().take() {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+/// Spinners of the cli, nothing is drawn in quiet mode
+///
+/// Convention: every network call that may take longer than ~1s runs inside
+/// `with_spinner`, with the endpoint name as message. `start`/`finish` nest, a finished
+/// spinner restores the message of the outer one and the bar is only cleared when the
+/// outermost one finishes, so sequential and nested spinners do not flicker.
 pub struct SpinnerManager {
     quiet: bool,
+    backend: Box<dyn SpinnerBackend>,
+    messages: Mutex<Vec<String>>,
+}
+
+impl std::fmt::Debug for SpinnerManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpinnerManager")
+            .field("quiet", &self.quiet)
+            .field("messages", &self.messages)
+            .finish()
+    }
 }
 
 impl SpinnerManager {
     pub fn new(quiet: bool) -> Self {
-        Self { quiet }
+        Self::with_backend(quiet, Box::new(TerminalSpinner::default()))
+    }
+
+    pub fn with_backend(quiet: bool, backend: Box<dyn SpinnerBackend>) -> Self {
+        Self {
+            quiet,
+            backend,
+            messages: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Start a (nested) spinner
+    pub fn start(&self, message: &str) {
+        self.messages.lock().unwrap().push(message.to_string());
+        if !self.quiet {
+            self.backend.show(message);
+        }
+    }
+
+    /// Finish the innermost spinner
+    pub fn finish(&self) {
+        let mut messages = self.messages.lock().unwrap();
+        messages.pop();
+        if self.quiet {
+            return;
+        }
+        match messages.last() {
+            Some(outer) => self.backend.show(outer),
+            None => self.backend.clear(),
+        }
+    }
+
+    /// Await `future` behind a spinner naming `endpoint`
+    pub async fn with_spinner<F: Future>(&self, endpoint: &str, future: F) -> F::Output {
+        self.start(&t!("linkura.cli.spinner.request", endpoint = endpoint));
+        let output = future.await;
+        self.finish();
+        output
     }
 
     pub fn create_spinner(&self, message: &str) -> ProgressBar {
@@ -50,3 +143,55 @@ impl SpinnerManager {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// Records what would be drawn
+    #[derive(Default, Clone)]
+    struct CapturingSpinner {
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl SpinnerBackend for CapturingSpinner {
+        fn show(&self, message: &str) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("show {}", message));
+        }
+
+        fn clear(&self) {
+            self.events.lock().unwrap().push("clear".to_string());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_quiet_mode_suppresses_spinners() {
+        let backend = CapturingSpinner::default();
+        let manager = SpinnerManager::with_backend(true, Box::new(backend.clone()));
+        manager.start("outer");
+        let value = manager
+            .with_spinner("/archive/get_home", async { 42 })
+            .await;
+        manager.finish();
+        assert_eq!(value, 42);
+        assert!(backend.events.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_nested_spinners() {
+        let backend = CapturingSpinner::default();
+        let manager = SpinnerManager::with_backend(false, Box::new(backend.clone()));
+        manager.start("outer");
+        manager.start("inner");
+        manager.finish();
+        manager.finish();
+        assert_eq!(
+            *backend.events.lock().unwrap(),
+            vec!["show outer", "show inner", "show outer", "clear"]
+        );
+    }
+}
@@ -1,90 +1,1095 @@
 use crate::config::Global;
 use anyhow::Result;
-use clap::{Args as ClapArgs, Subcommand};
-use linkura_api::ArchiveListOptions;
+use clap::{Args as ClapArgs, Subcommand, ValueEnum};
+use linkura_api::model::{
+    FesliveLobbyRequest, GetFesTimelineDataRequest, SetFesCameraRequest, WithliveCommentRequest,
+};
+use linkura_api::{
+    ARCHIVE_IMAGES_MANIFEST, ArchiveListOptions, ArchiveResource, BASE_CLIENT_VERSION,
+    VersionsAccepted,
+};
+use linkura_common::parse::HumanDuration;
+use std::path::PathBuf;
 
 use linkura_i18n::t;
 
 #[derive(Debug, Clone, ClapArgs)]
 pub struct ArgsAPI {
-    #[clap(short('o'), long = "output", value_name = "OUTPUT", help = t!("linkura.command.api.args.output.about").to_string())]
-    pub output: Option<String>,
+    #[command(flatten)]
+    pub output: OutputArgs,
     #[command(subcommand)]
     pub command: Commands,
 }
 
+/// Options shared by every api subcommand
+#[derive(Debug, Clone, ClapArgs)]
+pub struct OutputArgs {
+    #[clap(short('o'), long = "output", value_name = "OUTPUT", global = true, help = t!("linkura.command.api.args.output.about").to_string())]
+    pub output: Option<String>,
+    #[clap(short('f'), long = "format", value_name = "FORMAT", value_enum, default_value_t = OutputFormat::Pretty, global = true, help = t!("linkura.command.api.args.format.about").to_string())]
+    pub format: OutputFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Single line json
+    Json,
+    /// Indented json
+    Pretty,
+    /// Aligned columns for lists, other results are printed as indented json
+    Table,
+}
+
 #[derive(Subcommand, Debug, Clone)]
 pub enum Commands {
-    #[command(about = t!("linkura.command.api.subcommand.archive.about").to_string())]
+    #[command(
+        about = t!("linkura.command.api.subcommand.archive.about").to_string(),
+        long_about = with_examples(
+            t!("linkura.command.api.subcommand.archive.about").to_string(),
+            &[
+                "linkura-cli api archive list -l 10",
+                "linkura-cli api archive details -i <ARCHIVE_ID> -t 1",
+            ],
+        ),
+    )]
     Archive(ArgsArchive),
-    #[command(about = t!("linkura.command.api.subcommand.archive_details.about").to_string())]
+    #[command(
+        about = t!("linkura.command.api.subcommand.fes.about").to_string(),
+        long_about = with_examples(
+            t!("linkura.command.api.subcommand.fes.about").to_string(),
+            &["linkura-cli api fes enter <LIVE_ID>", "linkura-cli api fes token <LIVE_ID>"],
+        ),
+    )]
+    Fes(ArgsFes),
+    #[command(
+        about = t!("linkura.command.api.subcommand.with.about").to_string(),
+        long_about = with_examples(
+            t!("linkura.command.api.subcommand.with.about").to_string(),
+            &[
+                "linkura-cli api with enter <LIVE_ID>",
+                "linkura-cli api with token <LIVE_ID>",
+                "linkura-cli api with react <LIVE_ID> -m \"hello\"",
+            ],
+        ),
+    )]
+    With(ArgsWith),
+    #[command(
+        about = t!("linkura.command.api.subcommand.plan.about").to_string(),
+        long_about = with_examples(
+            t!("linkura.command.api.subcommand.plan.about").to_string(),
+            &["linkura-cli api plan list"],
+        ),
+    )]
+    Plan(ArgsPlan),
+    #[command(
+        about = t!("linkura.command.api.subcommand.subscription.about").to_string(),
+        long_about = with_examples(
+            t!("linkura.command.api.subcommand.subscription.about").to_string(),
+            &["linkura-cli api subscription", "linkura-cli api subscription --format json"],
+        ),
+    )]
+    Subscription,
+    #[command(
+        about = t!("linkura.command.api.subcommand.friends.about").to_string(),
+        long_about = with_examples(
+            t!("linkura.command.api.subcommand.friends.about").to_string(),
+            &["linkura-cli api friends list -f table", "linkura-cli api friends list --redact -o friends.json"],
+        ),
+    )]
+    Friends(ArgsFriends),
+    #[command(
+        about = t!("linkura.command.api.subcommand.probe_versions.about").to_string(),
+        long_about = with_examples(
+            t!("linkura.command.api.subcommand.probe_versions.about").to_string(),
+            &["linkura-cli api probe-versions -f table", "linkura-cli api probe-versions --pinned 3.1.0 --store 3.2.0"],
+        ),
+    )]
+    ProbeVersions(ArgsProbeVersions),
+    /// Deprecated, use `archive details`
+    #[command(hide = true)]
     ArchiveDetails(ArgsArchiveDetails),
 }
 
 #[derive(Debug, Clone, ClapArgs)]
 pub struct ArgsArchive {
+    #[command(subcommand)]
+    pub command: Option<ArchiveCommands>,
+    /// Deprecated, use `archive list --limit`
+    #[clap(short('l'), long = "limit", value_name = "LIMIT", hide = true)]
+    pub limit: Option<u32>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ArchiveCommands {
+    #[command(
+        about = t!("linkura.command.api.subcommand.archive_list.about").to_string(),
+        long_about = with_examples(
+            t!("linkura.command.api.subcommand.archive_list.about").to_string(),
+            &["linkura-cli api archive list -l 10", "linkura-cli api archive list --live-type 2"],
+        ),
+    )]
+    List(ArgsArchiveList),
+    #[command(
+        about = t!("linkura.command.api.subcommand.archive_details.about").to_string(),
+        long_about = with_examples(
+            t!("linkura.command.api.subcommand.archive_details.about").to_string(),
+            &[
+                "linkura-cli api archive details -i <ARCHIVE_ID> -t 2 -o details.json",
+                "linkura-cli api archive details -i <ARCHIVE_ID> -t 1 --wait-for replay --timeout 30m",
+            ],
+        ),
+    )]
+    Details(ArgsArchiveDetails),
+    #[command(
+        about = t!("linkura.command.api.subcommand.archive_comments.about").to_string(),
+        long_about = with_examples(
+            t!("linkura.command.api.subcommand.archive_comments.about").to_string(),
+            &["linkura-cli api archive comments -i <ARCHIVE_ID> -t 1 --at 600"],
+        ),
+    )]
+    Comments(ArgsArchiveComments),
+    #[command(
+        about = t!("linkura.command.api.subcommand.archive_position.about").to_string(),
+        long_about = with_examples(
+            t!("linkura.command.api.subcommand.archive_position.about").to_string(),
+            &["linkura-cli api archive position -i <LIVE_ID> --camera-type 1 --focus-character 1021"],
+        ),
+    )]
+    Position(ArgsArchivePosition),
+    #[command(
+        about = t!("linkura.command.api.subcommand.archive_images.about").to_string(),
+        long_about = with_examples(
+            t!("linkura.command.api.subcommand.archive_images.about").to_string(),
+            &[
+                "linkura-cli api archive images -i <ARCHIVE_ID> -t 2",
+                "linkura-cli api archive images -i <ARCHIVE_ID> -t 1 --download images",
+            ],
+        ),
+    )]
+    Images(ArgsArchiveImages),
+}
+
+#[derive(Debug, Clone, ClapArgs)]
+pub struct ArgsArchiveList {
     #[clap(short('l'), long = "limit", value_name = "LIMIT", help = t!("linkura.command.api.subcommand.archive.args.limit.about").to_string())]
     pub limit: Option<u32>,
+    #[clap(long = "live-type", value_name = "LIVE TYPE", help = t!("linkura.command.api.subcommand.archive_details.args.type.about").to_string())]
+    pub live_type: Option<i32>,
 }
 
 #[derive(Debug, Clone, ClapArgs)]
 pub struct ArgsArchiveDetails {
     #[clap(short('i'), long = "id", value_name = "ID", help = t!("linkura.command.api.subcommand.archive_details.args.id.about").to_string())]
-    pub id: String,
+    pub id: Option<String>,
     #[clap(short('t'), long = "type", value_name = "LIVE TYPE", help = t!("linkura.command.api.subcommand.archive_details.args.type.about").to_string())]
     pub live_type: u8,
+    #[clap(long = "wait-for", value_name = "RESOURCE", value_enum, help = t!("linkura.command.api.subcommand.archive_details.args.wait_for.about").to_string())]
+    pub wait_for: Option<WaitFor>,
+    #[clap(long = "timeout", value_name = "DURATION", default_value = "10m", requires = "wait_for", help = t!("linkura.command.api.subcommand.archive_details.args.timeout.about").to_string())]
+    pub timeout: HumanDuration,
+}
+
+/// Archive parts `archive details --wait-for` waits for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum WaitFor {
+    Replay,
+    Video,
+    Comments,
+    All,
+}
+
+impl WaitFor {
+    pub fn resources(self) -> Vec<ArchiveResource> {
+        match self {
+            Self::Replay => vec![ArchiveResource::Replay],
+            Self::Video => vec![ArchiveResource::Video],
+            Self::Comments => vec![ArchiveResource::Comments],
+            Self::All => ArchiveResource::ALL.to_vec(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, ClapArgs)]
+pub struct ArgsArchiveComments {
+    #[clap(short('i'), long = "id", value_name = "ID", help = t!("linkura.command.api.subcommand.archive_details.args.id.about").to_string())]
+    pub id: Option<String>,
+    #[clap(short('t'), long = "type", value_name = "LIVE TYPE", help = t!("linkura.command.api.subcommand.archive_details.args.type.about").to_string())]
+    pub live_type: u8,
+    #[clap(long = "at", value_name = "SECONDS", default_value = "0", help = t!("linkura.command.api.subcommand.archive_comments.args.at.about").to_string())]
+    pub at: i32,
+}
+
+#[derive(Debug, Clone, ClapArgs)]
+pub struct ArgsArchivePosition {
+    #[clap(short('i'), long = "id", value_name = "ID", help = t!("linkura.command.api.subcommand.archive_details.args.id.about").to_string())]
+    pub id: Option<String>,
+    #[clap(long = "camera-type", value_name = "CAMERA TYPE", help = t!("linkura.command.api.subcommand.archive_position.args.camera_type.about").to_string())]
+    pub camera_type: i32,
+    #[clap(long = "focus-character", value_name = "CHARACTER ID", help = t!("linkura.command.api.subcommand.archive_position.args.focus_character.about").to_string())]
+    pub focus_character: Option<i32>,
+}
+
+#[derive(Debug, Clone, ClapArgs)]
+pub struct ArgsArchiveImages {
+    #[clap(short('i'), long = "id", value_name = "ID", help = t!("linkura.command.api.subcommand.archive_details.args.id.about").to_string())]
+    pub id: Option<String>,
+    #[clap(short('t'), long = "type", value_name = "LIVE TYPE", help = t!("linkura.command.api.subcommand.archive_details.args.type.about").to_string())]
+    pub live_type: u8,
+    #[clap(long = "download", value_name = "DIR", help = t!("linkura.command.api.subcommand.archive_images.args.download.about").to_string())]
+    pub download: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, ClapArgs)]
+pub struct ArgsFes {
+    #[command(subcommand)]
+    pub command: FesCommands,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum FesCommands {
+    #[command(about = t!("linkura.command.api.subcommand.fes_enter.about").to_string())]
+    Enter(ArgsLive),
+    #[command(about = t!("linkura.command.api.subcommand.fes_token.about").to_string())]
+    Token(ArgsLive),
+}
+
+#[derive(Debug, Clone, ClapArgs)]
+pub struct ArgsWith {
+    #[command(subcommand)]
+    pub command: WithCommands,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum WithCommands {
+    #[command(about = t!("linkura.command.api.subcommand.with_enter.about").to_string())]
+    Enter(ArgsLive),
+    #[command(about = t!("linkura.command.api.subcommand.with_token.about").to_string())]
+    Token(ArgsLive),
+    #[command(about = t!("linkura.command.api.subcommand.with_react.about").to_string())]
+    React(ArgsWithReact),
+}
+
+#[derive(Debug, Clone, ClapArgs)]
+pub struct ArgsLive {
+    #[clap(value_name = "LIVE_ID", help = t!("linkura.command.api.args.live_id.about").to_string())]
+    pub live_id: Option<String>,
+}
+
+#[derive(Debug, Clone, ClapArgs)]
+pub struct ArgsWithReact {
+    #[clap(value_name = "LIVE_ID", help = t!("linkura.command.api.args.live_id.about").to_string())]
+    pub live_id: Option<String>,
+    #[clap(short('m'), long = "message", value_name = "MESSAGE", help = t!("linkura.command.api.subcommand.with_react.args.message.about").to_string())]
+    pub message: String,
+}
+
+#[derive(Debug, Clone, ClapArgs)]
+pub struct ArgsFriends {
+    #[command(subcommand)]
+    pub command: FriendsCommands,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum FriendsCommands {
+    #[command(about = t!("linkura.command.api.subcommand.friends_list.about").to_string())]
+    List(ArgsFriendsList),
+}
+
+#[derive(Debug, Clone, ClapArgs)]
+pub struct ArgsFriendsList {
+    #[clap(long = "redact", help = t!("linkura.command.api.subcommand.friends_list.args.redact.about").to_string())]
+    pub redact: bool,
+}
+
+#[derive(Debug, Clone, ClapArgs)]
+pub struct ArgsProbeVersions {
+    #[clap(long = "pinned", value_name = "VERSION", help = t!("linkura.command.api.subcommand.probe_versions.args.pinned.about").to_string())]
+    pub pinned: Option<String>,
+    #[clap(long = "store", value_name = "VERSION", help = t!("linkura.command.api.subcommand.probe_versions.args.store.about").to_string())]
+    pub store: Option<String>,
+}
+
+#[derive(Debug, Clone, ClapArgs)]
+pub struct ArgsPlan {
+    #[command(subcommand)]
+    pub command: PlanCommands,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum PlanCommands {
+    #[command(about = t!("linkura.command.api.subcommand.plan_list.about").to_string())]
+    List,
+}
+
+impl Commands {
+    /// Whether the command takes an archive/live id, i.e. accepts the top-level `--id`
+    pub fn consumes_id(&self) -> bool {
+        match self {
+            Commands::Archive(archive_args) => matches!(
+                archive_args.command,
+                Some(
+                    ArchiveCommands::Details(_)
+                        | ArchiveCommands::Comments(_)
+                        | ArchiveCommands::Position(_)
+                        | ArchiveCommands::Images(_)
+                )
+            ),
+            Commands::Fes(_) | Commands::With(_) | Commands::ArchiveDetails(_) => true,
+            Commands::Plan(_)
+            | Commands::Subscription
+            | Commands::Friends(_)
+            | Commands::ProbeVersions(_) => false,
+        }
+    }
+}
+
+/// The subcommand's own id takes precedence over the top-level `--id`
+pub fn resolve_id(own: Option<&str>, top_level: Option<&str>) -> Result<String> {
+    own.or(top_level)
+        .map(str::to_string)
+        .ok_or_else(|| anyhow::anyhow!(t!("linkura.command.api.id.missing")))
+}
+
+fn with_examples(about: String, examples: &[&str]) -> String {
+    let mut text = about;
+    text.push_str("\n\nExamples:");
+    for example in examples {
+        text.push_str("\n  ");
+        text.push_str(example);
+    }
+    text
+}
+
+fn deprecated(old: &str, new: &str) {
+    tracing::warn!(
+        "{}",
+        t!("linkura.command.api.deprecated", old = old, new = new)
+    );
 }
 
 pub async fn run(ctx: &Global, args: &ArgsAPI) -> Result<()> {
     let api_client = &ctx.api_client;
-    let save_json = &args.output.clone().unwrap_or_default();
-    match &args.command {
-        Commands::Archive(archive_args) => {
-            let archives = api_client
-                .high_level()
-                .get_archive_list(ArchiveListOptions {
-                    limit: archive_args.limit,
+    let spinner = &ctx.spinner_manager;
+    let top_level_id = ctx.args.id.as_deref();
+    let value = match &args.command {
+        Commands::Archive(archive_args) => match &archive_args.command {
+            Some(ArchiveCommands::List(list_args)) => {
+                spinner
+                    .with_spinner(
+                        "/archive/get_archive_list",
+                        api_client
+                            .high_level()
+                            .get_archive_list(ArchiveListOptions {
+                                limit: list_args.limit,
+                                live_type: list_args.live_type,
+                                ..Default::default()
+                            }),
+                    )
+                    .await?
+            }
+            Some(ArchiveCommands::Details(details_args)) => {
+                archive_details(ctx, details_args, top_level_id).await?
+            }
+            Some(ArchiveCommands::Comments(comments_args)) => {
+                let id = resolve_id(comments_args.id.as_deref(), top_level_id)?;
+                match comments_args.live_type {
+                    1 => {
+                        let request = GetFesTimelineDataRequest {
+                            archives_id: Some(id),
+                            play_time_second: Some(comments_args.at),
+                            ..Default::default()
+                        };
+                        let body = spinner
+                            .with_spinner(
+                                "/archive/get_fes_timeline_data",
+                                api_client.raw().archive().get_fes_timeline_data(&request),
+                            )
+                            .await?;
+                        serde_json::to_value(body)?
+                    }
+                    2 => {
+                        let body = spinner
+                            .with_spinner(
+                                "/archive/withlive_info",
+                                api_client.raw().archive().withlive_info(
+                                    id,
+                                    Some(comments_args.at),
+                                    None,
+                                ),
+                            )
+                            .await?;
+                        serde_json::to_value(body)?
+                    }
+                    live_type => {
+                        return Err(anyhow::anyhow!("Unsupported live type: {}", live_type));
+                    }
+                }
+            }
+            Some(ArchiveCommands::Position(position_args)) => {
+                let request = SetFesCameraRequest {
+                    live_id: Some(resolve_id(position_args.id.as_deref(), top_level_id)?),
+                    camera_type: Some(position_args.camera_type),
+                    focus_character_id: position_args.focus_character,
                     ..Default::default()
-                })
-                .await?;
-            if !save_json.is_empty() {
-                std::fs::write(save_json, serde_json::to_string_pretty(&archives)?)?;
-                tracing::info!("{}", t!("linkura.command.api.archive.saved", path = save_json));
-            } else {
-                tracing::info!(
-                    "{}",
-                    t!(
-                        "linkura.command.api.archives.output",
-                        json = serde_json::to_string_pretty(&archives)?
+                };
+                let body = spinner
+                    .with_spinner(
+                        "/archive/set_fes_camera",
+                        api_client.raw().archive().set_fes_camera(&request),
                     )
-                );
+                    .await?;
+                serde_json::to_value(body)?
             }
-        }
+            Some(ArchiveCommands::Images(images_args)) => {
+                archive_images(ctx, images_args, top_level_id).await?
+            }
+            None => {
+                deprecated("api archive --limit", "api archive list --limit");
+                spinner
+                    .with_spinner(
+                        "/archive/get_archive_list",
+                        api_client
+                            .high_level()
+                            .get_archive_list(ArchiveListOptions {
+                                limit: archive_args.limit,
+                                ..Default::default()
+                            }),
+                    )
+                    .await?
+            }
+        },
         Commands::ArchiveDetails(details_args) => {
-            let live_id = &details_args.id;
-            let live_type = details_args.live_type;
-            let details = api_client
-                .high_level()
-                .get_archive_details(live_id, live_type)
+            deprecated("api archive-details", "api archive details");
+            archive_details(ctx, details_args, top_level_id).await?
+        }
+        Commands::Fes(fes_args) => match &fes_args.command {
+            FesCommands::Enter(live_args) => {
+                let live_id = resolve_id(live_args.live_id.as_deref(), top_level_id)?;
+                // enter fes lobby first
+                let lobby_request = FesliveLobbyRequest {
+                    live_id: Some(live_id.clone()),
+                    ..Default::default()
+                };
+                let _ = spinner
+                    .with_spinner(
+                        "/feslive/lobby",
+                        api_client.raw().fes_live().lobby(&lobby_request),
+                    )
+                    .await;
+                spinner
+                    .with_spinner(
+                        "/feslive/enter",
+                        api_client.high_level().get_fes_live_info(&live_id),
+                    )
+                    .await?
+            }
+            FesCommands::Token(live_args) => {
+                let live_id = resolve_id(live_args.live_id.as_deref(), top_level_id)?;
+                serde_json::Value::from(
+                    spinner
+                        .with_spinner(
+                            "/feslive/connect_token",
+                            api_client.high_level().get_fes_live_connect_token(&live_id),
+                        )
+                        .await?,
+                )
+            }
+        },
+        Commands::With(with_args) => match &with_args.command {
+            WithCommands::Enter(live_args) => {
+                let live_id = resolve_id(live_args.live_id.as_deref(), top_level_id)?;
+                spinner
+                    .with_spinner(
+                        "/withlive/enter",
+                        api_client.high_level().get_with_meets_info(&live_id),
+                    )
+                    .await?
+            }
+            WithCommands::Token(live_args) => {
+                let live_id = resolve_id(live_args.live_id.as_deref(), top_level_id)?;
+                serde_json::Value::from(
+                    spinner
+                        .with_spinner(
+                            "/withlive/connect_token",
+                            api_client
+                                .high_level()
+                                .get_with_meets_connect_token(&live_id),
+                        )
+                        .await?,
+                )
+            }
+            WithCommands::React(react_args) => {
+                let request = WithliveCommentRequest {
+                    live_id: Some(resolve_id(react_args.live_id.as_deref(), top_level_id)?),
+                    comment: Some(react_args.message.clone()),
+                    ..Default::default()
+                };
+                let body = spinner
+                    .with_spinner(
+                        "/withlive/comment",
+                        api_client.raw().with_live().comment(&request),
+                    )
+                    .await?;
+                serde_json::to_value(body)?
+            }
+        },
+        Commands::Plan(plan_args) => match &plan_args.command {
+            PlanCommands::List => {
+                spinner
+                    .with_spinner("/archive/get_home", api_client.high_level().get_plan_list())
+                    .await?
+            }
+        },
+        Commands::Subscription => {
+            let status = spinner
+                .with_spinner(
+                    "/jewel_shop/get_membership_list",
+                    api_client.high_level().get_subscription_status(),
+                )
                 .await?;
-            if !save_json.is_empty() {
-                std::fs::write(save_json, serde_json::to_string_pretty(&details)?)?;
-                tracing::info!(
-                    "{}",
-                    t!("linkura.command.api.archive_details.saved", path = save_json)
-                );
-            } else {
-                tracing::info!(
-                    "{}",
-                    t!(
-                        "linkura.command.api.archive_details.output",
-                        json = serde_json::to_string_pretty(&details)?
+            serde_json::to_value(status)?
+        }
+        Commands::Friends(friends_args) => match &friends_args.command {
+            FriendsCommands::List(list_args) => {
+                let friends = spinner
+                    .with_spinner(
+                        "/friend/get_list",
+                        api_client.high_level().get_friend_list(),
                     )
-                );
+                    .await?;
+                let friends: Vec<_> = if list_args.redact {
+                    friends
+                        .into_iter()
+                        .enumerate()
+                        .map(|(index, friend)| friend.redacted(index))
+                        .collect()
+                } else {
+                    friends
+                };
+                serde_json::to_value(friends)?
             }
-            //     tracing::info!("Archive details: {}", serde_json::to_string_pretty(&archive_details)?);
-            // }
+        },
+        Commands::ProbeVersions(probe_args) => probe_versions(ctx, probe_args).await?,
+    };
+    write_output(&args.output, &value)
+}
+
+/// Status of each probed endpoint with the pinned and the store client version
+async fn probe_versions(ctx: &Global, args: &ArgsProbeVersions) -> Result<serde_json::Value> {
+    let pinned = args
+        .pinned
+        .clone()
+        .unwrap_or_else(|| BASE_CLIENT_VERSION.to_string());
+    let store = match &args.store {
+        Some(store) => store.clone(),
+        None => match linkura_api::get_appstore_version().await {
+            Some(version) => version,
+            None => linkura_api::get_google_play_version()
+                .await
+                .ok_or_else(|| {
+                    anyhow::anyhow!(t!("linkura.command.api.probe_versions.store.missing"))
+                })?,
+        },
+    };
+    tracing::info!(
+        "{}",
+        t!(
+            "linkura.command.api.probe_versions.versions",
+            pinned = pinned,
+            store = store
+        )
+    );
+    let probes = ctx
+        .spinner_manager
+        .with_spinner(
+            "probe versions",
+            ctx.api_client.high_level().probe_versions(&pinned, &store),
+        )
+        .await?;
+    if probes.iter().any(|probe| {
+        matches!(
+            probe.accepts,
+            VersionsAccepted::Pinned | VersionsAccepted::Store
+        )
+    }) {
+        tracing::info!(
+            "{}",
+            t!(
+                "linkura.command.api.probe_versions.hint",
+                path = ctx
+                    .config_manager
+                    .get_header_overrides_path()
+                    .display()
+                    .to_string()
+            )
+        );
+    }
+    Ok(serde_json::to_value(probes)?)
+}
+
+/// Details with the `availability` summary, waiting for `--wait-for` resources first
+async fn archive_details(
+    ctx: &Global,
+    args: &ArgsArchiveDetails,
+    top_level_id: Option<&str>,
+) -> Result<serde_json::Value> {
+    let id = resolve_id(args.id.as_deref(), top_level_id)?;
+    let high_level = ctx.api_client.high_level();
+    let endpoint = archive_details_endpoint(args.live_type);
+    let details = match args.wait_for {
+        Some(wait_for) => {
+            ctx.spinner_manager
+                .with_spinner(
+                    endpoint,
+                    high_level.wait_for_archive_details(
+                        &id,
+                        args.live_type,
+                        &wait_for.resources(),
+                        args.timeout.as_duration(),
+                    ),
+                )
+                .await?
+        }
+        None => {
+            ctx.spinner_manager
+                .with_spinner(
+                    endpoint,
+                    high_level.get_archive_details(&id, args.live_type),
+                )
+                .await?
+        }
+    };
+    let missing = details.availability.missing(&ArchiveResource::ALL);
+    if !missing.is_empty() {
+        let missing: Vec<_> = missing.iter().map(ToString::to_string).collect();
+        tracing::warn!(
+            "{}",
+            t!(
+                "linkura.command.api.archive.not_ready",
+                id = id,
+                missing = missing.join(", ")
+            )
+        );
+    }
+    Ok(details.to_json())
+}
+
+/// Image urls, or the manifest of the downloaded images with `--download`
+async fn archive_images(
+    ctx: &Global,
+    args: &ArgsArchiveImages,
+    top_level_id: Option<&str>,
+) -> Result<serde_json::Value> {
+    let id = resolve_id(args.id.as_deref(), top_level_id)?;
+    let images = ctx
+        .spinner_manager
+        .with_spinner(
+            archive_details_endpoint(args.live_type),
+            ctx.api_client
+                .high_level()
+                .get_archive_images(&id, args.live_type),
+        )
+        .await?;
+    let Some(dir) = &args.download else {
+        return Ok(serde_json::to_value(images)?);
+    };
+    if images.is_empty() {
+        tracing::warn!("{}", t!("linkura.command.api.archive.no_images", id = id));
+    }
+    let manifest = ctx
+        .spinner_manager
+        .with_spinner(
+            "assets",
+            ctx.api_client
+                .assets()
+                .download_archive_images(&id, &images, dir),
+        )
+        .await?;
+    let failed = manifest
+        .images
+        .iter()
+        .filter(|image| image.error.is_some())
+        .count();
+    tracing::info!(
+        "{}",
+        t!(
+            "linkura.command.api.archive.images_saved",
+            count = manifest.images.len() - failed,
+            path = dir.join(ARCHIVE_IMAGES_MANIFEST).display()
+        )
+    );
+    Ok(serde_json::to_value(manifest)?)
+}
+
+fn archive_details_endpoint(live_type: u8) -> &'static str {
+    match live_type {
+        1 => "/archive/get_fes_archive_data",
+        _ => "/archive/get_with_archive_data",
+    }
+}
+
+fn write_output(output: &OutputArgs, value: &serde_json::Value) -> Result<()> {
+    let json = match output.format {
+        OutputFormat::Json => serde_json::to_string(value)?,
+        OutputFormat::Pretty => serde_json::to_string_pretty(value)?,
+        OutputFormat::Table => match render_table(value) {
+            Some(table) => table,
+            None => serde_json::to_string_pretty(value)?,
+        },
+    };
+    match output.output.as_deref() {
+        Some(path) if !path.is_empty() => {
+            std::fs::write(path, json)?;
+            tracing::info!("{}", t!("linkura.command.api.output.saved", path = path));
         }
+        _ => println!("{}", json),
     }
     Ok(())
 }
+
+/// Columns of a list of objects, in the order the fields first appear.
+/// `None` when `value` is not such a list.
+fn render_table(value: &serde_json::Value) -> Option<String> {
+    let rows = value
+        .as_array()?
+        .iter()
+        .map(serde_json::Value::as_object)
+        .collect::<Option<Vec<_>>>()?;
+    let mut columns: Vec<&str> = Vec::new();
+    for row in &rows {
+        for key in row.keys() {
+            if !columns.contains(&key.as_str()) {
+                columns.push(key);
+            }
+        }
+    }
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            columns
+                .iter()
+                .map(|column| match row.get(*column) {
+                    None | Some(serde_json::Value::Null) => String::new(),
+                    Some(serde_json::Value::String(text)) => text.clone(),
+                    Some(other) => other.to_string(),
+                })
+                .collect()
+        })
+        .collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(index, column)| {
+            cells
+                .iter()
+                .map(|row| row[index].chars().count())
+                .chain([column.chars().count()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let line = |values: Vec<&str>| {
+        values
+            .iter()
+            .zip(&widths)
+            .map(|(value, width)| format!("{:<width$}", value, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    let mut lines = vec![line(columns.clone())];
+    lines.extend(
+        cells
+            .iter()
+            .map(|row| line(row.iter().map(String::as_str).collect())),
+    );
+    Some(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Args, Commands as RootCommands};
+    use clap::{CommandFactory, Parser};
+
+    fn parse_api(argv: &[&str]) -> ArgsAPI {
+        let args = Args::try_parse_from(argv).unwrap();
+        match args.command {
+            Some(RootCommands::API(api_args)) => api_args,
+            other => panic!("unexpected command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_subcommand_help() {
+        let mut command = Args::command();
+        command.build();
+        let api = command.find_subcommand_mut("api").unwrap();
+        let paths = [
+            vec!["archive", "list"],
+            vec!["archive", "details"],
+            vec!["archive", "comments"],
+            vec!["archive", "position"],
+            vec!["archive", "images"],
+            vec!["fes", "enter"],
+            vec!["fes", "token"],
+            vec!["with", "enter"],
+            vec!["with", "token"],
+            vec!["with", "react"],
+            vec!["plan", "list"],
+            vec!["friends", "list"],
+        ];
+        for path in paths {
+            let group = api.find_subcommand_mut(path[0]).unwrap();
+            let help = group.render_long_help().to_string();
+            assert!(help.contains(&format!("Usage: linkura-cli api {}", path[0])));
+            assert!(help.contains("Examples:"), "{} has no examples", path[0]);
+
+            let leaf = group.find_subcommand_mut(path[1]).unwrap();
+            let help = leaf.render_long_help().to_string();
+            assert!(
+                help.contains(&format!("Usage: linkura-cli api {} {}", path[0], path[1])),
+                "{}",
+                help
+            );
+            assert!(help.contains("--output"));
+            assert!(help.contains("--format"));
+        }
+
+        let help = api.render_long_help().to_string();
+        assert!(!help.contains("archive-details"));
+    }
+
+    #[test]
+    fn test_legacy_invocations() {
+        let api = parse_api(&["linkura-cli", "api", "archive", "-l", "3"]);
+        match api.command {
+            Commands::Archive(ArgsArchive {
+                command: None,
+                limit: Some(3),
+            }) => {}
+            other => panic!("unexpected command: {:?}", other),
+        }
+
+        let api = parse_api(&[
+            "linkura-cli",
+            "api",
+            "-o",
+            "out.json",
+            "archive-details",
+            "-i",
+            "abc",
+            "-t",
+            "1",
+        ]);
+        assert_eq!(api.output.output.as_deref(), Some("out.json"));
+        match api.command {
+            Commands::ArchiveDetails(details) => {
+                assert_eq!(details.id.as_deref(), Some("abc"));
+                assert_eq!(details.live_type, 1);
+                assert_eq!(details.wait_for, None);
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_new_invocations() {
+        let api = parse_api(&[
+            "linkura-cli",
+            "api",
+            "archive",
+            "list",
+            "-l",
+            "10",
+            "--format",
+            "json",
+        ]);
+        assert_eq!(api.output.format, OutputFormat::Json);
+        match api.command {
+            Commands::Archive(ArgsArchive {
+                command: Some(ArchiveCommands::List(list_args)),
+                ..
+            }) => assert_eq!(list_args.limit, Some(10)),
+            other => panic!("unexpected command: {:?}", other),
+        }
+
+        let api = parse_api(&[
+            "linkura-cli",
+            "api",
+            "archive",
+            "details",
+            "-i",
+            "abc",
+            "-t",
+            "1",
+            "--wait-for",
+            "all",
+            "--timeout",
+            "1m",
+        ]);
+        match api.command {
+            Commands::Archive(ArgsArchive {
+                command: Some(ArchiveCommands::Details(details_args)),
+                ..
+            }) => {
+                assert_eq!(details_args.wait_for, Some(WaitFor::All));
+                assert_eq!(details_args.wait_for.unwrap().resources().len(), 3);
+                assert_eq!(details_args.timeout.as_duration().as_secs(), 60);
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+        assert!(
+            Args::try_parse_from([
+                "linkura-cli",
+                "api",
+                "archive",
+                "details",
+                "-t",
+                "1",
+                "--timeout",
+                "60"
+            ])
+            .is_err()
+        );
+
+        let api = parse_api(&[
+            "linkura-cli",
+            "api",
+            "archive",
+            "images",
+            "-i",
+            "abc",
+            "-t",
+            "2",
+            "--download",
+            "images",
+        ]);
+        assert!(api.command.consumes_id());
+        match api.command {
+            Commands::Archive(ArgsArchive {
+                command: Some(ArchiveCommands::Images(images_args)),
+                ..
+            }) => {
+                assert_eq!(images_args.live_type, 2);
+                assert_eq!(images_args.download, Some(PathBuf::from("images")));
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+
+        let api = parse_api(&["linkura-cli", "api", "with", "react", "live", "-m", "hi"]);
+        match api.command {
+            Commands::With(ArgsWith {
+                command: WithCommands::React(react_args),
+            }) => {
+                assert_eq!(react_args.live_id.as_deref(), Some("live"));
+                assert_eq!(react_args.message, "hi");
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_friends_list() {
+        let api = parse_api(&[
+            "linkura-cli",
+            "api",
+            "friends",
+            "list",
+            "--redact",
+            "-f",
+            "table",
+        ]);
+        assert_eq!(api.output.format, OutputFormat::Table);
+        assert!(!api.command.consumes_id());
+        match api.command {
+            Commands::Friends(ArgsFriends {
+                command: FriendsCommands::List(list_args),
+            }) => assert!(list_args.redact),
+            other => panic!("unexpected command: {:?}", other),
+        }
+
+        // fields in alphabetical order, as serde_json keeps them without preserve_order
+        let friends = serde_json::json!([
+            { "last_login": null, "level": 120, "name": "Kaho", "player_id": "123456789" },
+            { "last_login": "2025-08-21", "level": 7, "name": "friend_2", "player_id": "" },
+        ]);
+        assert_eq!(
+            render_table(&friends).unwrap(),
+            [
+                "last_login  level  name      player_id",
+                "            120    Kaho      123456789",
+                "2025-08-21  7      friend_2",
+            ]
+            .join("\n")
+        );
+        assert_eq!(render_table(&serde_json::json!({ "a": 1 })), None);
+    }
+
+    #[test]
+    fn test_probe_versions() {
+        let api = parse_api(&[
+            "linkura-cli",
+            "api",
+            "probe-versions",
+            "--pinned",
+            "3.1.0",
+            "-f",
+            "table",
+        ]);
+        assert!(!api.command.consumes_id());
+        match api.command {
+            Commands::ProbeVersions(probe_args) => {
+                assert_eq!(probe_args.pinned.as_deref(), Some("3.1.0"));
+                assert_eq!(probe_args.store, None);
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+
+        let probes = serde_json::to_value([
+            linkura_api::VersionProbe::new("/archive/get_home", Some(200), Some(200)),
+            linkura_api::VersionProbe::new("/friend/get_request_list", Some(403), None),
+        ])
+        .unwrap();
+        assert_eq!(
+            render_table(&probes).unwrap(),
+            [
+                "accepts  endpoint                  pinned  store",
+                "both     /archive/get_home         200     200",
+                "neither  /friend/get_request_list  403",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_top_level_id() {
+        let args =
+            Args::try_parse_from(["linkura-cli", "--id", "top", "api", "fes", "token"]).unwrap();
+        assert_eq!(args.id.as_deref(), Some("top"));
+        match args.command {
+            Some(RootCommands::API(api_args)) => assert!(api_args.command.consumes_id()),
+            other => panic!("unexpected command: {:?}", other),
+        }
+        assert!(
+            !parse_api(&["linkura-cli", "api", "plan", "list"])
+                .command
+                .consumes_id()
+        );
+        assert!(
+            !parse_api(&["linkura-cli", "api", "archive", "list"])
+                .command
+                .consumes_id()
+        );
+        assert!(
+            !parse_api(&["linkura-cli", "api", "subscription", "-f", "json"])
+                .command
+                .consumes_id()
+        );
+
+        assert_eq!(resolve_id(Some("own"), Some("top")).unwrap(), "own");
+        assert_eq!(resolve_id(None, Some("top")).unwrap(), "top");
+        assert!(resolve_id(None, None).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+use anyhow::{Context, Result, anyhow};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use clap::{Args as ClapArgs, Subcommand};
+use qrcode::{QrCode, render::unicode};
+use std::io::Read;
+
+use crate::config::{Config, ConfigManager};
+use linkura_api::Credential;
+use linkura_i18n::t;
+
+/// Versioned prefix of a transferable credential blob
+const BLOB_PREFIX: &str = "linkura1:";
+
+#[derive(Debug, Clone, ClapArgs)]
+pub struct ArgsConfig {
+    #[command(subcommand)]
+    pub command: Commands,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Commands {
+    #[command(about = t!("linkura.command.config.subcommand.qr.about").to_string())]
+    Qr(ArgsQr),
+    #[command(about = t!("linkura.command.config.subcommand.import.about").to_string())]
+    Import(ArgsImport),
+}
+
+#[derive(Debug, Clone, ClapArgs)]
+pub struct ArgsQr {
+    #[arg(long = "include-token", help = t!("linkura.command.config.args.include_token.about").to_string())]
+    pub include_token: bool,
+    #[arg(long = "blob", help = t!("linkura.command.config.args.blob.about").to_string())]
+    pub blob: bool,
+}
+
+#[derive(Debug, Clone, ClapArgs)]
+pub struct ArgsImport {
+    #[arg(value_name = "BLOB", help = t!("linkura.command.config.args.import_blob.about").to_string())]
+    pub blob: Option<String>,
+}
+
+/// Encode the credential as `linkura1:<base64url(json)>`, without the session token unless asked
+pub fn encode_credential(credential: &Credential, include_token: bool) -> Result<String> {
+    let mut value = serde_json::to_value(credential)?;
+    if !include_token {
+        value["session_token"] = serde_json::Value::Null;
+    }
+    let json = serde_json::to_vec(&value)?;
+    Ok(format!("{}{}", BLOB_PREFIX, URL_SAFE_NO_PAD.encode(json)))
+}
+
+pub fn decode_credential(blob: &str) -> Result<Credential> {
+    let encoded = blob
+        .trim()
+        .strip_prefix(BLOB_PREFIX)
+        .ok_or_else(|| anyhow!(t!("linkura.command.config.blob.invalid")))?;
+    let json = URL_SAFE_NO_PAD
+        .decode(encoded)
+        .context(t!("linkura.command.config.blob.invalid"))?;
+    serde_json::from_slice(&json).context(t!("linkura.command.config.blob.invalid"))
+}
+
+pub fn run(config_manager: &mut ConfigManager, args: &ArgsConfig) -> Result<()> {
+    match &args.command {
+        Commands::Qr(qr_args) => {
+            let config = config_manager
+                .load_config()?
+                .ok_or_else(|| anyhow!(t!("linkura.command.config.not_found")))?;
+            if qr_args.include_token && config.credential.session_token.is_some() {
+                tracing::warn!("{}", t!("linkura.command.config.token.included"));
+            }
+            let blob = encode_credential(&config.credential, qr_args.include_token)?;
+            if !qr_args.blob {
+                let code = QrCode::new(blob.as_bytes())?;
+                println!(
+                    "{}",
+                    code.render::<unicode::Dense1x2>()
+                        .dark_color(unicode::Dense1x2::Light)
+                        .light_color(unicode::Dense1x2::Dark)
+                        .build()
+                );
+            }
+            println!("{}", blob);
+        }
+        Commands::Import(import_args) => {
+            let blob = match &import_args.blob {
+                Some(blob) => blob.clone(),
+                None => {
+                    let mut blob = String::new();
+                    std::io::stdin().read_to_string(&mut blob)?;
+                    blob
+                }
+            };
+            let credential = decode_credential(&blob)?;
+            // resolves the path the config is written to
+            config_manager.load_config().ok();
+            config_manager
+                .save_config(&Config { credential })
+                .context(t!("linkura.config.save.failed"))?;
+            tracing::info!(
+                "{}",
+                t!(
+                    "linkura.command.config.imported",
+                    path = config_manager.get_config_path().display().to_string()
+                )
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_credential_blob_round_trip() {
+        let credential = Credential {
+            res_version: "R1".to_string(),
+            client_version: "1.0.0".to_string(),
+            device_specific_id: "device".to_string(),
+            player_id: "player".to_string(),
+            session_token: Some("secret".to_string()),
+        };
+
+        let blob = encode_credential(&credential, false).unwrap();
+        assert!(blob.starts_with(BLOB_PREFIX));
+        let decoded = decode_credential(&format!("{}\n", blob)).unwrap();
+        assert_eq!(decoded.device_specific_id, "device");
+        assert_eq!(decoded.player_id, "player");
+        assert_eq!(decoded.session_token, None);
+
+        let blob = encode_credential(&credential, true).unwrap();
+        assert_eq!(
+            decode_credential(&blob).unwrap().session_token.as_deref(),
+            Some("secret")
+        );
+
+        assert!(decode_credential("player:device").is_err());
+        assert!(decode_credential("linkura1:!!").is_err());
+    }
+}
@@ -8,7 +8,11 @@ pub async fn run(ctx: &Global) {
     let _args = &ctx.args;
 
     let api_client = &ctx.api_client;
-    let wm_res: serde_json::Value = api_client.high_level().get_plan_list().await.unwrap();
+    let spinner = &ctx.spinner_manager;
+    let wm_res: serde_json::Value = spinner
+        .with_spinner("/archive/get_home", api_client.high_level().get_plan_list())
+        .await
+        .unwrap();
 
     let trailers = wm_res.as_array().unwrap();
     tracing::trace!("Trailers: {:?}", trailers);
@@ -17,12 +21,16 @@ pub async fn run(ctx: &Global) {
     });
     print_enterable_trailer_info(ctx, trailers).await;
 
-    let archive_res: serde_json::Value = api_client
-        .high_level()
-        .get_archive_list(ArchiveListOptions {
-            limit: Some(4),
-            ..Default::default()
-        })
+    let archive_res: serde_json::Value = spinner
+        .with_spinner(
+            "/archive/get_archive_list",
+            api_client
+                .high_level()
+                .get_archive_list(ArchiveListOptions {
+                    limit: Some(4),
+                    ..Default::default()
+                }),
+        )
         .await
         .unwrap();
     let latest_archive_res = archive_res.as_array().unwrap()[0].clone();
@@ -85,8 +93,13 @@ async fn print_latest_trailer_info(ctx: &Global, wm: &serde_json::Value) {
     }
 
     if live_type == 2 {
-        let res: Result<serde_json::Value, anyhow::Error> =
-            api_client.high_level().get_with_meets_info(id).await;
+        let res: Result<serde_json::Value, anyhow::Error> = ctx
+            .spinner_manager
+            .with_spinner(
+                "/withlive/enter",
+                api_client.high_level().get_with_meets_info(id),
+            )
+            .await;
         match res {
             Ok(res) => {
                 let characters = res
@@ -141,9 +154,20 @@ async fn print_latest_trailer_info(ctx: &Global, wm: &serde_json::Value) {
             live_id: Some(id.to_string()),
             ..Default::default()
         };
-        let _ = api_client.raw().fes_live().lobby(&lobby_request).await;
-        let res: Result<serde_json::Value, anyhow::Error> =
-            api_client.high_level().get_fes_live_info(id).await;
+        let _ = ctx
+            .spinner_manager
+            .with_spinner(
+                "/feslive/lobby",
+                api_client.raw().fes_live().lobby(&lobby_request),
+            )
+            .await;
+        let res: Result<serde_json::Value, anyhow::Error> = ctx
+            .spinner_manager
+            .with_spinner(
+                "/feslive/enter",
+                api_client.high_level().get_fes_live_info(id),
+            )
+            .await;
         match res {
             Ok(res) => {
                 let characters = res
@@ -223,10 +247,10 @@ async fn print_latest_archive_info(ctx: &Global, archive: &serde_json::Value) {
     let mut real_url = String::new();
     if !link.is_empty() {
         real_url = ctx
-            .api_client
-            .assets()
-            .get_hls_url_from_archive(link)
+            .spinner_manager
+            .with_spinner(link, ctx.api_client.assets().get_hls_url_from_archive(link))
             .await
+            .map(|info| info.full_url)
             .unwrap_or_else(|_| String::new());
     }
     tracing::info!(
@@ -213,14 +213,18 @@ impl LinkuraMcpServer {
             live_type,
         }): Parameters<GetArchiveDetailRequest>,
     ) -> Result<Json<ArchiveDetailResponse>, rmcp::ErrorData> {
-        let raw = self
+        let details = self
             .api_client
             .high_level()
             .get_archive_details(&archives_id, live_type as u8)
             .await
             .map_err(|err| rmcp::ErrorData::internal_error(err.to_string(), None))?;
 
-        Ok(Json(map_archive_detail(archives_id, live_type, raw)))
+        Ok(Json(map_archive_detail(
+            archives_id,
+            live_type,
+            details.to_json(),
+        )))
     }
 }
 
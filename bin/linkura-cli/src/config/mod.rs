@@ -1,4 +1,7 @@
-use crate::{cli::spinner::SpinnerManager, command::api::ArgsAPI, command::mcp::ArgsMcp};
+use crate::{
+    cli::spinner::SpinnerManager, command::api::ArgsAPI, command::config::ArgsConfig,
+    command::mcp::ArgsMcp,
+};
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use serde::{Deserialize, Serialize};
@@ -7,7 +10,9 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use linkura_api::{self, ApiClient, Credential};
+use linkura_api::{
+    self, ApiClient, Credential, HeaderOverrides, ResponseCache, SubscriptionStatus,
+};
 use linkura_i18n::t;
 
 /** ARG PARSER **/
@@ -29,6 +34,12 @@ pub struct Args {
     pub quiet: bool,
     #[arg(short('l'), long = "loglevel", value_name = "LOG_LEVEL", help = t!("linkura.cli.args.loglevel.about").to_string())]
     pub log_level: Option<String>,
+    #[arg(long = "pin-version", action = clap::ArgAction::SetTrue, help = t!("linkura.cli.args.pin_version.about").to_string())]
+    pub pin_version: bool,
+    #[arg(long = "offline", action = clap::ArgAction::SetTrue, help = t!("linkura.cli.args.offline.about").to_string())]
+    pub offline: bool,
+    #[arg(long = "id", value_name = "ID", help = t!("linkura.cli.args.id.about").to_string())]
+    pub id: Option<String>,
 
     #[clap(long = "player-id", value_name = "PLAYER_ID", help = t!("linkura.cli.args.player_id.about").to_string())]
     pub player_id: Option<String>,
@@ -45,6 +56,8 @@ pub enum Commands {
     API(ArgsAPI),
     #[command(about = t!("linkura.command.mcp.about").to_string())]
     Mcp(ArgsMcp),
+    #[command(about = t!("linkura.command.config.about").to_string())]
+    Config(ArgsConfig),
     #[command(about = t!("linkura.command.version.about").to_string())]
     Version,
 }
@@ -60,28 +73,35 @@ pub struct Config {
 pub struct ConfigManager {
     args_config_path: Option<PathBuf>,
     current_dir_config_path: PathBuf,
-    home_dir_config_path: PathBuf,
+    /// `None` when the home directory can't be determined
+    home_dir_config_path: Option<PathBuf>,
 
     runtime_config_path: PathBuf,
 }
 
 impl ConfigManager {
     pub fn new(args_path: Option<String>) -> Self {
-        let args_config_path = args_path.map(PathBuf::from);
-        // 获取当前目录下的配置文件路径
-        let current_dir_config_path = PathBuf::from("linkura-cli_config.json");
-
         #[cfg(unix)]
         let home = std::env::var("HOME").ok().map(PathBuf::from);
         #[cfg(windows)]
         let home = std::env::var("USERPROFILE").ok().map(PathBuf::from);
 
+        Self::with_home(args_path, home)
+    }
+
+    pub fn with_home(args_path: Option<String>, home: Option<PathBuf>) -> Self {
+        let args_config_path = args_path.map(PathBuf::from);
+        // 获取当前目录下的配置文件路径
+        let current_dir_config_path = PathBuf::from("linkura-cli_config.json");
+
         // 获取home目录下的配置文件路径
-        let mut home_dir_config_path = home.clone().unwrap();
-        home_dir_config_path.push(".config");
-        home_dir_config_path.push("linkura-cli");
-        home_dir_config_path.push("config.json");
-        let runtime_config_path = home_dir_config_path.clone();
+        let home_dir_config_path = home
+            .filter(|home| !home.as_os_str().is_empty())
+            .map(|home| home.join(".config").join("linkura-cli").join("config.json"));
+        let runtime_config_path = args_config_path
+            .clone()
+            .or_else(|| home_dir_config_path.clone())
+            .unwrap_or_else(|| current_dir_config_path.clone());
 
         Self {
             args_config_path,
@@ -107,15 +127,23 @@ impl ConfigManager {
         }
 
         // 3. 检查home目录下的配置文件
-        if self.home_dir_config_path.exists() {
-            self.runtime_config_path = self.home_dir_config_path.clone();
-            return Ok(Some(self.read_config(&self.home_dir_config_path)?));
+        if let Some(home_dir_config_path) = &self.home_dir_config_path {
+            if home_dir_config_path.exists() {
+                self.runtime_config_path = home_dir_config_path.clone();
+                return Ok(Some(self.read_config(home_dir_config_path)?));
+            }
         }
 
-        // 如果都没有，则创建home目录下的配置文件
+        // 如果都没有，则创建args或home目录下的配置文件
+        let new_config_path = self
+            .args_config_path
+            .clone()
+            .or_else(|| self.home_dir_config_path.clone())
+            .ok_or_else(|| anyhow::anyhow!(t!("linkura.config.home.not_found")))?;
+        self.runtime_config_path = new_config_path;
         // mkdir -p
-        if let Some(parent) = self.home_dir_config_path.parent() {
-            if !parent.exists() {
+        if let Some(parent) = self.runtime_config_path.parent() {
+            if !parent.as_os_str().is_empty() && !parent.exists() {
                 fs::create_dir_all(parent)?;
             }
         }
@@ -126,6 +154,38 @@ impl ConfigManager {
         &self.runtime_config_path
     }
 
+    /// Response cache lives next to the home config
+    pub fn get_cache_dir(&self) -> PathBuf {
+        self.home_dir_config_path
+            .as_ref()
+            .and_then(|path| path.parent())
+            .map(|parent| parent.join("cache"))
+            .unwrap_or_else(|| PathBuf::from("linkura-cli_cache"))
+    }
+
+    /// Per-endpoint header overrides live next to the home config
+    pub fn get_header_overrides_path(&self) -> PathBuf {
+        self.home_dir_config_path
+            .as_ref()
+            .and_then(|path| path.parent())
+            .map(|parent| parent.join("header_overrides.json"))
+            .unwrap_or_else(|| PathBuf::from("linkura-cli_header_overrides.json"))
+    }
+
+    /// A broken overrides file does not stop the cli, the built-in defaults are used
+    pub fn load_header_overrides(&self) -> HeaderOverrides {
+        HeaderOverrides::load(&self.get_header_overrides_path()).unwrap_or_else(|e| {
+            tracing::warn!(
+                "{}",
+                t!(
+                    "linkura.config.header_overrides.load.failed",
+                    error = format!("{:#}", e)
+                )
+            );
+            HeaderOverrides::builtin()
+        })
+    }
+
     fn read_config(&self, path: &Path) -> Result<Config> {
         let content = fs::read_to_string(path).context(t!(
             "linkura.config.file.read.failed",
@@ -175,8 +235,10 @@ pub struct Global {
 impl Global {
     pub async fn new(args: Args) -> Self {
         let spinner_manager = SpinnerManager::new(args.quiet);
-        let mut api_client = linkura_api::ApiClient::new();
         let mut config_manager = ConfigManager::new(args.config_path.clone());
+        let mut api_client = linkura_api::ApiClient::new()
+            .with_cache(ResponseCache::new(config_manager.get_cache_dir()))
+            .with_header_overrides(config_manager.load_header_overrides());
 
         let config_res = config_manager.load_config();
 
@@ -201,24 +263,46 @@ impl Global {
                             api_client.high_level().get_app_version().await.unwrap();
                         if let Some(res_version) = res_version {
                             if res_version != config.credential.res_version {
-                                sp.set_message(t!(
-                                    "linkura.config.new.res.version",
-                                    old = config.credential.res_version.clone(),
-                                    new = res_version.clone()
-                                ));
-
-                                config.credential.res_version = res_version;
+                                if args.pin_version {
+                                    tracing::warn!(
+                                        "{}",
+                                        t!(
+                                            "linkura.config.new.res.version.pinned",
+                                            old = config.credential.res_version.clone(),
+                                            new = res_version
+                                        )
+                                    );
+                                } else {
+                                    sp.set_message(t!(
+                                        "linkura.config.new.res.version",
+                                        old = config.credential.res_version.clone(),
+                                        new = res_version.clone()
+                                    ));
+
+                                    config.credential.res_version = res_version;
+                                }
                             }
                         }
 
                         if let Some(client_version) = client_version {
                             if client_version != config.credential.client_version {
-                                sp.set_message(t!(
-                                    "linkura.config.new.client.version",
-                                    old = config.credential.client_version.clone(),
-                                    new = client_version.clone()
-                                ));
-                                config.credential.client_version = client_version;
+                                if args.pin_version {
+                                    tracing::warn!(
+                                        "{}",
+                                        t!(
+                                            "linkura.config.new.client.version.pinned",
+                                            old = config.credential.client_version.clone(),
+                                            new = client_version
+                                        )
+                                    );
+                                } else {
+                                    sp.set_message(t!(
+                                        "linkura.config.new.client.version",
+                                        old = config.credential.client_version.clone(),
+                                        new = client_version.clone()
+                                    ));
+                                    config.credential.client_version = client_version;
+                                }
                             }
                         }
 
@@ -275,6 +359,51 @@ impl Global {
 
 /*  CONFIG END **/
 
+/// Days before expiry the subscription warning starts
+const SUBSCRIPTION_WARN_DAYS: i64 = 7;
+
+/// Localized warning for a lapsed plan or one expiring within `days`
+pub fn subscription_warning(
+    status: &SubscriptionStatus,
+    now: chrono::DateTime<chrono::Utc>,
+    days: i64,
+) -> Option<String> {
+    if !status.needs_warning(now, days) {
+        return None;
+    }
+    let plan = status.plan_name.clone().unwrap_or_default();
+    let expires_at = status.expires_at?.to_rfc3339();
+    let message = if status.active {
+        t!(
+            "linkura.config.subscription.expiring",
+            plan = plan,
+            days = status.days_left(now).unwrap_or_default(),
+            expires_at = expires_at
+        )
+    } else {
+        t!(
+            "linkura.config.subscription.lapsed",
+            plan = plan,
+            expires_at = expires_at
+        )
+    };
+    Some(message.to_string())
+}
+
+/// Warn about the plan once per run, a failed lookup does not stop the cli
+async fn check_subscription(global: &Global) {
+    match global.api_client.high_level().get_subscription_status().await {
+        Ok(status) => {
+            if let Some(message) =
+                subscription_warning(&status, chrono::Utc::now(), SUBSCRIPTION_WARN_DAYS)
+            {
+                tracing::warn!("{}", message);
+            }
+        }
+        Err(e) => tracing::debug!("Failed to get subscription status: {}", e),
+    }
+}
+
 pub async fn init(args: Args) -> Result<Global> {
     tracing::info!("{}", t!("linkura.config.initialize.start"));
     let mut global = Global::new(args).await;
@@ -332,15 +461,42 @@ pub async fn init(args: Args) -> Result<Global> {
             .to_string(),
         token = session_token
     ));
+    if !global.args.skip {
+        check_subscription(&global).await;
+    }
     Ok(global)
 }
 
+/// Load the config only, no version check and no login, api calls are served from cache
+pub fn init_offline(args: Args) -> Result<Global> {
+    tracing::info!("{}", t!("linkura.config.initialize.offline.start"));
+
+    let spinner_manager = SpinnerManager::new(args.quiet);
+    let mut config_manager = ConfigManager::new(args.config_path.clone());
+    let config = config_manager.load_config()?.unwrap_or_default();
+
+    let mut api_client = linkura_api::ApiClient::new()
+        .with_cache(ResponseCache::new(config_manager.get_cache_dir()))
+        .with_header_overrides(config_manager.load_header_overrides())
+        .offline(true);
+    api_client.update_with_credential(&config.credential);
+
+    Ok(Global {
+        config,
+        config_manager,
+        api_client,
+        args,
+        spinner_manager,
+    })
+}
+
 pub async fn init_non_interactive(args: Args) -> Result<Global> {
     tracing::info!("{}", t!("linkura.config.initialize.mcp.start"));
 
     let spinner_manager = SpinnerManager::new(true);
-    let mut api_client = linkura_api::ApiClient::new();
     let mut config_manager = ConfigManager::new(args.config_path.clone());
+    let mut api_client =
+        linkura_api::ApiClient::new().with_header_overrides(config_manager.load_header_overrides());
 
     let mut config = config_manager
         .load_config()?
@@ -385,3 +541,53 @@ pub async fn init_non_interactive(args: Args) -> Result<Global> {
 }
 
 pub mod interactive;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_home_dir() {
+        let mut config_manager = ConfigManager::with_home(None, None);
+        let err = config_manager.load_config().unwrap_err();
+        assert!(err.to_string().contains("--config"));
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let config_path = dir.join("config.json");
+        let mut config_manager =
+            ConfigManager::with_home(Some(config_path.display().to_string()), None);
+        assert!(config_manager.load_config().unwrap().is_none());
+        assert_eq!(config_manager.get_config_path(), &config_path);
+        assert!(dir.exists());
+        assert_eq!(
+            config_manager.get_header_overrides_path(),
+            PathBuf::from("linkura-cli_header_overrides.json")
+        );
+
+        let config_manager = ConfigManager::with_home(None, Some(dir.to_path_buf()));
+        assert_eq!(
+            config_manager.get_header_overrides_path(),
+            dir.join(".config/linkura-cli/header_overrides.json")
+        );
+        assert!(config_manager.load_header_overrides().is_empty());
+    }
+
+    #[test]
+    fn test_subscription_warning() {
+        let now = chrono::Utc::now();
+        let status = |days: i64| SubscriptionStatus {
+            plan_name: Some("Hasunosora Pass".to_string()),
+            active: days > 0,
+            expires_at: Some((now + chrono::TimeDelta::days(days)).fixed_offset()),
+            memberships: Vec::new(),
+        };
+        assert!(subscription_warning(&status(30), now, SUBSCRIPTION_WARN_DAYS).is_none());
+        let expiring = subscription_warning(&status(3), now, SUBSCRIPTION_WARN_DAYS).unwrap();
+        assert!(expiring.contains("Hasunosora Pass"));
+        assert_ne!(
+            subscription_warning(&status(-3), now, SUBSCRIPTION_WARN_DAYS).unwrap(),
+            expiring
+        );
+    }
+}
@@ -15,9 +15,30 @@ use crate::config::Commands;
 
 linkura_i18n::init!();
 
+/// No logger is initialized in quiet mode, errors still have to reach stderr
+fn report_error(quiet: bool, message: &str) {
+    if quiet {
+        eprintln!("{}", message);
+    } else {
+        tracing::error!("{}", message);
+    }
+}
+
 #[tokio::main]
 async fn main() {
     let args = config::Args::parse();
+    if args.offline && matches!(args.command, Some(Commands::Version | Commands::Mcp(_))) {
+        eprintln!("{}", t!("linkura.main.offline.unsupported"));
+        std::process::exit(1);
+    }
+    let consumes_id = match &args.command {
+        Some(Commands::API(api_args)) => api_args.command.consumes_id(),
+        _ => false,
+    };
+    if args.id.is_some() && !consumes_id {
+        eprintln!("{}", t!("linkura.main.id.unsupported"));
+        std::process::exit(1);
+    }
     // Commands that will not need to initialize
     match &args.command {
         Some(Commands::Version) => {
@@ -34,19 +55,37 @@ async fn main() {
         _ => {}
     }
 
-    if !args.quiet {
+    let quiet = args.quiet;
+    if !quiet {
         log::init(args.log_level.clone());
     }
 
     match args.command.clone() {
+        Some(Commands::Config(config_args)) => {
+            // works on the local config only, no version check and no login
+            let mut config_manager = config::ConfigManager::new(args.config_path.clone());
+            let _ = command::config::run(&mut config_manager, &config_args).map_err(|e| {
+                report_error(
+                    quiet,
+                    &t!(
+                        "linkura.main.command.config.run.failed",
+                        error = e.to_string()
+                    ),
+                );
+                std::process::exit(1);
+            });
+        }
         Some(Commands::API(api_args)) => {
-            let global = init(args)
-                .await
-                .expect(&t!("common.config.initialize.failed"));
+            let global = if args.offline {
+                config::init_offline(args)
+            } else {
+                init(args).await
+            }
+            .expect(&t!("common.config.initialize.failed"));
             let _ = command::api::run(&global, &api_args).await.map_err(|e| {
-                tracing::error!(
-                    "{}",
-                    t!("linkura.main.command.api.run.failed", error = e.to_string())
+                report_error(
+                    quiet,
+                    &t!("linkura.main.command.api.run.failed", error = e.to_string()),
                 );
                 std::process::exit(1);
             });
@@ -56,17 +95,20 @@ async fn main() {
                 .await
                 .expect(&t!("common.config.initialize.failed"));
             let _ = command::mcp::run(&global, &mcp_args).await.map_err(|e| {
-                tracing::error!(
-                    "{}",
-                    t!("linkura.main.command.mcp.run.failed", error = e.to_string())
+                report_error(
+                    quiet,
+                    &t!("linkura.main.command.mcp.run.failed", error = e.to_string()),
                 );
                 std::process::exit(1);
             });
         }
         None => {
-            let global = init(args)
-                .await
-                .expect(&t!("common.config.initialize.failed"));
+            let global = if args.offline {
+                config::init_offline(args)
+            } else {
+                init(args).await
+            }
+            .expect(&t!("common.config.initialize.failed"));
             command::default::run(&global).await;
         }
         _ => {
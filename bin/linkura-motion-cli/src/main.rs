@@ -1,22 +1,44 @@
 use anyhow::{Error, Result};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
 use clap::{Args as ClapArgs, Parser, Subcommand};
 use linkura_i18n::t;
+use notify::{EventKind, RecursiveMode, Watcher};
 use std::{
-    ops::Deref,
+    collections::BTreeSet,
     path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::RecvTimeoutError,
+    },
+    time::{Duration, Instant},
     usize,
 };
 use tracing::{info, warn};
 
 linkura_i18n::init!();
 
-use linkura_common::log;
-use linkura_downloader::{AlsDownloader, BaseDownloader, MrsDownloader, R2Uploader};
+use linkura_common::parse::{self, ByteSize, HumanDuration};
+use linkura_common::{disk, log};
+use linkura_downloader::{
+    AlsDownloader, BaseDownloader, DownloadReport, MrsDownloader, R2Uploader, UploadResult,
+    VerifyStatus, r2_uploader::UploadTask,
+};
 use linkura_packet::als::{
-    converter::AlsConverter,
+    anonymize::{AnonymizeConfig, run_anonymize},
+    archive::OutputTarget,
+    converter::{AlsConverter, DEFAULT_REORDER_WINDOW, SegmentObserver},
     extract::{ExtractConfig, ExtractTargetKind, ImageExtractOptions, run_extract},
+    join::{JoinConfig, run_join},
+    options::ConversionOptions,
     proto,
+    provenance::{PROVENANCE_FILE, Provenance},
+    retime::{RetimeConfig, run_retime},
+    split::{SplitBy, SplitConfig, extract_time_range, run_split},
+    sync,
+    template::PathTemplate,
+    transform::{TransformConfig, run_transform},
+    verify::{VerifyConfig, run_verify},
 };
 use url::Url;
 
@@ -33,6 +55,16 @@ use url::Url;
 pub struct Args {
     #[clap(short('q'), long = "quiet", help = t!("motion.cli.args.quiet").to_string(), default_value = "false")]
     pub quiet: bool,
+    #[clap(long = "data-dir", value_name = "DIR", help = t!("motion.cli.args.data_dir").to_string(), global(true))]
+    pub data_dir: Option<String>,
+    #[clap(
+        long = "live-marker",
+        value_name = "BYTE",
+        help = "Also accept this live marker byte in standard captures, e.g. 0x02",
+        value_parser = parse_live_marker,
+        global(true)
+    )]
+    pub live_marker: Option<u8>,
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
@@ -47,6 +79,10 @@ pub struct ArgsDownload {
     pub download_url: String,
     #[clap(short('p'), long = "parallel", help = t!("motion.cli.command.download.args.parallel").to_string(), default_value = "16")]
     pub parallel: usize,
+    #[clap(long = "verify-only", help = t!("motion.cli.command.download.args.verify_only").to_string())]
+    pub verify_only: bool,
+    #[clap(long = "ignore-space-check", help = t!("motion.cli.command.download.args.ignore_space_check").to_string())]
+    pub ignore_space_check: bool,
 }
 
 #[derive(Debug, ClapArgs)]
@@ -65,18 +101,21 @@ pub struct ArgsUpload {
     pub prefix: Option<String>,
     #[clap(short('c'), long = "concurrent", value_name = "CONCURRENT", help = t!("motion.cli.command.upload.args.concurrent").to_string(), default_value = "4")]
     pub concurrent: usize,
+    #[clap(long = "skip-existing", help = t!("motion.cli.command.upload.args.skip_existing").to_string())]
+    pub skip_existing: bool,
 }
 
 #[derive(Debug, ClapArgs)]
 pub struct ArgsAnalyze {
     #[clap(
         short('t'),
-        long = "type",
-        value_name = "TYPE",
-        help = "Analysis type: 'standard', 'mixed', 'mixed-legacy'",
-        default_value = "standard"
+        long = "input-format",
+        alias = "type",
+        value_name = "FORMAT",
+        help = "Input format: 'standard', 'mixed', 'legacy', 'auto'",
+        default_value = "auto"
     )]
-    pub analysis_type: String,
+    pub input_format: String,
     #[clap(
         short('o'),
         long = "output",
@@ -84,15 +123,50 @@ pub struct ArgsAnalyze {
         help = "Output file path"
     )]
     pub output_path: String,
-    #[clap(short('c'), long = "count", value_name = "COUNT", help = "Number of packets to analyze", default_value_t = usize::MAX)]
-    pub packet_count: usize,
-    #[clap(long = "file-count-limit", value_name = "FILE COUNT", help = "Limit the number of files to analyze (for mixed files)", default_value_t = usize::MAX)]
-    pub file_count_limit: usize,
-    #[clap(long = "file-size-limit", value_name = "FILE SIZE", help = "Limit the size of files to analyze in megabytes (for mixed files)", default_value_t = usize::MAX)]
-    pub file_size_limit: usize,
+    #[clap(
+        short('c'),
+        long = "max-packets",
+        alias = "count",
+        value_name = "COUNT",
+        help = "Maximum packets analyzed across all files [default: unlimited]"
+    )]
+    pub max_packets: Option<usize>,
+    #[clap(
+        long = "max-packets-per-file",
+        value_name = "COUNT",
+        help = "Maximum packets analyzed from each file [default: unlimited]"
+    )]
+    pub max_packets_per_file: Option<usize>,
+    #[clap(
+        long = "max-files",
+        alias = "file-count-limit",
+        value_name = "FILE COUNT",
+        help = "Maximum files analyzed from a directory [default: unlimited]"
+    )]
+    pub max_files: Option<usize>,
+    #[clap(
+        long = "max-file-size",
+        alias = "file-size-limit",
+        value_name = "SIZE",
+        help = "Maximum packet data read from each file, e.g. 512K or 2G, plain numbers are megabytes [default: unlimited]",
+        value_parser = parse::mebibytes
+    )]
+    pub max_file_size: Option<ByteSize>,
+    #[clap(
+        long = "max-packet-size",
+        value_name = "SIZE",
+        help = "Fail on packets with more data bytes than this, e.g. 64K, for corrupt captures [default: unlimited]"
+    )]
+    pub max_packet_size: Option<ByteSize>,
+    #[clap(
+        long = "all",
+        help = "Read everything, no packet, file, size or time limits",
+        conflicts_with_all = ["max_packets", "max_packets_per_file", "max_files", "max_file_size", "max_packet_size", "data_start_time", "data_end_time"]
+    )]
+    pub all: bool,
     #[clap(
         value_name = "FILE",
-        help = "Input binary file path (for diff: first file)"
+        help = "Input binary file path, '-' reads stdin (for diff: first file)"
     )]
     pub file_path: String,
     #[clap(
@@ -114,6 +188,104 @@ pub struct ArgsAnalyze {
         help = "Data end time in rfc3339 format (e.g., 2025-08-21T00:00:00Z, 2025-08-21T09:00:00+09:00), will ignore update object packets after this time"
     )]
     pub data_end_time: Option<String>,
+    #[clap(
+        short('j'),
+        long = "jobs",
+        value_name = "JOBS",
+        help = "Number of files to analyze in parallel (directory input)",
+        default_value_t = 1
+    )]
+    pub jobs: usize,
+    #[clap(
+        long = "sort-by",
+        value_name = "KEY",
+        help = "File order for directory input: 'created', 'modified', 'filename'",
+        default_value = "modified"
+    )]
+    pub sort_by: String,
+    #[clap(
+        long = "csv-summary",
+        value_name = "FILE",
+        help = "Write a per-file CSV summary (directory input)"
+    )]
+    pub csv_summary: Option<String>,
+    #[clap(
+        long = "stats-json",
+        value_name = "FILE",
+        help = "Write the final statistics as JSON"
+    )]
+    pub stats_json: Option<String>,
+    #[clap(
+        long = "packet-files",
+        value_name = "DIR",
+        help = "Write each packet's details to its own file in DIR with an index.tsv listing them, the output keeps the rest (file input)",
+        conflicts_with_all = ["watch", "follow"]
+    )]
+    pub packet_files: Option<String>,
+    #[clap(
+        long = "unknown-samples",
+        value_name = "BYTES",
+        help = "Include the first BYTES raw bytes of each unknown field in the JSON stats (max 1024)",
+        num_args = 0..=1,
+        default_missing_value = "64"
+    )]
+    pub unknown_samples: Option<usize>,
+    #[clap(
+        long = "gap-threshold",
+        value_name = "DURATION",
+        help = "Flag consecutive packets more than DURATION apart, e.g. capture stalls, plain numbers are milliseconds (default 5s)",
+        num_args = 0..=1,
+        default_missing_value = "5s",
+        value_parser = parse::milliseconds
+    )]
+    pub gap_threshold: Option<HumanDuration>,
+    #[clap(
+        long = "parallel-digest",
+        help = "Compute packet SHA-256 digests on a separate thread while writing the report (file input)",
+        default_value = "false"
+    )]
+    pub parallel_digest: bool,
+    #[clap(
+        long = "parallel-decode",
+        help = "Decode protobuf payloads on all cores, packets keep their order (standard and mixed input)",
+        default_value = "false"
+    )]
+    pub parallel_decode: bool,
+    #[clap(
+        long = "drop-keepalive",
+        value_name = "KIND",
+        help = "Drop keepalive packets before analysis (kept by default): 'pong' drops Pong packets, 'all' also drops Data(false) packets",
+        num_args = 0..=1,
+        default_missing_value = "pong"
+    )]
+    pub drop_keepalive: Option<String>,
+    #[clap(
+        long = "watch",
+        help = "Keep watching the input and append analysis of new or changed files until Ctrl+C",
+        default_value = "false"
+    )]
+    pub watch: bool,
+    #[clap(
+        long = "follow",
+        help = "Follow a capture file or directory that is still being written, like tail -f: analyze packets as they are appended and refresh statistics until Ctrl+C",
+        default_value = "false",
+        conflicts_with = "watch"
+    )]
+    pub follow: bool,
+    #[clap(
+        long = "fail-fast",
+        help = "Stop at the first file that fails to parse and exit with an error (directory input)",
+        default_value = "false",
+        overrides_with = "continue_on_error"
+    )]
+    pub fail_fast: bool,
+    #[clap(
+        long = "continue",
+        help = "Report files that fail to parse and go on with the rest, the default (directory input)",
+        default_value = "false",
+        overrides_with = "fail_fast"
+    )]
+    pub continue_on_error: bool,
 }
 
 #[derive(Debug, ClapArgs)]
@@ -127,6 +299,8 @@ pub struct ArgsSync {
     pub download_url: String,
     #[clap(long = "download-parallel", help = t!("motion.cli.command.download.args.parallel").to_string(), default_value = "16")]
     pub download_parallel: usize,
+    #[clap(long = "ignore-space-check", help = t!("motion.cli.command.download.args.ignore_space_check").to_string())]
+    pub ignore_space_check: bool,
 
     // Upload parameters
     #[clap(short('b'), long = "bucket", value_name = "BUCKET", help = t!("motion.cli.command.upload.args.bucket").to_string())]
@@ -141,6 +315,8 @@ pub struct ArgsSync {
     pub prefix: Option<String>,
     #[clap(short('c'), long = "concurrent", value_name = "CONCURRENT", help = t!("motion.cli.command.upload.args.concurrent").to_string(), default_value = "4")]
     pub upload_concurrent: usize,
+    #[clap(long = "skip-existing", help = t!("motion.cli.command.upload.args.skip_existing").to_string())]
+    pub skip_existing: bool,
 
     // Additional options
     #[clap(long = "delete-after-done", help = t!("motion.cli.command.sync.args.delete_after_done").to_string(), default_value = "true")]
@@ -150,44 +326,90 @@ pub struct ArgsSync {
 #[derive(Debug, ClapArgs)]
 pub struct ArgsConvert {
     #[clap(
-        long = "type",
-        value_name = "TYPE",
-        help = "Conversion type: 'als', 'als-legacy'",
-        default_value = "als"
+        long = "config",
+        value_name = "FILE",
+        help = "Read conversion options from a TOML or JSON file, flags given on the command line override it"
     )]
-    pub convert_type: String,
+    pub config: Option<String>,
+    #[clap(
+        long = "input-format",
+        alias = "type",
+        value_name = "FORMAT",
+        help = "Input format: 'standard', 'mixed', 'legacy', 'auto' [default: auto]"
+    )]
+    pub input_format: Option<String>,
     #[clap(
         short('i'),
         long = "input",
         value_name = "INPUT_FILE",
-        help = "Input mixed format file path"
+        help = "Input mixed format file path, '-' reads stdin"
     )]
     pub input_file: String,
     #[clap(
         short('o'),
         long = "output",
         value_name = "OUTPUT_DIR",
-        help = "Output directory for converted segments",
-        default_value = "output"
+        value_parser = parse_output_template,
+        help = "Output directory for converted segments, supports {date}, {time}, {live_id}, {room_id} and {part} placeholders, {live_id} is the room id of the capture ({{ and }} for literal braces) [default: <room_id>_<start> under the current directory]"
     )]
-    pub output_dir: String,
+    pub output_dir: Option<String>,
+    #[clap(
+        long = "force",
+        help = "Write into an existing templated output directory instead of appending -1, -2, ...",
+        default_value = "false"
+    )]
+    pub force: bool,
+    #[clap(
+        long = "no-segment-state",
+        help = "Do not repeat the full object state at the start of each segment, segments are then not seekable on their own",
+        default_value = "false"
+    )]
+    pub no_segment_state: bool,
+    #[clap(
+        long = "no-camera-swap",
+        help = "Keep the captured FixedCamera/Cameraman instantiation order instead of putting FixedCamera first",
+        default_value = "false"
+    )]
+    pub no_camera_swap: bool,
+    #[clap(
+        long = "dedup",
+        value_name = "DURATION",
+        help = "Drop packets repeated with the same timestamp and digest within this window, for overlapping captures after reconnects, plain numbers are milliseconds (default 5s)",
+        num_args = 0..=1,
+        default_missing_value = "5s",
+        value_parser = parse::milliseconds
+    )]
+    pub dedup: Option<HumanDuration>,
+    #[clap(
+        long = "timing-log",
+        value_name = "FILE",
+        help = "Write per-file processing times (filename, size, packets, milliseconds) to this CSV as each input file completes"
+    )]
+    pub timing_log: Option<String>,
+    #[clap(
+        long = "drop-keepalive",
+        value_name = "KIND",
+        help = "Drop keepalive packets before conversion: 'pong' drops Pong packets, 'all' also drops Data(false) packets",
+        num_args = 0..=1,
+        default_missing_value = "pong"
+    )]
+    pub drop_keepalive: Option<String>,
     #[clap(
         short('d'),
         long = "duration",
-        value_name = "SECONDS",
-        help = "Segment duration in seconds",
-        default_value = "10"
+        value_name = "DURATION",
+        help = "Segment duration in whole seconds, e.g. 10s or 1m, plain numbers are seconds [default: 10s]",
+        value_parser = parse::seconds
     )]
-    pub segment_duration: u64,
+    pub segment_duration: Option<HumanDuration>,
     #[clap(long = "split", help = "Split segments", default_value = "false")]
     pub split: bool,
     #[clap(
         long = "timeshift",
         value_name = "MILLSECONDS",
-        help = "Time shift in mill seconds, shift all packets' timestamps",
-        default_value = "0"
+        help = "Time shift in mill seconds, shift all packets' timestamps [default: 0]"
     )]
-    pub timeshift: i64,
+    pub timeshift: Option<i64>,
     #[clap(
         long = "start-time",
         value_name = "TIME",
@@ -214,10 +436,119 @@ pub struct ArgsConvert {
     pub metadata_path: Option<String>,
     #[clap(
         long = "auto-timestamp",
-        help = "Auto adjust timestamps to ensure chronological order",
+        help = "Rebuild timestamps from DateTimeReceiver dates and MusicBroadcaster packets, for captures with those objects",
         default_value = "false"
     )]
     pub auto_timestamp: bool,
+    #[clap(
+        long = "smooth-timestamps",
+        help = "Clamp timestamp jitter and remove backward jumps at reconnect seams, for captures without DateTimeReceiver/MusicBroadcaster",
+        default_value = "false"
+    )]
+    pub smooth_timestamps: bool,
+    #[clap(
+        long = "events-vtt",
+        help = "Write events.vtt with object instantiate/destroy cues",
+        default_value = "false"
+    )]
+    pub events_vtt: bool,
+    #[clap(
+        long = "include-object-ids",
+        value_name = "IDS",
+        value_delimiter = ',',
+        conflicts_with = "exclude_object_ids",
+        help = "Only keep object frames with these comma-separated object ids"
+    )]
+    pub include_object_ids: Option<Vec<i32>>,
+    #[clap(
+        long = "exclude-object-ids",
+        value_name = "IDS",
+        value_delimiter = ',',
+        help = "Drop object frames with these comma-separated object ids"
+    )]
+    pub exclude_object_ids: Option<Vec<i32>>,
+    #[clap(
+        long = "reset-on-rejoin",
+        help = "Switch to the new room when a later JoinRoomResponse arrives",
+        default_value = "false"
+    )]
+    pub reset_on_rejoin: bool,
+    #[clap(
+        long = "room-id-override",
+        value_name = "ID",
+        help = "Write this room id instead of the captured one, e.g. to anonymize output"
+    )]
+    pub room_id_override: Option<String>,
+    #[clap(
+        long = "provenance",
+        help = "Write provenance.bin tracing each output packet to its input file and offset",
+        default_value = "false"
+    )]
+    pub provenance: bool,
+    #[clap(
+        long = "output-target",
+        value_name = "TARGET",
+        help = "Write each part as a directory (dir) or a single uncompressed archive (zip, tar) [default: dir]"
+    )]
+    pub output_target: Option<String>,
+    #[clap(
+        long = "legacy-start-time",
+        value_name = "TIME",
+        help = "Stamp legacy packets from this rfc3339 time instead of the time they are read, for reproducible output"
+    )]
+    pub legacy_start_time: Option<String>,
+    #[clap(
+        long = "legacy-packet-interval",
+        value_name = "DURATION",
+        help = "Time between legacy packets after --legacy-start-time, plain numbers are milliseconds [default: 100ms]",
+        requires = "legacy_start_time",
+        value_parser = parse::milliseconds
+    )]
+    pub legacy_packet_interval: Option<HumanDuration>,
+    #[clap(
+        long = "segment-base-url",
+        value_name = "URL",
+        help = "Absolute http(s) URL the segments will be served from, prefixed to the segment names in index.m3u8"
+    )]
+    pub segment_base_url: Option<String>,
+    #[clap(
+        long = "out-of-order",
+        value_name = "POLICY",
+        help = "Packets stamped earlier than one before them: 'reorder' within the window, 'clamp' to the previous timestamp or 'error' [default: reorder]"
+    )]
+    pub out_of_order: Option<String>,
+    #[clap(
+        long = "reorder-window",
+        value_name = "DURATION",
+        help = "How far back a packet may be stamped and still count as out of order, larger jumps are kept as reconnect seams. Plain numbers are milliseconds [default: 2s]",
+        value_parser = parse::milliseconds
+    )]
+    pub reorder_window: Option<HumanDuration>,
+    #[clap(
+        long = "upload",
+        help = "Upload each segment to R2 as soon as it is written, the playlist last",
+        default_value = "false"
+    )]
+    pub upload: bool,
+    #[clap(long = "bucket", value_name = "BUCKET", help = t!("motion.cli.command.upload.args.bucket").to_string())]
+    pub bucket: Option<String>,
+    #[clap(long = "account-id", value_name = "ACCOUNT_ID", help = t!("motion.cli.command.upload.args.account_id").to_string())]
+    pub account_id: Option<String>,
+    #[clap(long = "access-key", value_name = "ACCESS_KEY", help = t!("motion.cli.command.upload.args.access_key").to_string())]
+    pub access_key: Option<String>,
+    #[clap(long = "secret-key", value_name = "SECRET_KEY", help = t!("motion.cli.command.upload.args.secret_key").to_string())]
+    pub secret_key: Option<String>,
+    #[clap(long = "upload-prefix", value_name = "PREFIX", help = t!("motion.cli.command.upload.args.prefix").to_string())]
+    pub upload_prefix: Option<String>,
+    #[clap(long = "upload-concurrent", value_name = "CONCURRENT", help = t!("motion.cli.command.upload.args.concurrent").to_string(), default_value = "4")]
+    pub upload_concurrent: usize,
+    #[clap(
+        long = "upload-queue",
+        value_name = "FILES",
+        help = "Written files waiting for upload before the conversion pauses",
+        default_value = "8"
+    )]
+    pub upload_queue: usize,
     #[cfg(feature = "audio")]
     #[clap(
         long = "audio-only",
@@ -233,16 +564,15 @@ pub struct ArgsExtract {
         short('i'),
         long = "input",
         value_name = "INPUT_DIR",
-        help = "Input standard packet directory",
-        default_value = "data",
+        help = "Input standard packet directory (default: the data directory)",
         global(true)
     )]
-    pub input_dir: String,
+    pub input_dir: Option<String>,
     #[clap(
         short('o'),
         long = "output",
         value_name = "OUTPUT_PATH",
-        help = "Output path (audio: directory, image: file; default stdout for image)",
+        help = "Output path (audio: directory, image and range: file; default stdout for image)",
         global(true)
     )]
     pub output_dir: Option<String>,
@@ -251,46 +581,418 @@ pub struct ArgsExtract {
     #[clap(long = "file-count-limit", value_name = "FILE_COUNT", help = "Maximum number of files to process", default_value_t = usize::MAX)]
     pub file_count_limit: usize,
     #[clap(
-        long = "data-start-time",
-        value_name = "TIME",
-        help = "Data start time in rfc3339 format (e.g., 2025-08-21T00:00:00Z)"
+        long = "data-start-time",
+        value_name = "TIME",
+        help = "Data start time in rfc3339 format (e.g., 2025-08-21T00:00:00Z)",
+        global(true)
+    )]
+    pub data_start_time: Option<String>,
+    #[clap(
+        long = "data-end-time",
+        value_name = "TIME",
+        help = "Data end time in rfc3339 format (e.g., 2025-08-21T00:00:00Z)",
+        global(true)
+    )]
+    pub data_end_time: Option<String>,
+    #[clap(
+        long = "strict",
+        help = "Fail immediately on packet parse errors",
+        default_value = "false"
+    )]
+    pub strict: bool,
+    #[command(subcommand)]
+    pub target: ExtractSubcommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ExtractSubcommands {
+    #[cfg(feature = "audio")]
+    Audio(ArgsExtractAudio),
+    Image(ArgsExtractImage),
+    /// Copy the packets between --data-start-time and --data-end-time of a capture file
+    /// to a new capture file in the same format
+    Range(ArgsExtractRange),
+}
+
+#[cfg(feature = "audio")]
+#[derive(Debug, ClapArgs, Default)]
+pub struct ArgsExtractAudio {}
+
+#[derive(Debug, ClapArgs)]
+pub struct ArgsExtractImage {
+    #[clap(
+        long = "json",
+        help = "Print deduplicated names as JSON array",
+        default_value = "false"
+    )]
+    pub json: bool,
+}
+
+#[derive(Debug, ClapArgs)]
+pub struct ArgsExtractRange {
+    #[clap(value_name = "FILE", help = "Capture file to cut the range from")]
+    pub input_path: String,
+    #[clap(
+        short('t'),
+        long = "input-format",
+        value_name = "FORMAT",
+        help = "Input format: 'standard', 'mixed', 'auto'",
+        default_value = "auto"
+    )]
+    pub input_format: String,
+}
+
+#[derive(Debug, ClapArgs)]
+pub struct ArgsEdit {
+    #[command(subcommand)]
+    pub command: EditSubcommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum EditSubcommands {
+    /// Estimate the offset between a converted replay and the official video
+    Sync(ArgsEditSync),
+    /// Rewrite packet timestamps of a converted replay with a piecewise-linear time map
+    Retime(ArgsEditRetime),
+    /// Rewrite frames of a converted replay with a YAML/JSON list of match/action rules
+    Transform(ArgsEditTransform),
+}
+
+#[derive(Debug, ClapArgs)]
+pub struct ArgsEditSync {
+    #[clap(
+        long = "replay",
+        value_name = "DIR",
+        help = "Converted replay directory (with index.md)"
+    )]
+    pub replay_dir: String,
+    #[clap(
+        long = "video-playlist",
+        value_name = "URL|FILE",
+        help = "Official video HLS playlist, local file or URL"
+    )]
+    pub video_playlist: String,
+    #[clap(
+        long = "write",
+        help = "Write the offset into index.md as video_sync_offset_ms",
+        default_value = "false"
+    )]
+    pub write: bool,
+}
+
+#[derive(Debug, ClapArgs)]
+pub struct ArgsEditRetime {
+    #[clap(
+        long = "input",
+        value_name = "DIR",
+        help = "Converted replay directory (with index.m3u8)"
+    )]
+    pub input_dir: String,
+    #[clap(
+        long = "map",
+        value_name = "FILE",
+        help = "Control points, one `replay_time -> target_time` per line, RFC3339 or seconds from the first packet"
+    )]
+    pub map: String,
+    #[clap(long = "output", value_name = "DIR", help = "Output directory")]
+    pub output_dir: String,
+}
+
+#[derive(Debug, ClapArgs)]
+pub struct ArgsEditTransform {
+    #[clap(
+        long = "input",
+        value_name = "DIR",
+        help = "Converted replay directory (with index.m3u8)"
+    )]
+    pub input_dir: String,
+    #[clap(
+        long = "script",
+        value_name = "FILE",
+        help = "Rules as a YAML or JSON list of `match` and `action`"
+    )]
+    pub script: String,
+    #[clap(
+        long = "output",
+        value_name = "DIR",
+        help = "Output directory",
+        required_unless_present = "dry_run"
+    )]
+    pub output_dir: Option<String>,
+    #[clap(
+        long = "dry-run",
+        help = "Print how many frames each rule matches without writing anything",
+        default_value = "false"
+    )]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, ClapArgs)]
+pub struct ArgsInfo {
+    #[clap(value_name = "FILE", help = "Capture file path, '-' reads stdin")]
+    pub file_path: String,
+    #[clap(
+        short('t'),
+        long = "input-format",
+        value_name = "FORMAT",
+        help = "Input format: 'standard', 'mixed', 'legacy', 'auto'",
+        default_value = "auto"
+    )]
+    pub input_format: String,
+    #[clap(
+        long = "brief",
+        help = "Stop at the first Room frame and print one tab-separated line",
+        default_value = "false"
+    )]
+    pub brief: bool,
+}
+
+#[derive(Debug, ClapArgs)]
+pub struct ArgsDetect {
+    #[clap(value_name = "FILE", help = "Capture file paths", required = true)]
+    pub file_paths: Vec<String>,
+}
+
+#[derive(Debug, ClapArgs)]
+pub struct ArgsCoverage {
+    #[clap(value_name = "PATH", help = "Capture file or directory path")]
+    pub input_path: String,
+    #[clap(
+        short('t'),
+        long = "input-format",
+        value_name = "FORMAT",
+        help = "Input format: 'standard', 'mixed', 'legacy', 'auto'",
+        default_value = "auto"
+    )]
+    pub input_format: String,
+    #[clap(
+        short('o'),
+        long = "output",
+        value_name = "FILE",
+        help = "Write the table to a file instead of stdout"
+    )]
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, ClapArgs)]
+pub struct ArgsCheckTimestamps {
+    #[clap(value_name = "PATH", help = "Capture file or directory path")]
+    pub input_path: String,
+    #[clap(
+        short('t'),
+        long = "input-format",
+        value_name = "FORMAT",
+        help = "Input format: 'standard', 'mixed', 'legacy', 'auto'",
+        default_value = "auto"
+    )]
+    pub input_format: String,
+    #[clap(
+        long = "gap-threshold",
+        value_name = "DURATION",
+        help = "Count consecutive packets more than DURATION apart as a gap, plain numbers are milliseconds",
+        default_value = "5s",
+        value_parser = parse::milliseconds
+    )]
+    pub gap_threshold: HumanDuration,
+    #[clap(
+        long = "max-listed",
+        value_name = "N",
+        help = "Number of backward jumps to list",
+        default_value = "20"
+    )]
+    pub max_listed: usize,
+    #[clap(
+        short('o'),
+        long = "output",
+        value_name = "FILE",
+        help = "Write the report to a file instead of stdout"
+    )]
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, ClapArgs)]
+pub struct ArgsCheckEncoding {
+    #[clap(value_name = "PATH", help = "Capture file or directory path")]
+    pub input_path: String,
+    #[clap(
+        short('t'),
+        long = "input-format",
+        value_name = "FORMAT",
+        help = "Input format: 'standard', 'mixed', 'legacy', 'auto'",
+        default_value = "auto"
+    )]
+    pub input_format: String,
+    #[clap(
+        long = "max-listed",
+        value_name = "N",
+        help = "Number of drifted packets to list",
+        default_value = "20"
+    )]
+    pub max_listed: usize,
+    #[clap(
+        short('o'),
+        long = "output",
+        value_name = "FILE",
+        help = "Write the report to a file instead of stdout"
+    )]
+    pub output_path: Option<String>,
+}
+
+#[derive(Debug, ClapArgs)]
+pub struct ArgsPrefabs {
+    #[clap(value_name = "PATH", help = "Capture file or directory path")]
+    pub input_path: String,
+    #[clap(
+        short('t'),
+        long = "input-format",
+        value_name = "FORMAT",
+        help = "Input format: 'standard', 'mixed', 'legacy', 'auto'",
+        default_value = "auto"
+    )]
+    pub input_format: String,
+    #[clap(
+        short('o'),
+        long = "output",
+        value_name = "FILE",
+        help = "Write the list to a file instead of stdout"
+    )]
+    pub output_path: Option<String>,
+    #[clap(
+        long = "json",
+        help = "Print prefabs as JSON array",
+        default_value = "false"
+    )]
+    pub json: bool,
+}
+
+#[derive(Debug, ClapArgs)]
+pub struct ArgsAnonymize {
+    #[clap(value_name = "PATH", help = "Capture file or directory path")]
+    pub input_path: String,
+    #[clap(
+        short('t'),
+        long = "input-format",
+        value_name = "FORMAT",
+        help = "Input format: 'standard', 'mixed', 'legacy', 'auto'",
+        default_value = "auto"
+    )]
+    pub input_format: String,
+    #[clap(
+        short('o'),
+        long = "output",
+        value_name = "PATH",
+        help = "Cleaned capture, a file for file input or a directory for directory input (standard format)"
+    )]
+    pub output_path: String,
+    #[clap(
+        long = "randomize-room-id",
+        help = "Also replace room ids with a random id, consistently across the capture",
+        default_value = "false"
+    )]
+    pub randomize_room_id: bool,
+}
+
+#[derive(Debug, ClapArgs)]
+pub struct ArgsSplit {
+    #[clap(value_name = "FILE", help = "Capture file path")]
+    pub input_path: String,
+    #[clap(
+        short('t'),
+        long = "input-format",
+        value_name = "FORMAT",
+        help = "Input format: 'standard', 'mixed', 'legacy', 'auto'",
+        default_value = "auto"
+    )]
+    pub input_format: String,
+    #[clap(
+        short('o'),
+        long = "output",
+        value_name = "OUTPUT_DIR",
+        help = "Directory for the shards, named <name>_1.bin, <name>_2.bin, ... in the input format"
+    )]
+    pub output_dir: String,
+    #[clap(
+        long = "split-by",
+        value_name = "KIND",
+        help = "Cut shards by 'duration' (seconds), 'count' (packets) or 'size' (MiB)",
+        default_value = "duration"
+    )]
+    pub split_by: String,
+    #[clap(
+        long = "every",
+        value_name = "VALUE",
+        help = "Largest shard in the unit of --split-by"
+    )]
+    pub every: u64,
+}
+
+#[derive(Debug, ClapArgs)]
+pub struct ArgsVerify {
+    #[clap(
+        value_name = "DIR",
+        help = "Converted output directory containing index.m3u8"
+    )]
+    pub input_dir: String,
+    #[clap(
+        long = "strict-state",
+        help = "Replay each segment from an empty object set and report updates or destroys of objects that are not alive",
+        default_value = "false"
+    )]
+    pub strict_state: bool,
+    #[clap(
+        long = "max-violations",
+        value_name = "N",
+        help = "Number of state violations to report",
+        default_value = "20"
     )]
-    pub data_start_time: Option<String>,
+    pub max_violations: usize,
+}
+
+#[derive(Debug, ClapArgs)]
+pub struct ArgsJoinParts {
     #[clap(
-        long = "data-end-time",
-        value_name = "TIME",
-        help = "Data end time in rfc3339 format (e.g., 2025-08-21T00:00:00Z)"
+        value_name = "DIR",
+        help = "Converted part directories in playback order, e.g. output output_001",
+        required = true
     )]
-    pub data_end_time: Option<String>,
+    pub part_dirs: Vec<String>,
     #[clap(
-        long = "strict",
-        help = "Fail immediately on packet parse errors",
-        default_value = "false"
+        short('o'),
+        long = "output",
+        value_name = "OUTPUT_DIR",
+        help = "Directory for the renumbered segments and the joined index.m3u8"
     )]
-    pub strict: bool,
+    pub output_dir: String,
+}
+
+#[derive(Debug, ClapArgs)]
+pub struct ArgsProvenance {
     #[command(subcommand)]
-    pub target: ExtractSubcommands,
+    pub command: ProvenanceSubcommands,
 }
 
 #[derive(Debug, Subcommand)]
-pub enum ExtractSubcommands {
-    #[cfg(feature = "audio")]
-    Audio(ArgsExtractAudio),
-    Image(ArgsExtractImage),
+pub enum ProvenanceSubcommands {
+    /// Print the input file, offset and timestamp adjustments of an output packet
+    Lookup(ArgsProvenanceLookup),
 }
 
-#[cfg(feature = "audio")]
-#[derive(Debug, ClapArgs, Default)]
-pub struct ArgsExtractAudio {}
-
 #[derive(Debug, ClapArgs)]
-pub struct ArgsExtractImage {
+pub struct ArgsProvenanceLookup {
     #[clap(
-        long = "json",
-        help = "Print deduplicated names as JSON array",
-        default_value = "false"
+        value_name = "DIR",
+        help = "Converted output directory containing provenance.bin",
+        default_value = "."
     )]
-    pub json: bool,
+    pub input_dir: String,
+    #[clap(long = "segment", value_name = "N", help = "Segment number")]
+    pub segment: u32,
+    #[clap(
+        long = "packet",
+        value_name = "M",
+        help = "Zero based packet index inside the segment"
+    )]
+    pub packet: u32,
 }
 
 #[derive(Subcommand, Debug)]
@@ -298,9 +1000,21 @@ pub enum Commands {
     Download(ArgsDownload),
     Upload(ArgsUpload),
     Sync(ArgsSync),
-    Analyze(ArgsAnalyze),
-    Convert(ArgsConvert),
+    Analyze(Box<ArgsAnalyze>),
+    Convert(Box<ArgsConvert>),
     Extract(ArgsExtract),
+    Edit(ArgsEdit),
+    Info(ArgsInfo),
+    Detect(ArgsDetect),
+    Coverage(ArgsCoverage),
+    CheckTimestamps(ArgsCheckTimestamps),
+    CheckEncoding(ArgsCheckEncoding),
+    Prefabs(ArgsPrefabs),
+    Anonymize(ArgsAnonymize),
+    Split(ArgsSplit),
+    Verify(ArgsVerify),
+    JoinParts(ArgsJoinParts),
+    Provenance(ArgsProvenance),
 }
 
 #[tokio::main]
@@ -322,13 +1036,18 @@ async fn main() -> Result<()> {
                     download_type = Some("mrs".into());
                 }
             }
+            let cancel = cancel_on_shutdown();
             let downloader: Box<dyn BaseDownloader> = match download_type.as_deref() {
-                Some("als") => {
-                    Box::new(AlsDownloader::with_progress(download_args.parallel, !quiet))
-                }
-                Some("mrs") => {
-                    Box::new(MrsDownloader::with_progress(download_args.parallel, !quiet))
-                }
+                Some("als") => Box::new(
+                    AlsDownloader::with_progress(download_args.parallel, !quiet)
+                        .with_ignore_space_check(download_args.ignore_space_check)
+                        .with_cancel(cancel),
+                ),
+                Some("mrs") => Box::new(
+                    MrsDownloader::with_progress(download_args.parallel, !quiet)
+                        .with_ignore_space_check(download_args.ignore_space_check)
+                        .with_cancel(cancel),
+                ),
                 _ => {
                     return Err(Error::msg(format!(
                         "Unknown download type: {:?}",
@@ -336,13 +1055,49 @@ async fn main() -> Result<()> {
                     )));
                 }
             };
-            let download_dir = download_args
-                .download_directory
-                .as_deref()
-                .unwrap_or("data");
-            downloader
-                .download(download_url, Path::new(download_dir))
-                .await?;
+            let download_dir = disk::resolve_data_dir(
+                download_args
+                    .download_directory
+                    .as_deref()
+                    .or(args.data_dir.as_deref()),
+            );
+            if download_args.verify_only {
+                let report = downloader.verify(download_url, &download_dir).await?;
+                for entry in report.problems() {
+                    match &entry.status {
+                        VerifyStatus::Missing => warn!("❌ Missing: {}", entry.filename),
+                        VerifyStatus::SizeMismatch { local, remote } => warn!(
+                            "⚠️ Size mismatch: {} (local {} bytes, remote {} bytes)",
+                            entry.filename, local, remote
+                        ),
+                        VerifyStatus::Corrupt { local, remote } => warn!(
+                            "⚠️ Checksum mismatch: {} (local MD5 {}, remote MD5 {})",
+                            entry.filename, local, remote
+                        ),
+                        VerifyStatus::RemoteError(e) => {
+                            warn!("❓ Unable to check: {} ({})", entry.filename, e)
+                        }
+                        VerifyStatus::Ok => {}
+                    }
+                }
+                info!(
+                    "📊 {} of {} files verified in '{}'",
+                    report.ok_count(),
+                    report.entries.len(),
+                    report.target_dir.display()
+                );
+                if !report.is_ok() {
+                    return Err(Error::msg(format!(
+                        "{} files failed verification",
+                        report.entries.len() - report.ok_count()
+                    )));
+                }
+            } else {
+                let download_dir = disk::prepare_data_dir(&download_dir)?;
+                info!("📂 Data directory: {}", download_dir.display());
+                let report = downloader.download(download_url, &download_dir).await?;
+                check_download_report(&report)?;
+            }
         }
         Some(Commands::Upload(ref upload_args)) => {
             let uploader = R2Uploader::from_env_or_args(
@@ -385,9 +1140,25 @@ async fn main() -> Result<()> {
                         "no prefix".to_string()
                     }
                 );
-                uploader
-                    .upload_file(path, upload_args.prefix.as_deref())
-                    .await?;
+                if upload_args.skip_existing {
+                    let remote_key = match upload_args.prefix.as_deref() {
+                        Some(key) => key.to_string(),
+                        None => path
+                            .file_name()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                            .to_string(),
+                    };
+                    if uploader.upload_if_not_exists(path, &remote_key).await?
+                        == UploadResult::Skipped
+                    {
+                        info!("⏭️ Remote key '{}' already exists, skipped", remote_key);
+                    }
+                } else {
+                    uploader
+                        .upload_file(path, upload_args.prefix.as_deref())
+                        .await?;
+                }
             } else if path.is_dir() {
                 info!(
                     "🚀 Starting R2 folder upload from '{}' to bucket '{}'",
@@ -402,9 +1173,19 @@ async fn main() -> Result<()> {
                         "no prefix".to_string()
                     }
                 );
-                uploader
-                    .upload_folder(path, upload_args.prefix.as_deref())
-                    .await?;
+                if upload_args.skip_existing {
+                    let summary = uploader
+                        .upload_directory_incremental(path, upload_args.prefix.as_deref())
+                        .await?;
+                    info!(
+                        "📊 {} files uploaded, {} already existed",
+                        summary.uploaded, summary.skipped
+                    );
+                } else {
+                    uploader
+                        .upload_folder(path, upload_args.prefix.as_deref())
+                        .await?;
+                }
             } else {
                 return Err(Error::msg(format!(
                     "Path is neither a file nor a directory: {}",
@@ -419,8 +1200,14 @@ async fn main() -> Result<()> {
             let download_url = sync_args.download_url.trim();
             info!("📥 Phase 1: Downloading from '{}'", download_url);
 
-            let download_dir = sync_args.download_directory.as_deref().unwrap_or("data");
-            let download_path = Path::new(download_dir);
+            let download_path = disk::prepare_data_dir(&disk::resolve_data_dir(
+                sync_args
+                    .download_directory
+                    .as_deref()
+                    .or(args.data_dir.as_deref()),
+            ))?;
+            info!("📂 Data directory: {}", download_path.display());
+            let download_path = download_path.as_path();
 
             let mut download_type = sync_args.download_type.clone();
             if download_type.is_none() {
@@ -432,15 +1219,18 @@ async fn main() -> Result<()> {
                 }
             }
 
+            let cancel = cancel_on_shutdown();
             let downloader: Box<dyn BaseDownloader> = match download_type.as_deref() {
-                Some("als") => Box::new(AlsDownloader::with_progress(
-                    sync_args.download_parallel,
-                    !quiet,
-                )),
-                Some("mrs") => Box::new(MrsDownloader::with_progress(
-                    sync_args.download_parallel,
-                    !quiet,
-                )),
+                Some("als") => Box::new(
+                    AlsDownloader::with_progress(sync_args.download_parallel, !quiet)
+                        .with_ignore_space_check(sync_args.ignore_space_check)
+                        .with_cancel(cancel),
+                ),
+                Some("mrs") => Box::new(
+                    MrsDownloader::with_progress(sync_args.download_parallel, !quiet)
+                        .with_ignore_space_check(sync_args.ignore_space_check)
+                        .with_cancel(cancel),
+                ),
                 _ => {
                     return Err(Error::msg(format!(
                         "Unknown download type: {:?}",
@@ -449,7 +1239,8 @@ async fn main() -> Result<()> {
                 }
             };
 
-            downloader.download(download_url, download_path).await?;
+            let report = downloader.download(download_url, download_path).await?;
+            check_download_report(&report)?;
 
             info!("✅ Download completed successfully!");
             info!("📤 Phase 2: Uploading to R2");
@@ -468,7 +1259,7 @@ async fn main() -> Result<()> {
             if !download_path.exists() {
                 return Err(Error::msg(format!(
                     "Download directory does not exist: {}",
-                    download_dir
+                    download_path.display()
                 )));
             }
 
@@ -496,18 +1287,23 @@ async fn main() -> Result<()> {
                     "no prefix".to_string()
                 }
             );
-            uploader
-                .upload_folder(
-                    &target_folder,
-                    Some(
-                        sync_args
-                            .prefix
-                            .clone()
-                            .unwrap_or(get_bucket_prefix(download_url)?)
-                            .deref(),
-                    ),
-                )
-                .await?;
+            let prefix = sync_args
+                .prefix
+                .clone()
+                .unwrap_or(get_bucket_prefix(download_url)?);
+            if sync_args.skip_existing {
+                let summary = uploader
+                    .upload_directory_incremental(&target_folder, Some(&prefix))
+                    .await?;
+                info!(
+                    "📊 {} files uploaded, {} already existed",
+                    summary.uploaded, summary.skipped
+                );
+            } else {
+                uploader
+                    .upload_folder(&target_folder, Some(&prefix))
+                    .await?;
+            }
 
             // Delete downloaded files if requested
             if sync_args.delete_after_done {
@@ -527,117 +1323,350 @@ async fn main() -> Result<()> {
             info!("🎉 Download + Upload finished!");
         }
         Some(Commands::Analyze(analyze_args)) => {
+            if analyze_args.watch && proto::reader::is_stdin(Path::new(&analyze_args.file_path)) {
+                return Err(Error::msg("--watch cannot be used when reading stdin"));
+            }
+            if analyze_args.follow && !Path::new(&analyze_args.file_path).exists() {
+                return Err(Error::msg(
+                    "--follow needs an existing capture file or directory",
+                ));
+            }
             // Handle standard and mixed analysis
             info!(
                 "🔍 Starting ALS packet analysis for file: {}",
                 analyze_args.file_path
             );
+            let limits = if analyze_args.all {
+                proto::application::AnalyzeLimits::unlimited()
+            } else {
+                proto::application::AnalyzeLimits::default()
+                    .with_max_packets(analyze_args.max_packets.unwrap_or(usize::MAX))
+                    .with_max_packets_per_file(
+                        analyze_args.max_packets_per_file.unwrap_or(usize::MAX),
+                    )
+                    .with_max_files(analyze_args.max_files.unwrap_or(usize::MAX))
+                    .with_max_bytes_per_file(
+                        analyze_args.max_file_size.map_or(u64::MAX, ByteSize::bytes),
+                    )
+                    .with_max_packet_size(
+                        analyze_args
+                            .max_packet_size
+                            .map_or(usize::MAX, ByteSize::as_usize),
+                    )
+                    .with_time_range(analyze_args.data_start_time, analyze_args.data_end_time)
+            };
             info!(
-                "📊 Analysis type: {}, Packet count: {}",
-                analyze_args.analysis_type, analyze_args.packet_count
+                "📊 Input format: {}, Limits: {:?}",
+                analyze_args.input_format, limits
             );
             info!("📄 Output will be written to: {}", analyze_args.output_path);
+            if let Some(dir) = &analyze_args.packet_files {
+                info!("🗂️ Packet details will be written to: {}", dir);
+            }
             // Convert async context to sync for the analysis functions
             let file_path = analyze_args.file_path.clone();
             let output_path = analyze_args.output_path.clone();
-            let packet_count = analyze_args.packet_count;
-            let analysis_type = analyze_args.analysis_type.clone();
+            let input_format = parse_input_format(&analyze_args.input_format, args.live_marker)?;
+            let options = proto::application::AnalyzeOptions::default()
+                .with_jobs(analyze_args.jobs)
+                .with_sort_key(analyze_args.sort_by.parse()?)
+                .with_csv_summary(analyze_args.csv_summary.map(std::path::PathBuf::from))
+                .with_stats_json(analyze_args.stats_json.map(std::path::PathBuf::from))
+                .with_packet_files(analyze_args.packet_files.map(std::path::PathBuf::from))
+                .with_unknown_samples(analyze_args.unknown_samples.unwrap_or(0))
+                .with_parallel_digest(analyze_args.parallel_digest)
+                .with_parallel_decode(analyze_args.parallel_decode)
+                .with_fail_fast(analyze_args.fail_fast)
+                .with_gap_threshold(
+                    analyze_args
+                        .gap_threshold
+                        .map(|gap| TimeDelta::from_std(gap.as_duration()))
+                        .transpose()?,
+                )
+                .with_keepalive_filter(
+                    analyze_args
+                        .drop_keepalive
+                        .as_deref()
+                        .unwrap_or("none")
+                        .parse()?,
+                );
+            if analyze_args.follow {
+                let stop = Arc::new(AtomicBool::new(false));
+                let stop_signal = stop.clone();
+                tokio::spawn(async move {
+                    if shutdown_signal().await.is_ok() {
+                        stop_signal.store(true, Ordering::Relaxed);
+                    }
+                });
+                info!("👀 Following {}, press Ctrl+C to stop", file_path);
+                let packets = tokio::task::spawn_blocking(move || {
+                    let mut writer =
+                        proto::formatter::OutputWriter::new(Some(output_path.as_str()))?;
+                    proto::application::follow_with_sink(
+                        &file_path,
+                        &mut writer,
+                        input_format,
+                        &limits,
+                        &options,
+                        &proto::application::FollowOptions::default(),
+                        &stop,
+                    )
+                })
+                .await??;
+                info!("🛑 Follow stopped after {} packets", packets);
+                return Ok(());
+            }
             proto::application::analyze(
                 file_path.as_ref(),
                 Some(output_path.as_ref()),
-                analysis_type.as_ref(),
-                packet_count,
-                analyze_args.data_start_time,
-                analyze_args.data_end_time,
+                input_format,
+                limits.clone(),
+                options.clone(),
             )?;
             info!("✅ ALS packet analysis completed successfully!");
+
+            if analyze_args.watch {
+                let stop = Arc::new(AtomicBool::new(false));
+                let stop_signal = stop.clone();
+                tokio::spawn(async move {
+                    if shutdown_signal().await.is_ok() {
+                        stop_signal.store(true, Ordering::Relaxed);
+                    }
+                });
+                info!(
+                    "👀 Watching {} for changes, press Ctrl+C to stop",
+                    file_path
+                );
+                tokio::task::spawn_blocking(move || {
+                    watch_analysis(
+                        Path::new(&file_path),
+                        &output_path,
+                        input_format,
+                        &limits,
+                        &options,
+                        &stop,
+                    )
+                })
+                .await??;
+                info!("🛑 Watch stopped");
+            }
         }
         Some(Commands::Convert(convert_args)) => {
             info!("🔄 Starting ALS conversion from mixed to standard format");
             info!("📂 Input file: {}", convert_args.input_file);
-            info!("📁 Output directory: {}", convert_args.output_dir);
-            info!(
-                "⏱️ Segment duration: {} seconds",
-                convert_args.segment_duration
-            );
 
             let input_path = std::path::Path::new(&convert_args.input_file);
-            if !input_path.exists() {
+            if !proto::reader::is_stdin(input_path) && !input_path.exists() {
                 return Err(Error::msg(format!(
                     "Input file does not exist: {}",
                     convert_args.input_file
                 )));
             }
 
+            let file_options = match &convert_args.config {
+                Some(config_path) => {
+                    info!("⚙️ Conversion options file: {}", config_path);
+                    ConversionOptions::from_file(Path::new(config_path))?
+                }
+                None => ConversionOptions::default(),
+            };
+            let options = file_options.overridden_by(ConversionOptions {
+                input_format: convert_args.input_format,
+                output: convert_args.output_dir,
+                segment_duration: convert_args
+                    .segment_duration
+                    .map(whole_seconds)
+                    .transpose()?,
+                timeshift: convert_args.timeshift,
+                split: convert_args.split.then_some(true),
+                start_time: convert_args.start_time,
+                data_start_time: convert_args.data_start_time,
+                data_end_time: convert_args.data_end_time,
+                metadata_path: convert_args.metadata_path,
+                auto_timestamp: convert_args.auto_timestamp.then_some(true),
+                smooth_timestamps: convert_args.smooth_timestamps.then_some(true),
+                events_vtt: convert_args.events_vtt.then_some(true),
+                include_object_ids: convert_args.include_object_ids,
+                exclude_object_ids: convert_args.exclude_object_ids,
+                reset_on_rejoin: convert_args.reset_on_rejoin.then_some(true),
+                force: convert_args.force.then_some(true),
+                segment_state: convert_args.no_segment_state.then_some(false),
+                camera_swap: convert_args.no_camera_swap.then_some(false),
+                dedup: convert_args
+                    .dedup
+                    .map(|window| window.as_duration().as_millis() as i64),
+                timing_log: convert_args.timing_log.map(PathBuf::from),
+                drop_keepalive: convert_args.drop_keepalive,
+                room_id_override: convert_args.room_id_override,
+                provenance: convert_args.provenance.then_some(true),
+                output_target: convert_args.output_target,
+                legacy_start_time: convert_args.legacy_start_time,
+                legacy_packet_interval: convert_args
+                    .legacy_packet_interval
+                    .map(|interval| interval.as_duration().as_millis() as i64),
+                segment_base_url: convert_args.segment_base_url,
+                out_of_order: convert_args.out_of_order,
+                reorder_window: convert_args
+                    .reorder_window
+                    .map(|window| window.as_duration().as_millis() as i64),
+            });
+            let mut config = options.to_config()?;
+            config.input_format = config.input_format.with_live_marker(args.live_marker)?;
+            let segment_duration = options.segment_duration();
+            info!("⏱️ Segment duration: {} seconds", segment_duration);
+
+            let output_dir = match &options.output {
+                Some(output_dir) => {
+                    AlsConverter::fill_live_id(output_dir, input_path, config.input_format)?
+                }
+                None => PathTemplate::escape(
+                    &AlsConverter::default_output_dir(
+                        input_path,
+                        config.input_format,
+                        config.room_id_override.as_deref(),
+                    )?
+                    .to_string_lossy(),
+                ),
+            };
+            info!("📁 Output directory: {}", output_dir);
+
             // Convert async context to sync for the conversion
             let input_file = convert_args.input_file.clone();
-            let output_dir = convert_args.output_dir.clone();
-            let segment_duration = convert_args.segment_duration;
 
             #[cfg(feature = "audio")]
             let use_audio_processing = convert_args.audio_only;
             #[cfg(not(feature = "audio"))]
             let use_audio_processing = false;
-            let converter = AlsConverter::new(segment_duration, use_audio_processing);
-            converter.convert_mixed_to_standard(
-                &input_file,
-                &output_dir,
-                &convert_args.convert_type,
-                // TODO: better args passing
-                convert_args.timeshift,
-                convert_args.split,
-                convert_args.start_time,
-                convert_args.data_start_time,
-                convert_args.data_end_time,
-                convert_args.metadata_path,
-                convert_args.auto_timestamp,
-            )?;
+            let converter = AlsConverter::new(segment_duration, use_audio_processing)
+                .with_cancel(cancel_on_shutdown());
+            let output_target = config.output_target;
+
+            if !convert_args.upload {
+                // blocking, so the shutdown signal handler keeps running
+                let output_dir = output_dir.clone();
+                tokio::task::spawn_blocking(move || {
+                    converter.convert_mixed_to_standard(&input_file, &output_dir, &config)
+                })
+                .await??;
+            } else {
+                let uploader = R2Uploader::from_env_or_args(
+                    convert_args.account_id,
+                    convert_args.access_key,
+                    convert_args.secret_key,
+                    convert_args.bucket,
+                    convert_args.upload_concurrent,
+                    !quiet,
+                )
+                .await?;
+                info!("☁️ Uploading segments to R2 while converting");
+
+                let (sender, queue) = tokio::sync::mpsc::channel(convert_args.upload_queue.max(1));
+                let prefix = convert_args.upload_prefix;
+                let converter =
+                    converter.with_segment_observer(SegmentObserver::new(move |path: &Path| {
+                        let task =
+                            match streamed_upload_task(path, output_target, prefix.as_deref()) {
+                                Ok(task) => task,
+                                Err(e) => {
+                                    warn!("Failed to queue {} for upload: {}", path.display(), e);
+                                    return;
+                                }
+                            };
+                        // blocks the conversion while the queue is full
+                        if sender.blocking_send(task).is_err() {
+                            warn!("Upload queue closed, {} was not uploaded", path.display());
+                        }
+                    }));
+                let upload = tokio::spawn(async move { uploader.upload_queue(queue).await });
+
+                let streamed_output_dir = output_dir.clone();
+                let conversion = tokio::task::spawn_blocking(move || {
+                    converter.convert_mixed_to_standard(&input_file, &streamed_output_dir, &config)
+                })
+                .await?;
+                // the observer, and with it the sender, is dropped with the converter
+                let summary = upload.await?;
+                conversion?;
+                let summary = summary?;
+                info!("📊 {} files uploaded", summary.uploaded);
+            }
             info!("✅ ALS conversion completed successfully!");
-            info!("📄 Output files written to: {}", convert_args.output_dir);
+            info!("📄 Output files written to: {}", output_dir);
         }
         Some(Commands::Extract(extract_args)) => {
-            let output_path = match &extract_args.target {
-                #[cfg(feature = "audio")]
-                ExtractSubcommands::Audio(_) => extract_args
-                    .output_dir
-                    .clone()
-                    .unwrap_or_else(|| "audio".to_string()),
-                ExtractSubcommands::Image(_) => extract_args
-                    .output_dir
-                    .clone()
-                    .unwrap_or_else(|| ".".to_string()),
-            };
-            let output_desc = match &extract_args.target {
-                #[cfg(feature = "audio")]
-                ExtractSubcommands::Audio(_) => output_path.clone(),
-                ExtractSubcommands::Image(_) => extract_args
-                    .output_dir
-                    .clone()
-                    .unwrap_or_else(|| "[stdout]".to_string()),
-            };
-            let image_output_file = match &extract_args.target {
+            let (output_path, output_desc, target_kind) = match &extract_args.target {
                 #[cfg(feature = "audio")]
-                ExtractSubcommands::Audio(_) => None,
-                ExtractSubcommands::Image(_) => extract_args.output_dir.as_ref().map(PathBuf::from),
+                ExtractSubcommands::Audio(_) => {
+                    let output_path = extract_args
+                        .output_dir
+                        .clone()
+                        .unwrap_or_else(|| "audio".to_string());
+                    (
+                        output_path.clone(),
+                        output_path,
+                        ExtractTargetKind::Audio(Default::default()),
+                    )
+                }
+                ExtractSubcommands::Image(image_args) => (
+                    extract_args
+                        .output_dir
+                        .clone()
+                        .unwrap_or_else(|| ".".to_string()),
+                    extract_args
+                        .output_dir
+                        .clone()
+                        .unwrap_or_else(|| "[stdout]".to_string()),
+                    ExtractTargetKind::Image(ImageExtractOptions {
+                        json: image_args.json,
+                        output_file: extract_args.output_dir.as_ref().map(PathBuf::from),
+                    }),
+                ),
+                ExtractSubcommands::Range(range_args) => {
+                    let output_path = extract_args
+                        .output_dir
+                        .as_deref()
+                        .ok_or_else(|| Error::msg("extract range needs --output FILE"))?;
+                    info!(
+                        "✂️ Extracting {} to {} to {}",
+                        extract_args.data_start_time.as_deref().unwrap_or("start"),
+                        extract_args.data_end_time.as_deref().unwrap_or("end"),
+                        output_path
+                    );
+                    let shard = extract_time_range(
+                        Path::new(&range_args.input_path),
+                        parse_input_format(&range_args.input_format, args.live_marker)?,
+                        Path::new(output_path),
+                        parse_rfc3339_utc(
+                            "data-start-time",
+                            extract_args.data_start_time.as_deref(),
+                        )?,
+                        parse_rfc3339_utc("data-end-time", extract_args.data_end_time.as_deref())?,
+                    )?;
+                    info!(
+                        "✅ Wrote {} packets ({} bytes, {} to {}) to {}",
+                        shard.packets,
+                        shard.bytes,
+                        shard.first_timestamp.to_rfc3339(),
+                        shard.last_timestamp.to_rfc3339(),
+                        shard.path.display()
+                    );
+                    return Ok(());
+                }
             };
+            let input_dir = disk::resolve_data_dir(
+                extract_args
+                    .input_dir
+                    .as_deref()
+                    .or(args.data_dir.as_deref()),
+            );
+            let input_dir = std::path::absolute(&input_dir).unwrap_or(input_dir);
             let data_start_time =
                 parse_rfc3339_utc("data-start-time", extract_args.data_start_time.as_deref())?;
             let data_end_time =
                 parse_rfc3339_utc("data-end-time", extract_args.data_end_time.as_deref())?;
 
-            let target_kind = match &extract_args.target {
-                #[cfg(feature = "audio")]
-                ExtractSubcommands::Audio(_) => ExtractTargetKind::Audio(Default::default()),
-                ExtractSubcommands::Image(image_args) => {
-                    ExtractTargetKind::Image(ImageExtractOptions {
-                        json: image_args.json,
-                        output_file: image_output_file,
-                    })
-                }
-            };
-
             let extract_config = ExtractConfig {
-                input_dir: PathBuf::from(&extract_args.input_dir),
+                input_dir: input_dir.clone(),
                 output_dir: PathBuf::from(&output_path),
                 packet_count: extract_args.packet_count,
                 file_count_limit: extract_args.file_count_limit,
@@ -647,7 +1676,7 @@ async fn main() -> Result<()> {
             };
 
             info!("🔍 Starting '{}' extraction", target_kind.name());
-            info!("📂 Input directory: {}", extract_args.input_dir);
+            info!("📂 Input directory: {}", input_dir.display());
             info!("📁 Output: {}", output_desc);
 
             let summary =
@@ -665,11 +1694,430 @@ async fn main() -> Result<()> {
                 summary.errors
             );
         }
+        Some(Commands::Edit(edit_args)) => match edit_args.command {
+            EditSubcommands::Sync(sync_args) => {
+                info!(
+                    "🎬 Estimating video sync offset for: {}",
+                    sync_args.replay_dir
+                );
+                let playlist = if sync_args.video_playlist.starts_with("http://")
+                    || sync_args.video_playlist.starts_with("https://")
+                {
+                    AlsDownloader::new(1)
+                        .fetch_m3u8_content(&sync_args.video_playlist)
+                        .await?
+                } else {
+                    std::fs::read_to_string(&sync_args.video_playlist)?
+                };
+                let video_started_at =
+                    AlsDownloader::new(1).parse_m3u8_program_date_time(&playlist)?;
+                if video_started_at.is_none() {
+                    warn!(
+                        "⚠️ No EXT-X-PROGRAM-DATE-TIME in playlist, falling back to live_started_at"
+                    );
+                }
+
+                let replay_dir = PathBuf::from(&sync_args.replay_dir);
+                let replay_clock = sync::read_replay_clock(&replay_dir)?;
+                let offset = sync::compute_sync_offset(&replay_clock, video_started_at)?;
+                info!(
+                    "⏱️ Replay zero: {} ({:?}), video zero source: {:?}",
+                    replay_clock.wall_clock, offset.replay_source, offset.video_source
+                );
+                info!("✅ Video sync offset: {} ms", offset.offset_ms);
+                if sync_args.write {
+                    sync::write_sync_offset(&replay_dir, offset.offset_ms)?;
+                    info!("📄 Offset written to index.md");
+                }
+            }
+            EditSubcommands::Retime(retime_args) => {
+                info!("⏱️ Retiming replay: {}", retime_args.input_dir);
+                let summary = run_retime(&RetimeConfig {
+                    input_dir: PathBuf::from(&retime_args.input_dir),
+                    map_path: PathBuf::from(&retime_args.map),
+                    output_dir: PathBuf::from(&retime_args.output_dir),
+                })?;
+                info!(
+                    "✅ Retimed {} packets, now from {} to {}",
+                    summary.packets,
+                    summary.first_timestamp.to_rfc3339(),
+                    summary.last_timestamp.to_rfc3339()
+                );
+                info!("📂 Written to {}", retime_args.output_dir);
+            }
+            EditSubcommands::Transform(transform_args) => {
+                info!("🛠️ Transforming replay: {}", transform_args.input_dir);
+                let summary = run_transform(&TransformConfig {
+                    input_dir: PathBuf::from(&transform_args.input_dir),
+                    script_path: PathBuf::from(&transform_args.script),
+                    output_dir: PathBuf::from(
+                        transform_args.output_dir.as_deref().unwrap_or_default(),
+                    ),
+                    dry_run: transform_args.dry_run,
+                })?;
+                for (index, (rule, matches)) in
+                    summary.rules.iter().zip(&summary.stats.matches).enumerate()
+                {
+                    info!(
+                        "📋 Rule {} ({}): {} frames matched",
+                        index + 1,
+                        rule.action.name(),
+                        matches
+                    );
+                }
+                info!(
+                    "✅ {} frames and {} packets dropped, {} packets left",
+                    summary.stats.dropped_frames, summary.stats.dropped_packets, summary.packets
+                );
+                if !transform_args.dry_run {
+                    info!(
+                        "📂 Written to {}",
+                        transform_args.output_dir.as_deref().unwrap_or_default()
+                    );
+                }
+            }
+        },
+        Some(Commands::Info(info_args)) => {
+            let path = Path::new(&info_args.file_path);
+            let input_format = parse_input_format(&info_args.input_format, args.live_marker)?;
+            if info_args.brief {
+                let room_info = proto::application::extract_room_info(path, input_format)?
+                    .ok_or_else(|| {
+                        Error::msg(format!("No Room frame found in {}", path.display()))
+                    })?;
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    room_info.room_id,
+                    room_info.started_at,
+                    room_info.ended_at,
+                    room_info.first_timestamp.to_rfc3339()
+                );
+            } else {
+                let room_info = proto::application::scan_room_info(path, input_format)?
+                    .ok_or_else(|| {
+                        Error::msg(format!("No Room frame found in {}", path.display()))
+                    })?;
+                info!("🏠 Room id: {}", room_info.room_id);
+                info!(
+                    "🕒 Room started at: {}, ended at: {}",
+                    room_info.started_at, room_info.ended_at
+                );
+                info!(
+                    "📦 Packets from {} to {}",
+                    room_info.first_timestamp.to_rfc3339(),
+                    room_info.last_timestamp.to_rfc3339()
+                );
+            }
+        }
+        Some(Commands::Detect(detect_args)) => {
+            for file_path in &detect_args.file_paths {
+                let detection = proto::reader::detect_format(Path::new(file_path))?;
+                info!(
+                    "🔎 {}: {} ({:?} confidence, {})",
+                    file_path, detection.format, detection.confidence, detection.reason
+                );
+            }
+        }
+        Some(Commands::Coverage(coverage_args)) => {
+            let coverage = proto::application::scan_schema_coverage(
+                Path::new(&coverage_args.input_path),
+                parse_input_format(&coverage_args.input_format, args.live_marker)?,
+            )?;
+            let mut writer =
+                proto::formatter::OutputWriter::new(coverage_args.output_path.as_deref())?;
+            proto::formatter::SchemaCoverageFormatter::format_table(&mut writer, &coverage)?;
+            writer.flush()?;
+        }
+        Some(Commands::CheckTimestamps(check_args)) => {
+            let check = proto::application::scan_timestamps(
+                Path::new(&check_args.input_path),
+                parse_input_format(&check_args.input_format, args.live_marker)?,
+                TimeDelta::from_std(check_args.gap_threshold.as_duration())?,
+            )?;
+            let mut writer =
+                proto::formatter::OutputWriter::new(check_args.output_path.as_deref())?;
+            proto::formatter::TimestampCheckFormatter::format_table(
+                &mut writer,
+                &check,
+                check_args.max_listed,
+            )?;
+            writer.flush()?;
+            if let Some(largest) = check.largest_regression {
+                info!(
+                    "💡 convert reorders packets stamped up to {} back by default, see --out-of-order",
+                    HumanDuration(DEFAULT_REORDER_WINDOW.to_std()?)
+                );
+                if -largest.gap.gap > DEFAULT_REORDER_WINDOW {
+                    info!(
+                        "💡 Larger jumps back are reconnect seams, --smooth-timestamps shifts the packets after them forward"
+                    );
+                }
+            }
+        }
+        Some(Commands::CheckEncoding(check_args)) => {
+            let check = proto::application::scan_encode_drift(
+                Path::new(&check_args.input_path),
+                parse_input_format(&check_args.input_format, args.live_marker)?,
+            )?;
+            let mut writer =
+                proto::formatter::OutputWriter::new(check_args.output_path.as_deref())?;
+            proto::formatter::EncodeDriftFormatter::format_table(
+                &mut writer,
+                &check,
+                check_args.max_listed,
+            )?;
+            writer.flush()?;
+            if check.is_exact() {
+                info!("✅ Every packet encodes back to the captured bytes");
+            } else {
+                warn!(
+                    "⚠️ {} of {} packets encode differently, converted output will not match the server bytes for them",
+                    check.drift_count, check.total_packets
+                );
+            }
+        }
+        Some(Commands::Prefabs(prefabs_args)) => {
+            let catalog = proto::application::scan_prefab_catalog(
+                Path::new(&prefabs_args.input_path),
+                parse_input_format(&prefabs_args.input_format, args.live_marker)?,
+            )?;
+            let mut writer =
+                proto::formatter::OutputWriter::new(prefabs_args.output_path.as_deref())?;
+            if prefabs_args.json {
+                proto::formatter::PrefabCatalogFormatter::format_json(&mut writer, &catalog)?;
+            } else {
+                proto::formatter::PrefabCatalogFormatter::format_table(&mut writer, &catalog)?;
+            }
+            writer.flush()?;
+        }
+        Some(Commands::Anonymize(anonymize_args)) => {
+            let config = AnonymizeConfig {
+                input_path: PathBuf::from(&anonymize_args.input_path),
+                input_format: parse_input_format(&anonymize_args.input_format, args.live_marker)?,
+                output_path: PathBuf::from(&anonymize_args.output_path),
+                randomize_room_id: anonymize_args.randomize_room_id,
+            };
+            let summary = run_anonymize(&config)?;
+            info!(
+                "🕶️ Anonymized {} packets in {} files: {} players, {} rooms",
+                summary.packets,
+                summary.files.len(),
+                summary.stats.players,
+                summary.stats.rooms
+            );
+            for (message_type, count) in &summary.stats.rewritten_frames {
+                info!("  {}: {} frames rewritten", message_type, count);
+            }
+            info!("📄 Output written to: {}", anonymize_args.output_path);
+        }
+        Some(Commands::Split(split_args)) => {
+            let config = SplitConfig {
+                input_path: PathBuf::from(&split_args.input_path),
+                input_format: parse_input_format(&split_args.input_format, args.live_marker)?,
+                output_dir: PathBuf::from(&split_args.output_dir),
+                split_by: SplitBy::new(&split_args.split_by, split_args.every)?,
+            };
+            let shards = run_split(&config)?;
+            for shard in &shards {
+                info!(
+                    "  {}: {} packets, {} bytes, {} to {}",
+                    shard.path.display(),
+                    shard.packets,
+                    shard.bytes,
+                    shard.first_timestamp.to_rfc3339(),
+                    shard.last_timestamp.to_rfc3339()
+                );
+            }
+            info!(
+                "✂️ Split {} into {} files in {}",
+                split_args.input_path,
+                shards.len(),
+                split_args.output_dir
+            );
+        }
+        Some(Commands::Verify(verify_args)) => {
+            let config = VerifyConfig {
+                input_dir: PathBuf::from(&verify_args.input_dir),
+                strict_state: verify_args.strict_state,
+                max_violations: verify_args.max_violations,
+            };
+            let report = run_verify(&config)?;
+            info!(
+                "📦 Checked {} segments, {} packets",
+                report.segments, report.packets
+            );
+            for segment in &report.missing_segments {
+                warn!("❌ Missing segment: {}", segment);
+            }
+            for (segment, error) in &report.unreadable_segments {
+                warn!("❌ Unreadable segment {}: {}", segment, error);
+            }
+            for violation in &report.violations {
+                warn!(
+                    "⚠️ {} of object {} ({}) which is not alive: {} packet #{} frame #{}",
+                    violation.kind,
+                    violation.object_id,
+                    violation.prefab_name.as_deref().unwrap_or("unknown prefab"),
+                    violation.segment,
+                    violation.packet_index,
+                    violation.frame_index
+                );
+            }
+            if report.total_violations > report.violations.len() {
+                warn!(
+                    "... {} more state violations not shown",
+                    report.total_violations - report.violations.len()
+                );
+            }
+            if !report.is_ok() {
+                return Err(Error::msg(format!(
+                    "Verification failed for {}",
+                    config.input_dir.display()
+                )));
+            }
+            info!("✅ Verification passed");
+        }
+        Some(Commands::JoinParts(join_args)) => {
+            info!("🔗 Joining {} parts", join_args.part_dirs.len());
+            let summary = run_join(&JoinConfig {
+                part_dirs: join_args.part_dirs.iter().map(PathBuf::from).collect(),
+                output_dir: PathBuf::from(&join_args.output_dir),
+            })?;
+            info!(
+                "✅ Joined {} segments from {} parts, {} in total",
+                summary.segments,
+                summary.parts,
+                HumanDuration(summary.duration.to_std().unwrap_or_default())
+            );
+            info!("📂 Written to {}", join_args.output_dir);
+        }
+        Some(Commands::Provenance(provenance_args)) => match provenance_args.command {
+            ProvenanceSubcommands::Lookup(lookup_args) => {
+                let path = Path::new(&lookup_args.input_dir).join(PROVENANCE_FILE);
+                let provenance = Provenance::read(&path)?;
+                let record = provenance
+                    .lookup(lookup_args.segment, lookup_args.packet)
+                    .ok_or_else(|| {
+                        Error::msg(format!(
+                            "No packet #{} in segment {}",
+                            lookup_args.packet, lookup_args.segment
+                        ))
+                    })?;
+                println!("segment_{:05}.ts packet #{}", record.segment, record.packet);
+                match &record.source {
+                    Some(source) => {
+                        println!(
+                            "  source:     {}",
+                            provenance.source_file(record).unwrap_or("unknown file")
+                        );
+                        println!("  offset:     {} (0x{:x})", source.offset, source.offset);
+                        println!("  timestamp:  {}", source.timestamp.to_rfc3339());
+                        println!(
+                            "  output:     {} ({:+}ms)",
+                            (source.timestamp + record.delta).to_rfc3339(),
+                            record.delta.num_milliseconds()
+                        );
+                        let adjustments = record.adjustments.names();
+                        println!(
+                            "  adjusted:   {}",
+                            if adjustments.is_empty() {
+                                "none".to_string()
+                            } else {
+                                adjustments.join(", ")
+                            }
+                        );
+                    }
+                    None => {
+                        println!("  generated by the converter (segment header or object state)")
+                    }
+                }
+            }
+        },
         None => {}
     }
     Ok(())
 }
 
+/// Re-analyze new or changed files under `input_path`, appending to `output_path`
+fn watch_analysis(
+    input_path: &Path,
+    output_path: &str,
+    input_format: proto::reader::InputFormat,
+    limits: &proto::application::AnalyzeLimits,
+    options: &proto::application::AnalyzeOptions,
+    stop: &AtomicBool,
+) -> Result<()> {
+    let (tx, rx) = std::sync::mpsc::channel::<notify::Result<notify::Event>>();
+    let mut watcher = notify::recommended_watcher(tx)?;
+    watcher.watch(input_path, RecursiveMode::NonRecursive)?;
+    let output_path = std::path::absolute(output_path)?;
+
+    while !stop.load(Ordering::Relaxed) {
+        let first_event = match rx.recv_timeout(Duration::from_millis(500)) {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+
+        // Collect events for a short while, a growing capture fires many modify events
+        let mut changed = BTreeSet::new();
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let mut event = Some(first_event);
+        while let Some(result) = event.take() {
+            match result {
+                Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                    changed.extend(event.paths);
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Watch error: {}", e),
+            }
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            event = rx.recv_timeout(timeout).ok();
+        }
+
+        let mut writer = proto::formatter::OutputWriter::append(&output_path.to_string_lossy())?;
+        for path in changed {
+            // Skip our own output when it lives inside the watched directory
+            if !path.is_file() || std::path::absolute(&path)? == output_path {
+                continue;
+            }
+            info!("🔁 Re-analyzing: {}", path.display());
+            writer.writeln(&format!(
+                "--- Incremental update at {} ---",
+                Utc::now().to_rfc3339()
+            ))?;
+            let result = input_format.resolve(&path).and_then(|format| {
+                proto::application::analyze_file_with_sink(
+                    &path.to_string_lossy(),
+                    &mut writer,
+                    limits,
+                    &format.reader_factory()?,
+                    options,
+                )
+            });
+            if let Err(e) = result {
+                // The capture may end with a partially written packet
+                warn!("Incremental analysis of {} failed: {:#}", path.display(), e);
+                writer.writeln(&format!("Error: {:#}", e))?;
+            }
+        }
+        writer.flush()?;
+    }
+
+    Ok(())
+}
+
+fn whole_seconds(duration: HumanDuration) -> Result<u64> {
+    let duration = duration.as_duration();
+    if duration.subsec_nanos() != 0 {
+        return Err(Error::msg(format!(
+            "Segment duration must be whole seconds: {}",
+            HumanDuration(duration)
+        )));
+    }
+    Ok(duration.as_secs())
+}
+
 fn parse_rfc3339_utc(field_name: &str, value: Option<&str>) -> Result<Option<DateTime<Utc>>> {
     let Some(value) = value else {
         return Ok(None);
@@ -680,6 +2128,124 @@ fn parse_rfc3339_utc(field_name: &str, value: Option<&str>) -> Result<Option<Dat
     Ok(Some(parsed.with_timezone(&Utc)))
 }
 
+/// Input format of a command, also accepting the `--live-marker` byte
+fn parse_input_format(format: &str, live_marker: Option<u8>) -> Result<proto::reader::InputFormat> {
+    format
+        .parse::<proto::reader::InputFormat>()?
+        .with_live_marker(live_marker)
+}
+
+/// Parse a `--live-marker` byte, in hex with a `0x` prefix or in decimal
+fn parse_live_marker(value: &str) -> Result<u8> {
+    let parsed = match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => value.parse(),
+    };
+    parsed.map_err(|e| Error::msg(format!("Invalid live marker '{}': {}", value, e)))
+}
+
+/// Validate `convert --output` placeholders at parse time
+fn parse_output_template(value: &str) -> Result<String> {
+    value.parse::<PathTemplate>()?;
+    Ok(value.to_string())
+}
+
+/// Log the outcome of a download, failing when any file could not be downloaded
+fn check_download_report(report: &DownloadReport) -> Result<()> {
+    info!(
+        "📊 {} of {} files downloaded to '{}'",
+        report.ok_count(),
+        report.entries.len(),
+        report.target_dir.display()
+    );
+    if report.cancelled {
+        return Err(Error::msg(format!(
+            "Download cancelled, {} files not downloaded",
+            report.entries.len() - report.ok_count()
+        )));
+    }
+    if report.is_ok() {
+        return Ok(());
+    }
+    let failed: Vec<&str> = report
+        .failures()
+        .map(|entry| entry.filename.as_str())
+        .collect();
+    Err(Error::msg(format!(
+        "{} files failed to download: {}",
+        failed.len(),
+        failed.join(", ")
+    )))
+}
+
+/// Flag set by the first shutdown signal so conversions and downloads can stop cleanly,
+/// a second one exits right away
+fn cancel_on_shutdown() -> Arc<AtomicBool> {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let cancel_signal = cancel.clone();
+    tokio::spawn(async move {
+        if shutdown_signal().await.is_err() {
+            return;
+        }
+        warn!("🛑 Stopping, press Ctrl+C again to exit immediately");
+        cancel_signal.store(true, Ordering::Relaxed);
+        if shutdown_signal().await.is_ok() {
+            std::process::exit(130);
+        }
+    });
+    cancel
+}
+
+/// Ctrl+C, and on Unix SIGTERM or SIGHUP too, so `kill`, a systemd or container stop and
+/// a closed terminal also flush what was written so far
+async fn shutdown_signal() -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        let mut terminate = signal(SignalKind::terminate())?;
+        let mut hangup = signal(SignalKind::hangup())?;
+        tokio::select! {
+            result = tokio::signal::ctrl_c() => result,
+            _ = terminate.recv() => Ok(()),
+            _ = hangup.recv() => Ok(()),
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await
+}
+
+/// Upload task of a file written by a streamed conversion
+///
+/// Files of a part directory keep the directory name in their key, archives are
+/// uploaded under their own name.
+fn streamed_upload_task(
+    path: &Path,
+    output_target: OutputTarget,
+    prefix: Option<&str>,
+) -> Result<UploadTask> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| Error::msg("Could not extract filename"))?
+        .to_string_lossy();
+    let key = match (output_target, path.parent().and_then(Path::file_name)) {
+        (OutputTarget::Directory, Some(part_dir)) => {
+            format!("{}/{}", part_dir.to_string_lossy(), file_name)
+        }
+        _ => file_name.to_string(),
+    };
+    Ok(UploadTask {
+        local_path: path.to_path_buf(),
+        remote_key: match prefix {
+            Some(prefix) => format!("{}/{}", prefix.trim_end_matches('/'), key),
+            None => key,
+        },
+        file_size: std::fs::metadata(path)?.len(),
+    })
+}
+
 fn get_bucket_prefix(url: &str) -> Result<String> {
     let parsed_url = Url::parse(url)?;
     let path = parsed_url.path();
@@ -709,4 +2275,21 @@ mod tests {
         assert!(prefix.is_ok());
         assert_eq!(prefix.unwrap(), "archive/alst/directory_name");
     }
+
+    #[test]
+    fn test_parse_output_template() {
+        assert!(parse_output_template("out/{live_id}_{part}").is_ok());
+        assert!(parse_output_template("out/{day}").is_err());
+    }
+
+    #[test]
+    fn test_parse_live_marker() {
+        assert_eq!(parse_live_marker("0x02").unwrap(), 0x02);
+        assert_eq!(parse_live_marker("3").unwrap(), 3);
+        assert!(parse_live_marker("0x100").is_err());
+
+        let format = parse_input_format("auto", Some(0x02)).unwrap();
+        assert_eq!(format, proto::reader::InputFormat::Standard(Some(0x02)));
+        assert!(parse_input_format("legacy", Some(0x02)).is_err());
+    }
 }
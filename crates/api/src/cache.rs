@@ -0,0 +1,142 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Default time a cached response is considered fresh
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Cached response of a single endpoint
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CacheEntry {
+    pub endpoint: String,
+    /// Unix timestamp (seconds) of the successful online request
+    pub stored_at: u64,
+    pub ttl_secs: u64,
+    pub body: serde_json::Value,
+}
+
+impl CacheEntry {
+    pub fn is_expired(&self) -> bool {
+        unix_now() > self.stored_at.saturating_add(self.ttl_secs)
+    }
+}
+
+/// File based response cache, one json file per endpoint
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl ResponseCache {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            ttl: DEFAULT_CACHE_TTL,
+        }
+    }
+
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn store(&self, endpoint: &str, body: &serde_json::Value) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create cache dir: {}", self.dir.display()))?;
+        let entry = CacheEntry {
+            endpoint: endpoint.to_string(),
+            stored_at: unix_now(),
+            ttl_secs: self.ttl.as_secs(),
+            body: body.clone(),
+        };
+        let path = self.entry_path(endpoint);
+        std::fs::write(&path, serde_json::to_string_pretty(&entry)?)
+            .with_context(|| format!("Failed to write cache entry: {}", path.display()))
+    }
+
+    /// Returns `None` when the endpoint was never cached
+    pub fn load(&self, endpoint: &str) -> Result<Option<CacheEntry>> {
+        let path = self.entry_path(endpoint);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read cache entry: {}", path.display()))?;
+        Ok(Some(serde_json::from_str(&content)?))
+    }
+
+    fn entry_path(&self, endpoint: &str) -> PathBuf {
+        let name: String = endpoint
+            .trim_matches('/')
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        self.dir.join(format!("{name}.json"))
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ApiClient, ArchiveListOptions};
+    use std::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_offline_archive_list_from_cache() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let cache = ResponseCache::new(dir);
+        let archives = serde_json::json!([
+            { "archives_id": "a1", "name": "first" },
+            { "archives_id": "a2", "name": "second" },
+        ]);
+        cache.store("/archive/get_archive_list", &archives).unwrap();
+
+        // Any request would go through this proxy, nothing may connect to it
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let proxy = format!("http://{}", listener.local_addr().unwrap());
+        let mut api_client = ApiClient::new().with_cache(cache).offline(true);
+        api_client.client = reqwest::Client::builder()
+            .proxy(reqwest::Proxy::all(&proxy).unwrap())
+            .build()
+            .unwrap();
+        api_client.assets_client = api_client.client.clone();
+
+        let list = api_client
+            .high_level()
+            .get_archive_list(ArchiveListOptions {
+                limit: Some(1),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            list,
+            serde_json::json!([{ "archives_id": "a1", "name": "first" }])
+        );
+
+        let err = api_client.high_level().get_with_meets_info("a1").await;
+        assert!(err.unwrap_err().to_string().contains("requires network"));
+        let err = api_client.high_level().get_plan_list().await;
+        assert!(err.unwrap_err().to_string().contains("not cached"));
+
+        assert_eq!(
+            listener.accept().unwrap_err().kind(),
+            std::io::ErrorKind::WouldBlock
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! The account's friend list in a shape fit for stats and sharing
+//!
+//! `/friend/get_list` returns every friend in one response, up to `max_friend_count`,
+//! so there is no paging. [`FriendEntry::redacted`] drops the player id and name when a
+//! list is shared.
+//!
+//! Live co-watching presence is not covered, none of the known endpoints report which
+//! friends are in a with-meets. Requests are sent once, the client has no retry layer.
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::{FriendGetListResponse, FriendInfo};
+
+/// One friend of the account
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FriendEntry {
+    pub player_id: String,
+    pub name: String,
+    pub level: Option<i64>,
+    pub last_login: Option<String>,
+}
+
+impl FriendEntry {
+    pub fn from_info(info: &FriendInfo) -> Self {
+        Self {
+            player_id: info.player_id.clone().unwrap_or_default(),
+            name: info.name.clone().unwrap_or_default(),
+            level: info.player_level,
+            last_login: info.last_login.clone(),
+        }
+    }
+
+    /// Friends of a `get_list` response, in the order the server sent them
+    pub fn from_response(body: &FriendGetListResponse) -> Vec<Self> {
+        body.friend_info_list
+            .iter()
+            .flatten()
+            .map(Self::from_info)
+            .collect()
+    }
+
+    /// Without the player id and name, `index` keeps entries of one list apart
+    pub fn redacted(self, index: usize) -> Self {
+        Self {
+            player_id: String::new(),
+            name: format!("friend_{}", index + 1),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ApiClient;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // Answers each request with the next of `bodies`, requested paths are recorded
+    async fn serve(
+        listener: tokio::net::TcpListener,
+        bodies: Vec<serde_json::Value>,
+        paths: Arc<Mutex<Vec<String>>>,
+    ) {
+        for body in bodies {
+            let Ok((mut stream, _)) = listener.accept().await else {
+                return;
+            };
+            let mut request = vec![0; 4096];
+            let len = stream.read(&mut request).await.unwrap_or(0);
+            let request = String::from_utf8_lossy(&request[..len]).to_string();
+            let path = request.split_whitespace().nth(1).unwrap_or("").to_string();
+            paths.lock().unwrap().push(path);
+            let body = body.to_string();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_friend_list() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}/v1", listener.local_addr().unwrap());
+        let paths = Arc::new(Mutex::new(Vec::new()));
+        let bodies = vec![
+            json!({
+                "friend_info_list": [
+                    { "player_id": "123456789", "name": "Kaho", "player_level": 120 },
+                    { "player_id": "987654321", "name": "Sayaka" },
+                ],
+                "max_friend_count": 100,
+            }),
+            json!({ "friend_info_list": [], "max_friend_count": 100 }),
+        ];
+        tokio::spawn(serve(listener, bodies, paths.clone()));
+        let mut api_client = ApiClient::new();
+        api_client.client = reqwest::Client::new();
+        api_client.api_base = base;
+
+        let friends = api_client.high_level().get_friend_list().await.unwrap();
+        let names: Vec<_> = friends.iter().map(|friend| friend.name.as_str()).collect();
+        assert_eq!(names, ["Kaho", "Sayaka"]);
+        assert_eq!(friends[0].level, Some(120));
+
+        assert!(
+            api_client
+                .high_level()
+                .get_friend_list()
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            *paths.lock().unwrap(),
+            ["/v1/friend/get_list", "/v1/friend/get_list"]
+        );
+    }
+
+    #[test]
+    fn test_friend_entries() {
+        let body: FriendGetListResponse = serde_json::from_value(json!({
+            "friend_info_list": [
+                { "d_friends_id": "f1", "player_id": "123456789", "name": "Kaho",
+                  "player_level": 120, "last_login": "2025-08-21T09:00:00+09:00",
+                  "comment": "hello" },
+                { "player_id": "987654321" },
+            ],
+            "max_friend_count": 100,
+        }))
+        .unwrap();
+        let friends = FriendEntry::from_response(&body);
+        assert_eq!(
+            friends,
+            vec![
+                FriendEntry {
+                    player_id: "123456789".to_string(),
+                    name: "Kaho".to_string(),
+                    level: Some(120),
+                    last_login: Some("2025-08-21T09:00:00+09:00".to_string()),
+                },
+                FriendEntry {
+                    player_id: "987654321".to_string(),
+                    ..Default::default()
+                },
+            ]
+        );
+
+        let redacted: Vec<_> = friends
+            .into_iter()
+            .enumerate()
+            .map(|(index, friend)| friend.redacted(index))
+            .collect();
+        assert_eq!(redacted[0].player_id, "");
+        assert_eq!(redacted[0].name, "friend_1");
+        assert_eq!(redacted[0].level, Some(120));
+        assert_eq!(redacted[1].name, "friend_2");
+
+        assert!(FriendEntry::from_response(&FriendGetListResponse::default()).is_empty());
+    }
+}
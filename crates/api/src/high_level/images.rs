@@ -0,0 +1,452 @@
+//! Thumbnail, banner and performer icon images of an archive
+//!
+//! Archive list entries and the fes / with meets details name their image fields
+//! differently and leave out the ones an archive does not have, so [`ArchiveImages`] reads
+//! whichever are present. Downloads keep an `images.json` manifest next to the files, a
+//! file whose etag or size is unchanged on the server is not fetched again.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use reqwest::header;
+use serde::{Deserialize, Serialize};
+
+use super::AssetsApi;
+
+/// Manifest written next to the downloaded images
+pub const ARCHIVE_IMAGES_MANIFEST: &str = "images.json";
+
+const THUMBNAIL_FIELDS: [&str; 2] = ["thumbnail_image_url", "thumbnail_url"];
+const BANNER_FIELDS: [&str; 2] = ["banner_image_url", "banner_url"];
+const PERFORMER_LIST_FIELDS: [&str; 2] = ["characters", "character_list"];
+const PERFORMER_ICON_FIELDS: [&str; 3] = ["icon_image_url", "icon_url", "thumbnail_image_url"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveImageRole {
+    Thumbnail,
+    Banner,
+    PerformerIcon,
+}
+
+impl ArchiveImageRole {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Thumbnail => "thumbnail",
+            Self::Banner => "banner",
+            Self::PerformerIcon => "performer_icon",
+        }
+    }
+}
+
+/// One image of an archive
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchiveImage {
+    pub role: ArchiveImageRole,
+    /// Performer of a `PerformerIcon`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub character_id: Option<i32>,
+    pub url: String,
+}
+
+impl ArchiveImage {
+    /// `<archive id>_<role>[_<character id>].<ext>`, png when the url has no extension
+    pub fn file_name(&self, archive_id: &str) -> String {
+        let extension = url::Url::parse(&self.url)
+            .ok()
+            .and_then(|url| {
+                let name = url.path_segments()?.next_back()?.to_string();
+                let (_, extension) = name.rsplit_once('.')?;
+                (!extension.is_empty() && extension.chars().all(|c| c.is_ascii_alphanumeric()))
+                    .then(|| extension.to_ascii_lowercase())
+            })
+            .unwrap_or_else(|| "png".to_string());
+        match self.character_id {
+            Some(character_id) => format!(
+                "{}_{}_{}.{}",
+                archive_id,
+                self.role.name(),
+                character_id,
+                extension
+            ),
+            None => format!("{}_{}.{}", archive_id, self.role.name(), extension),
+        }
+    }
+}
+
+/// Image urls of an archive, each `None` or left out when the archive has no such image
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ArchiveImages {
+    pub thumbnail: Option<String>,
+    pub banner: Option<String>,
+    /// Icon url by character id
+    pub performer_icons: BTreeMap<i32, String>,
+}
+
+impl ArchiveImages {
+    /// Read an archive list entry or a fes / with meets details body, empty strings count
+    /// as missing like null
+    pub fn from_archive(archive: &serde_json::Value) -> Self {
+        let performer_icons = PERFORMER_LIST_FIELDS
+            .iter()
+            .filter_map(|name| archive[name].as_array())
+            .flatten()
+            .filter_map(|performer| {
+                let character_id = performer["character_id"].as_i64()?;
+                let url = first_url(performer, &PERFORMER_ICON_FIELDS)?;
+                Some((i32::try_from(character_id).ok()?, url))
+            })
+            .collect();
+        Self {
+            thumbnail: first_url(archive, &THUMBNAIL_FIELDS),
+            banner: first_url(archive, &BANNER_FIELDS),
+            performer_icons,
+        }
+    }
+
+    /// Fill the images missing here from `other`
+    pub fn merge(mut self, other: Self) -> Self {
+        self.thumbnail = self.thumbnail.or(other.thumbnail);
+        self.banner = self.banner.or(other.banner);
+        for (character_id, url) in other.performer_icons {
+            self.performer_icons.entry(character_id).or_insert(url);
+        }
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.thumbnail.is_none() && self.banner.is_none() && self.performer_icons.is_empty()
+    }
+
+    /// Every image, thumbnail and banner first, then the icons by character id
+    pub fn images(&self) -> Vec<ArchiveImage> {
+        let single = |role, url: &Option<String>| {
+            url.clone().map(|url| ArchiveImage {
+                role,
+                character_id: None,
+                url,
+            })
+        };
+        let mut images: Vec<_> = [
+            single(ArchiveImageRole::Thumbnail, &self.thumbnail),
+            single(ArchiveImageRole::Banner, &self.banner),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        images.extend(
+            self.performer_icons
+                .iter()
+                .map(|(character_id, url)| ArchiveImage {
+                    role: ArchiveImageRole::PerformerIcon,
+                    character_id: Some(*character_id),
+                    url: url.clone(),
+                }),
+        );
+        images
+    }
+}
+
+/// Contents of `images.json`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ArchiveImageManifest {
+    pub archive_id: String,
+    pub images: Vec<DownloadedImage>,
+}
+
+/// Result of downloading one image
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DownloadedImage {
+    #[serde(flatten)]
+    pub image: ArchiveImage,
+    /// File name in the download directory
+    pub file: String,
+    pub bytes: Option<u64>,
+    pub etag: Option<String>,
+    /// The file on disk was unchanged on the server and not fetched again
+    #[serde(default)]
+    pub skipped: bool,
+    /// Why the image could not be downloaded, the other images are still fetched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// Helper: first non-empty string of `fields`
+fn first_url(json: &serde_json::Value, fields: &[&str]) -> Option<String> {
+    fields
+        .iter()
+        .filter_map(|name| json[name].as_str())
+        .find(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+// Helper: etag header without quotes or weak prefix
+fn etag(headers: &header::HeaderMap) -> Option<String> {
+    let value = headers.get(header::ETAG)?.to_str().ok()?;
+    Some(value.trim_start_matches("W/").trim_matches('"').to_string())
+}
+
+impl<'a> AssetsApi<'a> {
+    /// Download `images` of `archive_id` into `dir` and write the `images.json` manifest
+    ///
+    /// A file already in `dir` is kept when the server reports the etag recorded in the
+    /// previous manifest, or the same size when it sends no etag.
+    pub async fn download_archive_images(
+        &self,
+        archive_id: &str,
+        images: &ArchiveImages,
+        dir: &Path,
+    ) -> Result<ArchiveImageManifest> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+        let manifest_path = dir.join(ARCHIVE_IMAGES_MANIFEST);
+        let previous: ArchiveImageManifest = std::fs::read_to_string(&manifest_path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+
+        let mut manifest = ArchiveImageManifest {
+            archive_id: archive_id.to_string(),
+            images: Vec::new(),
+        };
+        for image in images.images() {
+            let file = image.file_name(archive_id);
+            let recorded = previous
+                .images
+                .iter()
+                .find(|downloaded| downloaded.file == file && downloaded.error.is_none());
+            let downloaded = match self
+                .download_image(&image, &dir.join(&file), recorded)
+                .await
+            {
+                Ok((bytes, etag, skipped)) => DownloadedImage {
+                    image,
+                    file,
+                    bytes: Some(bytes),
+                    etag,
+                    skipped,
+                    error: None,
+                },
+                Err(e) => {
+                    tracing::warn!("Failed to download {}: {:#}", image.url, e);
+                    DownloadedImage {
+                        image,
+                        file,
+                        bytes: None,
+                        etag: None,
+                        skipped: false,
+                        error: Some(format!("{:#}", e)),
+                    }
+                }
+            };
+            manifest.images.push(downloaded);
+        }
+        std::fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)?)
+            .with_context(|| format!("Failed to write manifest: {}", manifest_path.display()))?;
+        Ok(manifest)
+    }
+
+    // Helper: returns the file size, etag and whether the file on disk was kept
+    async fn download_image(
+        &self,
+        image: &ArchiveImage,
+        path: &Path,
+        recorded: Option<&DownloadedImage>,
+    ) -> Result<(u64, Option<String>, bool)> {
+        self.ensure_online(&image.url)?;
+        if let Ok(metadata) = path.metadata() {
+            let res = self
+                .assets_client
+                .head(&image.url)
+                .headers(self.assets_header.clone())
+                .send()
+                .await?;
+            if res.status().is_success() {
+                let remote_etag = etag(res.headers());
+                let remote_len = res
+                    .headers()
+                    .get(header::CONTENT_LENGTH)
+                    .and_then(|value| value.to_str().ok()?.parse::<u64>().ok());
+                let recorded_etag = recorded.and_then(|recorded| recorded.etag.as_deref());
+                let unchanged = match (remote_etag.as_deref(), recorded_etag) {
+                    (Some(remote), Some(recorded)) => remote == recorded,
+                    _ => remote_len == Some(metadata.len()),
+                };
+                if unchanged {
+                    return Ok((metadata.len(), remote_etag, true));
+                }
+            }
+        }
+
+        let res = self
+            .assets_client
+            .get(&image.url)
+            .headers(self.assets_header.clone())
+            .send()
+            .await?;
+        if !res.status().is_success() {
+            return Err(anyhow::anyhow!(
+                "GET {} failed: {}",
+                image.url,
+                res.status()
+            ));
+        }
+        let etag = etag(res.headers());
+        let body = res.bytes().await?;
+        std::fs::write(path, &body)
+            .with_context(|| format!("Failed to write image: {}", path.display()))?;
+        Ok((body.len() as u64, etag, false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ApiClient;
+    use serde_json::json;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_archive_images_shapes() {
+        let fes = ArchiveImages::from_archive(&json!({
+            "title": "Fes",
+            "banner_image_url": "https://assets.example.com/banner/fes.jpg",
+            "characters": [
+                { "character_id": 1021, "is_guest": false,
+                  "icon_image_url": "https://assets.example.com/icon/1021.png" },
+                { "character_id": 1022, "is_guest": false },
+            ],
+        }));
+        assert_eq!(fes.thumbnail, None);
+        assert_eq!(
+            fes.banner.as_deref(),
+            Some("https://assets.example.com/banner/fes.jpg")
+        );
+        assert_eq!(fes.performer_icons.len(), 1);
+
+        let with_entry = ArchiveImages::from_archive(&json!({
+            "archives_id": "a1",
+            "live_type": 2,
+            "thumbnail_image_url": "https://assets.example.com/thumb/a1",
+            "banner_image_url": "",
+            "character_list": [{ "character_id": 1031, "icon_url": "https://assets.example.com/icon/1031.webp" }],
+        }));
+        assert_eq!(with_entry.banner, None);
+        let merged = fes.merge(with_entry);
+        let files: Vec<_> = merged
+            .images()
+            .iter()
+            .map(|image| image.file_name("a1"))
+            .collect();
+        assert_eq!(
+            files,
+            [
+                "a1_thumbnail.png",
+                "a1_banner.jpg",
+                "a1_performer_icon_1021.png",
+                "a1_performer_icon_1031.webp",
+            ]
+        );
+
+        let none = ArchiveImages::from_archive(&json!({ "title": "Live", "characters": null }));
+        assert!(none.is_empty());
+        assert!(none.images().is_empty());
+    }
+
+    // Serves `/<name>` with a fixed body, etags only for names starting with `e`
+    async fn serve(listener: tokio::net::TcpListener, gets: Arc<AtomicUsize>) {
+        loop {
+            let Ok((mut stream, _)) = listener.accept().await else {
+                return;
+            };
+            let mut request = vec![0; 4096];
+            let len = stream.read(&mut request).await.unwrap_or(0);
+            let request = String::from_utf8_lossy(&request[..len]).to_string();
+            let mut words = request.split_whitespace();
+            let (method, path) = (words.next().unwrap_or(""), words.next().unwrap_or(""));
+            let body = format!("image {}", path);
+            let status = if path == "/missing.png" {
+                "404 Not Found"
+            } else {
+                "200 OK"
+            };
+            let etag = if path.starts_with("/e") {
+                "ETag: \"v1\"\r\n"
+            } else {
+                ""
+            };
+            if method == "GET" {
+                gets.fetch_add(1, Ordering::SeqCst);
+            }
+            let mut response = format!(
+                "HTTP/1.1 {}\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n",
+                status,
+                body.len(),
+                etag
+            );
+            if method == "GET" {
+                response.push_str(&body);
+            }
+            let _ = stream.write_all(response.as_bytes()).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_download_archive_images() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let gets = Arc::new(AtomicUsize::new(0));
+        tokio::spawn(serve(listener, gets.clone()));
+        let mut api_client = ApiClient::new();
+        api_client.assets_client = reqwest::Client::new();
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let images = ArchiveImages {
+            thumbnail: Some(format!("{}/thumb.png", base)),
+            banner: Some(format!("{}/etag_banner.jpg", base)),
+            performer_icons: BTreeMap::from([(1021, format!("{}/missing.png", base))]),
+        };
+
+        let manifest = api_client
+            .assets()
+            .download_archive_images("a1", &images, dir)
+            .await
+            .unwrap();
+        assert_eq!(gets.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("a1_thumbnail.png")).unwrap(),
+            "image /thumb.png"
+        );
+        assert_eq!(manifest.images[1].etag.as_deref(), Some("v1"));
+        assert!(manifest.images[2].error.is_some());
+        assert!(!dir.join("a1_performer_icon_1021.png").exists());
+
+        // Unchanged by size and by etag, the failed icon is tried again
+        let manifest = api_client
+            .assets()
+            .download_archive_images("a1", &images, dir)
+            .await
+            .unwrap();
+        assert_eq!(gets.load(Ordering::SeqCst), 4);
+        assert!(manifest.images[0].skipped && manifest.images[1].skipped);
+
+        // A local file of another size is fetched again
+        std::fs::write(dir.join("a1_thumbnail.png"), "stale").unwrap();
+        api_client
+            .assets()
+            .download_archive_images("a1", &images, dir)
+            .await
+            .unwrap();
+        assert_eq!(gets.load(Ordering::SeqCst), 6);
+        let written: ArchiveImageManifest =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("images.json")).unwrap())
+                .unwrap();
+        assert_eq!(written.archive_id, "a1");
+        assert_eq!(written.images[0].bytes, Some(16));
+        assert_eq!(written.images[0].image.role, ArchiveImageRole::Thumbnail);
+    }
+}
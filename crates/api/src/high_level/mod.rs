@@ -1,4 +1,6 @@
 use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
 
 use crate::{
     get_appstore_version, get_google_play_version,
@@ -6,14 +8,29 @@ use crate::{
     model::{
         AccountConnectRequest, ArchiveGetArchiveListRequest, ArchiveGetFesArchiveDataRequest,
         ArchiveGetWithArchiveDataRequest, FesliveConnectTokenRequest, FesliveEnterRequest,
-        LiveConnectTokenRequest, UserLoginRequest, WithliveEnterRequest,
+        FesliveLobbyRequest, FriendGetListRequest, JewelShopGetMembershipListResponse,
+        LiveConnectTokenRequest, MembershipListInfo, MrsRoomRoom, UserLoginRequest,
+        WithliveEnterRequest,
     },
 };
+use chrono::{DateTime, FixedOffset, Utc};
 use reqwest::header;
+use serde::Serialize;
 use serde_json::json;
 
 use crate::UA_PREFIX;
 
+mod friends;
+mod images;
+mod versions;
+
+pub use friends::FriendEntry;
+pub use images::{
+    ARCHIVE_IMAGES_MANIFEST, ArchiveImage, ArchiveImageManifest, ArchiveImageRole, ArchiveImages,
+    DownloadedImage,
+};
+pub use versions::{PROBE_ENDPOINTS, VersionProbe, VersionsAccepted};
+
 use_common_crate!();
 
 /// Helper struct to format Response with body for debugging
@@ -32,6 +49,297 @@ pub struct ArchiveListOptions {
     pub live_type: Option<i32>,
 }
 
+/// Everything needed to connect to a live's ALS room
+#[derive(Debug, Clone, Serialize)]
+pub struct AlsConnectionInfo {
+    pub host: String,
+    pub port: u16,
+    pub room_id: i32,
+    pub token: String,
+}
+
+impl AlsConnectionInfo {
+    fn from_room(room: Option<MrsRoomRoom>, token: String) -> Result<Self> {
+        let room = room.ok_or_else(|| anyhow::anyhow!("Live has no room info"))?;
+        let host = room
+            .ip_addr
+            .filter(|host| !host.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("Room has no ip address: {:?}", room.extra))?;
+        let port = room
+            .port
+            .and_then(|port| u16::try_from(port).ok())
+            .ok_or_else(|| anyhow::anyhow!("Room has no valid port: {:?}", room.port))?;
+        let room_id = room
+            .room_id
+            .ok_or_else(|| anyhow::anyhow!("Room has no room id"))?;
+        Ok(Self {
+            host,
+            port,
+            room_id,
+            token,
+        })
+    }
+}
+
+/// Hls playlist location of an archive
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveHlsInfo {
+    pub base_url: String,
+    pub playlist_file: String,
+    /// `{base_url}/{playlist_file}`, validated as url
+    pub full_url: String,
+}
+
+impl ArchiveHlsInfo {
+    fn from_json(json: &serde_json::Value) -> Result<Self> {
+        let field = |name: &str| {
+            json[name]
+                .as_str()
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+                .ok_or_else(|| anyhow::anyhow!("Archive json has no `{}`: {}", name, json))
+        };
+        let base_url = field("path")?;
+        let playlist_file = field("playlist_file")?;
+        let full_url = format!("{}/{}", base_url, playlist_file);
+        url::Url::parse(&full_url)
+            .map_err(|e| anyhow::anyhow!("Invalid archive hls url {}: {}", full_url, e))?;
+        Ok(Self {
+            base_url,
+            playlist_file,
+            full_url,
+        })
+    }
+}
+
+/// Part of an archive that can be published after the rest of its details
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArchiveResource {
+    /// `video_url`
+    Video,
+    /// `archive_url`, the motion replay data
+    Replay,
+    /// `timeline_ids`
+    Comments,
+}
+
+impl ArchiveResource {
+    pub const ALL: [ArchiveResource; 3] = [Self::Video, Self::Replay, Self::Comments];
+}
+
+impl fmt::Display for ArchiveResource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Video => "video",
+            Self::Replay => "replay",
+            Self::Comments => "comments",
+        })
+    }
+}
+
+impl FromStr for ArchiveResource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "video" => Ok(Self::Video),
+            "replay" => Ok(Self::Replay),
+            "comments" => Ok(Self::Comments),
+            _ => Err(anyhow::anyhow!("Unknown archive resource: {}", s)),
+        }
+    }
+}
+
+/// Which parts of an archive are published, derived from the details response
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ArchiveAvailability {
+    pub video_ready: bool,
+    pub replay_ready: bool,
+    pub comments_ready: bool,
+}
+
+impl ArchiveAvailability {
+    pub fn is_ready(&self, resource: ArchiveResource) -> bool {
+        match resource {
+            ArchiveResource::Video => self.video_ready,
+            ArchiveResource::Replay => self.replay_ready,
+            ArchiveResource::Comments => self.comments_ready,
+        }
+    }
+
+    /// Resources of `wanted` that are not published yet
+    pub fn missing(&self, wanted: &[ArchiveResource]) -> Vec<ArchiveResource> {
+        wanted
+            .iter()
+            .copied()
+            .filter(|resource| !self.is_ready(*resource))
+            .collect()
+    }
+}
+
+/// Archive details of a fes or with meets archive
+///
+/// Right after a live ends the api answers with some parts still null, those are `None`
+/// here instead of failing the whole request.
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveDetails {
+    pub title: Option<String>,
+    pub video_url: Option<String>,
+    /// Motion replay data
+    pub archive_url: Option<String>,
+    /// Comment timeline ids
+    pub timeline_ids: Option<Vec<i64>>,
+    pub availability: ArchiveAvailability,
+    /// Response body as returned by the api
+    #[serde(skip)]
+    pub raw: serde_json::Value,
+}
+
+impl ArchiveDetails {
+    /// Empty strings count as missing, like null
+    pub fn from_json(raw: serde_json::Value) -> Self {
+        let string = |name: &str| {
+            raw[name]
+                .as_str()
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        let title = string("title");
+        let video_url = string("video_url");
+        let archive_url = string("archive_url");
+        let timeline_ids = raw["timeline_ids"]
+            .as_array()
+            .map(|ids| ids.iter().filter_map(serde_json::Value::as_i64).collect());
+        let availability = ArchiveAvailability {
+            video_ready: video_url.is_some(),
+            replay_ready: archive_url.is_some(),
+            comments_ready: timeline_ids.is_some(),
+        };
+        Self {
+            title,
+            video_url,
+            archive_url,
+            timeline_ids,
+            availability,
+            raw,
+        }
+    }
+
+    /// Response body with the `availability` summary added
+    pub fn to_json(&self) -> serde_json::Value {
+        let mut json = self.raw.clone();
+        if let Some(object) = json.as_object_mut() {
+            object.insert("availability".to_string(), json!(self.availability));
+        }
+        json
+    }
+
+    /// Images named in the details body
+    pub fn images(&self) -> ArchiveImages {
+        ArchiveImages::from_archive(&self.raw)
+    }
+
+    /// Fails with [`ArchiveNotReadyError`] when a resource of `wanted` is missing
+    pub fn require(&self, id: &str, wanted: &[ArchiveResource]) -> Result<()> {
+        let missing = self.availability.missing(wanted);
+        if missing.is_empty() {
+            return Ok(());
+        }
+        Err(ArchiveNotReadyError {
+            id: id.to_string(),
+            missing,
+            retry_after: ARCHIVE_POLL_INTERVAL,
+        }
+        .into())
+    }
+}
+
+/// Archives searched for the list entry by [`HighLevelApi::get_archive_images`]
+pub const ARCHIVE_IMAGES_LIST_LIMIT: u32 = 100;
+
+/// How often [`HighLevelApi::wait_for_archive_details`] asks again
+pub const ARCHIVE_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Parts of an archive are not published yet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveNotReadyError {
+    pub id: String,
+    pub missing: Vec<ArchiveResource>,
+    /// Suggested wait before asking again
+    pub retry_after: Duration,
+}
+
+impl fmt::Display for ArchiveNotReadyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let missing: Vec<_> = self.missing.iter().map(ToString::to_string).collect();
+        write!(
+            f,
+            "Archive {} is not yet available ({} missing), retry after {}s",
+            self.id,
+            missing.join(", "),
+            self.retry_after.as_secs()
+        )
+    }
+}
+
+impl std::error::Error for ArchiveNotReadyError {}
+
+/// Cache key of the membership list the subscription status is built from
+pub(crate) const SUBSCRIPTION_ENDPOINT: &str = "/jewel_shop/get_membership_list";
+
+/// Plan the account is subscribed to, fes lives and some archives need an active one
+#[derive(Debug, Clone, Serialize)]
+pub struct SubscriptionStatus {
+    /// Name of the latest expiring membership, `None` when the account never subscribed
+    pub plan_name: Option<String>,
+    pub active: bool,
+    pub expires_at: Option<DateTime<FixedOffset>>,
+    /// Every membership as returned by the api
+    pub memberships: Vec<MembershipListInfo>,
+}
+
+impl SubscriptionStatus {
+    /// Only memberships with an `expire_time` were ever contracted, the latest one wins
+    pub fn from_response(body: JewelShopGetMembershipListResponse, now: DateTime<Utc>) -> Self {
+        let memberships = body.membership_list.unwrap_or_default();
+        let latest = memberships
+            .iter()
+            .filter_map(|membership| {
+                let expires_at = parse_api_time(membership.expire_time.as_deref()?)?;
+                Some((membership, expires_at))
+            })
+            .max_by_key(|(_, expires_at)| *expires_at);
+        Self {
+            plan_name: latest.and_then(|(membership, _)| membership.name.clone()),
+            active: latest.is_some_and(|(_, expires_at)| expires_at > now),
+            expires_at: latest.map(|(_, expires_at)| expires_at),
+            memberships,
+        }
+    }
+
+    /// Whole days until expiry, negative once lapsed
+    pub fn days_left(&self, now: DateTime<Utc>) -> Option<i64> {
+        self.expires_at
+            .map(|expires_at| (expires_at.with_timezone(&Utc) - now).num_days())
+    }
+
+    /// Lapsed, or expiring within `days`, accounts that never subscribed get no warning
+    pub fn needs_warning(&self, now: DateTime<Utc>, days: i64) -> bool {
+        match self.expires_at {
+            Some(expires_at) => !self.active || expires_at <= now + chrono::TimeDelta::days(days),
+            None => false,
+        }
+    }
+}
+
+/// Api times are rfc3339, some without the colon in the offset
+fn parse_api_time(value: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(value)
+        .or_else(|_| DateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f%z"))
+        .ok()
+}
+
 impl fmt::Debug for ResponseDebug {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Response")
@@ -63,18 +371,19 @@ impl ResponseDebug {
 define_api_struct!(AssetsApi);
 
 impl<'a> AssetsApi<'a> {
-    pub async fn get_hls_url_from_archive(&self, url: &str) -> Result<String> {
-        let res = self.assets_client.get(url).send().await?;
+    pub async fn get_hls_url_from_archive(&self, url: &str) -> Result<ArchiveHlsInfo> {
+        self.ensure_online(url)?;
+        let res = self
+            .assets_client
+            .get(url)
+            .headers(self.assets_header.clone())
+            .send()
+            .await?;
         if res.status() != reqwest::StatusCode::OK {
             return Err(anyhow::anyhow!("Get archive failed: {:?}", res));
         }
         let json: serde_json::Value = res.json().await?;
-        let hls_url = format!(
-            "{}/{}",
-            json["path"].as_str().unwrap(),
-            json["playlist_file"].as_str().unwrap()
-        );
-        Ok(hls_url.to_string())
+        ArchiveHlsInfo::from_json(&json)
     }
 }
 
@@ -89,13 +398,14 @@ impl<'a> HighLevelApi<'a> {
     ///
     /// Returns (x-res-version, `app version from website`)
     pub async fn get_app_version(&self) -> Result<(Option<String>, Option<String>)> {
+        self.ensure_online("/user/login")?;
         let app_version = match get_appstore_version().await {
             Some(version) => Some(version),
             None => get_google_play_version().await,
         };
         tracing::info!("Detected app version: {:?}", app_version);
         // empty id login check
-        let url = format!("{API_BASE}/user/login");
+        let url = format!("{}/user/login", self.api_base);
         let res = self
             .client
             .post(url)
@@ -185,24 +495,56 @@ impl<'a> HighLevelApi<'a> {
         Ok(session_token)
     }
 
+    /// Cached as `/archive/get_home`
     pub async fn get_plan_list(&self) -> Result<serde_json::Value> {
-        let body = self.raw().archive().get_home().await?;
-        let mut merged = body.live_archive_list.unwrap_or_default();
-        merged.extend(body.trailer_archive_list.unwrap_or_default());
-        Ok(serde_json::to_value(merged)?)
+        self.cached("/archive/get_home", async {
+            let body = self.raw().archive().get_home().await?;
+            let mut merged = body.live_archive_list.unwrap_or_default();
+            merged.extend(body.trailer_archive_list.unwrap_or_default());
+            Ok(serde_json::to_value(merged)?)
+        })
+        .await
+    }
+
+    /// Cached as `/jewel_shop/get_membership_list`
+    pub async fn get_subscription_status(&self) -> Result<SubscriptionStatus> {
+        let body = self
+            .cached(SUBSCRIPTION_ENDPOINT, async {
+                let body = self.raw().jewel_shop().get_membership_list().await?;
+                Ok(serde_json::to_value(body)?)
+            })
+            .await?;
+        Ok(SubscriptionStatus::from_response(
+            serde_json::from_value(body)?,
+            Utc::now(),
+        ))
     }
 
+    /// Cached as `/archive/get_archive_list`, offline results are cut to `limit`
     pub async fn get_archive_list(&self, options: ArchiveListOptions) -> Result<serde_json::Value> {
+        let limit = options.limit.unwrap_or(4);
         let request = ArchiveGetArchiveListRequest {
             order: Some(options.order.unwrap_or_else(|| "desc".to_string())),
             characters: Some(Vec::new()),
-            limit: Some(options.limit.unwrap_or(4) as i32),
-            sort: Some(options.sort.unwrap_or_else(|| "live_start_time".to_string())),
+            limit: Some(limit as i32),
+            sort: Some(
+                options
+                    .sort
+                    .unwrap_or_else(|| "live_start_time".to_string()),
+            ),
             live_type: options.live_type,
             ..Default::default()
         };
-        let body = self.raw().archive().get_archive_list(&request).await?;
-        Ok(serde_json::to_value(body.archive_list.unwrap_or_default())?)
+        let mut list = self
+            .cached("/archive/get_archive_list", async {
+                let body = self.raw().archive().get_archive_list(&request).await?;
+                Ok(serde_json::to_value(body.archive_list.unwrap_or_default())?)
+            })
+            .await?;
+        if let Some(list) = list.as_array_mut() {
+            list.truncate(limit as usize);
+        }
+        Ok(list)
     }
 
     pub async fn get_with_meets_info(&self, id: &str) -> Result<serde_json::Value> {
@@ -249,23 +591,278 @@ impl<'a> HighLevelApi<'a> {
         Ok(connect_token)
     }
 
-    pub async fn get_archive_details(&self, id: &str, live_type: u8) -> Result<serde_json::Value> {
-        if live_type == 1 {
+    /// Enter the live and fetch its connect token, `live_type` 1 = fes live, 2 = with meets
+    pub async fn get_als_connection_info(
+        &self,
+        live_id: &str,
+        live_type: u8,
+    ) -> Result<AlsConnectionInfo> {
+        match live_type {
+            1 => {
+                // fes live has to be entered from the lobby
+                let lobby_request = FesliveLobbyRequest {
+                    live_id: Some(live_id.to_string()),
+                    ..Default::default()
+                };
+                let _ = self.raw().fes_live().lobby(&lobby_request).await;
+                let request = FesliveEnterRequest {
+                    live_id: Some(live_id.to_string()),
+                    ..Default::default()
+                };
+                let body = self.raw().fes_live().enter(&request).await?;
+                let token = self.get_fes_live_connect_token(live_id).await?;
+                AlsConnectionInfo::from_room(body.room, token)
+            }
+            2 => {
+                let request = WithliveEnterRequest {
+                    live_id: Some(live_id.to_string()),
+                    ..Default::default()
+                };
+                let body = self.raw().with_live().enter(&request).await?;
+                let token = self.get_with_meets_connect_token(live_id).await?;
+                AlsConnectionInfo::from_room(body.room, token)
+            }
+            _ => Err(anyhow::anyhow!("Unsupported live type: {}", live_type)),
+        }
+    }
+
+    pub async fn get_archive_details(&self, id: &str, live_type: u8) -> Result<ArchiveDetails> {
+        let body = if live_type == 1 {
             let request = ArchiveGetFesArchiveDataRequest {
                 archives_id: Some(id.to_string()),
                 ..Default::default()
             };
             let body = self.raw().archive().get_fes_archive_data(&request).await?;
-            Ok(serde_json::to_value(body)?)
+            serde_json::to_value(body)?
         } else if live_type == 2 {
             let request = ArchiveGetWithArchiveDataRequest {
                 archives_id: Some(id.to_string()),
                 ..Default::default()
             };
             let body = self.raw().archive().get_with_archive_data(&request).await?;
-            Ok(serde_json::to_value(body)?)
+            serde_json::to_value(body)?
         } else {
-            Err(anyhow::anyhow!("Unsupported live type: {}", live_type))
+            return Err(anyhow::anyhow!("Unsupported live type: {}", live_type));
+        };
+        Ok(ArchiveDetails::from_json(body))
+    }
+
+    /// Images of the details merged with those of the archive's list entry, the thumbnail
+    /// is usually only in the list. The entry is looked up among the latest
+    /// [`ARCHIVE_IMAGES_LIST_LIMIT`] archives and skipped when the list can't be fetched.
+    pub async fn get_archive_images(&self, id: &str, live_type: u8) -> Result<ArchiveImages> {
+        let images = self.get_archive_details(id, live_type).await?.images();
+        let list = self
+            .get_archive_list(ArchiveListOptions {
+                limit: Some(ARCHIVE_IMAGES_LIST_LIMIT),
+                live_type: Some(live_type as i32),
+                ..Default::default()
+            })
+            .await;
+        let entry = match &list {
+            Ok(list) => list.as_array().and_then(|list| {
+                list.iter()
+                    .find(|entry| entry["archives_id"].as_str() == Some(id))
+            }),
+            Err(e) => {
+                tracing::debug!(archives_id = id, "Archive list unavailable: {:#}", e);
+                None
+            }
+        };
+        Ok(match entry {
+            Some(entry) => images.merge(ArchiveImages::from_archive(entry)),
+            None => images,
+        })
+    }
+
+    /// Cached as `/friend/get_list`, every friend of the account in one request
+    pub async fn get_friend_list(&self) -> Result<Vec<FriendEntry>> {
+        let body = self
+            .cached("/friend/get_list", async {
+                let body = self
+                    .raw()
+                    .friend()
+                    .get_list(&FriendGetListRequest::default())
+                    .await?;
+                Ok(serde_json::to_value(body)?)
+            })
+            .await?;
+        Ok(FriendEntry::from_response(&serde_json::from_value(body)?))
+    }
+
+    /// Poll the archive details every [`ARCHIVE_POLL_INTERVAL`] until `wanted` are published,
+    /// fails with [`ArchiveNotReadyError`] when they are still missing after `timeout`
+    pub async fn wait_for_archive_details(
+        &self,
+        id: &str,
+        live_type: u8,
+        wanted: &[ArchiveResource],
+        timeout: Duration,
+    ) -> Result<ArchiveDetails> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let details = self.get_archive_details(id, live_type).await?;
+            let missing = details.availability.missing(wanted);
+            let now = tokio::time::Instant::now();
+            if missing.is_empty() || now >= deadline {
+                details.require(id, wanted)?;
+                return Ok(details);
+            }
+            tracing::debug!(
+                archives_id = id,
+                ?missing,
+                "Archive not ready, polling again"
+            );
+            tokio::time::sleep(ARCHIVE_POLL_INTERVAL.min(deadline - now)).await;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_hls_info() {
+        let info = ArchiveHlsInfo::from_json(&json!({
+            "path": "https://assets.example.com/archive/1",
+            "playlist_file": "index.m3u8",
+        }))
+        .unwrap();
+        assert_eq!(
+            info.full_url,
+            "https://assets.example.com/archive/1/index.m3u8"
+        );
+
+        assert!(ArchiveHlsInfo::from_json(&json!({ "path": "https://a.example.com" })).is_err());
+        assert!(
+            ArchiveHlsInfo::from_json(&json!({ "path": "not a url", "playlist_file": "a.m3u8" }))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_archive_details_availability() {
+        let ready = ArchiveDetails::from_json(json!({
+            "title": "Live",
+            "video_url": "https://assets.example.com/video.m3u8",
+            "archive_url": "https://assets.example.com/archive/1",
+            "timeline_ids": [1, 2],
+        }));
+        assert_eq!(
+            ready.availability,
+            ArchiveAvailability {
+                video_ready: true,
+                replay_ready: true,
+                comments_ready: true,
+            }
+        );
+        assert!(ready.require("1", &ArchiveResource::ALL).is_ok());
+        assert_eq!(ready.to_json()["availability"]["replay_ready"], true);
+
+        let partial = ArchiveDetails::from_json(json!({
+            "title": "Live",
+            "video_url": "https://assets.example.com/video.m3u8",
+            "archive_url": "",
+            "timeline_ids": null,
+        }));
+        assert_eq!(partial.archive_url, None);
+        assert_eq!(
+            partial.availability.missing(&ArchiveResource::ALL),
+            vec![ArchiveResource::Replay, ArchiveResource::Comments]
+        );
+        assert!(partial.require("1", &[ArchiveResource::Video]).is_ok());
+        let error = partial
+            .require("1", &[ArchiveResource::Replay])
+            .unwrap_err();
+        let error = error.downcast_ref::<ArchiveNotReadyError>().unwrap();
+        assert_eq!(error.missing, vec![ArchiveResource::Replay]);
+        assert_eq!(
+            error.to_string(),
+            "Archive 1 is not yet available (replay missing), retry after 30s"
+        );
+
+        let none = ArchiveDetails::from_json(json!({ "title": "Live" }));
+        assert_eq!(none.availability, ArchiveAvailability::default());
+        assert_eq!(none.title.as_deref(), Some("Live"));
+        assert_eq!(
+            "comments".parse::<ArchiveResource>().unwrap(),
+            ArchiveResource::Comments
+        );
+        assert!("all".parse::<ArchiveResource>().is_err());
+    }
+
+    #[test]
+    fn test_als_connection_info_from_room() {
+        let room = MrsRoomRoom {
+            room_id: Some(42),
+            ip_addr: Some("10.0.0.1".to_string()),
+            port: Some(30000),
+            ..Default::default()
+        };
+        let info = AlsConnectionInfo::from_room(Some(room.clone()), "token".to_string()).unwrap();
+        assert_eq!(info.host, "10.0.0.1");
+        assert_eq!(info.port, 30000);
+        assert_eq!(info.room_id, 42);
+
+        let no_port = MrsRoomRoom {
+            port: Some(-1),
+            ..room
+        };
+        assert!(AlsConnectionInfo::from_room(Some(no_port), String::new()).is_err());
+        assert!(AlsConnectionInfo::from_room(None, String::new()).is_err());
+    }
+
+    fn subscription(body: &str, now: DateTime<Utc>) -> SubscriptionStatus {
+        SubscriptionStatus::from_response(serde_json::from_str(body).unwrap(), now)
+    }
+
+    #[test]
+    fn test_subscription_status() {
+        let now = DateTime::parse_from_rfc3339("2025-08-21T12:00:00+09:00")
+            .unwrap()
+            .with_timezone(&Utc);
+        let active = r#"{
+            "bundle_id": "com.oddno.lovelive",
+            "membership_list": [
+                { "memberships_id": 1, "name": "Hasunosora Pass", "contract_state": 1,
+                  "expire_time": "2025-10-01T04:00:00+09:00" },
+                { "memberships_id": 2, "name": "Premium Pass", "expire_time": null }
+            ]
+        }"#;
+        let status = subscription(active, now);
+        assert_eq!(status.plan_name.as_deref(), Some("Hasunosora Pass"));
+        assert!(status.active);
+        assert_eq!(status.days_left(now), Some(40));
+        assert_eq!(status.memberships.len(), 2);
+        assert!(!status.needs_warning(now, 7));
+
+        let expiring_soon = r#"{
+            "membership_list": [
+                { "name": "Old Pass", "expire_time": "2025-06-01T04:00:00+0900" },
+                { "name": "Hasunosora Pass", "expire_time": "2025-08-24T04:00:00+09:00" }
+            ]
+        }"#;
+        let status = subscription(expiring_soon, now);
+        assert_eq!(status.plan_name.as_deref(), Some("Hasunosora Pass"));
+        assert!(status.active);
+        assert_eq!(status.days_left(now), Some(2));
+        assert!(status.needs_warning(now, 7));
+        assert!(!status.needs_warning(now, 1));
+
+        let lapsed = r#"{
+            "membership_list": [
+                { "name": "Hasunosora Pass", "expire_time": "2025-08-01T04:00:00+09:00" }
+            ]
+        }"#;
+        let status = subscription(lapsed, now);
+        assert!(!status.active);
+        assert_eq!(status.days_left(now), Some(-20));
+        assert!(status.needs_warning(now, 0));
+
+        let never = subscription(r#"{ "membership_list": [{ "name": "Pass" }] }"#, now);
+        assert!(!never.active);
+        assert_eq!(never.plan_name, None);
+        assert!(!never.needs_warning(now, 7));
+    }
+}
@@ -0,0 +1,238 @@
+//! Which client version each endpoint accepts
+//!
+//! [`HighLevelApi::probe_versions`] sends the same request to each of the read-only
+//! [`PROBE_ENDPOINTS`], once with the pinned and once with the store `x-client-version`,
+//! the User-Agent following it. Header overrides are left out, the result is what goes
+//! into them after a game update. Nothing is cached.
+
+use reqwest::header;
+use serde::Serialize;
+use serde_json::json;
+
+use super::HighLevelApi;
+use crate::{UA_PREFIX, gen_random_idempotency_key};
+
+/// Read-only endpoints taking an empty request
+pub const PROBE_ENDPOINTS: [&str; 4] = [
+    "/archive/get_home",
+    "/archive/get_channel_list",
+    "/jewel_shop/get_membership_list",
+    "/friend/get_request_list",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionsAccepted {
+    Both,
+    Pinned,
+    Store,
+    Neither,
+}
+
+/// Response status of one endpoint with each version, `None` when no response came back
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VersionProbe {
+    pub endpoint: String,
+    pub pinned: Option<u16>,
+    pub store: Option<u16>,
+    pub accepts: VersionsAccepted,
+}
+
+impl VersionProbe {
+    pub fn new(endpoint: &str, pinned: Option<u16>, store: Option<u16>) -> Self {
+        let accepted =
+            |status: Option<u16>| status.is_some_and(|status| (200..300).contains(&status));
+        let accepts = match (accepted(pinned), accepted(store)) {
+            (true, true) => VersionsAccepted::Both,
+            (true, false) => VersionsAccepted::Pinned,
+            (false, true) => VersionsAccepted::Store,
+            (false, false) => VersionsAccepted::Neither,
+        };
+        Self {
+            endpoint: endpoint.to_string(),
+            pinned,
+            store,
+            accepts,
+        }
+    }
+}
+
+impl<'a> HighLevelApi<'a> {
+    /// One row per [`PROBE_ENDPOINTS`] entry
+    pub async fn probe_versions(
+        &self,
+        pinned: &str,
+        store: &str,
+    ) -> anyhow::Result<Vec<VersionProbe>> {
+        self.ensure_online("probe versions")?;
+        let mut probes = Vec::new();
+        for endpoint in PROBE_ENDPOINTS {
+            let pinned_status = self.probe_status(endpoint, pinned).await;
+            let store_status = self.probe_status(endpoint, store).await;
+            probes.push(VersionProbe::new(endpoint, pinned_status, store_status));
+        }
+        Ok(probes)
+    }
+
+    // Helper: status of `endpoint` with `client_version`, the failure is only logged
+    async fn probe_status(&self, endpoint: &str, client_version: &str) -> Option<u16> {
+        // `header` would add a second value next to the runtime one
+        let mut headers = self.runtime_header.clone();
+        let version_headers = [
+            ("x-client-version", client_version.to_string()),
+            ("user-agent", format!("{UA_PREFIX}/{client_version}")),
+        ];
+        for (name, value) in version_headers {
+            match header::HeaderValue::from_str(&value) {
+                Ok(value) => headers.insert(name, value),
+                Err(_) => return None,
+            };
+        }
+        let res = self
+            .client
+            .post(format!("{}{}", self.api_base, endpoint))
+            .headers(headers)
+            .header("x-idempotency-key", gen_random_idempotency_key())
+            .json(&json!({}))
+            .send()
+            .await;
+        match res {
+            Ok(res) => Some(res.status().as_u16()),
+            Err(e) => {
+                tracing::debug!(endpoint, client_version, "Probe request failed: {}", e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ApiClient, HeaderOverrides};
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    const PINNED: &str = "3.1.0";
+    const STORE: &str = "3.2.0";
+
+    // Versions each path accepts, every request is recorded as (path, x-client-version)
+    async fn serve(listener: tokio::net::TcpListener, requests: Arc<Mutex<Vec<(String, String)>>>) {
+        loop {
+            let Ok((mut stream, _)) = listener.accept().await else {
+                return;
+            };
+            let mut request = vec![0; 4096];
+            let len = stream.read(&mut request).await.unwrap_or(0);
+            let request = String::from_utf8_lossy(&request[..len]).to_string();
+            let path = request
+                .split_whitespace()
+                .nth(1)
+                .unwrap_or("")
+                .trim_start_matches("/v1")
+                .to_string();
+            let version = request
+                .lines()
+                .find_map(|line| line.strip_prefix("x-client-version: "))
+                .unwrap_or("")
+                .to_string();
+            let accepted: &[&str] = match path.as_str() {
+                "/archive/get_home" => &[PINNED, STORE],
+                "/archive/get_channel_list" => &[],
+                "/jewel_shop/get_membership_list" => &[STORE],
+                _ => &[PINNED],
+            };
+            let status = if accepted.contains(&version.as_str()) {
+                "200 OK"
+            } else {
+                "403 Forbidden"
+            };
+            requests.lock().unwrap().push((path, version));
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{{}}",
+                status
+            );
+            let _ = stream.write_all(response.as_bytes()).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_probe_versions() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}/v1", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        tokio::spawn(serve(listener, requests.clone()));
+        let overrides = BTreeMap::from([("x-client-version".to_string(), PINNED.to_string())]);
+        let mut header_overrides = HeaderOverrides::default();
+        header_overrides.insert("/friend/*", &overrides).unwrap();
+        let mut api_client = ApiClient::new().with_header_overrides(header_overrides);
+        api_client.client = reqwest::Client::new();
+        api_client.api_base = base;
+        api_client.update_version("R2504300", STORE);
+
+        let probes = api_client
+            .high_level()
+            .probe_versions(PINNED, STORE)
+            .await
+            .unwrap();
+        assert_eq!(
+            probes,
+            vec![
+                VersionProbe::new("/archive/get_home", Some(200), Some(200)),
+                VersionProbe::new("/archive/get_channel_list", Some(403), Some(403)),
+                VersionProbe::new("/jewel_shop/get_membership_list", Some(403), Some(200)),
+                VersionProbe::new("/friend/get_request_list", Some(200), Some(403)),
+            ]
+        );
+        let accepts: Vec<_> = probes.iter().map(|probe| probe.accepts).collect();
+        assert_eq!(
+            accepts,
+            [
+                VersionsAccepted::Both,
+                VersionsAccepted::Neither,
+                VersionsAccepted::Store,
+                VersionsAccepted::Pinned,
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(&probes[2]).unwrap()["accepts"],
+            "store"
+        );
+
+        // the probe ignores the overrides, regular requests get them
+        requests.lock().unwrap().clear();
+        api_client.raw().friend().get_request_list().await.unwrap();
+        assert!(
+            api_client
+                .raw()
+                .jewel_shop()
+                .get_membership_list()
+                .await
+                .is_ok()
+        );
+        assert_eq!(
+            *requests.lock().unwrap(),
+            [
+                ("/friend/get_request_list".to_string(), PINNED.to_string()),
+                (
+                    "/jewel_shop/get_membership_list".to_string(),
+                    STORE.to_string()
+                ),
+            ]
+        );
+
+        assert_eq!(
+            VersionProbe::new("/archive/get_home", None, Some(204)).accepts,
+            VersionsAccepted::Store
+        );
+        let offline = ApiClient::new().offline(true);
+        assert!(
+            offline
+                .high_level()
+                .probe_versions(PINNED, STORE)
+                .await
+                .is_err()
+        );
+    }
+}
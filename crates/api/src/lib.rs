@@ -1,15 +1,25 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use rand::Rng;
 use rand::distr::Alphanumeric;
 use reqwest::header;
 use serde::{Deserialize, Serialize};
 
+mod cache;
 mod high_level;
 mod l4;
 mod macros;
+mod overrides;
 
 pub mod model;
-pub use high_level::ArchiveListOptions;
+pub use cache::{CacheEntry, DEFAULT_CACHE_TTL, ResponseCache};
+pub use high_level::{
+    ARCHIVE_IMAGES_LIST_LIMIT, ARCHIVE_IMAGES_MANIFEST, ARCHIVE_POLL_INTERVAL, AlsConnectionInfo,
+    ArchiveAvailability, ArchiveDetails, ArchiveHlsInfo, ArchiveImage, ArchiveImageManifest,
+    ArchiveImageRole, ArchiveImages, ArchiveListOptions, ArchiveNotReadyError, ArchiveResource,
+    DownloadedImage, FriendEntry, PROBE_ENDPOINTS, SubscriptionStatus, VersionProbe,
+    VersionsAccepted,
+};
+pub use overrides::HeaderOverrides;
 #[derive(Debug, Default, Deserialize, Serialize)]
 pub struct Credential {
     /// x-res-version
@@ -33,6 +43,11 @@ const WEB_UA: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.
 pub const UA_PREFIX: &str = "inspix-android";
 pub const BASE_RES_VERSION: &str = "R2504300";
 pub const BASE_CLIENT_VERSION: &str = "3.1.0";
+/// X-Unity-Version of the assets client, overridden by `LINKURA_UNITY_VERSION`
+pub const BASE_UNITY_VERSION: &str = "2021.3.36f1";
+/// Overrides the assets client User-Agent built from the Unity version
+pub const ASSETS_USER_AGENT_ENV: &str = "LINKURA_ASSETS_USER_AGENT";
+pub const UNITY_VERSION_ENV: &str = "LINKURA_UNITY_VERSION";
 
 mod api_header {
     /// x-device-type
@@ -56,11 +71,20 @@ pub struct ApiClient {
     pub(crate) client: reqwest::Client,
     pub(crate) assets_client: reqwest::Client,
     pub(crate) runtime_header: header::HeaderMap,
+    /// Applied on top of `runtime_header` per endpoint
+    pub(crate) header_overrides: HeaderOverrides,
+    /// `API_BASE`, a local server in tests
+    pub(crate) api_base: String,
+    /// User-Agent and X-Unity-Version sent by the assets client
+    pub(crate) assets_header: header::HeaderMap,
+    pub(crate) cache: Option<ResponseCache>,
+    /// Never touch the network, serve cached responses only
+    pub(crate) offline: bool,
 }
 
 impl ApiClient {
     pub fn new() -> Self {
-        Self {
+        let mut client = Self {
             client: reqwest::Client::builder()
                 .default_headers({
                     let mut headers = header::HeaderMap::new();
@@ -87,27 +111,60 @@ impl ApiClient {
                 .build()
                 .unwrap(),
             runtime_header: header::HeaderMap::new(),
+            header_overrides: HeaderOverrides::builtin(),
+            api_base: API_BASE.to_string(),
             assets_client: reqwest::Client::builder()
                 .default_headers({
                     let mut headers = header::HeaderMap::new();
-                    headers.insert(
-                        header::USER_AGENT,
-                        "UnityPlayer/2021.3.36f1 (UnityWebRequest/1.0, libcurl/8.5.0-DEV)"
-                            .parse()
-                            .unwrap(),
-                    );
                     headers.insert(header::ACCEPT, "*/*".parse().unwrap());
                     headers.insert(
                         header::HOST,
                         "assets.link-like-lovelive.app".parse().unwrap(),
                     );
                     headers.insert(header::ACCEPT_ENCODING, "deflate, gzip".parse().unwrap());
-                    headers.insert("X-Unity-Version", "2021.3.36f1".parse().unwrap());
                     headers
                 })
                 .build()
                 .unwrap(),
+            assets_header: header::HeaderMap::new(),
+            cache: None,
+            offline: false,
+        };
+        client
+            .set_unity_version(BASE_UNITY_VERSION)
+            .expect("built-in Unity version is a valid header value");
+        if let Ok(unity_version) = std::env::var(UNITY_VERSION_ENV)
+            && let Err(e) = client.set_unity_version(&unity_version)
+        {
+            tracing::warn!("Ignoring {}: {}", UNITY_VERSION_ENV, e);
         }
+        if let Ok(user_agent) = std::env::var(ASSETS_USER_AGENT_ENV)
+            && let Err(e) = client.set_assets_user_agent(&user_agent)
+        {
+            tracing::warn!("Ignoring {}: {}", ASSETS_USER_AGENT_ENV, e);
+        }
+        client
+    }
+
+    /// Cache successful responses, and serve them in offline mode
+    pub fn with_cache(mut self, cache: ResponseCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Replace the built-in per-endpoint header overrides
+    pub fn with_header_overrides(mut self, header_overrides: HeaderOverrides) -> Self {
+        self.header_overrides = header_overrides;
+        self
+    }
+
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline
     }
 
     pub fn raw(&self) -> l4::LinkuraApi {
@@ -144,6 +201,27 @@ impl ApiClient {
         );
     }
 
+    /// Set X-Unity-Version of the assets client, the User-Agent follows it.
+    /// Headers are left unchanged when `unity_version` is not a valid header value.
+    pub fn set_unity_version(&mut self, unity_version: &str) -> Result<()> {
+        let version: header::HeaderValue = unity_version
+            .parse()
+            .with_context(|| format!("Invalid Unity version {:?}", unity_version))?;
+        self.set_assets_user_agent(&format!(
+            "UnityPlayer/{unity_version} (UnityWebRequest/1.0, libcurl/8.5.0-DEV)"
+        ))?;
+        self.assets_header.insert("X-Unity-Version", version);
+        Ok(())
+    }
+
+    pub fn set_assets_user_agent(&mut self, user_agent: &str) -> Result<()> {
+        let user_agent = user_agent
+            .parse()
+            .with_context(|| format!("Invalid User-Agent {:?}", user_agent))?;
+        self.assets_header.insert(header::USER_AGENT, user_agent);
+        Ok(())
+    }
+
     pub fn set_session_token(&mut self, token: &str) {
         self.runtime_header.insert(
             header::AUTHORIZATION,
@@ -156,6 +234,71 @@ impl ApiClient {
     }
 }
 
+// request
+impl ApiClient {
+    /// Runtime headers with the overrides of `endpoint` on top
+    pub(crate) fn request_headers(&self, endpoint: &str) -> header::HeaderMap {
+        self.header_overrides.apply(endpoint, &self.runtime_header)
+    }
+}
+
+// offline & cache
+impl ApiClient {
+    pub(crate) fn ensure_online(&self, endpoint: &str) -> Result<()> {
+        if self.offline {
+            return Err(anyhow::anyhow!(
+                "{} requires network, remove --offline",
+                endpoint
+            ));
+        }
+        Ok(())
+    }
+
+    /// Serve `endpoint` from the cache when offline, otherwise fetch and cache it
+    pub(crate) async fn cached<F>(&self, endpoint: &str, fetch: F) -> Result<serde_json::Value>
+    where
+        F: Future<Output = Result<serde_json::Value>>,
+    {
+        if self.offline {
+            let entry = self
+                .cache
+                .as_ref()
+                .map(|cache| cache.load(endpoint))
+                .transpose()?
+                .flatten()
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "{} is not cached, run once without --offline first",
+                        endpoint
+                    )
+                })?;
+            if entry.is_expired() {
+                tracing::warn!("Cached response of {} is stale", endpoint);
+            }
+            return Ok(entry.body);
+        }
+
+        let body = fetch.await?;
+        if let Some(Err(e)) = self
+            .cache
+            .as_ref()
+            .map(|cache| cache.store(endpoint, &body))
+        {
+            tracing::warn!("Failed to cache response of {}: {}", endpoint, e);
+        }
+        Ok(body)
+    }
+
+    /// Whether the last cached subscription status says the plan is not active
+    pub(crate) fn subscription_lapsed(&self) -> bool {
+        self.cache
+            .as_ref()
+            .and_then(|cache| cache.load(high_level::SUBSCRIPTION_ENDPOINT).ok().flatten())
+            .and_then(|entry| serde_json::from_value(entry.body).ok())
+            .is_some_and(|body| !SubscriptionStatus::from_response(body, chrono::Utc::now()).active)
+    }
+}
+
 async fn _get_appstore_version() -> Result<Option<String>> {
     let website = reqwest::Client::new()
         .get(LINKURA_APP_STORE_URL)
@@ -200,3 +343,65 @@ async fn _get_google_play_version() -> Result<Option<String>> {
 pub async fn get_google_play_version() -> Option<String> {
     _get_google_play_version().await.ok().flatten()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assets_header_setters() {
+        let mut api_client = ApiClient::new();
+        api_client.set_unity_version("2022.3.10f1").unwrap();
+        assert_eq!(api_client.assets_header["X-Unity-Version"], "2022.3.10f1");
+        assert_eq!(
+            api_client.assets_header[header::USER_AGENT],
+            "UnityPlayer/2022.3.10f1 (UnityWebRequest/1.0, libcurl/8.5.0-DEV)"
+        );
+
+        api_client
+            .set_assets_user_agent("UnityPlayer/custom")
+            .unwrap();
+        assert_eq!(
+            api_client.assets_header[header::USER_AGENT],
+            "UnityPlayer/custom"
+        );
+        assert_eq!(api_client.assets_header["X-Unity-Version"], "2022.3.10f1");
+
+        assert!(api_client.set_unity_version("2022.3\n10f1").is_err());
+        assert!(api_client.set_assets_user_agent("UnityPlayer\r\n").is_err());
+        assert_eq!(api_client.assets_header["X-Unity-Version"], "2022.3.10f1");
+        assert_eq!(
+            api_client.assets_header[header::USER_AGENT],
+            "UnityPlayer/custom"
+        );
+    }
+
+    #[test]
+    fn test_subscription_lapsed_from_cache() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let cache = ResponseCache::new(dir);
+        let api_client = ApiClient::new().with_cache(cache.clone());
+        assert!(!api_client.subscription_lapsed());
+
+        let membership = |expire_time: &str| {
+            serde_json::json!({
+                "membership_list": [{ "name": "Plan", "expire_time": expire_time }]
+            })
+        };
+        cache
+            .store(
+                high_level::SUBSCRIPTION_ENDPOINT,
+                &membership("2000-01-01T00:00:00+09:00"),
+            )
+            .unwrap();
+        assert!(api_client.subscription_lapsed());
+        cache
+            .store(
+                high_level::SUBSCRIPTION_ENDPOINT,
+                &membership("2999-01-01T00:00:00+09:00"),
+            )
+            .unwrap();
+        assert!(!api_client.subscription_lapsed());
+    }
+}
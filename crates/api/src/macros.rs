@@ -2,9 +2,23 @@ use anyhow::Result;
 use reqwest::Response;
 use serde::de::DeserializeOwned;
 
-pub(crate) async fn parse_response<T: DeserializeOwned>(res: Response, path: &str) -> Result<T> {
+use crate::ApiClient;
+
+pub(crate) async fn parse_response<T: DeserializeOwned>(
+    api: &ApiClient,
+    res: Response,
+    path: &str,
+) -> Result<T> {
     let status = res.status();
     let body = res.text().await.unwrap_or_default();
+    if status == reqwest::StatusCode::FORBIDDEN && api.subscription_lapsed() {
+        return Err(anyhow::anyhow!(
+            "POST {} failed: {} {} (the cached subscription status is inactive, check your plan)",
+            path,
+            status,
+            body
+        ));
+    }
     if !status.is_success() {
         return Err(anyhow::anyhow!(
             "POST {} failed: {} {}",
@@ -35,7 +49,7 @@ pub(crate) async fn parse_response<T: DeserializeOwned>(res: Response, path: &st
 macro_rules! use_common_crate {
     () => {
         #[allow(unused)]
-        use crate::{API_BASE, ApiClient, gen_random_idempotency_key};
+        use crate::{ApiClient, gen_random_idempotency_key};
         #[allow(unused)]
         use anyhow::Result;
         #[allow(unused)]
@@ -63,28 +77,30 @@ macro_rules! define_api_struct {
 macro_rules! post {
     ($name:ident, $path:expr, $response_ty:ty) => {
         pub async fn $name(&self) -> Result<$response_ty> {
-            let url = format!("{API_BASE}{}", $path);
+            self.ensure_online($path)?;
+            let url = format!("{}{}", self.api_base, $path);
             let req = self
                 .client
                 .post(url)
-                .headers(self.runtime_header.clone())
+                .headers(self.request_headers($path))
                 .header("x-idempotency-key", gen_random_idempotency_key());
             // Some endpoints have no logical payload but still require Content-Length.
             let res = req.json(&serde_json::json!({})).send().await?;
-            crate::macros::parse_response(res, $path).await
+            crate::macros::parse_response(self, res, $path).await
         }
     };
 
     ($name:ident, $path:expr, $request_ty:ty, $response_ty:ty) => {
         pub async fn $name(&self, request: &$request_ty) -> Result<$response_ty> {
-            let url = format!("{API_BASE}{}", $path);
+            self.ensure_online($path)?;
+            let url = format!("{}{}", self.api_base, $path);
             let req = self
                 .client
                 .post(url)
-                .headers(self.runtime_header.clone())
+                .headers(self.request_headers($path))
                 .header("x-idempotency-key", gen_random_idempotency_key());
             let res = req.json(request).send().await?;
-            crate::macros::parse_response(res, $path).await
+            crate::macros::parse_response(self, res, $path).await
         }
     };
 }
@@ -92,15 +108,16 @@ macro_rules! post {
 macro_rules! post_params {
     ($name:ident, $path:expr, $response_ty:ty, $( $param:ident : $param_ty:ty ),+ $(,)?) => {
         pub async fn $name(&self, $( $param: $param_ty ),+ ) -> Result<$response_ty> {
-            let url = format!("{API_BASE}{}", $path);
+            self.ensure_online($path)?;
+            let url = format!("{}{}", self.api_base, $path);
             let req = self
                 .client
                 .post(url)
-                .headers(self.runtime_header.clone())
+                .headers(self.request_headers($path))
                 .header("x-idempotency-key", gen_random_idempotency_key());
             let payload = serde_json::json!({ $( stringify!($param): $param ),+ });
             let res = req.json(&payload).send().await?;
-            crate::macros::parse_response(res, $path).await
+            crate::macros::parse_response(self, res, $path).await
         }
     };
 }
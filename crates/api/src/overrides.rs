@@ -0,0 +1,239 @@
+//! Per-endpoint header overrides on top of the runtime headers
+//!
+//! After a game update some endpoints reject requests unless `x-client-version` is the
+//! store version, while others keep wanting the pinned one. The table maps an endpoint
+//! pattern to the headers it needs. A pattern is an exact path like `/friend/get_list`, a
+//! prefix ending in `*` like `/friend/*`, or `*` for every endpoint. When several patterns
+//! match, the more specific one wins per header: an exact path over any prefix, a longer
+//! prefix over a shorter one. A client version override also sets the matching
+//! User-Agent, unless the rule sets one itself.
+//!
+//! The file form is a json object of pattern to headers:
+//!
+//! ```json
+//! {
+//!     "/friend/*": { "x-client-version": "3.1.0" },
+//!     "/jewel_shop/get_membership_list": { "x-client-version": "3.2.0" }
+//! }
+//! ```
+
+use anyhow::{Context, Result, anyhow};
+use reqwest::header::{self, HeaderMap, HeaderName, HeaderValue};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::UA_PREFIX;
+
+/// Overrides known to be needed, no endpoint needs one yet
+const BUILTIN_OVERRIDES: &[(&str, &[(&str, &str)])] = &[];
+
+#[derive(Debug, Clone)]
+struct OverrideRule {
+    pattern: String,
+    headers: HeaderMap,
+}
+
+impl OverrideRule {
+    fn matches(&self, path: &str) -> bool {
+        match self.pattern.strip_suffix('*') {
+            Some(prefix) => path.starts_with(prefix),
+            None => self.pattern == path,
+        }
+    }
+
+    /// Exact paths sort after every prefix, longer prefixes after shorter ones
+    fn specificity(&self) -> (bool, usize) {
+        (!self.pattern.ends_with('*'), self.pattern.len())
+    }
+}
+
+/// Endpoint pattern to header overrides
+#[derive(Debug, Clone, Default)]
+pub struct HeaderOverrides {
+    /// Least specific first, so later rules overwrite earlier ones
+    rules: Vec<OverrideRule>,
+}
+
+impl HeaderOverrides {
+    /// The built-in table
+    pub fn builtin() -> Self {
+        let mut overrides = Self::default();
+        for (pattern, headers) in BUILTIN_OVERRIDES {
+            let headers = headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect();
+            overrides
+                .insert(pattern, &headers)
+                .expect("built-in header overrides are valid");
+        }
+        overrides
+    }
+
+    /// The built-in table with the entries of `path` on top, just the built-in table
+    /// when the file does not exist
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut overrides = Self::builtin();
+        if !path.exists() {
+            return Ok(overrides);
+        }
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read header overrides: {}", path.display()))?;
+        let table: BTreeMap<String, BTreeMap<String, String>> = serde_json::from_str(&content)
+            .with_context(|| format!("Invalid header overrides: {}", path.display()))?;
+        for (pattern, headers) in &table {
+            overrides
+                .insert(pattern, headers)
+                .with_context(|| format!("Invalid header overrides: {}", path.display()))?;
+        }
+        Ok(overrides)
+    }
+
+    /// Add the headers of `pattern`, replacing the ones it already had
+    pub fn insert(&mut self, pattern: &str, headers: &BTreeMap<String, String>) -> Result<()> {
+        if !pattern.starts_with('/') && pattern != "*" {
+            return Err(anyhow!(
+                "Endpoint pattern must start with '/' or be '*': {}",
+                pattern
+            ));
+        }
+        if pattern.trim_end_matches('*').contains('*') {
+            return Err(anyhow!("'*' is only allowed at the end: {}", pattern));
+        }
+        let mut parsed = HeaderMap::new();
+        for (name, value) in headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| anyhow!("Invalid header name for {}: {}", pattern, name))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|_| anyhow!("Invalid value of {} for {}: {:?}", name, pattern, value))?;
+            parsed.insert(name, value);
+        }
+
+        match self.rules.iter_mut().find(|rule| rule.pattern == pattern) {
+            Some(rule) => rule.headers.extend(parsed),
+            None => self.rules.push(OverrideRule {
+                pattern: pattern.to_string(),
+                headers: parsed,
+            }),
+        }
+        self.rules.sort_by_key(OverrideRule::specificity);
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Overrides for `path`, merged from every matching pattern
+    pub fn headers_for(&self, path: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for rule in self.rules.iter().filter(|rule| rule.matches(path)) {
+            headers.extend(rule.headers.clone());
+        }
+        let user_agent = headers
+            .get("x-client-version")
+            .and_then(|version| version.to_str().ok())
+            .and_then(|version| format!("{UA_PREFIX}/{version}").parse().ok());
+        if let Some(user_agent) = user_agent {
+            headers.entry(header::USER_AGENT).or_insert(user_agent);
+        }
+        headers
+    }
+
+    /// `runtime` with the overrides for `path` on top
+    pub fn apply(&self, path: &str, runtime: &HeaderMap) -> HeaderMap {
+        let mut headers = runtime.clone();
+        headers.extend(self.headers_for(path));
+        headers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(entries: &[(&str, &[(&str, &str)])]) -> HeaderOverrides {
+        let mut overrides = HeaderOverrides::default();
+        for (pattern, headers) in entries {
+            let headers = headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect();
+            overrides.insert(pattern, &headers).unwrap();
+        }
+        overrides
+    }
+
+    #[test]
+    fn test_override_precedence() {
+        // inserted from most to least specific, the order does not matter
+        let overrides = table(&[
+            ("/friend/get_list", &[("x-client-version", "3.3.0")]),
+            (
+                "/friend/*",
+                &[("x-client-version", "3.2.0"), ("x-res-version", "R2508010")],
+            ),
+            ("*", &[("x-client-version", "3.1.0"), ("x-extra", "all")]),
+        ]);
+        let mut runtime = HeaderMap::new();
+        runtime.insert("x-client-version", "3.0.0".parse().unwrap());
+        runtime.insert("x-res-version", "R2504300".parse().unwrap());
+        runtime.insert(header::USER_AGENT, "inspix-android/3.0.0".parse().unwrap());
+        runtime.insert(header::AUTHORIZATION, "Bearer token".parse().unwrap());
+
+        let headers = overrides.apply("/friend/get_list", &runtime);
+        assert_eq!(headers["x-client-version"], "3.3.0");
+        assert_eq!(headers["x-res-version"], "R2508010");
+        assert_eq!(headers["x-extra"], "all");
+        assert_eq!(headers[header::USER_AGENT], "inspix-android/3.3.0");
+        assert_eq!(headers[header::AUTHORIZATION], "Bearer token");
+
+        let headers = overrides.apply("/friend/get_request_list", &runtime);
+        assert_eq!(headers["x-client-version"], "3.2.0");
+        assert_eq!(headers[header::USER_AGENT], "inspix-android/3.2.0");
+
+        let headers = overrides.apply("/archive/get_home", &runtime);
+        assert_eq!(headers["x-client-version"], "3.1.0");
+        assert_eq!(headers["x-res-version"], "R2504300");
+
+        // a rule's own User-Agent is kept, no rule leaves the runtime headers alone
+        let overrides = table(&[(
+            "/user/login",
+            &[("x-client-version", "3.3.0"), ("user-agent", "custom")],
+        )]);
+        assert_eq!(
+            overrides.apply("/user/login", &runtime)[header::USER_AGENT],
+            "custom"
+        );
+        assert_eq!(overrides.apply("/user/logout", &runtime), runtime);
+
+        let mut overrides = HeaderOverrides::default();
+        let headers = BTreeMap::from([("x-client-version".to_string(), "3.1.0".to_string())]);
+        assert!(overrides.insert("friend/*", &headers).is_err());
+        assert!(overrides.insert("/friend/*/list", &headers).is_err());
+        let invalid = BTreeMap::from([("bad header".to_string(), "1".to_string())]);
+        assert!(overrides.insert("/friend/*", &invalid).is_err());
+        assert!(overrides.is_empty());
+    }
+
+    #[test]
+    fn test_load_overrides() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let path = dir.join("header_overrides.json");
+
+        let missing = HeaderOverrides::load(&path).unwrap();
+        assert_eq!(missing.is_empty(), HeaderOverrides::builtin().is_empty());
+
+        std::fs::write(&path, r#"{ "/friend/*": { "x-client-version": "3.2.0" } }"#).unwrap();
+        let loaded = HeaderOverrides::load(&path).unwrap();
+        assert_eq!(
+            loaded.headers_for("/friend/get_list")["x-client-version"],
+            "3.2.0"
+        );
+
+        std::fs::write(&path, r#"{ "/friend/*": { "x-client-version": 3 } }"#).unwrap();
+        let error = HeaderOverrides::load(&path).unwrap_err();
+        assert!(format!("{:#}", error).contains("header_overrides.json"));
+    }
+}
@@ -0,0 +1,235 @@
+use anyhow::{Result, anyhow};
+use std::path::{Path, PathBuf};
+
+/// Headroom kept free on the target filesystem, 512 MiB
+pub const DEFAULT_SPACE_MARGIN: u64 = 512 * 1024 * 1024;
+
+/// Environment variable overriding the default data directory
+pub const DATA_DIR_ENV: &str = "LINKURA_DATA_DIR";
+
+/// Data directory used when neither a flag nor `LINKURA_DATA_DIR` is given
+pub const DEFAULT_DATA_DIR: &str = "data";
+
+/// Source of filesystem capacity, swappable for tests
+pub trait FsInfoProvider {
+    /// Bytes available to the current user on the filesystem holding `path`
+    fn available_space(&self, path: &Path) -> Result<u64>;
+}
+
+/// Queries the operating system (`statvfs` / `GetDiskFreeSpaceExW`)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemFsInfo;
+
+impl FsInfoProvider for SystemFsInfo {
+    fn available_space(&self, path: &Path) -> Result<u64> {
+        available_space(path)
+    }
+}
+
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> Result<u64> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| anyhow!("Invalid path {:?}: {}", path, e))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) };
+    if ret != 0 {
+        return Err(anyhow!(
+            "statvfs failed for {:?}: {}",
+            path,
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok((stat.f_bavail as u64).saturating_mul(stat.f_frsize as u64))
+}
+
+#[cfg(windows)]
+pub fn available_space(path: &Path) -> Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use windows_sys::Win32::Storage::FileSystem::GetDiskFreeSpaceExW;
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut available = 0u64;
+    let ret = unsafe {
+        GetDiskFreeSpaceExW(
+            wide.as_ptr(),
+            &mut available,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+        )
+    };
+    if ret == 0 {
+        return Err(anyhow!(
+            "GetDiskFreeSpaceExW failed for {:?}: {}",
+            path,
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(available)
+}
+
+/// Result of comparing projected usage against free space
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpaceCheck {
+    pub required: u64,
+    pub available: u64,
+    pub margin: u64,
+}
+
+impl SpaceCheck {
+    pub fn new(required: u64, available: u64, margin: u64) -> Self {
+        Self {
+            required,
+            available,
+            margin,
+        }
+    }
+
+    /// Free space left for the download once the margin is reserved
+    pub fn usable(&self) -> u64 {
+        self.available.saturating_sub(self.margin)
+    }
+
+    pub fn fits(&self) -> bool {
+        self.required <= self.usable()
+    }
+
+    /// Bytes missing for the download to fit, zero when it does
+    pub fn shortfall(&self) -> u64 {
+        self.required.saturating_sub(self.usable())
+    }
+}
+
+/// Check `required` bytes against the filesystem holding `path`.
+/// `path` may not exist yet; its nearest existing ancestor is queried instead.
+pub fn check_space(
+    provider: &dyn FsInfoProvider,
+    path: &Path,
+    required: u64,
+    margin: u64,
+) -> Result<SpaceCheck> {
+    let probe = nearest_existing_ancestor(path);
+    let available = provider.available_space(&probe)?;
+    Ok(SpaceCheck::new(required, available, margin))
+}
+
+/// Resolve the data directory: explicit flag first, then `LINKURA_DATA_DIR`, then `data`
+pub fn resolve_data_dir(flag: Option<&str>) -> PathBuf {
+    data_dir_from(flag, std::env::var(DATA_DIR_ENV).ok().as_deref())
+}
+
+fn data_dir_from(flag: Option<&str>, env: Option<&str>) -> PathBuf {
+    [flag, env]
+        .into_iter()
+        .flatten()
+        .map(str::trim)
+        .find(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_DATA_DIR))
+}
+
+/// Create `path` if needed, make sure it is writable and return its absolute form
+pub fn prepare_data_dir(path: &Path) -> Result<PathBuf> {
+    std::fs::create_dir_all(path)
+        .map_err(|e| anyhow!("Failed to create data directory {:?}: {}", path, e))?;
+    let path = path
+        .canonicalize()
+        .map_err(|e| anyhow!("Failed to resolve data directory {:?}: {}", path, e))?;
+    let probe = path.join(format!(".linkura-write-test-{}", std::process::id()));
+    std::fs::write(&probe, b"")
+        .map_err(|e| anyhow!("Data directory {:?} is not writable: {}", path, e))?;
+    let _ = std::fs::remove_file(&probe);
+    Ok(path)
+}
+
+fn nearest_existing_ancestor(path: &Path) -> PathBuf {
+    path.ancestors()
+        .find(|ancestor| !ancestor.as_os_str().is_empty() && ancestor.exists())
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    struct MockFsInfo {
+        available: u64,
+        queried: RefCell<Vec<PathBuf>>,
+    }
+
+    impl FsInfoProvider for MockFsInfo {
+        fn available_space(&self, path: &Path) -> Result<u64> {
+            self.queried.borrow_mut().push(path.to_path_buf());
+            Ok(self.available)
+        }
+    }
+
+    #[test]
+    fn test_space_check_margin() {
+        let check = SpaceCheck::new(600, 1000, 300);
+        assert_eq!(check.usable(), 700);
+        assert!(check.fits());
+        assert_eq!(check.shortfall(), 0);
+
+        let check = SpaceCheck::new(800, 1000, 300);
+        assert!(!check.fits());
+        assert_eq!(check.shortfall(), 100);
+
+        // margin larger than free space leaves nothing usable
+        let check = SpaceCheck::new(1, 100, 300);
+        assert_eq!(check.usable(), 0);
+        assert!(!check.fits());
+    }
+
+    #[test]
+    fn test_check_space_with_mock_provider() {
+        let provider = MockFsInfo {
+            available: 10_000,
+            queried: RefCell::new(Vec::new()),
+        };
+        let existing = std::env::temp_dir();
+        let missing = existing.join("linkura-common-disk-test/not/created");
+
+        let check = check_space(&provider, &missing, 4_000, 5_000).unwrap();
+        assert!(check.fits());
+        let check = check_space(&provider, &missing, 6_000, 5_000).unwrap();
+        assert!(!check.fits());
+
+        assert_eq!(provider.queried.borrow()[0], existing);
+    }
+
+    #[test]
+    fn test_data_dir_precedence() {
+        assert_eq!(data_dir_from(None, None), PathBuf::from(DEFAULT_DATA_DIR));
+        assert_eq!(data_dir_from(None, Some("/env")), PathBuf::from("/env"));
+        assert_eq!(
+            data_dir_from(Some("flag"), Some("/env")),
+            PathBuf::from("flag")
+        );
+        // an empty variable counts as unset
+        assert_eq!(
+            data_dir_from(None, Some(" ")),
+            PathBuf::from(DEFAULT_DATA_DIR)
+        );
+    }
+
+    #[test]
+    fn test_prepare_data_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("nested");
+        let resolved = prepare_data_dir(&dir).unwrap();
+        assert!(resolved.is_absolute());
+        assert!(resolved.is_dir());
+        assert_eq!(std::fs::read_dir(&resolved).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_system_available_space() {
+        let available = SystemFsInfo.available_space(&std::env::temp_dir()).unwrap();
+        assert!(available > 0);
+    }
+}
@@ -1,2 +1,4 @@
+pub mod disk;
 pub mod jwt;
 pub mod log;
+pub mod parse;
@@ -1,5 +1,16 @@
+//! Logging setup and the per-live span convention
+//!
+//! Work on one live (capture, conversion, download) runs inside a [`live_span`] carrying
+//! `live_id` and `room_id`, so log lines of lives processed side by side can be told apart.
+//! Ids go into span or event fields, not into message strings.
+
+use tracing::{Span, field};
 use tracing_subscriber::{EnvFilter, fmt, prelude::*};
 
+/// Name of the span entered around the work on one live
+pub const LIVE_SPAN: &str = "live";
+
+/// Span fields are printed before each message as `live{live_id=.. room_id=..}:`
 pub fn init(log_level: Option<String>) {
     tracing_subscriber::registry()
         .with(fmt::layer())
@@ -9,3 +20,66 @@ pub fn init(log_level: Option<String>) {
         )
         .init();
 }
+
+/// Span for the work on one live, ids not known yet can be filled in with [`record_room_id`]
+pub fn live_span(live_id: Option<&str>, room_id: Option<&str>) -> Span {
+    let span = tracing::info_span!(LIVE_SPAN, live_id = field::Empty, room_id = field::Empty);
+    if let Some(live_id) = live_id {
+        span.record("live_id", live_id);
+    }
+    if let Some(room_id) = room_id {
+        span.record("room_id", room_id);
+    }
+    span
+}
+
+/// Set `room_id` on the current span, no-op outside a [`live_span`]
+pub fn record_room_id(room_id: &str) {
+    Span::current().record("room_id", room_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_live_span_fields() {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::registry().with(
+            fmt::layer()
+                .with_ansi(false)
+                .with_writer(move || writer.clone()),
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            let _live = live_span(Some("live-1"), None).entered();
+            tracing::info!("before join");
+            record_room_id("room-1");
+            let _batch = tracing::info_span!("batch").entered();
+            tracing::info!("nested");
+        });
+
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("live{live_id=\"live-1\"}:"));
+        assert!(lines[0].ends_with("before join"));
+        assert!(lines[1].contains("live{live_id=\"live-1\" room_id=\"room-1\"}:batch:"));
+        assert!(lines[1].ends_with("nested"));
+    }
+}
@@ -0,0 +1,282 @@
+//! Human friendly size and duration arguments
+//!
+//! [`ByteSize`] accepts `123`, `4K`, `4KiB`, `1.5M` or `2G`. Units are binary, `K`, `KB` and
+//! `KiB` all mean 1024 bytes. [`HumanDuration`] accepts `90s`, `15m`, `2h30m` or `500ms`.
+//! Both implement `FromStr`, so clap parses them directly. Flags that took a plain number
+//! in another unit before keep doing so through [`mebibytes`], [`seconds`] and
+//! [`milliseconds`].
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+const SIZE_UNITS: [(&str, u64); 4] = [
+    ("GiB", 1024 * 1024 * 1024),
+    ("MiB", 1024 * 1024),
+    ("KiB", 1024),
+    ("B", 1),
+];
+
+const SIZE_FORMS: &str =
+    "a number of bytes with an optional K, M or G suffix (123, 4K, 4KiB, 1.5M, 2G)";
+
+const DURATION_UNITS: [(&str, u128); 4] = [
+    ("h", 3_600_000_000_000),
+    ("m", 60_000_000_000),
+    ("s", 1_000_000_000),
+    ("ms", 1_000_000),
+];
+
+const DURATION_FORMS: &str = "a number with h, m, s or ms units (90s, 15m, 2h30m, 500ms)";
+
+/// Invalid size or duration argument, clap already names the input in its message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub input: String,
+    /// Accepted forms
+    pub expected: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected {}", self.expected)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Size in bytes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteSize(pub u64);
+
+impl ByteSize {
+    pub fn bytes(self) -> u64 {
+        self.0
+    }
+
+    /// Bytes as `usize`, saturating on 32-bit targets
+    pub fn as_usize(self) -> usize {
+        usize::try_from(self.0).unwrap_or(usize::MAX)
+    }
+
+    /// Parse with plain numbers counted in `unit` bytes
+    pub fn parse_in(input: &str, unit: u64) -> Result<Self, ParseError> {
+        let error = || ParseError {
+            input: input.to_string(),
+            expected: SIZE_FORMS,
+        };
+        let trimmed = input.trim();
+        let split = trimmed
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(trimmed.len());
+        let (number, suffix) = trimmed.split_at(split);
+        let multiplier = match suffix.trim().to_ascii_lowercase().as_str() {
+            "" => unit,
+            "b" => 1,
+            "k" | "kb" | "kib" => 1024,
+            "m" | "mb" | "mib" => 1024 * 1024,
+            "g" | "gb" | "gib" => 1024 * 1024 * 1024,
+            _ => return Err(error()),
+        };
+        if let Ok(whole) = number.parse::<u64>() {
+            return whole.checked_mul(multiplier).map(Self).ok_or_else(error);
+        }
+        let fraction = number.parse::<f64>().map_err(|_| error())?;
+        let bytes = (fraction * multiplier as f64).round();
+        if !bytes.is_finite() || bytes > u64::MAX as f64 {
+            return Err(error());
+        }
+        Ok(Self(bytes as u64))
+    }
+}
+
+impl FromStr for ByteSize {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_in(s, 1)
+    }
+}
+
+/// Largest unit the size is a whole multiple of, `1536KiB` for 1.5 MiB
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, unit) = SIZE_UNITS
+            .iter()
+            .find(|(_, unit)| self.0 > 0 && self.0.is_multiple_of(*unit))
+            .unwrap_or(&("B", 1));
+        write!(f, "{}{}", self.0 / unit, name)
+    }
+}
+
+/// Duration with at most millisecond precision
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HumanDuration(pub Duration);
+
+impl HumanDuration {
+    pub fn as_duration(self) -> Duration {
+        self.0
+    }
+
+    /// Parse with a plain number counted in `unit`
+    pub fn parse_in(input: &str, unit: Duration) -> Result<Self, ParseError> {
+        let error = || ParseError {
+            input: input.to_string(),
+            expected: DURATION_FORMS,
+        };
+        let trimmed = input.trim();
+        if let Ok(count) = trimmed.parse::<u32>() {
+            return unit.checked_mul(count).map(Self).ok_or_else(error);
+        }
+        if trimmed.is_empty() {
+            return Err(error());
+        }
+
+        let mut nanos: u128 = 0;
+        let mut rest = trimmed;
+        while !rest.is_empty() {
+            let digits = rest.find(|c: char| !c.is_ascii_digit()).ok_or_else(error)?;
+            let letters = rest[digits..]
+                .find(|c: char| c.is_ascii_digit())
+                .map_or(rest.len(), |end| digits + end);
+            let count: u128 = rest[..digits].parse().map_err(|_| error())?;
+            let (_, unit_nanos) = DURATION_UNITS
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(&rest[digits..letters]))
+                .ok_or_else(error)?;
+            nanos = count
+                .checked_mul(*unit_nanos)
+                .and_then(|component| nanos.checked_add(component))
+                .ok_or_else(error)?;
+            rest = &rest[letters..];
+        }
+        let secs = u64::try_from(nanos / 1_000_000_000).map_err(|_| error())?;
+        Ok(Self(Duration::new(secs, (nanos % 1_000_000_000) as u32)))
+    }
+}
+
+impl FromStr for HumanDuration {
+    type Err = ParseError;
+
+    /// Plain numbers are seconds
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse_in(s, Duration::from_secs(1))
+    }
+}
+
+/// Non-zero components from hours down to milliseconds, `2h30m`, `0s` when empty
+impl fmt::Display for HumanDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rest = self.0.as_millis() * 1_000_000;
+        if rest == 0 {
+            return f.write_str("0s");
+        }
+        for (name, unit) in DURATION_UNITS {
+            if rest >= unit {
+                write!(f, "{}{}", rest / unit, name)?;
+                rest %= unit;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// clap value parser for flags that took plain MiB
+pub fn mebibytes(input: &str) -> Result<ByteSize, ParseError> {
+    ByteSize::parse_in(input, 1024 * 1024)
+}
+
+/// clap value parser for flags that took plain seconds
+pub fn seconds(input: &str) -> Result<HumanDuration, ParseError> {
+    HumanDuration::parse_in(input, Duration::from_secs(1))
+}
+
+/// clap value parser for flags that took plain milliseconds
+pub fn milliseconds(input: &str) -> Result<HumanDuration, ParseError> {
+    HumanDuration::parse_in(input, Duration::from_millis(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_size() {
+        let parse = |s: &str| s.parse::<ByteSize>().map(ByteSize::bytes);
+        assert_eq!(parse("123"), Ok(123));
+        assert_eq!(parse("0"), Ok(0));
+        assert_eq!(parse("4K"), Ok(4096));
+        assert_eq!(parse("4k"), Ok(4096));
+        assert_eq!(parse("4KB"), Ok(4096));
+        assert_eq!(parse("4KiB"), Ok(4096));
+        assert_eq!(parse("1.5M"), Ok(1536 * 1024));
+        assert_eq!(parse("2G"), Ok(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse(" 10 MiB "), Ok(10 * 1024 * 1024));
+        assert_eq!(parse("512B"), Ok(512));
+        for invalid in [
+            "",
+            "K",
+            "4X",
+            "-1",
+            "1.2.3M",
+            "4 K B",
+            "99999999999999999999G",
+        ] {
+            let error = parse(invalid).unwrap_err();
+            assert_eq!(error.input, invalid);
+            assert!(error.to_string().contains("4KiB"), "{}", error);
+        }
+
+        assert_eq!(mebibytes("100"), Ok(ByteSize(100 * 1024 * 1024)));
+        assert_eq!(mebibytes("512K"), Ok(ByteSize(512 * 1024)));
+
+        for (bytes, text) in [
+            (0, "0B"),
+            (123, "123B"),
+            (4096, "4KiB"),
+            (1536 * 1024, "1536KiB"),
+            (2 * 1024 * 1024 * 1024, "2GiB"),
+        ] {
+            assert_eq!(ByteSize(bytes).to_string(), text);
+            assert_eq!(text.parse(), Ok(ByteSize(bytes)));
+        }
+    }
+
+    #[test]
+    fn test_human_duration() {
+        let parse = |s: &str| s.parse::<HumanDuration>().map(HumanDuration::as_duration);
+        assert_eq!(parse("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse("15m"), Ok(Duration::from_secs(900)));
+        assert_eq!(parse("2h30m"), Ok(Duration::from_secs(9000)));
+        assert_eq!(parse("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse("1m30s250ms"), Ok(Duration::from_millis(90_250)));
+        assert_eq!(parse("1H"), Ok(Duration::from_secs(3600)));
+        for invalid in ["", "s", "10x", "1.5s", "-5s", "5 m", "h1"] {
+            let error = parse(invalid).unwrap_err();
+            assert_eq!(error.input, invalid);
+            assert!(error.to_string().contains("2h30m"), "{}", error);
+        }
+
+        assert_eq!(seconds("10"), Ok(HumanDuration(Duration::from_secs(10))));
+        assert_eq!(
+            milliseconds("5000"),
+            Ok(HumanDuration(Duration::from_secs(5)))
+        );
+        assert_eq!(
+            milliseconds("2s"),
+            Ok(HumanDuration(Duration::from_secs(2)))
+        );
+
+        for (duration, text) in [
+            (Duration::ZERO, "0s"),
+            (Duration::from_millis(500), "500ms"),
+            (Duration::from_secs(90), "1m30s"),
+            (Duration::from_secs(9000), "2h30m"),
+            (Duration::from_millis(3_723_004), "1h2m3s4ms"),
+        ] {
+            assert_eq!(HumanDuration(duration).to_string(), text);
+            assert_eq!(text.parse(), Ok(HumanDuration(duration)));
+        }
+    }
+}
@@ -1,8 +1,17 @@
-use crate::downloader::{BaseDownloader, BaseDownloaderImpl, DownloadItem, ProgressConfig};
+use crate::downloader::{
+    BaseDownloader, BaseDownloaderImpl, DownloadItem, DownloadReport, ProgressConfig, UrlRefresher,
+    VerifyReport,
+};
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
+use chrono::{DateTime, TimeDelta, Utc};
+use linkura_common::log::{live_span, record_room_id};
+use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use tracing::Instrument;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct AlsMetadata {
@@ -36,8 +45,18 @@ impl AlsDownloader {
         }
     }
 
-    async fn fetch_metadata(&self, url: &str) -> Result<AlsMetadata> {
-        let response = self.base.client().get(url).send().await?;
+    pub fn with_ignore_space_check(mut self, ignore_space_check: bool) -> Self {
+        self.base = self.base.with_ignore_space_check(ignore_space_check);
+        self
+    }
+
+    pub fn with_cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.base = self.base.with_cancel(cancel);
+        self
+    }
+
+    async fn fetch_metadata(client: &Client, url: &str) -> Result<AlsMetadata> {
+        let response = client.get(url).send().await?;
 
         if !response.status().is_success() {
             return Err(anyhow!(
@@ -53,7 +72,28 @@ impl AlsDownloader {
         Ok(metadata)
     }
 
-    async fn fetch_m3u8_content(&self, url: &str) -> Result<String> {
+    /// Refreshes the URL of `filename` by fetching the metadata again, whose path
+    /// carries a new signature once the old one expired
+    pub(crate) fn url_refresher(&self, metadata_url: &str, filename: &str) -> UrlRefresher {
+        let client = self.base.client().clone();
+        let metadata_url = metadata_url.to_string();
+        let filename = filename.to_string();
+        UrlRefresher::new(move |expired_url| {
+            let client = client.clone();
+            let metadata_url = metadata_url.clone();
+            let filename = filename.clone();
+            async move {
+                let metadata = Self::fetch_metadata(&client, &metadata_url).await?;
+                let url = format!("{}/{}", metadata.path, filename);
+                if url == expired_url {
+                    return Err(anyhow!("Metadata still points at the expired URL"));
+                }
+                Ok(url)
+            }
+        })
+    }
+
+    pub async fn fetch_m3u8_content(&self, url: &str) -> Result<String> {
         let response = self.base.client().get(url).send().await?;
 
         if !response.status().is_success() {
@@ -84,12 +124,47 @@ impl AlsDownloader {
 
         Ok(segments)
     }
+
+    /// Wall clock of the first segment, from the first `#EXT-X-PROGRAM-DATE-TIME` tag
+    /// minus the durations of the segments listed before it. `None` if the tag is absent.
+    pub fn parse_m3u8_program_date_time(&self, content: &str) -> Result<Option<DateTime<Utc>>> {
+        let mut elapsed = 0.0;
+        let mut pending_duration = 0.0;
+
+        for line in content.lines() {
+            let line = line.trim();
+            if let Some(value) = line.strip_prefix("#EXT-X-PROGRAM-DATE-TIME:") {
+                let date_time = DateTime::parse_from_rfc3339(value)
+                    .or_else(|_| DateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f%z"))
+                    .map_err(|e| anyhow!("Invalid EXT-X-PROGRAM-DATE-TIME '{}': {}", value, e))?
+                    .with_timezone(&Utc);
+                let elapsed = TimeDelta::microseconds((elapsed * 1_000_000.0) as i64);
+                return Ok(Some(date_time - elapsed));
+            } else if let Some(value) = line.strip_prefix("#EXTINF:") {
+                let duration = value.split(',').next().unwrap_or_default();
+                pending_duration = duration
+                    .trim()
+                    .parse::<f64>()
+                    .map_err(|e| anyhow!("Invalid EXTINF duration '{}': {}", duration, e))?;
+            } else if !line.is_empty() && !line.starts_with('#') {
+                elapsed += pending_duration;
+                pending_duration = 0.0;
+            }
+        }
+
+        Ok(None)
+    }
 }
 
-#[async_trait]
-impl BaseDownloader for AlsDownloader {
-    async fn download(&self, url: &str, output_dir: &Path) -> Result<()> {
-        let metadata = self.fetch_metadata(url).await?;
+impl AlsDownloader {
+    /// Target directory and every file belonging to the playback behind `url`
+    async fn collect_download_items(
+        &self,
+        url: &str,
+        output_dir: &Path,
+    ) -> Result<(PathBuf, Vec<DownloadItem>)> {
+        let metadata = Self::fetch_metadata(self.base.client(), url).await?;
+        record_room_id(&metadata.room_id);
         let mut download_items = Vec::new();
 
         let base_url = &metadata.path;
@@ -99,13 +174,18 @@ impl BaseDownloader for AlsDownloader {
         download_items.push(DownloadItem {
             url: url.to_string(),
             filename: metadata.playlist_file.replace(".m3u8", ".md"),
+            refresh_url: None,
         });
 
         let m3u8_url = format!("{}/{}", base_url, metadata.playlist_file);
-        download_items.push(DownloadItem {
-            url: m3u8_url.clone(),
-            filename: metadata.playlist_file.clone(),
-        });
+        download_items.push(
+            DownloadItem {
+                url: m3u8_url.clone(),
+                filename: metadata.playlist_file.clone(),
+                refresh_url: None,
+            }
+            .with_refresh_url(self.url_refresher(url, &metadata.playlist_file)),
+        );
 
         if download_items.len() < 2 {
             return Err(anyhow!("The url provided is invalid!"));
@@ -116,16 +196,39 @@ impl BaseDownloader for AlsDownloader {
 
         for ts_file in ts_files {
             let ts_url = format!("{}/{}", base_url, ts_file);
-            download_items.push(DownloadItem {
-                url: ts_url,
-                filename: ts_file,
-            });
+            let refresher = self.url_refresher(url, &ts_file);
+            download_items.push(
+                DownloadItem {
+                    url: ts_url,
+                    filename: ts_file,
+                    refresh_url: None,
+                }
+                .with_refresh_url(refresher),
+            );
+        }
+
+        Ok((target_dir, download_items))
+    }
+}
+
+#[async_trait]
+impl BaseDownloader for AlsDownloader {
+    async fn download(&self, url: &str, output_dir: &Path) -> Result<DownloadReport> {
+        async {
+            let (target_dir, download_items) = self.collect_download_items(url, output_dir).await?;
+            self.base.download_files(download_items, &target_dir).await
         }
-        self.base
-            .download_files(download_items, &target_dir)
-            .await?;
+        .instrument(live_span(None, None))
+        .await
+    }
 
-        Ok(())
+    async fn verify(&self, url: &str, output_dir: &Path) -> Result<VerifyReport> {
+        async {
+            let (target_dir, download_items) = self.collect_download_items(url, output_dir).await?;
+            self.base.verify_files(download_items, &target_dir).await
+        }
+        .instrument(live_span(None, None))
+        .await
     }
 
     // TODO: maybe use self.fetch_metadata in the future
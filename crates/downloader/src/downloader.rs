@@ -1,11 +1,24 @@
-use anyhow::{Result, anyhow};
+use anyhow::{Context, Result, anyhow};
 use async_trait::async_trait;
-use futures::future::join_all;
-use reqwest::Client;
-use std::path::Path;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+use futures::future::{BoxFuture, join_all};
+use linkura_common::disk::{
+    DEFAULT_SPACE_MARGIN, FsInfoProvider, SpaceCheck, SystemFsInfo, check_space,
+};
+use md5::{Digest, Md5};
+use reqwest::header::HeaderName;
+use reqwest::{Client, StatusCode, header};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::fs;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tracing::{info, warn};
 use url::Url;
 
 use crate::progress_ui::{
@@ -17,11 +30,272 @@ use crate::progress_ui::{
 pub struct DownloadItem {
     pub url: String,
     pub filename: String,
+    /// Asked for a new URL when `url` is a pre-signed URL that expired
+    pub refresh_url: Option<UrlRefresher>,
+}
+
+impl DownloadItem {
+    /// Refresh the URL with `refresher` once it expires (Builder pattern)
+    pub fn with_refresh_url(mut self, refresher: UrlRefresher) -> Self {
+        self.refresh_url = Some(refresher);
+        self
+    }
+}
+
+/// Returns a fresh URL for the expired URL it is given, e.g. by calling the API again
+#[derive(Clone)]
+pub struct UrlRefresher(Arc<dyn Fn(String) -> BoxFuture<'static, Result<String>> + Send + Sync>);
+
+impl UrlRefresher {
+    pub fn new<F, Fut>(refresher: F) -> Self
+    where
+        F: Fn(String) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<String>> + Send + 'static,
+    {
+        Self(Arc::new(move |url| Box::pin(refresher(url))))
+    }
+
+    pub(crate) async fn refresh(&self, expired_url: &str) -> Result<String> {
+        (self.0)(expired_url.to_string()).await
+    }
+}
+
+impl fmt::Debug for UrlRefresher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("UrlRefresher")
+    }
+}
+
+/// A pre-signed URL expired and its item has no `refresh_url`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UrlExpiredError {
+    pub url: String,
+}
+
+impl fmt::Display for UrlExpiredError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "URL expired: {}", self.url)
+    }
+}
+
+impl std::error::Error for UrlExpiredError {}
+
+/// The batch was cancelled before the file finished, its partial file is removed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DownloadCancelledError;
+
+impl fmt::Display for DownloadCancelledError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Download cancelled")
+    }
+}
+
+impl std::error::Error for DownloadCancelledError {}
+
+/// Expiry of a pre-signed URL from its query: `X-Amz-Date`/`X-Goog-Date` plus
+/// `X-Amz-Expires`/`X-Goog-Expires` seconds, or an `Expires`/`exp` unix timestamp
+pub fn signed_url_expiry(url: &Url) -> Option<DateTime<Utc>> {
+    let params: HashMap<String, String> = url
+        .query_pairs()
+        .map(|(key, value)| (key.to_ascii_lowercase(), value.into_owned()))
+        .collect();
+    for prefix in ["x-amz", "x-goog"] {
+        if let (Some(date), Some(expires)) = (
+            params.get(&format!("{}-date", prefix)),
+            params.get(&format!("{}-expires", prefix)),
+        ) {
+            let signed_at = NaiveDateTime::parse_from_str(date, "%Y%m%dT%H%M%SZ").ok()?;
+            return Some(signed_at.and_utc() + TimeDelta::seconds(expires.parse().ok()?));
+        }
+    }
+    ["expires", "exp"]
+        .iter()
+        .find_map(|key| params.get(*key)?.parse::<i64>().ok())
+        .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+}
+
+/// Whether a failed response means its pre-signed URL expired: a 403 for a URL whose
+/// query expiry is past, or whose structured error body names the expiration
+pub fn is_url_expired(status: StatusCode, url: &str, body: &str, now: DateTime<Utc>) -> bool {
+    if status != StatusCode::FORBIDDEN {
+        return false;
+    }
+    let expiry = Url::parse(url).ok().and_then(|url| signed_url_expiry(&url));
+    if expiry.is_some_and(|expiry| expiry <= now) {
+        return true;
+    }
+    error_body_fields(body)
+        .iter()
+        .any(|field| field.to_ascii_lowercase().contains("expired"))
+}
+
+/// `Code`/`Message` of an S3 style XML error, or `code`/`message`/`error` of a JSON one
+fn error_body_fields(body: &str) -> Vec<String> {
+    let body = body.trim();
+    if body.starts_with('<') {
+        return ["Code", "Message"]
+            .iter()
+            .filter_map(|tag| {
+                let start = body.find(&format!("<{}>", tag))? + tag.len() + 2;
+                let end = start + body[start..].find(&format!("</{}>", tag))?;
+                Some(body[start..end].to_string())
+            })
+            .collect();
+    }
+    let Ok(serde_json::Value::Object(object)) = serde_json::from_str(body) else {
+        return Vec::new();
+    };
+    let mut fields = Vec::new();
+    for key in ["code", "message", "error"] {
+        match object.get(key) {
+            Some(serde_json::Value::String(value)) => fields.push(value.clone()),
+            Some(serde_json::Value::Object(error)) => fields.extend(
+                ["code", "message"]
+                    .iter()
+                    .filter_map(|key| error.get(*key)?.as_str().map(str::to_string)),
+            ),
+            _ => {}
+        }
+    }
+    fields
+}
+
+/// Outcome of checking one local file against the server's `HEAD` response, by size
+/// and, when the server sends one, by MD5
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyStatus {
+    Ok,
+    Missing,
+    SizeMismatch { local: u64, remote: u64 },
+    Corrupt { local: String, remote: String },
+    RemoteError(String),
+}
+
+impl VerifyStatus {
+    /// Compare a local file size against the remote `Content-Length`.
+    /// A missing remote length only checks that the file exists.
+    pub fn from_sizes(local: Option<u64>, remote: Option<u64>) -> Self {
+        match (local, remote) {
+            (None, _) => VerifyStatus::Missing,
+            (Some(local), Some(remote)) if local != remote => {
+                VerifyStatus::SizeMismatch { local, remote }
+            }
+            _ => VerifyStatus::Ok,
+        }
+    }
+
+    /// Compare a local digest against the remote one, if the server sent one
+    pub fn from_digests(local: &[u8], remote: Option<&[u8]>) -> Self {
+        match remote {
+            Some(remote) if remote != local => VerifyStatus::Corrupt {
+                local: hex::encode(local),
+                remote: hex::encode(remote),
+            },
+            _ => VerifyStatus::Ok,
+        }
+    }
+}
+
+/// What a `HEAD` response tells about a remote file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RemoteFileInfo {
+    pub size: Option<u64>,
+    pub md5: Option<Vec<u8>>,
+}
+
+impl RemoteFileInfo {
+    /// Read `Content-Length` and an MD5 from `Content-MD5`, or from an `ETag` holding a
+    /// plain MD5 (S3/R2 single part uploads; multipart ETags end in `-<parts>`)
+    pub fn from_headers(headers: &header::HeaderMap) -> Self {
+        let text = |name| headers.get(name).and_then(|value| value.to_str().ok());
+        let size = text(header::CONTENT_LENGTH).and_then(|value| value.parse::<u64>().ok());
+        let content_md5 = text(HeaderName::from_static("content-md5"))
+            .and_then(|value| STANDARD.decode(value.trim()).ok())
+            .filter(|digest| digest.len() == 16);
+        let etag_md5 = text(header::ETAG)
+            .map(|etag| etag.trim_start_matches("W/").trim_matches('"'))
+            .filter(|etag| etag.len() == 32)
+            .and_then(|etag| hex::decode(etag).ok());
+        Self {
+            size,
+            md5: content_md5.or(etag_md5),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VerifyEntry {
+    pub filename: String,
+    pub url: String,
+    pub status: VerifyStatus,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    pub target_dir: PathBuf,
+    pub entries: Vec<VerifyEntry>,
+}
+
+impl VerifyReport {
+    pub fn ok_count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| entry.status == VerifyStatus::Ok)
+            .count()
+    }
+
+    pub fn problems(&self) -> impl Iterator<Item = &VerifyEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.status != VerifyStatus::Ok)
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.problems().next().is_none()
+    }
+}
+
+/// Outcome of downloading one file, `error` is `None` on success
+#[derive(Debug, Clone)]
+pub struct DownloadEntry {
+    pub filename: String,
+    pub url: String,
+    pub error: Option<String>,
+}
+
+/// Per-file results of a batch, in the order the files were requested
+#[derive(Debug, Clone, Default)]
+pub struct DownloadReport {
+    pub target_dir: PathBuf,
+    pub entries: Vec<DownloadEntry>,
+    /// The batch was cancelled, unfinished files are recorded as failures
+    pub cancelled: bool,
+}
+
+impl DownloadReport {
+    pub fn ok_count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|entry| entry.error.is_none())
+            .count()
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &DownloadEntry> {
+        self.entries.iter().filter(|entry| entry.error.is_some())
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.failures().next().is_none()
+    }
 }
 
 #[async_trait]
 pub trait BaseDownloader: Send + Sync {
-    async fn download(&self, url: &str, output_dir: &Path) -> Result<()>;
+    /// Download every file of the playback behind `url`, a failed file does not stop
+    /// the others; check the report for failures
+    async fn download(&self, url: &str, output_dir: &Path) -> Result<DownloadReport>;
+    /// Check already downloaded files against the server without transferring bodies
+    async fn verify(&self, url: &str, output_dir: &Path) -> Result<VerifyReport>;
     fn extract_folder_name(&self, url: &str) -> Result<String>;
 }
 
@@ -38,6 +312,27 @@ pub struct BaseDownloaderImpl {
     client: Client,
     concurrent_downloads: usize,
     progress_factory: Box<dyn ProgressReporterFactory + Send + Sync>,
+    ignore_space_check: bool,
+    fs_info: Box<dyn FsInfoProvider + Send + Sync>,
+    cancel: Option<Arc<AtomicBool>>,
+}
+
+/// Files at least this large are preallocated before their body is written
+const PREALLOCATE_THRESHOLD: u64 = 64 * 1024 * 1024;
+
+/// Times a file's URL is refreshed after expiring before giving up
+const MAX_URL_REFRESHES: usize = 3;
+
+/// Times an interrupted body is resumed from its current offset before giving up
+const MAX_RESUMES: usize = 3;
+
+/// Bytes a batch adds to the disk: remote sizes minus what already exists locally.
+/// Entries are `(remote, local)` sizes; unknown remote sizes count as zero.
+pub fn projected_download_size(sizes: impl IntoIterator<Item = (Option<u64>, Option<u64>)>) -> u64 {
+    sizes
+        .into_iter()
+        .map(|(remote, local)| remote.unwrap_or(0).saturating_sub(local.unwrap_or(0)))
+        .sum()
 }
 
 impl ProgressConfig for BaseDownloaderImpl {
@@ -62,6 +357,9 @@ impl ProgressConfig for BaseDownloaderImpl {
             client: Client::new(),
             concurrent_downloads,
             progress_factory,
+            ignore_space_check: false,
+            fs_info: Box::new(SystemFsInfo),
+            cancel: None,
         }
     }
 }
@@ -71,6 +369,30 @@ impl BaseDownloaderImpl {
         &self.client
     }
 
+    /// Skip the free space check and the HEAD request it sends for every file
+    pub fn with_ignore_space_check(mut self, ignore_space_check: bool) -> Self {
+        self.ignore_space_check = ignore_space_check;
+        self
+    }
+
+    pub fn with_fs_info(mut self, fs_info: Box<dyn FsInfoProvider + Send + Sync>) -> Self {
+        self.fs_info = fs_info;
+        self
+    }
+
+    /// Stop the batch once `cancel` is set: files not started yet are skipped and
+    /// files in flight are abandoned, both are reported as cancelled
+    pub fn with_cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+
     pub fn extract_folder_name_from_url(&self, url_str: &str) -> Result<String> {
         let url = Url::parse(url_str).map_err(|e| anyhow!("Invalid URL: {}", e))?;
 
@@ -124,105 +446,379 @@ impl BaseDownloaderImpl {
 }
 
 impl BaseDownloaderImpl {
-    pub async fn download_files(&self, items: Vec<DownloadItem>, output_dir: &Path) -> Result<()> {
+    /// Download `items` with at most `concurrent_downloads` files in flight
+    ///
+    /// Only setup failures (directory, free space) are an error, failed files are
+    /// recorded in the report.
+    pub async fn download_files(
+        &self,
+        items: Vec<DownloadItem>,
+        output_dir: &Path,
+    ) -> Result<DownloadReport> {
         fs::create_dir_all(output_dir).await?;
 
-        let total_files = items.len() as u64;
-        let progress_reporter = self
-            .progress_factory
-            .create_reporter(total_files, self.concurrent_downloads);
-
-        let semaphore = Arc::new(tokio::sync::Semaphore::new(self.concurrent_downloads));
-        let active_threads = Arc::new(std::sync::atomic::AtomicUsize::new(0));
-
-        let tasks: Vec<_> = items
-            .into_iter()
-            .map(|item| {
-                let client = self.client.clone();
-                let semaphore = semaphore.clone();
-                let output_path = output_dir.join(&item.filename);
-                let progress_reporter = progress_reporter.as_ref();
-                let active_threads = active_threads.clone();
-                let concurrent_downloads = self.concurrent_downloads;
-
-                async move {
-                    let _permit = semaphore.acquire().await.unwrap();
-
-                    let thread_id = active_threads
-                        .fetch_add(1, std::sync::atomic::Ordering::SeqCst)
-                        % concurrent_downloads;
+        if self.ignore_space_check {
+            info!("💾 Space check skipped");
+        } else {
+            let space_check = self.check_free_space(&items, output_dir).await?;
+            if !space_check.fits() {
+                return Err(anyhow!(
+                    "Not enough free space in {:?}: {} bytes needed, {} bytes usable ({} bytes short); pass --ignore-space-check to download anyway",
+                    output_dir,
+                    space_check.required,
+                    space_check.usable(),
+                    space_check.shortfall()
+                ));
+            }
+            info!(
+                "💾 Space check: {} bytes needed, {} bytes free ({} bytes reserved)",
+                space_check.required, space_check.available, space_check.margin
+            );
+        }
 
+        let total_files = items.len() as u64;
+        let workers = self.concurrent_downloads.max(1);
+        let progress_reporter = self.progress_factory.create_reporter(total_files, workers);
+        let progress_reporter = progress_reporter.as_ref();
+
+        // Each worker owns a progress line, so files are assigned to the worker picking them up
+        let queue = tokio::sync::Mutex::new(items.into_iter().enumerate());
+        let worker_tasks = (0..workers).map(|thread_id| {
+            let queue = &queue;
+            async move {
+                let mut entries = Vec::new();
+                loop {
+                    let next = queue.lock().await.next();
+                    let Some((index, item)) = next else {
+                        break;
+                    };
+                    if self.is_cancelled() {
+                        entries.push((
+                            index,
+                            DownloadEntry {
+                                filename: item.filename,
+                                url: item.url,
+                                error: Some(DownloadCancelledError.to_string()),
+                            },
+                        ));
+                        continue;
+                    }
+                    let output_path = output_dir.join(&item.filename);
                     let result = Self::download_single_file_with_progress_reporter(
-                        &client,
-                        &item.url,
+                        &self.client,
+                        &item,
                         &output_path,
                         thread_id,
-                        &item.filename,
                         progress_reporter,
+                        self.cancel.as_deref(),
                     )
                     .await;
 
                     progress_reporter.finish_file(thread_id, &item.filename);
-
-                    result
+                    match &result {
+                        Err(e) if e.is::<DownloadCancelledError>() => {
+                            // a partial file would look complete to a later verify
+                            let _ = fs::remove_file(&output_path).await;
+                            info!("🛑 Abandoned {}", item.filename);
+                        }
+                        Err(e) => warn!("❌ Failed to download {}: {}", item.filename, e),
+                        Ok(()) => {}
+                    }
+
+                    entries.push((
+                        index,
+                        DownloadEntry {
+                            filename: item.filename,
+                            url: item.url,
+                            error: result.err().map(|e| e.to_string()),
+                        },
+                    ));
                 }
-            })
-            .collect();
+                entries
+            }
+        });
 
-        let results = join_all(tasks).await;
+        let mut entries: Vec<_> = join_all(worker_tasks).await.into_iter().flatten().collect();
+        entries.sort_by_key(|(index, _)| *index);
 
         progress_reporter.finish_all();
 
-        for result in results {
-            result?;
-        }
-
-        Ok(())
+        Ok(DownloadReport {
+            target_dir: output_dir.to_path_buf(),
+            entries: entries.into_iter().map(|(_, entry)| entry).collect(),
+            cancelled: self.is_cancelled(),
+        })
     }
 
+    /// Stream `item` to `output_path`. An interrupted body is requested again from the
+    /// bytes already written; an expired pre-signed URL is replaced through
+    /// `item.refresh_url` and the download resumes at the same offset.
     async fn download_single_file_with_progress_reporter(
         client: &Client,
-        url: &str,
+        item: &DownloadItem,
         output_path: &Path,
         thread_id: usize,
-        filename: &str,
         progress_reporter: &dyn ProgressReporter,
+        cancel: Option<&AtomicBool>,
     ) -> Result<()> {
-        let response = client
-            .get(url)
-            .send()
+        let mut url = item.url.clone();
+        let mut file = fs::File::create(output_path)
             .await
-            .map_err(|e| anyhow!("Failed to fetch {}: {}", url, e))?;
+            .map_err(|e| anyhow!("Failed to create file {:?}: {}", output_path, e))?;
+        let mut written: u64 = 0;
+        let result: Result<()> = async {
+            let mut file_progress = None;
+            let mut total_size = 0;
+            // A restarted body may be shorter than what the first attempt wrote
+            let mut rewound = false;
+            let mut refreshes = 0;
+            let mut resumes = 0;
+
+            'request: loop {
+                let mut request = client.get(&url);
+                if written > 0 {
+                    request = request.header(header::RANGE, format!("bytes={}-", written));
+                }
+                let mut response = request
+                    .send()
+                    .await
+                    .map_err(|e| anyhow!("Failed to fetch {}: {}", url, e))?;
+
+                let status = response.status();
+                if !status.is_success() {
+                    let body = response.text().await.unwrap_or_default();
+                    if !is_url_expired(status, &url, &body, Utc::now()) {
+                        return Err(anyhow!("HTTP error {} for URL: {}", status, url));
+                    }
+                    let Some(refresher) = &item.refresh_url else {
+                        return Err(UrlExpiredError { url }.into());
+                    };
+                    if refreshes == MAX_URL_REFRESHES {
+                        return Err(anyhow!(
+                            "URL expired {} times, giving up: {}",
+                            MAX_URL_REFRESHES,
+                            url
+                        ));
+                    }
+                    refreshes += 1;
+                    url = refresher
+                        .refresh(&url)
+                        .await
+                        .with_context(|| format!("Failed to refresh expired URL: {}", url))?;
+                    info!(
+                        "🔑 Refreshed expired URL of {}, resuming at byte {}",
+                        item.filename, written
+                    );
+                    continue;
+                }
 
-        if !response.status().is_success() {
-            return Err(anyhow!("HTTP error {} for URL: {}", response.status(), url));
+                if written > 0 && status != StatusCode::PARTIAL_CONTENT {
+                    // The server ignored the range, start over
+                    file.seek(SeekFrom::Start(0))
+                        .await
+                        .map_err(|e| anyhow!("Failed to rewind file {:?}: {}", output_path, e))?;
+                    written = 0;
+                    rewound = true;
+                }
+                if file_progress.is_none() {
+                    total_size = written + response.content_length().unwrap_or(0);
+                    file_progress = progress_reporter.assign_file_to_thread(
+                        thread_id,
+                        &item.filename,
+                        total_size,
+                    );
+                    if total_size >= PREALLOCATE_THRESHOLD {
+                        file.set_len(total_size).await.map_err(|e| {
+                            anyhow!("Failed to preallocate file {:?}: {}", output_path, e)
+                        })?;
+                    }
+                    if let Some(file_progress) = &file_progress
+                        && total_size > 0
+                    {
+                        file_progress.set_total_size(total_size);
+                    }
+                }
+
+                loop {
+                    match response.chunk().await {
+                        Ok(Some(_))
+                            if cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed)) =>
+                        {
+                            return Err(DownloadCancelledError.into());
+                        }
+                        Ok(Some(chunk)) => {
+                            file.write_all(&chunk).await.map_err(|e| {
+                                anyhow!("Failed to write to file {:?}: {}", output_path, e)
+                            })?;
+                            written += chunk.len() as u64;
+                            if let Some(file_progress) = &file_progress {
+                                file_progress.update_progress(written);
+                            }
+                        }
+                        Ok(None) => break 'request,
+                        Err(e) if resumes < MAX_RESUMES => {
+                            resumes += 1;
+                            warn!(
+                                "⚠️ Download of {} interrupted at byte {}, resuming: {}",
+                                item.filename, written, e
+                            );
+                            continue 'request;
+                        }
+                        Err(e) => {
+                            return Err(anyhow!(
+                                "Failed to read response body from {}: {}",
+                                url,
+                                e
+                            ));
+                        }
+                    }
+                }
+            }
+
+            if rewound || (written != total_size && total_size >= PREALLOCATE_THRESHOLD) {
+                file.set_len(written)
+                    .await
+                    .map_err(|e| anyhow!("Failed to truncate file {:?}: {}", output_path, e))?;
+            }
+            // tokio finishes writes in the background, make sure they landed before reporting
+            file.flush()
+                .await
+                .map_err(|e| anyhow!("Failed to write to file {:?}: {}", output_path, e))?;
+
+            Ok(())
         }
+        .await;
 
-        let total_size = response.content_length().unwrap_or(0);
-        let file_progress =
-            progress_reporter.assign_file_to_thread(thread_id, filename, total_size);
+        if result.is_err() {
+            // a preallocated file already has its full size and would pass a later verify
+            let _ = file.set_len(written).await;
+        }
+        result
+    }
+}
 
-        let mut file = fs::File::create(output_path)
-            .await
-            .map_err(|e| anyhow!("Failed to create file {:?}: {}", output_path, e))?;
+impl BaseDownloaderImpl {
+    pub async fn verify_files(
+        &self,
+        items: Vec<DownloadItem>,
+        output_dir: &Path,
+    ) -> Result<VerifyReport> {
+        let semaphore = Arc::new(tokio::sync::Semaphore::new(self.concurrent_downloads));
 
-        let content = response
-            .bytes()
-            .await
-            .map_err(|e| anyhow!("Failed to read response body from {}: {}", url, e))?;
+        let tasks: Vec<_> = items
+            .into_iter()
+            .map(|item| {
+                let client = self.client.clone();
+                let semaphore = semaphore.clone();
+                let output_path = output_dir.join(&item.filename);
+
+                async move {
+                    let _permit = semaphore.acquire().await.unwrap();
+                    let status = Self::verify_single_file(&client, &item.url, &output_path).await;
+                    VerifyEntry {
+                        filename: item.filename,
+                        url: item.url,
+                        status,
+                    }
+                }
+            })
+            .collect();
+
+        Ok(VerifyReport {
+            target_dir: output_dir.to_path_buf(),
+            entries: join_all(tasks).await,
+        })
+    }
+
+    async fn verify_single_file(client: &Client, url: &str, output_path: &Path) -> VerifyStatus {
+        let local_size = Self::local_size(output_path).await;
+        if local_size.is_none() {
+            return VerifyStatus::Missing;
+        }
+
+        let remote = match Self::fetch_remote_info(client, url).await {
+            Ok(remote) => remote,
+            Err(e) => return VerifyStatus::RemoteError(e.to_string()),
+        };
+        let status = VerifyStatus::from_sizes(local_size, remote.size);
+        let Some(remote_md5) = remote.md5 else {
+            return status;
+        };
+        if status != VerifyStatus::Ok {
+            return status;
+        }
+        match Self::local_md5(output_path).await {
+            Ok(local_md5) => VerifyStatus::from_digests(&local_md5, Some(&remote_md5)),
+            Err(e) => VerifyStatus::RemoteError(format!("Failed to read local file: {}", e)),
+        }
+    }
 
-        if let Some(file_progress) = file_progress {
-            if total_size > 0 {
-                file_progress.set_total_size(total_size);
+    /// Projected usage of `items` from HEAD requests, compared against free space
+    async fn check_free_space(
+        &self,
+        items: &[DownloadItem],
+        output_dir: &Path,
+    ) -> Result<SpaceCheck> {
+        let semaphore = Arc::new(tokio::sync::Semaphore::new(self.concurrent_downloads));
+
+        let tasks: Vec<_> = items
+            .iter()
+            .map(|item| {
+                let semaphore = semaphore.clone();
+                let output_path = output_dir.join(&item.filename);
+
+                async move {
+                    let _permit = semaphore.acquire().await.unwrap();
+                    let remote = Self::fetch_remote_info(&self.client, &item.url)
+                        .await
+                        .map_or(None, |remote| remote.size);
+                    (remote, Self::local_size(&output_path).await)
+                }
+            })
+            .collect();
+
+        let required = projected_download_size(join_all(tasks).await);
+        check_space(
+            self.fs_info.as_ref(),
+            output_dir,
+            required,
+            DEFAULT_SPACE_MARGIN,
+        )
+    }
+
+    async fn local_size(path: &Path) -> Option<u64> {
+        match fs::metadata(path).await {
+            Ok(metadata) if metadata.is_file() => Some(metadata.len()),
+            _ => None,
+        }
+    }
+
+    async fn local_md5(path: &Path) -> std::io::Result<Vec<u8>> {
+        let mut file = fs::File::open(path).await?;
+        let mut hasher = Md5::new();
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = file.read(&mut buf).await?;
+            if n == 0 {
+                return Ok(hasher.finalize().to_vec());
             }
-            file_progress.update_progress(content.len() as u64);
+            hasher.update(&buf[..n]);
         }
+    }
 
-        file.write_all(&content)
+    async fn fetch_remote_info(client: &Client, url: &str) -> Result<RemoteFileInfo> {
+        let response = client
+            .head(url)
+            .send()
             .await
-            .map_err(|e| anyhow!("Failed to write to file {:?}: {}", output_path, e))?;
+            .map_err(|e| anyhow!("Failed to fetch {}: {}", url, e))?;
+        if !response.status().is_success() {
+            return Err(anyhow!("HTTP error {}", response.status()));
+        }
 
-        Ok(())
+        // `Response::content_length` reports the (empty) body of a HEAD response,
+        // so read the headers directly
+        Ok(RemoteFileInfo::from_headers(response.headers()))
     }
 }
 
@@ -8,10 +8,13 @@ pub mod r2_uploader;
 mod tests;
 
 pub use als_downloader::AlsDownloader;
-pub use downloader::{BaseDownloader, Downloader};
+pub use downloader::{
+    BaseDownloader, DownloadCancelledError, DownloadEntry, DownloadReport, Downloader,
+    RemoteFileInfo, UrlExpiredError, UrlRefresher, VerifyEntry, VerifyReport, VerifyStatus,
+};
 pub use mrs_downloader::MrsDownloader;
 pub use progress_ui::{
     FileProgressReporter, ProgressReporter, ProgressReporterFactory, SilentProgressReporterFactory,
     TreeProgressReporterFactory,
 };
-pub use r2_uploader::R2Uploader;
+pub use r2_uploader::{R2Uploader, UploadResult, UploadSummary};
@@ -1,7 +1,11 @@
-use crate::downloader::{BaseDownloader, BaseDownloaderImpl, DownloadItem, ProgressConfig};
+use crate::downloader::{
+    BaseDownloader, BaseDownloaderImpl, DownloadItem, DownloadReport, ProgressConfig, VerifyReport,
+};
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 
 pub struct MrsDownloader {
     base: BaseDownloaderImpl,
@@ -26,6 +30,16 @@ impl MrsDownloader {
         }
     }
 
+    pub fn with_ignore_space_check(mut self, ignore_space_check: bool) -> Self {
+        self.base = self.base.with_ignore_space_check(ignore_space_check);
+        self
+    }
+
+    pub fn with_cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.base = self.base.with_cancel(cancel);
+        self
+    }
+
     async fn fetch_iarc_content(&self, url: &str) -> Result<Vec<u8>> {
         let response = self.base.client().get(url).send().await?;
 
@@ -75,9 +89,13 @@ impl MrsDownloader {
     }
 }
 
-#[async_trait]
-impl BaseDownloader for MrsDownloader {
-    async fn download(&self, url: &str, output_dir: &Path) -> Result<()> {
+impl MrsDownloader {
+    /// Target directory and every file belonging to the playback behind `url`
+    async fn collect_download_items(
+        &self,
+        url: &str,
+        output_dir: &Path,
+    ) -> Result<(PathBuf, Vec<DownloadItem>)> {
         let iarc_content = self.fetch_iarc_content(url).await?;
         let mut download_items = Vec::new();
 
@@ -88,6 +106,7 @@ impl BaseDownloader for MrsDownloader {
         download_items.push(DownloadItem {
             url: url.to_string(),
             filename: self.base.extract_filename_from_url(url)?,
+            refresh_url: None,
         });
 
         let segment_files = self.parse_iarc_segments(&iarc_content)?;
@@ -97,14 +116,24 @@ impl BaseDownloader for MrsDownloader {
             download_items.push(DownloadItem {
                 url: segment_url,
                 filename: segment_file,
+                refresh_url: None,
             });
         }
 
-        self.base
-            .download_files(download_items, &target_dir)
-            .await?;
+        Ok((target_dir, download_items))
+    }
+}
+
+#[async_trait]
+impl BaseDownloader for MrsDownloader {
+    async fn download(&self, url: &str, output_dir: &Path) -> Result<DownloadReport> {
+        let (target_dir, download_items) = self.collect_download_items(url, output_dir).await?;
+        self.base.download_files(download_items, &target_dir).await
+    }
 
-        Ok(())
+    async fn verify(&self, url: &str, output_dir: &Path) -> Result<VerifyReport> {
+        let (target_dir, download_items) = self.collect_download_items(url, output_dir).await?;
+        self.base.verify_files(download_items, &target_dir).await
     }
 
     fn extract_folder_name(&self, url: &str) -> Result<String> {
@@ -1,5 +1,6 @@
 use anyhow::{Error, Result};
 use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
 use hex;
 use hmac::{Hmac, Mac};
 use reqwest::Client;
@@ -7,6 +8,7 @@ use sha2::{Digest, Sha256};
 use std::fs;
 use std::path::Path;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use tokio::fs::File;
 use tokio::io::AsyncReadExt;
 use tokio::sync::mpsc;
@@ -34,6 +36,19 @@ pub struct UploadTask {
     pub file_size: u64,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UploadResult {
+    Uploaded,
+    /// Remote key already existed, nothing was uploaded
+    Skipped,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct UploadSummary {
+    pub uploaded: usize,
+    pub skipped: usize,
+}
+
 impl R2Uploader {
     pub async fn from_env_or_args(
         account_id: Option<String>,
@@ -148,6 +163,94 @@ impl R2Uploader {
         self.upload_files_concurrent(vec![task]).await
     }
 
+    /// Check whether `key` exists in the bucket with a `HEAD` request
+    pub async fn exists(&self, key: &str) -> Result<bool> {
+        let url = format!("{}/{}/{}", self.endpoint, self.bucket, key);
+
+        let content_sha256 = hex::encode(Sha256::digest(b""));
+
+        let now = Utc::now();
+        let authorization = self.generate_auth_header("HEAD", key, &[], "", &now)?;
+
+        let response = self
+            .client
+            .head(&url)
+            .header("Authorization", authorization)
+            .header("x-amz-date", now.format("%Y%m%dT%H%M%SZ").to_string())
+            .header("x-amz-content-sha256", content_sha256)
+            .send()
+            .await
+            .map_err(|e| Error::msg(format!("Failed to send request: {}", e)))?;
+
+        match response.status() {
+            reqwest::StatusCode::OK => Ok(true),
+            reqwest::StatusCode::NOT_FOUND => Ok(false),
+            status => Err(Error::msg(format!(
+                "Exists check for '{}' failed with status {}",
+                key, status
+            ))),
+        }
+    }
+
+    pub async fn upload_if_not_exists(
+        &self,
+        local_path: &Path,
+        remote_key: &str,
+    ) -> Result<UploadResult> {
+        if self.exists(remote_key).await? {
+            return Ok(UploadResult::Skipped);
+        }
+        self.upload_file(local_path, Some(remote_key)).await?;
+        Ok(UploadResult::Uploaded)
+    }
+
+    /// Upload a folder, skipping files whose remote key already exists
+    pub async fn upload_directory_incremental(
+        &self,
+        local_folder: &Path,
+        remote_prefix: Option<&str>,
+    ) -> Result<UploadSummary> {
+        if !local_folder.is_dir() {
+            return Err(Error::msg("Local path must be a directory"));
+        }
+
+        let tasks = self.collect_upload_tasks(local_folder, local_folder, remote_prefix)?;
+        let total = tasks.len();
+
+        let checked: Vec<(UploadTask, Result<bool>)> = stream::iter(tasks)
+            .map(|task| async move {
+                let exists = self.exists(&task.remote_key).await;
+                (task, exists)
+            })
+            .buffer_unordered(self.concurrent_uploads.max(1))
+            .collect()
+            .await;
+
+        let mut pending = Vec::new();
+        for (task, exists) in checked {
+            if !exists? {
+                pending.push(task);
+            }
+        }
+        let summary = UploadSummary {
+            uploaded: pending.len(),
+            skipped: total - pending.len(),
+        };
+
+        if let Some(reporter) = &self.progress_reporter {
+            // Only the missing files are uploaded
+            if let Some(tree_reporter) = reporter
+                .as_any()
+                .downcast_ref::<crate::progress_ui::TreeProgressReporter>()
+            {
+                tree_reporter.set_total_files(pending.len() as u64);
+            }
+        }
+
+        self.upload_files_concurrent(pending).await?;
+        Ok(summary)
+    }
+
     pub async fn upload_folder(
         &self,
         local_folder: &Path,
@@ -246,38 +349,7 @@ impl R2Uploader {
                         None => break, // 没有更多任务
                     };
 
-                    let file_reporter = if let Some(reporter) = &uploader.progress_reporter {
-                        reporter.assign_file_to_thread(
-                            thread_id,
-                            &task
-                                .local_path
-                                .file_name()
-                                .unwrap_or_default()
-                                .to_string_lossy(),
-                            task.file_size,
-                        )
-                    } else {
-                        None
-                    };
-
-                    let result = uploader
-                        .upload_single_file(&task, file_reporter.as_ref())
-                        .await;
-
-                    if let Some(reporter) = &uploader.progress_reporter {
-                        reporter.finish_file(
-                            thread_id,
-                            &task
-                                .local_path
-                                .file_name()
-                                .unwrap_or_default()
-                                .to_string_lossy(),
-                        );
-                    }
-
-                    if let Err(e) = result {
-                        return Err(e);
-                    }
+                    uploader.upload_reported(thread_id, &task).await?;
 
                     if uploader.progress_reporter.is_none()
                         || uploader
@@ -289,7 +361,7 @@ impl R2Uploader {
                     {
                     }
                 }
-                Ok(())
+                Ok::<(), Error>(())
             });
 
             handles.push(handle);
@@ -308,6 +380,92 @@ impl R2Uploader {
         Ok(())
     }
 
+    /// Upload files as they arrive on `queue` until every sender is dropped
+    ///
+    /// Playlists (`.m3u8`) are held back until all other files are uploaded, so the bucket
+    /// never serves a playlist listing segments that are not there yet.
+    pub async fn upload_queue(&self, queue: mpsc::Receiver<UploadTask>) -> Result<UploadSummary> {
+        let queue = Arc::new(tokio::sync::Mutex::new(queue));
+        let received = Arc::new(AtomicU64::new(0));
+        let playlists = Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let mut handles = Vec::new();
+        for thread_id in 0..self.concurrent_uploads.max(1) {
+            let queue = Arc::clone(&queue);
+            let received = Arc::clone(&received);
+            let playlists = Arc::clone(&playlists);
+            let uploader = self.clone();
+
+            handles.push(tokio::spawn(async move {
+                loop {
+                    let task = {
+                        let mut queue = queue.lock().await;
+                        queue.recv().await
+                    };
+                    let Some(task) = task else {
+                        break;
+                    };
+
+                    // the total grows while the producer is still writing files
+                    let total = received.fetch_add(1, Ordering::Relaxed) + 1;
+                    if let Some(tree_reporter) = uploader.progress_reporter.as_ref().and_then(|r| {
+                        r.as_any()
+                            .downcast_ref::<crate::progress_ui::TreeProgressReporter>()
+                    }) {
+                        tree_reporter.set_total_files(total);
+                    }
+
+                    if task.remote_key.ends_with(".m3u8") {
+                        playlists.lock().unwrap().push(task);
+                        continue;
+                    }
+                    uploader.upload_reported(thread_id, &task).await?;
+                }
+                Ok::<(), Error>(())
+            }));
+        }
+
+        for handle in handles {
+            handle
+                .await
+                .map_err(|e| Error::msg(format!("Thread join error: {}", e)))??;
+        }
+
+        let playlists = std::mem::take(&mut *playlists.lock().unwrap());
+        if playlists.is_empty() {
+            if let Some(reporter) = &self.progress_reporter {
+                reporter.finish_all();
+            }
+        } else {
+            self.upload_files_concurrent(playlists).await?;
+        }
+
+        Ok(UploadSummary {
+            uploaded: received.load(Ordering::Relaxed) as usize,
+            skipped: 0,
+        })
+    }
+
+    /// Upload one file on worker `thread_id`, reporting its progress
+    async fn upload_reported(&self, thread_id: usize, task: &UploadTask) -> Result<()> {
+        let filename = task
+            .local_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let file_reporter = self.progress_reporter.as_ref().and_then(|reporter| {
+            reporter.assign_file_to_thread(thread_id, &filename, task.file_size)
+        });
+
+        let result = self.upload_single_file(task, file_reporter.as_ref()).await;
+
+        if let Some(reporter) = &self.progress_reporter {
+            reporter.finish_file(thread_id, &filename);
+        }
+        result
+    }
+
     async fn upload_single_file(
         &self,
         task: &UploadTask,
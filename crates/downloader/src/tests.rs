@@ -1,6 +1,16 @@
 #[cfg(test)]
 mod tests {
-    use crate::{AlsDownloader, Downloader, MrsDownloader};
+    use crate::downloader::{
+        DownloadItem, ProgressConfig, is_url_expired, projected_download_size, signed_url_expiry,
+    };
+    use crate::{
+        AlsDownloader, Downloader, MrsDownloader, RemoteFileInfo, UrlRefresher, VerifyStatus,
+    };
+    use linkura_common::disk::FsInfoProvider;
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
     #[tokio::test]
     async fn test_downloader_creation() {
@@ -47,4 +57,589 @@ mod tests {
         assert_eq!(segments[0], "segment_001.ts");
         assert_eq!(segments[1], "segment_002.ts");
     }
+
+    #[test]
+    fn test_als_m3u8_program_date_time() {
+        let als_downloader = AlsDownloader::new(1);
+
+        let test_m3u8 = "#EXTM3U\n#EXTINF:10.000,\nsegment_001.ts\n#EXTINF:10.000,\n#EXT-X-PROGRAM-DATE-TIME:2025-08-21T19:00:10.500+09:00\nsegment_002.ts\n#EXT-X-ENDLIST";
+        let started_at = als_downloader
+            .parse_m3u8_program_date_time(test_m3u8)
+            .unwrap()
+            .unwrap();
+        assert_eq!(started_at.to_rfc3339(), "2025-08-21T10:00:00.500+00:00");
+
+        let test_m3u8 = "#EXTM3U\n#EXTINF:10.000,\nsegment_001.ts\n#EXT-X-ENDLIST";
+        assert!(
+            als_downloader
+                .parse_m3u8_program_date_time(test_m3u8)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_verify_status_from_sizes() {
+        assert_eq!(
+            VerifyStatus::from_sizes(None, Some(10)),
+            VerifyStatus::Missing
+        );
+        assert_eq!(
+            VerifyStatus::from_sizes(Some(10), Some(10)),
+            VerifyStatus::Ok
+        );
+        assert_eq!(VerifyStatus::from_sizes(Some(10), None), VerifyStatus::Ok);
+        assert_eq!(
+            VerifyStatus::from_sizes(Some(4), Some(10)),
+            VerifyStatus::SizeMismatch {
+                local: 4,
+                remote: 10
+            }
+        );
+    }
+
+    #[test]
+    fn test_remote_file_info_from_headers() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("content-length", "5".parse().unwrap());
+        headers.insert(
+            "etag",
+            "\"5d41402abc4b2a76b9719d911017c592\"".parse().unwrap(),
+        );
+        let info = RemoteFileInfo::from_headers(&headers);
+        assert_eq!(info.size, Some(5));
+        assert_eq!(
+            info.md5.as_deref().map(hex::encode).as_deref(),
+            Some("5d41402abc4b2a76b9719d911017c592")
+        );
+
+        // multipart ETags are not an MD5 of the content
+        headers.insert(
+            "etag",
+            "\"5d41402abc4b2a76b9719d911017c592-3\"".parse().unwrap(),
+        );
+        assert_eq!(RemoteFileInfo::from_headers(&headers).md5, None);
+
+        // Content-MD5 wins over the ETag
+        headers.insert("content-md5", "XUFAKrxLKna5cZ2REBfFkg==".parse().unwrap());
+        assert_eq!(
+            RemoteFileInfo::from_headers(&headers).md5.map(hex::encode),
+            Some("5d41402abc4b2a76b9719d911017c592".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_detects_corrupt_file() {
+        // HEAD answers with the size and ETag of "hello"
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf).await;
+                let _ = stream
+                    .write_all(
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nETag: \"5d41402abc4b2a76b9719d911017c592\"\r\nConnection: close\r\n\r\n",
+                    )
+                    .await;
+            }
+        });
+
+        let tmp = tempfile::tempdir().unwrap();
+        let output_dir = tmp.path();
+        std::fs::write(output_dir.join("segment_001.ts"), b"hello").unwrap();
+        std::fs::write(output_dir.join("segment_002.ts"), b"\0\0\0\0\0").unwrap();
+        let items: Vec<_> = ["segment_001.ts", "segment_002.ts"]
+            .into_iter()
+            .map(|filename| DownloadItem {
+                url: format!("http://{}/{}", addr, filename),
+                filename: filename.to_string(),
+                refresh_url: None,
+            })
+            .collect();
+
+        let report = Downloader::new(1)
+            .verify_files(items, output_dir)
+            .await
+            .unwrap();
+        assert_eq!(report.entries[0].status, VerifyStatus::Ok);
+        assert_eq!(
+            report.entries[1].status,
+            VerifyStatus::Corrupt {
+                local: "ca9c491ac66b2c62500882e93f3719a8".to_string(),
+                remote: "5d41402abc4b2a76b9719d911017c592".to_string(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_verify_missing_files_without_request() {
+        let downloader = Downloader::new(2);
+        let tmp = tempfile::tempdir().unwrap();
+        let output_dir = tmp.path();
+
+        let report = downloader
+            .verify_files(
+                vec![DownloadItem {
+                    url: "http://127.0.0.1:9/segment_001.ts".to_string(),
+                    filename: "segment_001.ts".to_string(),
+                    refresh_url: None,
+                }],
+                output_dir,
+            )
+            .await
+            .unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(report.ok_count(), 0);
+        assert_eq!(report.entries[0].status, VerifyStatus::Missing);
+    }
+
+    #[test]
+    fn test_projected_download_size() {
+        let sizes = vec![
+            (Some(100), None),
+            (Some(100), Some(40)),
+            (Some(100), Some(150)),
+            (None, Some(10)),
+        ];
+        assert_eq!(projected_download_size(sizes), 160);
+    }
+
+    struct FixedFsInfo(u64);
+
+    impl FsInfoProvider for FixedFsInfo {
+        fn available_space(&self, _path: &Path) -> anyhow::Result<u64> {
+            Ok(self.0)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_download_refused_without_free_space() {
+        // Answers every request with a 1 GiB Content-Length and no body
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf).await;
+                let _ = stream
+                    .write_all(
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 1073741824\r\nConnection: close\r\n\r\n",
+                    )
+                    .await;
+            }
+        });
+
+        let tmp = tempfile::tempdir().unwrap();
+        let output_dir = tmp.path();
+        let items = vec![DownloadItem {
+            url: format!("http://{}/segment_001.ts", addr),
+            filename: "segment_001.ts".to_string(),
+            refresh_url: None,
+        }];
+
+        let downloader = Downloader::with_progress(1, false)
+            .with_fs_info(Box::new(FixedFsInfo(1024 * 1024 * 1024)));
+        let err = downloader
+            .download_files(items, output_dir)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Not enough free space"), "{}", err);
+        assert!(!output_dir.join("segment_001.ts").exists());
+    }
+
+    #[tokio::test]
+    async fn test_ignored_space_check_sends_no_head() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let heads = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let server_heads = heads.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let n = stream.read(&mut buf).await.unwrap_or(0);
+                if buf[..n].starts_with(b"HEAD ") {
+                    server_heads.fetch_add(1, Ordering::SeqCst);
+                }
+                let _ = stream
+                    .write_all(
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+                    )
+                    .await;
+            }
+        });
+
+        let tmp = tempfile::tempdir().unwrap();
+        let output_dir = tmp.path();
+        let items = vec![DownloadItem {
+            url: format!("http://{}/segment_001.ts", addr),
+            filename: "segment_001.ts".to_string(),
+            refresh_url: None,
+        }];
+
+        let downloader = Downloader::with_progress(1, false)
+            .with_fs_info(Box::new(FixedFsInfo(0)))
+            .with_ignore_space_check(true);
+        let report = downloader.download_files(items, output_dir).await.unwrap();
+        assert!(report.is_ok(), "{:?}", report.entries);
+        assert_eq!(heads.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_download_failure_truncates_preallocated_file() {
+        // Announces a 128 MiB body but always drops the connection after 5 bytes
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf).await;
+                let _ = stream
+                    .write_all(
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 134217728\r\nConnection: close\r\n\r\nhello",
+                    )
+                    .await;
+            }
+        });
+
+        let tmp = tempfile::tempdir().unwrap();
+        let output_dir = tmp.path();
+        let items = vec![DownloadItem {
+            url: format!("http://{}/segment_001.ts", addr),
+            filename: "segment_001.ts".to_string(),
+            refresh_url: None,
+        }];
+
+        let downloader =
+            Downloader::with_progress(1, false).with_fs_info(Box::new(FixedFsInfo(u64::MAX / 2)));
+        let report = downloader.download_files(items, output_dir).await.unwrap();
+        assert!(!report.is_ok());
+        let local = std::fs::metadata(output_dir.join("segment_001.ts")).unwrap();
+        assert_eq!(local.len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_download_failure_does_not_abort_batch() {
+        // `missing_*` paths answer 404, everything else a 5 byte body
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let n = stream.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]);
+                let response: &[u8] = if request.contains("/missing_") {
+                    b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                } else {
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello"
+                };
+                let _ = stream.write_all(response).await;
+            }
+        });
+
+        let tmp = tempfile::tempdir().unwrap();
+        let output_dir = tmp.path();
+        let items: Vec<_> = ["segment_001.ts", "missing_002.ts", "segment_003.ts"]
+            .into_iter()
+            .map(|filename| DownloadItem {
+                url: format!("http://{}/{}", addr, filename),
+                filename: filename.to_string(),
+                refresh_url: None,
+            })
+            .collect();
+
+        let downloader =
+            Downloader::with_progress(2, false).with_fs_info(Box::new(FixedFsInfo(u64::MAX / 2)));
+        let report = downloader.download_files(items, output_dir).await.unwrap();
+        let filenames: Vec<_> = report.entries.iter().map(|e| e.filename.as_str()).collect();
+        assert_eq!(
+            filenames,
+            vec!["segment_001.ts", "missing_002.ts", "segment_003.ts"]
+        );
+        assert!(!report.is_ok());
+        assert_eq!(report.ok_count(), 2);
+        let failed: Vec<_> = report.failures().map(|e| e.filename.as_str()).collect();
+        assert_eq!(failed, vec!["missing_002.ts"]);
+        assert!(report.entries[1].error.as_ref().unwrap().contains("404"));
+        assert_eq!(
+            std::fs::read(output_dir.join("segment_003.ts")).unwrap(),
+            b"hello"
+        );
+    }
+
+    #[test]
+    fn test_signed_url_expiry() {
+        let now = chrono::DateTime::from_timestamp(1_755_770_400, 0).unwrap();
+        let expiry = |url: &str| signed_url_expiry(&url::Url::parse(url).unwrap());
+
+        assert_eq!(
+            expiry("https://r2.example/a.ts?X-Amz-Date=20250821T094500Z&X-Amz-Expires=900"),
+            Some(now)
+        );
+        assert_eq!(
+            expiry("https://cdn.example/a.ts?Expires=1755770400&Signature=x"),
+            Some(now)
+        );
+        assert_eq!(expiry("https://cdn.example/a.ts"), None);
+
+        let forbidden = reqwest::StatusCode::FORBIDDEN;
+        let url = "https://cdn.example/a.ts?Expires=1755770400";
+        assert!(is_url_expired(forbidden, url, "", now));
+        assert!(!is_url_expired(
+            forbidden,
+            url,
+            "",
+            now - chrono::TimeDelta::seconds(1)
+        ));
+        assert!(!is_url_expired(
+            reqwest::StatusCode::NOT_FOUND,
+            url,
+            "",
+            now
+        ));
+        assert!(is_url_expired(
+            forbidden,
+            "https://cdn.example/a.ts",
+            "<Error><Code>AccessDenied</Code><Message>Request has expired</Message></Error>",
+            now
+        ));
+        assert!(is_url_expired(
+            forbidden,
+            "https://cdn.example/a.ts",
+            r#"{"error": {"code": "SignatureExpired", "message": "Signature has expired"}}"#,
+            now
+        ));
+        // only a structured error body counts, not any page mentioning expiry
+        assert!(!is_url_expired(
+            forbidden,
+            "https://cdn.example/a.ts",
+            "<html><body>Your session expired, please log in again</body></html>",
+            now
+        ));
+        assert!(!is_url_expired(
+            forbidden,
+            "https://cdn.example/a.ts",
+            "Token expired",
+            now
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_download_resumes_with_refreshed_url() {
+        // `/old/` sends the first 4 bytes of a GET then drops the connection, after which
+        // it answers 403 with an expiry message; `/new/` serves ranges of the body
+        const BODY: &[u8] = b"hello world";
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let server_requests = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let n = stream.read(&mut buf).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                let path = request.split_whitespace().nth(1).unwrap_or("").to_string();
+                let offset = request
+                    .lines()
+                    .find_map(|line| line.strip_prefix("range: bytes="))
+                    .and_then(|range| range.trim_end_matches('-').parse::<usize>().ok());
+                if request.starts_with("head ") {
+                    let _ = stream
+                        .write_all(
+                            b"HTTP/1.1 200 OK\r\nContent-Length: 11\r\nConnection: close\r\n\r\n",
+                        )
+                        .await;
+                    continue;
+                }
+                let old_requests = {
+                    let mut requests = server_requests.lock().unwrap();
+                    requests.push((path.clone(), offset));
+                    requests
+                        .iter()
+                        .filter(|(p, _)| p.starts_with("/old/"))
+                        .count()
+                };
+
+                let response = if path.starts_with("/old/") && old_requests == 1 {
+                    let mut response =
+                        b"HTTP/1.1 200 OK\r\nContent-Length: 11\r\nConnection: close\r\n\r\n"
+                            .to_vec();
+                    response.extend_from_slice(&BODY[..4]);
+                    response
+                } else if path.starts_with("/old/") {
+                    let body = "<Error><Code>AccessDenied</Code><Message>Request has expired</Message></Error>";
+                    format!(
+                        "HTTP/1.1 403 Forbidden\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                    .into_bytes()
+                } else {
+                    let offset = offset.unwrap_or(0);
+                    let mut response = format!(
+                        "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        offset,
+                        BODY.len() - 1,
+                        BODY.len(),
+                        BODY.len() - offset
+                    )
+                    .into_bytes();
+                    response.extend_from_slice(&BODY[offset..]);
+                    response
+                };
+                let _ = stream.write_all(&response).await;
+            }
+        });
+
+        let tmp = tempfile::tempdir().unwrap();
+        let output_dir = tmp.path();
+        let refreshed = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let refresher = {
+            let refreshed = refreshed.clone();
+            UrlRefresher::new(move |url: String| {
+                refreshed.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                async move { Ok(url.replace("/old/", "/new/")) }
+            })
+        };
+        let items = vec![
+            DownloadItem {
+                url: format!("http://{}/old/segment_001.ts", addr),
+                filename: "segment_001.ts".to_string(),
+                refresh_url: None,
+            }
+            .with_refresh_url(refresher),
+        ];
+
+        let downloader =
+            Downloader::with_progress(1, false).with_fs_info(Box::new(FixedFsInfo(u64::MAX / 2)));
+        let report = downloader.download_files(items, output_dir).await.unwrap();
+        assert!(report.is_ok(), "{:?}", report.entries);
+        assert_eq!(
+            std::fs::read(output_dir.join("segment_001.ts")).unwrap(),
+            BODY
+        );
+        assert_eq!(refreshed.load(std::sync::atomic::Ordering::SeqCst), 1);
+        let requests = requests.lock().unwrap();
+        assert_eq!(
+            requests.last().unwrap(),
+            &("/new/segment_001.ts".to_string(), Some(4))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_als_url_refresher_refetches_metadata() {
+        // every metadata fetch hands out the next path version
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut version = 0;
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf).await;
+                version += 1;
+                let body = format!(
+                    r#"{{"path": "http://{}/v{}", "room_id": "room", "playlist_file": "index.m3u8", "live_started_at": "", "joined_room_at": ""}}"#,
+                    addr, version
+                );
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+
+        let refresher = AlsDownloader::new(1)
+            .url_refresher(&format!("http://{}/meta.json", addr), "segment_001.ts");
+        let expired = format!("http://{}/v1/segment_001.ts", addr);
+        // the first fetch still returns the expired path
+        assert!(refresher.refresh(&expired).await.is_err());
+        assert_eq!(
+            refresher.refresh(&expired).await.unwrap(),
+            format!("http://{}/v2/segment_001.ts", addr)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_expired_url_without_refresh() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf).await;
+                let _ = stream
+                    .write_all(
+                        b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    )
+                    .await;
+            }
+        });
+
+        let tmp = tempfile::tempdir().unwrap();
+        let output_dir = tmp.path();
+        let items = vec![DownloadItem {
+            url: format!("http://{}/segment_001.ts?Expires=1000000000", addr),
+            filename: "segment_001.ts".to_string(),
+            refresh_url: None,
+        }];
+
+        let downloader =
+            Downloader::with_progress(1, false).with_fs_info(Box::new(FixedFsInfo(u64::MAX / 2)));
+        let report = downloader.download_files(items, output_dir).await.unwrap();
+        let error = report.entries[0].error.as_ref().unwrap();
+        assert!(error.starts_with("URL expired:"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn test_download_cancelled() {
+        // the body trickles in so the download is still running when it is cancelled
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buf = [0u8; 1024];
+                    let _ = stream.read(&mut buf).await;
+                    let _ = stream
+                        .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 1000\r\n\r\n")
+                        .await;
+                    for _ in 0..100 {
+                        if stream.write_all(&[0u8; 10]).await.is_err() {
+                            break;
+                        }
+                        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                    }
+                });
+            }
+        });
+
+        let tmp = tempfile::tempdir().unwrap();
+        let output_dir = tmp.path();
+        let items: Vec<_> = ["segment_001.ts", "segment_002.ts"]
+            .into_iter()
+            .map(|filename| DownloadItem {
+                url: format!("http://{}/{}", addr, filename),
+                filename: filename.to_string(),
+                refresh_url: None,
+            })
+            .collect();
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let downloader = Downloader::with_progress(1, false)
+            .with_fs_info(Box::new(FixedFsInfo(u64::MAX / 2)))
+            .with_cancel(cancel.clone());
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+            cancel.store(true, Ordering::Relaxed);
+        });
+        let report = downloader.download_files(items, output_dir).await.unwrap();
+        assert!(report.cancelled);
+        assert_eq!(report.ok_count(), 0);
+        for entry in &report.entries {
+            assert_eq!(entry.error.as_deref(), Some("Download cancelled"));
+            assert!(!output_dir.join(&entry.filename).exists());
+        }
+    }
 }
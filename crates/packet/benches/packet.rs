@@ -0,0 +1,165 @@
+//! Baselines for reading, decoding and converting large captures
+//!
+//! Run with `cargo bench -p linkura-packet`. Every benchmark works on the same
+//! synthetic standard capture, written once to a temp dir.
+
+use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use prost::Message;
+use std::fs::File;
+use std::hint::black_box;
+use std::path::{Path, PathBuf};
+
+use linkura_packet::als::converter::{AlsConverter, AlsConverterConfig};
+use linkura_packet::als::proto::PacketInfo;
+use linkura_packet::als::proto::define::{
+    DataFrame, DataPack, InstantiateObject, Room, UpdateObject, data_frame, data_pack,
+};
+use linkura_packet::als::proto::reader::{InputFormat, PacketReaderTrait, StandardPacketReader};
+
+const FILES: usize = 4;
+const PACKETS_PER_FILE: usize = 5_000;
+const FRAMES_PER_PACKET: usize = 4;
+const PAYLOAD_SIZE: usize = 64;
+
+struct Capture {
+    dir: PathBuf,
+    /// protobuf bytes of every packet, for decode throughput
+    raw_packets: Vec<Vec<u8>>,
+}
+
+impl Capture {
+    fn input_dir(&self) -> PathBuf {
+        self.dir.join("input")
+    }
+
+    fn first_file(&self) -> PathBuf {
+        self.input_dir().join("data_1.bin")
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn packet(timestamp: DateTime<Utc>, frames: Vec<DataFrame>) -> PacketInfo {
+    PacketInfo {
+        timestamp,
+        data_pack: DataPack {
+            control: Some(data_pack::Control::Data(true)),
+            frames,
+        },
+        raw_data: Vec::new(),
+        source: None,
+    }
+}
+
+fn frame(message: data_frame::Message) -> DataFrame {
+    DataFrame {
+        message: Some(message),
+    }
+}
+
+/// Room join followed by object updates every 100ms, spread over `FILES` files
+fn synthetic_capture() -> Capture {
+    let dir = std::env::temp_dir().join(format!("linkura-bench-{}", std::process::id()));
+    let input_dir = dir.join("input");
+    std::fs::create_dir_all(&input_dir).unwrap();
+
+    let started_at = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+    let mut raw_packets = Vec::new();
+    for file in 0..FILES {
+        let mut bytes = Vec::new();
+        if file == 0 {
+            for message in [
+                data_frame::Message::Room(Room::default()),
+                data_frame::Message::JoinRoomResponse(Default::default()),
+                data_frame::Message::InstantiateObject(InstantiateObject {
+                    object_id: 7,
+                    ..Default::default()
+                }),
+            ] {
+                bytes.extend(packet(started_at, vec![frame(message)]).to_vec());
+            }
+        }
+        for index in 0..PACKETS_PER_FILE {
+            let sequence = file * PACKETS_PER_FILE + index;
+            let frames = (0..FRAMES_PER_PACKET)
+                .map(|i| {
+                    frame(data_frame::Message::UpdateObject(UpdateObject {
+                        object_id: 7,
+                        method: i as i32,
+                        payload: vec![(sequence + i) as u8; PAYLOAD_SIZE],
+                        target: None,
+                    }))
+                })
+                .collect();
+            let timestamp = started_at + TimeDelta::milliseconds(100 * sequence as i64 + 100);
+            let packet = packet(timestamp, frames);
+            raw_packets.push(packet.data_pack.encode_to_vec());
+            bytes.extend(packet.to_vec());
+        }
+        std::fs::write(input_dir.join(format!("data_{}.bin", file + 1)), bytes).unwrap();
+    }
+    Capture { dir, raw_packets }
+}
+
+fn read_all(path: &Path) -> usize {
+    let mut reader = StandardPacketReader::new(File::open(path).unwrap());
+    reader.read_packets().unwrap().len()
+}
+
+fn bench_reader(c: &mut Criterion, capture: &Capture) {
+    let path = capture.first_file();
+    let mut group = c.benchmark_group("standard_reader");
+    group.throughput(Throughput::Bytes(std::fs::metadata(&path).unwrap().len()));
+    group.bench_function("read_packets", |b| b.iter(|| black_box(read_all(&path))));
+    group.finish();
+}
+
+fn bench_decode(c: &mut Criterion, capture: &Capture) {
+    let bytes: usize = capture.raw_packets.iter().map(Vec::len).sum();
+    let mut group = c.benchmark_group("data_pack");
+    group.throughput(Throughput::Bytes(bytes as u64));
+    group.bench_function("decode", |b| {
+        b.iter(|| {
+            for raw in &capture.raw_packets {
+                black_box(DataPack::decode(raw.as_slice()).unwrap());
+            }
+        })
+    });
+    group.finish();
+}
+
+fn bench_converter(c: &mut Criterion, capture: &Capture) {
+    let input_dir = capture.input_dir();
+    let output_dir = capture.dir.join("output");
+    let config = AlsConverterConfig {
+        input_format: InputFormat::Standard(None),
+        force_output: true,
+        ..Default::default()
+    };
+    let mut group = c.benchmark_group("converter");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements((FILES * PACKETS_PER_FILE) as u64));
+    group.bench_function("convert_standard", |b| {
+        b.iter(|| {
+            AlsConverter::default()
+                .convert_mixed_to_standard(&input_dir, &output_dir, &config)
+                .unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn benches(c: &mut Criterion) {
+    let capture = synthetic_capture();
+    bench_reader(c, &capture);
+    bench_decode(c, &capture);
+    bench_converter(c, &capture);
+}
+
+criterion_group!(packet_benches, benches);
+criterion_main!(packet_benches);
@@ -0,0 +1,417 @@
+//! Removal of player specific ids from a capture before sharing it
+//!
+//! Every distinct player id (`AuthorizeResponse.player_id`, `owner_id` of instantiated
+//! objects and `player_id` targets) is replaced with a placeholder `player_<n>`, numbered
+//! in order of appearance, so frames of the same player still line up. Room ids can be
+//! replaced with random ids the same way. Cleaned packets are written in the standard
+//! format, one output file per input file.
+
+use anyhow::{Context, Result, anyhow};
+use rand_core::{OsRng, RngCore};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use super::proto::PacketInfo;
+use super::proto::application::{FileSortKey, collect_files};
+use super::proto::define::{
+    DataFrame, RoomAll, data_frame, destroy_object, instantiate_object, update_object,
+};
+use super::proto::reader::InputFormat;
+use super::transform::MessageType;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AnonymizeStats {
+    /// Frames with at least one id replaced, per message type
+    pub rewritten_frames: BTreeMap<MessageType, usize>,
+    /// Distinct player ids replaced
+    pub players: usize,
+    /// Distinct room ids replaced
+    pub rooms: usize,
+}
+
+/// Rewrites ids of packets in capture order
+#[derive(Debug, Default)]
+pub struct Anonymizer {
+    randomize_room_id: bool,
+    players: HashMap<Vec<u8>, Vec<u8>>,
+    rooms: HashMap<Vec<u8>, Vec<u8>>,
+    stats: AnonymizeStats,
+}
+
+impl Anonymizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace room ids with random ids, the same id always gets the same replacement
+    pub fn with_randomized_room_id(mut self, enabled: bool) -> Self {
+        self.randomize_room_id = enabled;
+        self
+    }
+
+    pub fn stats(&self) -> &AnonymizeStats {
+        &self.stats
+    }
+
+    pub fn apply(&mut self, packet: &mut PacketInfo) {
+        for frame in &mut packet.data_pack.frames {
+            self.apply_frame(frame);
+        }
+    }
+
+    fn apply_frame(&mut self, frame: &mut DataFrame) {
+        let Some(message) = &mut frame.message else {
+            return;
+        };
+        let rewritten = match message {
+            data_frame::Message::InstantiateObject(obj) => {
+                let owner = self.replace_player(&mut obj.owner_id);
+                let target = match &mut obj.target {
+                    Some(instantiate_object::Target::PlayerId(id)) => self.replace_player(id),
+                    Some(instantiate_object::Target::RoomAll(room_all)) => {
+                        self.replace_room_all(room_all)
+                    }
+                    _ => false,
+                };
+                owner | target
+            }
+            data_frame::Message::UpdateObject(obj) => match &mut obj.target {
+                Some(update_object::Target::PlayerId(id)) => self.replace_player(id),
+                Some(update_object::Target::RoomAll(room_all)) => self.replace_room_all(room_all),
+                _ => false,
+            },
+            data_frame::Message::DestroyObject(obj) => match &mut obj.target {
+                Some(destroy_object::Target::PlayerId(id)) => self.replace_player(id),
+                Some(destroy_object::Target::RoomAll(room_all)) => self.replace_room_all(room_all),
+                _ => false,
+            },
+            data_frame::Message::Room(room) => self.replace_room(&mut room.id),
+            data_frame::Message::AuthorizeResponse(response) => {
+                let mut rewritten = self.replace_player(&mut response.player_id);
+                for room_id in &mut response.allowed_room_ids {
+                    rewritten |= self.replace_room(room_id);
+                }
+                rewritten
+            }
+            data_frame::Message::JoinRoomResponse(response) => match &mut response.room {
+                Some(room) => self.replace_room(&mut room.id),
+                None => false,
+            },
+        };
+        if rewritten {
+            *self
+                .stats
+                .rewritten_frames
+                .entry(MessageType::of(message))
+                .or_default() += 1;
+        }
+    }
+
+    // Empty ids carry nothing to hide and stay empty
+    fn replace_player(&mut self, id: &mut Vec<u8>) -> bool {
+        if id.is_empty() {
+            return false;
+        }
+        let next = self.players.len() + 1;
+        *id = self
+            .players
+            .entry(std::mem::take(id))
+            .or_insert_with(|| format!("player_{}", next).into_bytes())
+            .clone();
+        self.stats.players = self.players.len();
+        true
+    }
+
+    fn replace_room(&mut self, id: &mut Vec<u8>) -> bool {
+        if !self.randomize_room_id || id.is_empty() {
+            return false;
+        }
+        *id = self
+            .rooms
+            .entry(std::mem::take(id))
+            .or_insert_with(random_room_id)
+            .clone();
+        self.stats.rooms = self.rooms.len();
+        true
+    }
+
+    fn replace_room_all(&mut self, room_all: &mut RoomAll) -> bool {
+        self.replace_room(&mut room_all.room_id)
+    }
+}
+
+fn random_room_id() -> Vec<u8> {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes).into_bytes()
+}
+
+#[derive(Debug, Clone)]
+pub struct AnonymizeConfig {
+    /// Capture file or directory
+    pub input_path: PathBuf,
+    pub input_format: InputFormat,
+    /// Output file for a file input, output directory for a directory input
+    pub output_path: PathBuf,
+    pub randomize_room_id: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnonymizeSummary {
+    pub files: Vec<PathBuf>,
+    pub packets: usize,
+    pub stats: AnonymizeStats,
+}
+
+pub fn run_anonymize(config: &AnonymizeConfig) -> Result<AnonymizeSummary> {
+    let inputs = if config.input_path.is_dir() {
+        let files = collect_files(&config.input_path, FileSortKey::FilenameNumeric)?;
+        std::fs::create_dir_all(&config.output_path).with_context(|| {
+            format!(
+                "Failed to create output directory: {}",
+                config.output_path.display()
+            )
+        })?;
+        files
+            .into_iter()
+            .map(|file| {
+                let output = config
+                    .output_path
+                    .join(file.file_name().unwrap_or_default());
+                (file, output)
+            })
+            .collect()
+    } else if config.input_path.is_file() {
+        vec![(config.input_path.clone(), config.output_path.clone())]
+    } else {
+        return Err(anyhow!(
+            "Input path does not exist: {}",
+            config.input_path.display()
+        ));
+    };
+
+    let mut anonymizer = Anonymizer::new().with_randomized_room_id(config.randomize_room_id);
+    let mut summary = AnonymizeSummary {
+        files: Vec::with_capacity(inputs.len()),
+        packets: 0,
+        stats: AnonymizeStats::default(),
+    };
+    for (input, output) in inputs {
+        if same_file(&input, &output) {
+            return Err(anyhow!(
+                "Output would overwrite the input capture: {}",
+                input.display()
+            ));
+        }
+        summary.packets += anonymize_file(&input, &output, config.input_format, &mut anonymizer)?;
+        summary.files.push(output);
+    }
+    summary.stats = anonymizer.stats().clone();
+    Ok(summary)
+}
+
+// Helper: rewrite one capture file, returns the packets written
+fn anonymize_file(
+    input: &Path,
+    output: &Path,
+    input_format: InputFormat,
+    anonymizer: &mut Anonymizer,
+) -> Result<usize> {
+    let reader_factory = input_format.resolve(input)?.reader_factory()?;
+    let file =
+        File::open(input).with_context(|| format!("Failed to open file: {}", input.display()))?;
+    let mut reader = reader_factory(file);
+    let mut writer = BufWriter::new(
+        File::create(output)
+            .with_context(|| format!("Failed to create file: {}", output.display()))?,
+    );
+
+    let mut packets = 0;
+    while let Some(mut packet) = reader.read_packet()? {
+        anonymizer.apply(&mut packet);
+        writer.write_all(&packet.to_vec())?;
+        packets += 1;
+    }
+    writer.flush()?;
+    Ok(packets)
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::als::proto::define::{
+        AuthorizeResponse, DataPack, InstantiateObject, JoinRoomResponse, Room, UpdateObject,
+        data_pack,
+    };
+    use crate::als::proto::reader::{PacketReaderTrait, StandardPacketReader};
+    use chrono::{TimeZone, Utc};
+
+    fn packet(messages: Vec<data_frame::Message>) -> PacketInfo {
+        PacketInfo {
+            timestamp: Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+            data_pack: DataPack {
+                control: Some(data_pack::Control::Data(true)),
+                frames: messages
+                    .into_iter()
+                    .map(|message| DataFrame {
+                        message: Some(message),
+                    })
+                    .collect(),
+            },
+            raw_data: vec![],
+            source: None,
+        }
+    }
+
+    fn capture() -> Vec<PacketInfo> {
+        let room = || Room {
+            id: b"room-secret".to_vec(),
+            started_at: 1,
+            ended_at: 0,
+        };
+        vec![
+            packet(vec![
+                data_frame::Message::AuthorizeResponse(AuthorizeResponse {
+                    player_id: b"alice".to_vec(),
+                    role: 1,
+                    allowed_room_ids: vec![b"room-secret".to_vec()],
+                }),
+                data_frame::Message::JoinRoomResponse(JoinRoomResponse {
+                    room: Some(room()),
+                    joined_at: 1,
+                }),
+            ]),
+            packet(vec![
+                data_frame::Message::Room(room()),
+                data_frame::Message::InstantiateObject(InstantiateObject {
+                    target: Some(instantiate_object::Target::RoomAll(RoomAll {
+                        room_id: b"room-secret".to_vec(),
+                    })),
+                    object_id: 1,
+                    owner_id: b"bob".to_vec(),
+                    prefab_name: b"Character".to_vec(),
+                    init_data: vec![],
+                }),
+                data_frame::Message::UpdateObject(UpdateObject {
+                    target: Some(update_object::Target::PlayerId(b"alice".to_vec())),
+                    object_id: 1,
+                    method: 2,
+                    payload: vec![1, 2, 3],
+                }),
+            ]),
+        ]
+    }
+
+    #[test]
+    fn test_anonymizer() {
+        let mut packets = capture();
+        let mut anonymizer = Anonymizer::new();
+        for packet in &mut packets {
+            anonymizer.apply(packet);
+        }
+        let frames: Vec<_> = packets
+            .iter()
+            .flat_map(|p| p.data_pack.frames.iter())
+            .filter_map(|f| f.message.clone())
+            .collect();
+        let data_frame::Message::AuthorizeResponse(auth) = &frames[0] else {
+            panic!("expected AuthorizeResponse");
+        };
+        assert_eq!(auth.player_id, b"player_1");
+        // Room ids are kept unless randomized
+        assert_eq!(auth.allowed_room_ids, vec![b"room-secret".to_vec()]);
+        let data_frame::Message::InstantiateObject(obj) = &frames[3] else {
+            panic!("expected InstantiateObject");
+        };
+        assert_eq!(obj.owner_id, b"player_2");
+        let data_frame::Message::UpdateObject(update) = &frames[4] else {
+            panic!("expected UpdateObject");
+        };
+        assert_eq!(
+            update.target,
+            Some(update_object::Target::PlayerId(b"player_1".to_vec()))
+        );
+        assert_eq!(anonymizer.stats().players, 2);
+        assert_eq!(anonymizer.stats().rooms, 0);
+        assert_eq!(
+            anonymizer.stats().rewritten_frames.values().sum::<usize>(),
+            3
+        );
+
+        let mut packets = capture();
+        let mut anonymizer = Anonymizer::new().with_randomized_room_id(true);
+        for packet in &mut packets {
+            anonymizer.apply(packet);
+        }
+        let encoded: Vec<u8> = packets.iter().flat_map(|p| p.to_vec()).collect();
+        assert!(!encoded.windows(11).any(|w| w == b"room-secret"));
+        assert!(!encoded.windows(5).any(|w| w == b"alice"));
+        assert_eq!(anonymizer.stats().rooms, 1);
+        assert_eq!(
+            anonymizer.stats().rewritten_frames.values().sum::<usize>(),
+            5
+        );
+    }
+
+    #[test]
+    fn test_run_anonymize() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let input_dir = dir.join("capture");
+        std::fs::create_dir_all(&input_dir).unwrap();
+        for (index, packet) in capture().iter().enumerate() {
+            std::fs::write(
+                input_dir.join(format!("data_{}.bin", index)),
+                packet.to_vec(),
+            )
+            .unwrap();
+        }
+
+        let config = AnonymizeConfig {
+            input_path: input_dir.clone(),
+            input_format: InputFormat::Standard(None),
+            output_path: dir.join("clean"),
+            randomize_room_id: true,
+        };
+        let summary = run_anonymize(&config).unwrap();
+        assert_eq!(summary.files.len(), 2);
+        assert_eq!(summary.packets, 2);
+
+        let rooms: Vec<Vec<u8>> = summary
+            .files
+            .iter()
+            .flat_map(|path| {
+                StandardPacketReader::new(File::open(path).unwrap())
+                    .read_packets()
+                    .unwrap()
+            })
+            .flat_map(|packet| packet.data_pack.frames)
+            .filter_map(|frame| match frame.message {
+                Some(data_frame::Message::Room(room)) => Some(room.id),
+                Some(data_frame::Message::JoinRoomResponse(response)) => {
+                    response.room.map(|room| room.id)
+                }
+                _ => None,
+            })
+            .collect();
+        // The same random id across files
+        assert_eq!(rooms.len(), 2);
+        assert_eq!(rooms[0], rooms[1]);
+        assert_ne!(rooms[0], b"room-secret");
+
+        let in_place = AnonymizeConfig {
+            output_path: input_dir.clone(),
+            ..config
+        };
+        assert!(run_anonymize(&in_place).is_err());
+    }
+}
@@ -0,0 +1,263 @@
+//! Conversion output written as a single archive instead of a directory
+//!
+//! Entries are stored without compression and appended as they are produced, so an
+//! archive needs no packaging step after the conversion. Zip archives switch to zip64
+//! when an entry or the archive outgrows 4 GiB, tar archives use GNU headers.
+
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Datelike, FixedOffset, Timelike};
+use std::fs::File;
+use std::io::{BufWriter, Seek, Write};
+use std::path::{Path, PathBuf};
+use zip::CompressionMethod;
+use zip::write::SimpleFileOptions;
+
+/// Where the files of a converted part go
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputTarget {
+    #[default]
+    Directory,
+    Zip,
+    Tar,
+}
+
+impl std::str::FromStr for OutputTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "dir" | "directory" => Ok(Self::Directory),
+            "zip" => Ok(Self::Zip),
+            "tar" => Ok(Self::Tar),
+            _ => Err(anyhow!("Unsupported output target: {}", s)),
+        }
+    }
+}
+
+impl OutputTarget {
+    /// File extension of the archive, `None` for directory output
+    pub fn extension(self) -> Option<&'static str> {
+        match self {
+            Self::Directory => None,
+            Self::Zip => Some("zip"),
+            Self::Tar => Some("tar"),
+        }
+    }
+
+    /// `path` without the archive extension, so part suffixes go before it
+    pub fn strip_extension(self, path: &Path) -> PathBuf {
+        match self.extension() {
+            Some(ext)
+                if path
+                    .extension()
+                    .is_some_and(|e| e.eq_ignore_ascii_case(ext)) =>
+            {
+                path.with_extension("")
+            }
+            _ => path.to_path_buf(),
+        }
+    }
+
+    /// Path written for the output dir `dir`, `dir.zip` / `dir.tar` for archives
+    pub fn output_path(self, dir: &Path) -> PathBuf {
+        match self.extension() {
+            Some(ext) => {
+                let mut name = dir.as_os_str().to_os_string();
+                name.push(".");
+                name.push(ext);
+                PathBuf::from(name)
+            }
+            None => dir.to_path_buf(),
+        }
+    }
+}
+
+/// Receives the files of one converted part, in the order they are produced
+pub(crate) enum PartWriter {
+    Directory(PathBuf),
+    Zip(Box<zip::ZipWriter<BufWriter<File>>>, SimpleFileOptions),
+    Tar(tar::Builder<BufWriter<File>>, u64),
+}
+
+impl PartWriter {
+    /// Create the output dir, or the archive file for `dir`
+    pub fn create(
+        target: OutputTarget,
+        dir: &Path,
+        modified: DateTime<FixedOffset>,
+    ) -> Result<Self> {
+        if target == OutputTarget::Directory {
+            std::fs::create_dir_all(dir)?;
+            return Ok(Self::Directory(dir.to_path_buf()));
+        }
+        let path = target.output_path(dir);
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file =
+            File::create(&path).with_context(|| format!("Failed to create archive: {:?}", path))?;
+        let writer = BufWriter::new(file);
+        Ok(match target {
+            OutputTarget::Zip => {
+                Self::Zip(Box::new(zip::ZipWriter::new(writer)), zip_options(modified))
+            }
+            _ => Self::Tar(
+                tar::Builder::new(writer),
+                modified.timestamp().max(0) as u64,
+            ),
+        })
+    }
+
+    pub fn add(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        match self {
+            Self::Directory(dir) => {
+                let path = dir.join(name);
+                std::fs::write(&path, data)
+                    .with_context(|| format!("Failed to create file: {:?}", path))
+            }
+            Self::Zip(zip, options) => add_zip_entry(zip, *options, name, data),
+            Self::Tar(tar, mtime) => add_tar_entry(tar, *mtime, name, data),
+        }
+    }
+
+    pub fn finish(self) -> Result<()> {
+        let mut writer = match self {
+            Self::Directory(_) => return Ok(()),
+            Self::Zip(zip, _) => zip.finish()?,
+            Self::Tar(tar, _) => tar.into_inner()?,
+        };
+        writer.flush().map_err(Into::into)
+    }
+}
+
+/// Stored entries modified at `modified`, clamped to the DOS date range
+fn zip_options(modified: DateTime<FixedOffset>) -> SimpleFileOptions {
+    let last_modified = zip::DateTime::from_date_and_time(
+        modified.year().clamp(1980, 2107) as u16,
+        modified.month() as u8,
+        modified.day() as u8,
+        modified.hour() as u8,
+        modified.minute() as u8,
+        modified.second() as u8,
+    )
+    .unwrap_or_default();
+    SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Stored)
+        .last_modified_time(last_modified)
+        .unix_permissions(0o644)
+}
+
+fn add_zip_entry<W: Write + Seek>(
+    zip: &mut zip::ZipWriter<W>,
+    options: SimpleFileOptions,
+    name: &str,
+    data: &[u8],
+) -> Result<()> {
+    let options = options.large_file(data.len() as u64 >= u32::MAX as u64);
+    zip.start_file(name, options)
+        .with_context(|| format!("Failed to add zip entry: {}", name))?;
+    zip.write_all(data)?;
+    Ok(())
+}
+
+fn add_tar_entry<W: Write>(
+    tar: &mut tar::Builder<W>,
+    mtime: u64,
+    name: &str,
+    data: &[u8],
+) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(mtime);
+    header.set_entry_type(tar::EntryType::Regular);
+    tar.append_data(&mut header, name, data)
+        .with_context(|| format!("Failed to add tar entry: {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::io::{Cursor, Read};
+
+    fn modified() -> DateTime<FixedOffset> {
+        FixedOffset::east_opt(9 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2025, 8, 21, 19, 30, 42)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_zip_entries() {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip_options(modified());
+        add_zip_entry(&mut zip, options, "index.m3u8", b"#EXTM3U8\n").unwrap();
+        add_zip_entry(&mut zip, options, "segment_00000.ts", &[1, 2, 3]).unwrap();
+        let bytes = zip.finish().unwrap().into_inner();
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(archive.len(), 2);
+        let mut entry = archive.by_name("segment_00000.ts").unwrap();
+        assert_eq!(entry.compression(), CompressionMethod::Stored);
+        let modified = entry.last_modified().unwrap();
+        assert_eq!(
+            (modified.year(), modified.hour(), modified.second()),
+            (2025, 19, 42)
+        );
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).unwrap();
+        assert_eq!(data, [1, 2, 3]);
+    }
+
+    #[test]
+    fn test_tar_entries() {
+        let mut tar = tar::Builder::new(Vec::new());
+        let mtime = modified().timestamp() as u64;
+        add_tar_entry(&mut tar, mtime, "segment_00000.ts", &[7; 600]).unwrap();
+        // names past the 100 bytes of a plain header use the GNU long name extension
+        let long_name = format!("{}.ts", "a".repeat(120));
+        add_tar_entry(&mut tar, mtime, &long_name, b"long").unwrap();
+        let bytes = tar.into_inner().unwrap();
+
+        let mut archive = tar::Archive::new(Cursor::new(bytes));
+        let entries: Vec<_> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let mut data = Vec::new();
+                entry.read_to_end(&mut data).unwrap();
+                let header = entry.header();
+                assert_eq!(header.mtime().unwrap(), mtime);
+                assert_eq!(header.mode().unwrap(), 0o644);
+                (entry.path().unwrap().display().to_string(), data)
+            })
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                ("segment_00000.ts".to_string(), vec![7; 600]),
+                (long_name, b"long".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_output_target_paths() {
+        let target: OutputTarget = "zip".parse().unwrap();
+        assert_eq!(
+            target.strip_extension(Path::new("out/room.ZIP")),
+            PathBuf::from("out/room")
+        );
+        assert_eq!(
+            target.output_path(Path::new("out/room_001")),
+            PathBuf::from("out/room_001.zip")
+        );
+        assert_eq!(
+            OutputTarget::Directory.output_path(Path::new("out")),
+            PathBuf::from("out")
+        );
+        assert!("rar".parse::<OutputTarget>().is_err());
+    }
+}
@@ -3,19 +3,28 @@ use chrono::{DateTime, Utc};
 use hound::WavSpec;
 use std::{
     collections::{HashMap, HashSet},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
-use super::proto::{PacketInfo, define::data_frame};
+use super::proto::{PacketInfo, define::data_frame, extension::InstantiateObjectExt};
 
 pub struct AudioRawPacket {
     pub timestamp: DateTime<Utc>,
     pub payload: Vec<u8>,
 }
 
+/// Object treated as an audio source, i.e. its prefab name contains `Voice`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioSource {
+    pub object_id: i32,
+    pub prefab_name: String,
+}
+
 pub struct AudioBuilder {
     /// object id sets
     pub channel_sets: HashSet<i32>,
+    /// audio sources in the order they were instantiated
+    pub sources: Vec<AudioSource>,
     /// audio raw packets
     pub audio_packets: HashMap<i32, Vec<AudioRawPacket>>,
     output_dir: Option<String>,
@@ -25,6 +34,7 @@ impl AudioBuilder {
     pub fn new(output_dir: Option<String>) -> Self {
         Self {
             channel_sets: HashSet::new(),
+            sources: Vec::new(),
             audio_packets: HashMap::new(),
             output_dir,
         }
@@ -69,10 +79,15 @@ impl AudioBuilder {
 
     pub fn handle_instantiate_audio(&mut self, obj: &data_frame::Message) {
         if let data_frame::Message::InstantiateObject(obj) = obj {
-            let name = String::from_utf8_lossy(&obj.prefab_name);
+            let name = obj.prefab_name_lossy();
             if name.contains("Voice") {
                 let object_id = obj.object_id;
-                self.add_source(object_id);
+                if self.channel_sets.insert(object_id) {
+                    self.sources.push(AudioSource {
+                        object_id,
+                        prefab_name: name.clone(),
+                    });
+                }
                 tracing::trace!(
                     "Found audio source with object id: {} and prefab name: {}",
                     object_id,
@@ -102,7 +117,7 @@ impl AudioBuilder {
         }
     }
 
-    pub fn write(&mut self) -> Result<()> {
+    pub fn write(&mut self) -> Result<Vec<PathBuf>> {
         if let Some(output_dir) = self.output_dir.clone() {
             self.write_to_file(&output_dir)
         } else {
@@ -110,8 +125,10 @@ impl AudioBuilder {
         }
     }
 
-    pub fn write_to_file<P: AsRef<Path>>(&mut self, output_dir: P) -> Result<()> {
+    /// Decode the buffered packets into one `audio_<object_id>.wav` per source, returns the written files
+    pub fn write_to_file<P: AsRef<Path>>(&mut self, output_dir: P) -> Result<Vec<PathBuf>> {
         std::fs::create_dir_all(&output_dir)?;
+        let mut files = Vec::new();
         // handle things here
         for (object_id, packets) in &self.audio_packets {
             let mut dec = opus::Decoder::new(48000, opus::Channels::Stereo)?;
@@ -150,11 +167,14 @@ impl AudioBuilder {
             }
             writer.finalize()?;
             tracing::info!("Wrote audio file: {:?}", file_path);
+            files.push(file_path);
         }
         // clear cache
         self.channel_sets.clear();
+        self.sources.clear();
         self.audio_packets.clear();
 
-        Ok(())
+        files.sort();
+        Ok(files)
     }
 }
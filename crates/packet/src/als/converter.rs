@@ -6,13 +6,27 @@ use super::proto::{
     },
     reader::PacketReaderTrait,
 };
+use crate::als::archive::{OutputTarget, PartWriter};
 use crate::als::proto::{
+    analyzer::KeepaliveFilter,
+    application::{RoomInfo, extract_room_info},
     extension::{UpdateObjectExt, prefab_name},
-    reader::{LegacyPacketReader, MixedPacketReader, PacketsBufferReader, StandardPacketReader},
+    reader::{
+        DedupPacketReader, FileReadSummary, InputFormat, OrderedPacketReader, OutOfOrderPolicy,
+        PacketClock, PacketsBufferReader, is_stdin, open_stream,
+    },
 };
+use crate::als::provenance::{Adjustments, PROVENANCE_FILE, Provenance, ProvenanceRecord};
+use crate::als::template::{PathTemplate, Placeholder, TemplateVars, unique_dir_by};
 use anyhow::{Context, Ok, Result, anyhow};
-use chrono::{DateTime, FixedOffset, TimeDelta, Utc};
+use chrono::{DateTime, FixedOffset, SecondsFormat, TimeDelta, Utc};
+use linkura_common::log::{live_span, record_room_id};
+use std::fmt;
 use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+use std::time::Instant;
 use std::{
     cmp::Ordering,
     fs::{DirEntry, File},
@@ -24,7 +38,9 @@ use std::{
 };
 
 #[cfg(feature = "audio")]
-use super::audio::AudioBuilder;
+use super::audio::{AudioBuilder, AudioSource};
+#[cfg(feature = "audio")]
+use crate::als::proto::reader::StandardPacketReader;
 
 #[derive(PartialEq, Eq, Debug)]
 enum AlsConverterStateMachine {
@@ -36,10 +52,150 @@ enum AlsConverterStateMachine {
     End,
 }
 
+/// Options for `AlsConverter::convert_mixed_to_standard`
+#[derive(Debug, Clone)]
+pub struct AlsConverterConfig {
+    /// Input packet format, `Auto` sniffs the first input file
+    pub input_format: InputFormat,
+    /// Time shift applied to all packets, microseconds
+    pub timeshift: i64,
+    pub split: bool,
+    pub start_time: Option<String>,
+    pub data_start_time: Option<String>,
+    pub data_end_time: Option<String>,
+    pub metadata_path: Option<String>,
+    /// Rebuild timestamps from DateTimeReceiver dates, spreading packets by MusicBroadcaster
+    /// audio packets. Use when the capture has those objects and recorded times are unreliable.
+    pub auto_timestamp: bool,
+    /// Keep recorded timestamps but make them monotonic: small backward jitter is clamped and
+    /// large backward jumps (reconnect seams) shift the rest of the capture forward.
+    /// Works without DateTimeReceiver/MusicBroadcaster, applied after `auto_timestamp`.
+    pub smooth_timestamps: bool,
+    /// Write `events.vtt` with InstantiateObject cues next to `index.m3u8`
+    pub write_events_vtt: bool,
+    /// Only keep object frames whose `object_id` is in this set
+    pub include_object_ids: Option<HashSet<i32>>,
+    /// Drop object frames whose `object_id` is in this set
+    pub exclude_object_ids: Option<HashSet<i32>>,
+    /// Replace the data room when a later `JoinRoomResponse` arrives (mid-stream re-join)
+    pub reset_on_rejoin: bool,
+    /// Write into an existing templated output dir instead of appending `-1`, `-2`, ...
+    pub force_output: bool,
+    /// Repeat all current object states at the start of every segment,
+    /// so each segment can be played back on its own
+    pub embed_full_state_in_segments: bool,
+    /// Move the FixedCamera InstantiateObject ahead of Cameraman in the initial dataframes.
+    /// The player sets up cameras in instantiation order and expects the fixed camera first,
+    /// captures that recorded them the other way round start on the wrong view.
+    pub swap_camera_order: bool,
+    /// Drop packets repeated across overlapping input files within this window,
+    /// `None` keeps every packet
+    pub dedup_window: Option<TimeDelta>,
+    /// Append `filename,file_size_bytes,packets_processed,processing_time_ms` here
+    /// as each input file completes
+    pub timing_log_path: Option<PathBuf>,
+    /// Keepalive packets dropped before conversion
+    pub keepalive_filter: KeepaliveFilter,
+    /// Room id written instead of the captured one, in `RoomAll` targets and metadata
+    pub room_id_override: Option<String>,
+    /// Write `provenance.bin` tracing each output packet back to its input file and offset
+    pub provenance: bool,
+    /// Write each part as a directory (default) or as a single zip/tar archive
+    pub output_target: OutputTarget,
+    /// Timestamps of legacy captures, which record none. Defaults to the time each packet
+    /// is read, a fixed clock makes the output reproducible.
+    pub packet_clock: PacketClock,
+    /// Prepended to the segment names in `index.m3u8`, so a playlist uploaded to a CDN
+    /// finds its segments there. Ends with '/', see [`parse_segment_base_url`].
+    pub segment_base_url: Option<String>,
+    /// Packets stamped earlier than a packet read before them, by at most `reorder_window`
+    pub out_of_order: OutOfOrderPolicy,
+    /// Larger backward jumps are reconnect seams and kept, see `smooth_timestamps`
+    pub reorder_window: TimeDelta,
+}
+
+impl Default for AlsConverterConfig {
+    fn default() -> Self {
+        Self {
+            input_format: InputFormat::Auto,
+            timeshift: 0,
+            split: false,
+            start_time: None,
+            data_start_time: None,
+            data_end_time: None,
+            metadata_path: None,
+            auto_timestamp: false,
+            smooth_timestamps: false,
+            write_events_vtt: false,
+            include_object_ids: None,
+            exclude_object_ids: None,
+            reset_on_rejoin: false,
+            force_output: false,
+            embed_full_state_in_segments: true,
+            swap_camera_order: true,
+            dedup_window: None,
+            timing_log_path: None,
+            keepalive_filter: KeepaliveFilter::default(),
+            room_id_override: None,
+            provenance: false,
+            output_target: OutputTarget::Directory,
+            packet_clock: PacketClock::WallClock,
+            segment_base_url: None,
+            out_of_order: OutOfOrderPolicy::Reorder,
+            reorder_window: DEFAULT_REORDER_WINDOW,
+        }
+    }
+}
+
+/// Result of `AlsConverter::extract_audio_from_standard`
+#[cfg(feature = "audio")]
+#[derive(Debug, Clone, Default)]
+pub struct AudioExtraction {
+    /// Written wav files, sorted by path
+    pub files: Vec<PathBuf>,
+    /// Objects treated as audio sources
+    pub sources: Vec<AudioSource>,
+}
+
+/// Receives the path of every file of a converted part as soon as it is written,
+/// `index.m3u8` last. For archive targets only the finished archive is reported.
+#[derive(Clone)]
+pub struct SegmentObserver(Arc<dyn Fn(&Path) + Send + Sync>);
+
+impl SegmentObserver {
+    pub fn new(observer: impl Fn(&Path) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(observer))
+    }
+
+    fn notify(&self, path: &Path) {
+        (self.0)(path)
+    }
+}
+
+impl fmt::Debug for SegmentObserver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SegmentObserver")
+    }
+}
+
+/// The conversion was cancelled, the output written so far is marked `partial` in `index.md`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConversionCancelledError;
+
+impl fmt::Display for ConversionCancelledError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Conversion cancelled, partial output written")
+    }
+}
+
+impl std::error::Error for ConversionCancelledError {}
+
 pub struct AlsConverter {
     #[allow(unused)]
     segment_duration: u64, // microseconds, default 10 seconds
     use_audio_processing: bool, // 是否启用音频处理
+    segment_observer: Option<SegmentObserver>,
+    cancel: Option<Arc<AtomicBool>>,
 }
 
 impl Default for AlsConverter {
@@ -47,6 +203,8 @@ impl Default for AlsConverter {
         Self {
             segment_duration: 10_000_000, // 10 seconds in microseconds
             use_audio_processing: false,
+            segment_observer: None,
+            cancel: None,
         }
     }
 }
@@ -56,10 +214,27 @@ impl AlsConverter {
         Self {
             segment_duration: segment_duration_seconds * 1_000_000,
             use_audio_processing,
+            segment_observer: None,
+            cancel: None,
         }
     }
 
-    fn get_file_entries(
+    /// Stream the output: each segment is written as soon as the next one starts and
+    /// reported to `observer`, instead of writing the whole part at its end.
+    /// Directory output only, archives are reported once finished.
+    pub fn with_segment_observer(mut self, observer: SegmentObserver) -> Self {
+        self.segment_observer = Some(observer);
+        self
+    }
+
+    /// Stop reading once `cancel` is set. The packets read so far are written as usual
+    /// and marked partial, then the conversion fails with [`ConversionCancelledError`].
+    pub fn with_cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    pub(crate) fn get_file_entries(
         input_dir: &Path,
         ext: Option<&str>,
     ) -> Result<std::collections::VecDeque<DirEntry>> {
@@ -102,51 +277,189 @@ impl AlsConverter {
         Ok(std::collections::VecDeque::from(input_files))
     }
 
+    /// Default output directory `<room_id>_<start>` under the current directory,
+    /// read from the first input file. Falls back to `output` when it has no Room frame,
+    /// or when reading stdin which cannot be read twice.
+    /// `room_id_override` replaces the captured room id in the name
+    pub fn default_output_dir<P: AsRef<Path>>(
+        input_dir: P,
+        input_format: InputFormat,
+        room_id_override: Option<&str>,
+    ) -> Result<PathBuf> {
+        if is_stdin(input_dir.as_ref()) {
+            return Ok(PathBuf::from("output"));
+        }
+        let Some(room_info) = Self::first_room_info(input_dir.as_ref(), input_format)? else {
+            return Ok(PathBuf::from("output"));
+        };
+        // live start time, the first packet when the room has none
+        let start = DateTime::<Utc>::from_timestamp_micros(room_info.started_at)
+            .filter(|_| room_info.started_at > 0)
+            .unwrap_or(room_info.first_timestamp)
+            .with_timezone(&FixedOffset::east_opt(9 * 3600).unwrap());
+        Ok(PathBuf::from(sanitize_path_component(&format!(
+            "{}_{}",
+            room_id_override.unwrap_or(&room_info.room_id),
+            start.format("%Y%m%d_%H%M%S")
+        ))))
+    }
+
+    /// `output` with `{live_id}` filled in from the room id of the first input file, other
+    /// placeholders are left as they are. Fails when the template uses `{live_id}` and the
+    /// capture has no Room frame, or is read from stdin which cannot be read twice
+    pub fn fill_live_id<P: AsRef<Path>>(
+        output: &str,
+        input_dir: P,
+        input_format: InputFormat,
+    ) -> Result<String> {
+        let template = PathTemplate::from_str(output)?;
+        if !template.uses(Placeholder::LiveId) {
+            return Ok(output.to_string());
+        }
+        let input_dir = input_dir.as_ref();
+        let room_info = if is_stdin(input_dir) {
+            None
+        } else {
+            Self::first_room_info(input_dir, input_format)?
+        };
+        let room_info = room_info.ok_or_else(|| {
+            anyhow!(
+                "{{live_id}} needs the room of the capture, {} has no Room frame",
+                input_dir.display()
+            )
+        })?;
+        Ok(template.fill(Placeholder::LiveId, &room_info.room_id))
+    }
+
+    fn first_room_info(input_dir: &Path, input_format: InputFormat) -> Result<Option<RoomInfo>> {
+        let file_entries = Self::get_file_entries(input_dir, None)?;
+        extract_room_info(&file_entries[0].path(), input_format)
+    }
+
     pub fn convert_mixed_to_standard<P: AsRef<Path>>(
         &self,
         input_dir: P,
         output_dir: P,
-        convert_type: &str,
-        // todo: config struct
-        timeshift: i64,
-        split: bool,
-        start_time: Option<String>,
-        data_start_time: Option<String>,
-        data_end_time: Option<String>,
-        metadata_path: Option<String>,
-        auto_timestamp: bool,
+        config: &AlsConverterConfig,
     ) -> Result<()> {
         let input_dir = input_dir.as_ref();
         let output_dir = output_dir.as_ref();
+        // room id is recorded once the capture's Room arrives
+        let _live = live_span(None, config.room_id_override.as_deref()).entered();
+        if self.use_audio_processing
+            && PathTemplate::from_str(&output_dir.to_string_lossy())?.is_templated()
+        {
+            return Err(anyhow!("Output path templates are not supported for audio"));
+        }
+        if self.use_audio_processing && config.output_target != OutputTarget::Directory {
+            return Err(anyhow!("Archive output is not supported for audio"));
+        }
         let mut context = ConversionContext::new(
-            timeshift,
-            split,
-            start_time,
-            data_start_time,
-            data_end_time,
-            metadata_path,
+            config,
             output_dir.to_str().map(String::from),
             self.use_audio_processing,
-            auto_timestamp,
         );
+        context.segment_builder.segment_observer = self.segment_observer.clone();
+        let started_at = Instant::now();
+        if is_stdin(input_dir) {
+            if config.provenance {
+                context.segment_builder.provenance_files =
+                    Some(vec![input_dir.display().to_string()]);
+            }
+            if config.timing_log_path.is_some() {
+                tracing::warn!("Timing log is not written when reading stdin");
+            }
+            let (input_format, reader) = open_stream(std::io::stdin(), config.input_format)?;
+            tracing::info!("Input format: {:?}", input_format);
+            self.process_input(&mut context, reader, config)?;
+            self.finalize_conversion(&mut context, output_dir)?;
+            tracing::info!("Total processing time: {:.2?}", started_at.elapsed());
+            return Self::check_cancelled(&context);
+        }
+
         let file_entries = Self::get_file_entries(input_dir, None)?;
-        let mut packet_buffer = if convert_type == "als-legacy" {
-            PacketsBufferReader::new(file_entries, |file| LegacyPacketReader::boxed(file))
-        } else {
-            PacketsBufferReader::new(file_entries, |file| MixedPacketReader::boxed(file))
-        };
+        if config.provenance {
+            context.segment_builder.provenance_files = Some(
+                file_entries
+                    .iter()
+                    .map(|entry| entry.path().display().to_string())
+                    .collect(),
+            );
+        }
+        let input_format = config.input_format.resolve(&file_entries[0].path())?;
+        tracing::info!("Input format: {:?}", input_format);
+        let mut packet_buffer =
+            PacketsBufferReader::new(file_entries, input_format.reader_factory()?);
+        if let Some(timing_log_path) = &config.timing_log_path {
+            let mut timing_log = TimingLog::create(timing_log_path)?;
+            packet_buffer = packet_buffer.with_file_observer(move |summary| {
+                if let Err(e) = timing_log.write(summary) {
+                    tracing::warn!("Failed to write timing log: {:#}", e);
+                }
+            });
+        }
 
-        self.process_all_packets(&mut context, &mut packet_buffer)?;
+        self.process_input(&mut context, packet_buffer, config)?;
         self.finalize_conversion(&mut context, output_dir)?;
+        tracing::info!("Total processing time: {:.2?}", started_at.elapsed());
+        Self::check_cancelled(&context)
+    }
+
+    // Helper: fail a conversion whose partial output was written after a cancel
+    fn check_cancelled(context: &ConversionContext) -> Result<()> {
+        if context.segment_builder.partial {
+            return Err(ConversionCancelledError.into());
+        }
         Ok(())
     }
 
+    // Helper: process every packet of `reader` in timestamp order, dropping duplicates
+    // when configured
+    fn process_input<R: PacketReaderTrait>(
+        &self,
+        context: &mut ConversionContext,
+        mut reader: R,
+        config: &AlsConverterConfig,
+    ) -> Result<()> {
+        reader.set_clock(config.packet_clock.clone());
+        match config.dedup_window {
+            Some(window) => {
+                let mut reader = OrderedPacketReader::new(
+                    DedupPacketReader::new(reader, window),
+                    config.out_of_order,
+                    config.reorder_window,
+                );
+                self.process_all_packets(context, &mut reader)?;
+                tracing::info!(
+                    "Removed {} duplicate packets",
+                    reader.get_ref().duplicates()
+                );
+                log_out_of_order(&reader, config.out_of_order);
+            }
+            None => {
+                let mut reader =
+                    OrderedPacketReader::new(reader, config.out_of_order, config.reorder_window);
+                self.process_all_packets(context, &mut reader)?;
+                log_out_of_order(&reader, config.out_of_order);
+            }
+        }
+        if context.keepalive_dropped > 0 {
+            tracing::info!("Removed {} keepalive packets", context.keepalive_dropped);
+        }
+        Ok(())
+    }
+
+    /// Decode the voice streams of a standard (`.ts`) capture in `input_dir` to wav files
+    ///
+    /// Every object whose prefab name contains `Voice` is an audio source and is written to
+    /// `output_dir/audio_<object_id>.wav`. Fails when the capture can not be read or a file
+    /// can not be written.
     #[cfg(feature = "audio")]
-    pub fn extract_audio_from_standard<P: AsRef<Path>>(
+    pub fn extract_audio_from_standard<P: AsRef<Path>, Q: AsRef<Path>>(
         &self,
         input_dir: P,
-        output_dir: P,
-    ) -> Result<()> {
+        output_dir: Q,
+    ) -> Result<AudioExtraction> {
         let input_dir = input_dir.as_ref();
         let output_dir = output_dir.as_ref();
         // Process each audio file in the input directory
@@ -158,18 +471,39 @@ impl AlsConverter {
         while let Some(packet) = packet_buffer.read_packet()? {
             audio_builder.handle_audio_packet(&packet);
         }
-        audio_builder.write_to_file(output_dir).unwrap_or_else(|e| {
-            tracing::error!("Failed to write audio files: {:?}", e);
-        });
-        Ok(())
+        let sources = audio_builder.sources.clone();
+        for source in &sources {
+            tracing::info!(
+                "Audio source: object id {} ({})",
+                source.object_id,
+                source.prefab_name
+            );
+        }
+        let files = audio_builder
+            .write_to_file(output_dir)
+            .with_context(|| format!("Failed to write audio files to {}", output_dir.display()))?;
+        Ok(AudioExtraction { files, sources })
     }
 
     fn process_all_packets(
         &self,
         context: &mut ConversionContext,
-        packet_buffer: &mut PacketsBufferReader,
+        packet_buffer: &mut dyn PacketReaderTrait,
     ) -> Result<()> {
         while let Some(packet_info) = packet_buffer.read_packet()? {
+            if self
+                .cancel
+                .as_ref()
+                .is_some_and(|cancel| cancel.load(AtomicOrdering::Relaxed))
+            {
+                tracing::info!("Conversion cancelled, writing the packets read so far");
+                context.segment_builder.partial = true;
+                break;
+            }
+            if context.keepalive_filter.drops(&packet_info) {
+                context.keepalive_dropped += 1;
+                continue;
+            }
             let end = context.process_packet(packet_info)?;
             if end {
                 break;
@@ -186,11 +520,16 @@ impl AlsConverter {
         tracing::debug!("All packets processed, writing final segment if exists.");
         if self.use_audio_processing {
             #[cfg(feature = "audio")]
-            context.audio_builder.write_to_file(output_dir)?;
+            context
+                .audio_builder
+                .write_to_file(output_dir)
+                .with_context(|| {
+                    format!("Failed to write audio files to {}", output_dir.display())
+                })?;
             #[cfg(not(feature = "audio"))]
             unreachable!("Audio feature is not enabled");
         } else {
-            if context.auto_timestamp {
+            if context.buffer_packets() {
                 context.handle_packetinfo_buffer()?;
             }
             context.segment_builder.write_to_file(
@@ -201,21 +540,93 @@ impl AlsConverter {
         }
         Ok(())
     }
+
+    /// Segment data packets of an already converted replay again and write them to `output_dir`
+    ///
+    /// `packets` are the replay's object packets without segment headers, in timestamp
+    /// order. Segment headers, durations and `index.md` are rebuilt as in a conversion.
+    pub(crate) fn write_data_packets(
+        config: &AlsConverterConfig,
+        room: Room,
+        packets: Vec<PacketInfo>,
+        output_dir: &Path,
+    ) -> Result<()> {
+        if packets.is_empty() {
+            return Err(anyhow!("No data packets to write"));
+        }
+        let mut context =
+            ConversionContext::new(config, output_dir.to_str().map(String::from), false);
+        context.data_room = room;
+        context.packetinfo_buffer = packets;
+        context.handle_packetinfo_buffer()?;
+        context.segment_builder.write_to_file(
+            output_dir,
+            context.data_room.started_at,
+            &context.data_room.id,
+        )?;
+        Ok(())
+    }
+}
+
+/// Per-file CSV written line by line, so it survives a crashed conversion
+struct TimingLog {
+    writer: BufWriter<File>,
+}
+
+impl TimingLog {
+    const HEADER: &'static str = "filename,file_size_bytes,packets_processed,processing_time_ms";
+
+    fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create timing log {}", path.display()))?;
+        let mut log = Self {
+            writer: BufWriter::new(file),
+        };
+        writeln!(log.writer, "{}", Self::HEADER)?;
+        log.writer.flush()?;
+        Ok(log)
+    }
+
+    fn write(&mut self, summary: &FileReadSummary) -> Result<()> {
+        let filename = summary
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let size = summary
+            .path
+            .metadata()
+            .map(|metadata| metadata.len().to_string())
+            .unwrap_or_default();
+        writeln!(
+            self.writer,
+            "{},{},{},{}",
+            filename,
+            size,
+            summary.packets,
+            summary.elapsed.as_millis()
+        )?;
+        self.writer.flush()?;
+        Ok(())
+    }
 }
 
 #[derive(Debug, Default)]
 struct Segment {
     number: u32,
-    duration: f64, // seconds
+    duration: TimeDelta,
     packets: Vec<PacketInfo>,
+    /// Already streamed out, `packets` were released
+    written: bool,
 }
 
 impl Segment {
     pub fn new(sequence: u32) -> Self {
         Segment {
             number: sequence,
-            duration: 0.0,
+            duration: TimeDelta::zero(),
             packets: Vec::new(),
+            written: false,
         }
     }
 
@@ -225,6 +636,15 @@ impl Segment {
     }
 }
 
+/// Lifetime of an instantiated object, used for events.vtt
+#[derive(Debug)]
+struct ObjectEvent {
+    object_id: i32,
+    prefab_name: String,
+    started_at: DateTime<Utc>,
+    ended_at: Option<DateTime<Utc>>,
+}
+
 #[derive(Debug, Default)]
 struct SegmentBuilder {
     current_sequence: u32,
@@ -233,6 +653,29 @@ struct SegmentBuilder {
     output_dir: Option<String>,
     part_count: u32,
     timeshift: i64,
+    events: Vec<ObjectEvent>,
+    /// `{date}`/`{time}` of templated output dirs
+    created_at: DateTime<FixedOffset>,
+    force_output: bool,
+    output_target: OutputTarget,
+    /// Add `events.vtt` to every part
+    write_events_vtt: bool,
+    /// Prefix of the segment URIs in `index.m3u8`
+    segment_base_url: Option<String>,
+    /// Input files of the conversion, `provenance.bin` is written when set
+    provenance_files: Option<Vec<String>>,
+    /// Timestamp rewrites applied to every captured packet
+    adjustments: Adjustments,
+    /// Streams finished segments to the part dir, see `AlsConverter::with_segment_observer`
+    segment_observer: Option<SegmentObserver>,
+    /// Part dir of the current part once a segment was streamed to it
+    streamed_dir: Option<PathBuf>,
+    /// Provenance records of the streamed segments
+    streamed_provenance: Vec<ProvenanceRecord>,
+    /// First packet timestamp of the part, kept when its segment is streamed out
+    first_timestamp: Option<DateTime<Utc>>,
+    /// The conversion was cancelled, `index.md` is marked `partial`
+    partial: bool,
 }
 
 impl SegmentBuilder {
@@ -244,7 +687,49 @@ impl SegmentBuilder {
             output_dir,
             part_count: 0,
             timeshift,
+            events: Vec::new(),
+            created_at: chrono::Local::now().fixed_offset(),
+            force_output: false,
+            output_target: OutputTarget::Directory,
+            write_events_vtt: false,
+            segment_base_url: None,
+            provenance_files: None,
+            adjustments: Adjustments::default(),
+            segment_observer: None,
+            streamed_dir: None,
+            streamed_provenance: Vec::new(),
+            first_timestamp: None,
+            partial: false,
+        }
+    }
+
+    /// Add a captured data packet, tracking object lifetimes for events.vtt
+    pub fn add_data(&mut self, packet_info: PacketInfo) -> &mut Self {
+        let timestamp = packet_info.timestamp + TimeDelta::microseconds(self.timeshift);
+        for frame in &packet_info.data_pack.frames {
+            match &frame.message {
+                Some(data_frame::Message::InstantiateObject(obj)) => {
+                    self.events.push(ObjectEvent {
+                        object_id: obj.object_id,
+                        prefab_name: String::from_utf8_lossy(&obj.prefab_name).to_string(),
+                        started_at: timestamp,
+                        ended_at: None,
+                    });
+                }
+                Some(data_frame::Message::DestroyObject(obj)) => {
+                    if let Some(event) = self
+                        .events
+                        .iter_mut()
+                        .rev()
+                        .find(|e| e.object_id == obj.object_id && e.ended_at.is_none())
+                    {
+                        event.ended_at = Some(timestamp);
+                    }
+                }
+                _ => {}
+            }
         }
+        self.add(packet_info)
     }
 
     pub fn add(&mut self, mut packet_info: PacketInfo) -> &mut Self {
@@ -269,6 +754,7 @@ impl SegmentBuilder {
                                 frames: std::mem::take(&mut packets_buf),
                             },
                             raw_data: Vec::new(),
+                            source: packet_info.source,
                         });
                         check_buf.clear();
                         packets_buf.clear();
@@ -282,6 +768,7 @@ impl SegmentBuilder {
                             frames: std::mem::take(&mut packets_buf),
                         },
                         raw_data: Vec::new(),
+                        source: packet_info.source,
                     });
                 }
             } else {
@@ -299,12 +786,16 @@ impl SegmentBuilder {
 
     pub fn start(&mut self) -> &mut Self {
         self.segments.clear();
+        self.events.clear();
+        self.streamed_dir = None;
+        self.streamed_provenance.clear();
+        self.first_timestamp = None;
         self.current_sequence = 0;
         self.part_count += 1;
         return self.next();
     }
 
-    pub fn set_current_segment_duration(&mut self, duration: f64) -> &mut Self {
+    pub fn set_current_segment_duration(&mut self, duration: TimeDelta) -> &mut Self {
         if let Some(segment) = self.segments.last_mut() {
             segment.duration = duration;
         }
@@ -321,13 +812,8 @@ impl SegmentBuilder {
         }
     }
 
-    pub fn write_to_file<P: AsRef<Path>>(
-        &mut self,
-        output_dir: P,
-        started_at: i64,
-        data_room_id: &[u8],
-    ) -> Result<()> {
-        let output_dir = if self.part_count > 1 {
+    fn part_suffixed_dir<P: AsRef<Path>>(&self, output_dir: P) -> PathBuf {
+        if self.part_count > 1 {
             PathBuf::from(format!(
                 "{}_{:03}",
                 output_dir.as_ref().to_string_lossy(),
@@ -335,83 +821,449 @@ impl SegmentBuilder {
             ))
         } else {
             PathBuf::from(output_dir.as_ref())
-        };
-        tracing::debug!("Writing segments to directory: {:?}", output_dir);
-        std::fs::create_dir_all(&output_dir)?;
-        let last_segment = self.segments.last_mut().unwrap();
-        last_segment.duration = (|| {
-            let last_timestamp = last_segment.packets.last().unwrap().timestamp;
-            let first_timestamp = last_segment.packets.first().unwrap().timestamp;
-            (last_timestamp - first_timestamp)
-                .num_microseconds()
-                .unwrap_or(0) as f64
-                / 1_000_000.0
-        })();
-        for segment in &self.segments {
-            let segment_file_path = output_dir.join(format!("segment_{:05}.ts", segment.number));
-            let file = File::create(&segment_file_path).with_context(|| {
-                format!("Failed to create segment file: {:?}", segment_file_path)
+        }
+    }
+
+    /// Expand the output dir template for the current part, `{part}` replaces the `_{:03}` suffix,
+    /// and create the dir or archive the part is written to
+    fn create_part_writer<P: AsRef<Path>>(
+        &mut self,
+        output_dir: P,
+        data_room_id: &[u8],
+    ) -> Result<(PathBuf, PartWriter)> {
+        let output_dir = self.output_target.strip_extension(output_dir.as_ref());
+        let template = PathTemplate::from_str(&output_dir.to_string_lossy())?;
+        let part = self.part_count.saturating_sub(1);
+        let dir = if template.uses(Placeholder::Part) {
+            template.expand(&TemplateVars {
+                room_id: Some(String::from_utf8_lossy(data_room_id).to_string()),
+                part: Some(part),
+                ..TemplateVars::new(self.created_at)
+            })?
+        } else {
+            let dir = template.expand(&TemplateVars {
+                room_id: Some(String::from_utf8_lossy(data_room_id).to_string()),
+                ..TemplateVars::new(self.created_at)
             })?;
-            let mut writer = BufWriter::new(file);
+            self.part_suffixed_dir(dir)
+        };
+        let target = self.output_target;
+        let dir = if template.is_templated() && !self.force_output {
+            unique_dir_by(&dir, |dir| target.output_path(dir).exists())
+        } else {
+            dir
+        };
+        let writer = PartWriter::create(target, &dir, self.created_at)?;
+        Ok((target.output_path(&dir), writer))
+    }
+
+    /// `events.vtt`, one cue per InstantiateObject until its DestroyObject
+    fn events_vtt(&self) -> Option<String> {
+        let (Some(recording_start), Some(last_packet)) = (
+            self.first_packet_timestamp(),
+            self.segments.last().and_then(|s| s.packets.last()),
+        ) else {
+            return None;
+        };
+        let recording_end = last_packet.timestamp;
+
+        let mut vtt = String::from("WEBVTT\n");
+        for event in &self.events {
+            let start = (event.started_at - recording_start).max(TimeDelta::zero());
+            let end = (event.ended_at.unwrap_or(recording_end) - recording_start).max(start);
+            vtt.push_str(&format!(
+                "\n{} --> {}\n{} (id: {})\n",
+                format_vtt_timestamp(start),
+                format_vtt_timestamp(end),
+                event.prefab_name,
+                event.object_id
+            ));
+        }
+        Some(vtt)
+    }
+
+    fn first_packet_timestamp(&self) -> Option<DateTime<Utc>> {
+        self.first_timestamp.or_else(|| {
+            self.segments
+                .iter()
+                .find_map(|s| s.packets.first())
+                .map(|p| p.timestamp)
+        })
+    }
+
+    /// Lineage of every packet in the current part, generated packets have no source
+    fn provenance(&self, files: Vec<String>) -> Provenance {
+        let mut records = self.streamed_provenance.clone();
+        for segment in self.segments.iter().filter(|s| !s.written) {
+            records.extend(self.segment_provenance(segment));
+        }
+        Provenance { files, records }
+    }
+
+    fn segment_provenance(&self, segment: &Segment) -> Vec<ProvenanceRecord> {
+        let mut records = Vec::new();
+        for (index, packet) in segment.packets.iter().enumerate() {
+            let Some(source) = packet.source else {
+                records.push(ProvenanceRecord {
+                    segment: segment.number,
+                    packet: index as u32,
+                    source: None,
+                    delta: TimeDelta::zero(),
+                    adjustments: Adjustments::default(),
+                });
+                continue;
+            };
+            // parts of a split packet are adjacent and share the source
+            let split = [index.checked_sub(1), Some(index + 1)]
+                .into_iter()
+                .flatten()
+                .filter_map(|i| segment.packets.get(i))
+                .any(|p| p.source == Some(source));
+            records.push(ProvenanceRecord {
+                segment: segment.number,
+                packet: index as u32,
+                source: Some(source),
+                delta: packet.timestamp - source.timestamp,
+                adjustments: self.adjustments.with(Adjustments::SPLIT, split),
+            });
+        }
+        records
+    }
+
+    fn notify(&self, path: &Path) {
+        if let Some(observer) = &self.segment_observer {
+            observer.notify(path);
+        }
+    }
+
+    /// Add a file to the part, directory output reports it right away
+    fn add_file(
+        &self,
+        writer: &mut PartWriter,
+        output_path: &Path,
+        name: &str,
+        data: &[u8],
+    ) -> Result<()> {
+        writer.add(name, data)?;
+        if self.output_target == OutputTarget::Directory {
+            self.notify(&output_path.join(name));
+        }
+        Ok(())
+    }
+
+    fn segment_data(segment: &Segment) -> Vec<u8> {
+        segment
+            .packets
+            .iter()
+            .flat_map(PacketInfo::to_vec)
+            .collect()
+    }
 
-            for packet in &segment.packets {
-                writer.write_all(&packet.to_vec())?;
+    /// Write every segment but the open last one to the part dir and release its packets,
+    /// only when streaming to a directory
+    fn flush_finished_segments(&mut self, data_room_id: &[u8]) -> Result<()> {
+        if self.segment_observer.is_none() || self.output_target != OutputTarget::Directory {
+            return Ok(());
+        }
+        let Some(output_dir) = self.output_dir.clone() else {
+            return Ok(());
+        };
+        for index in 0..self.segments.len().saturating_sub(1) {
+            if self.segments[index].written || self.segments[index].packets.is_empty() {
+                continue;
+            }
+            let dir = match &self.streamed_dir {
+                Some(dir) => dir.clone(),
+                None => {
+                    let (dir, _) = self.create_part_writer(&output_dir, data_room_id)?;
+                    self.streamed_dir = Some(dir.clone());
+                    dir
+                }
+            };
+            let segment = &self.segments[index];
+            if self.provenance_files.is_some() {
+                let records = self.segment_provenance(segment);
+                self.streamed_provenance.extend(records);
             }
-            writer.flush()?;
-        }
-        // m3u8
-        let m3u8_file_path = output_dir.join("index.m3u8");
-        let mut m3u8_file = File::create(&m3u8_file_path)
-            .with_context(|| format!("Failed to create m3u8 file: {:?}", m3u8_file_path))?;
-        // write template
-        writeln!(m3u8_file, "#EXTM3U8")?;
-        writeln!(m3u8_file, "#EXT-X-VERSION:3")?;
-        writeln!(m3u8_file, "#EXT-X-PLAYLIST-TYPE:VOD")?;
-        writeln!(m3u8_file, "#EXT-X-MEDIA-SEQUENCE:0")?;
-        writeln!(m3u8_file, "#EXT-X-TARGETDURATION:10")?;
-        for segment in &self.segments {
-            writeln!(
-                m3u8_file,
-                "#EXTINF:{:.3},\nsegment_{:05}.ts",
-                segment.duration, segment.number
+            let segment = &self.segments[index];
+            self.first_timestamp
+                .get_or_insert(segment.packets[0].timestamp);
+            self.add_file(
+                &mut PartWriter::Directory(dir.clone()),
+                &dir,
+                &format!("segment_{:05}.ts", segment.number),
+                &Self::segment_data(segment),
+            )?;
+            let segment = &mut self.segments[index];
+            segment.packets = Vec::new();
+            segment.written = true;
+        }
+        Ok(())
+    }
+
+    pub fn write_to_file<P: AsRef<Path>>(
+        &mut self,
+        output_dir: P,
+        started_at: i64,
+        data_room_id: &[u8],
+    ) -> Result<()> {
+        // a segment is opened before its first packet arrives
+        while self
+            .segments
+            .last()
+            .is_some_and(|s| s.packets.is_empty() && !s.written)
+        {
+            self.segments.pop();
+        }
+        if self.segments.is_empty() {
+            return Err(anyhow!(
+                "No playable data frames found in input; capture may be empty or truncated"
+            ));
+        }
+        let (output_path, mut writer) = match self.streamed_dir.take() {
+            Some(dir) => (dir.clone(), PartWriter::Directory(dir)),
+            None => self.create_part_writer(output_dir, data_room_id)?,
+        };
+        tracing::debug!("Writing segments to: {:?}", output_path);
+        let last_segment = self.segments.last_mut().unwrap();
+        if let (Some(first_packet), Some(last_packet)) =
+            (last_segment.packets.first(), last_segment.packets.last())
+        {
+            last_segment.duration = last_packet.timestamp - first_packet.timestamp;
+        }
+        for segment in self.segments.iter().filter(|s| !s.written) {
+            self.add_file(
+                &mut writer,
+                &output_path,
+                &format!("segment_{:05}.ts", segment.number),
+                &Self::segment_data(segment),
             )?;
         }
-        writeln!(m3u8_file, "#EXT-X-ENDLIST")?;
+        if let Some(files) = &self.provenance_files {
+            self.add_file(
+                &mut writer,
+                &output_path,
+                PROVENANCE_FILE,
+                &self.provenance(files.clone()).to_bytes(),
+            )?;
+        }
+        let entries: Vec<_> = self
+            .segments
+            .iter()
+            .map(|segment| PlaylistEntry {
+                uri: format!(
+                    "{}segment_{:05}.ts",
+                    self.segment_base_url.as_deref().unwrap_or_default(),
+                    segment.number
+                ),
+                duration: segment.duration,
+                discontinuity: false,
+            })
+            .collect();
+        writer.add("index.m3u8", playlist_m3u8(&entries).as_bytes())?;
 
         // metadata file
-        let metadata_file_path = output_dir.join("index.md");
-        let mut metadata_file = File::create(&metadata_file_path)
-            .with_context(|| format!("Failed to create metadata file: {:?}", metadata_file_path))?;
         let jst_offset = FixedOffset::east_opt(9 * 3600).unwrap();
         let live_started_at = chrono::DateTime::<Utc>::from_timestamp_micros(started_at)
-            .unwrap_or_else(|| Utc::now())
-            .with_timezone(&jst_offset)
-            .to_rfc3339();
-        let joined_room_at = self
-            .segments
-            .first()
-            .unwrap()
-            .packets
-            .first()
-            .unwrap()
-            .timestamp
-            .with_timezone(&jst_offset)
-            .to_rfc3339();
-        let metadata = serde_json::json!({
+            .unwrap_or_else(|| Utc::now());
+        let joined_room_at = self.first_packet_timestamp().unwrap();
+        let mut metadata = serde_json::json!({
             "path": self.metadata_path.as_deref().unwrap_or("/"),
             "room_id": std::str::from_utf8(&data_room_id)
                 .unwrap_or("unknown_room_id"),
             "playlist_file": "index.m3u8",
-            "live_started_at": live_started_at,
-            "joined_room_at": joined_room_at,
+            "live_started_at": live_started_at
+                .with_timezone(&jst_offset)
+                .to_rfc3339_opts(SecondsFormat::Micros, false),
+            "live_started_at_micros": live_started_at.timestamp_micros(),
+            "joined_room_at": joined_room_at
+                .with_timezone(&jst_offset)
+                .to_rfc3339_opts(SecondsFormat::Micros, false),
+            "joined_room_at_micros": joined_room_at.timestamp_micros(),
+        });
+        if self.partial {
+            metadata["partial"] = true.into();
+        }
+        self.add_file(
+            &mut writer,
+            &output_path,
+            "index.md",
+            format!("{}\n", metadata).as_bytes(),
+        )?;
+        if self.write_events_vtt
+            && let Some(vtt) = self.events_vtt()
+        {
+            self.add_file(&mut writer, &output_path, "events.vtt", vtt.as_bytes())?;
+        }
+        writer.finish()?;
+        // the playlist is reported last, everything it lists is already written
+        self.notify(&match self.output_target {
+            OutputTarget::Directory => output_path.join("index.m3u8"),
+            _ => output_path,
         });
-        writeln!(metadata_file, "{}", metadata.to_string())?;
-
         Ok(())
     }
 }
 
+/// Make a single path component from capture data (e.g. room id) safe for filesystems
+///
+/// Separators, null, control and Windows reserved characters become `_`,
+/// names made only of dots (`.`/`..`) or empty names become `_`.
+pub fn sanitize_path_component(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    if sanitized.chars().all(|c| c == '.') {
+        "_".to_string()
+    } else {
+        sanitized
+    }
+}
+
+/// Check an absolute http(s) base URL for segment URIs, adding the trailing '/' that
+/// keeps the last path component when a segment name is appended
+pub fn parse_segment_base_url(input: &str) -> Result<String> {
+    let url = url::Url::parse(input.trim())
+        .with_context(|| format!("Invalid segment base URL: {}", input))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(anyhow!("Segment base URL must be http or https: {}", input));
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err(anyhow!(
+            "Segment base URL can not have a query or fragment: {}",
+            input
+        ));
+    }
+    let mut base = url.to_string();
+    if !base.ends_with('/') {
+        base.push('/');
+    }
+    Ok(base)
+}
+
+/// One segment listed in `index.m3u8`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct PlaylistEntry {
+    pub uri: String,
+    pub duration: TimeDelta,
+    /// Preceded by `#EXT-X-DISCONTINUITY`, the timeline restarts at this segment
+    pub discontinuity: bool,
+}
+
+/// VOD playlist listing `entries` in order, starting at media sequence 0
+pub(super) fn playlist_m3u8(entries: &[PlaylistEntry]) -> String {
+    let mut m3u8 = String::from(
+        "#EXTM3U8\n#EXT-X-VERSION:3\n#EXT-X-PLAYLIST-TYPE:VOD\n#EXT-X-MEDIA-SEQUENCE:0\n#EXT-X-TARGETDURATION:10\n",
+    );
+    for entry in entries {
+        if entry.discontinuity {
+            m3u8.push_str("#EXT-X-DISCONTINUITY\n");
+        }
+        m3u8.push_str(&format!(
+            "#EXTINF:{},\n{}\n",
+            format_extinf_duration(entry.duration),
+            entry.uri
+        ));
+    }
+    m3u8.push_str("#EXT-X-ENDLIST\n");
+    m3u8
+}
+
+/// `#EXTINF` duration in seconds with microsecond precision
+fn format_extinf_duration(duration: TimeDelta) -> String {
+    let micros = duration.num_microseconds().unwrap_or(0).max(0);
+    format!("{}.{:06}", micros / 1_000_000, micros % 1_000_000)
+}
+
+/// `start + total * step / steps` in integer microseconds
+///
+/// Flooring the exact product spreads the division remainder over the steps instead of
+/// repeating a truncated step, so `step == steps` lands exactly on `start + total`.
+fn interpolate_timestamp(
+    start: DateTime<Utc>,
+    total: TimeDelta,
+    step: usize,
+    steps: usize,
+) -> DateTime<Utc> {
+    let total_micros = total.num_microseconds().unwrap_or(0) as i128;
+    let offset = total_micros * step as i128 / steps.max(1) as i128;
+    start + TimeDelta::microseconds(offset as i64)
+}
+
+/// WebVTT cue timestamp, `HH:MM:SS.mmm`
+fn format_vtt_timestamp(delta: TimeDelta) -> String {
+    let millis = delta.num_milliseconds();
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1_000 % 60,
+        millis % 1_000
+    )
+}
+
 static DURATION: TimeDelta = TimeDelta::seconds(10);
+/// Default `AlsConverterConfig::reorder_window`
+pub const DEFAULT_REORDER_WINDOW: TimeDelta = TimeDelta::seconds(2);
+/// Backward jumps up to this size are jitter, larger ones are reconnect seams
+const MAX_TIMESTAMP_JITTER: TimeDelta = TimeDelta::milliseconds(500);
+/// Gap inserted after a reconnect seam
+const SEAM_INTERVAL: TimeDelta = TimeDelta::milliseconds(20);
+
+fn log_out_of_order<R: PacketReaderTrait>(
+    reader: &OrderedPacketReader<R>,
+    policy: OutOfOrderPolicy,
+) {
+    if reader.out_of_order() > 0 {
+        tracing::warn!(
+            "{} packets were stamped earlier than a packet before them, {}",
+            reader.out_of_order(),
+            match policy {
+                OutOfOrderPolicy::Clamp => "clamped to the packet before them",
+                _ => "reordered",
+            }
+        );
+    }
+    if reader.seams() > 0 {
+        tracing::info!(
+            "Kept {} timestamp seams larger than the reorder window",
+            reader.seams()
+        );
+    }
+}
+
+/// Make timestamps monotonic: backward jumps within `max_jitter` are clamped to the previous
+/// packet, larger ones shift this and every later packet forward to continue after it
+fn smooth_timestamps(packets: &mut [PacketInfo], max_jitter: TimeDelta) {
+    let mut shift = TimeDelta::zero();
+    let mut previous: Option<DateTime<Utc>> = None;
+    for packet in packets {
+        let mut timestamp = packet.timestamp + shift;
+        if let Some(previous) = previous
+            && timestamp < previous
+        {
+            if previous - timestamp > max_jitter {
+                tracing::debug!(
+                    "Timestamp seam of {}ms at {}",
+                    (previous - timestamp).num_milliseconds(),
+                    packet.timestamp
+                );
+                shift += previous - timestamp + SEAM_INTERVAL;
+                timestamp = packet.timestamp + shift;
+            } else {
+                timestamp = previous;
+            }
+        }
+        packet.timestamp = timestamp;
+        previous = Some(timestamp);
+    }
+}
+
 // 创建一个上下文结构体来管理状态
 struct ConversionContext {
     state: AlsConverterStateMachine,
@@ -429,62 +1281,93 @@ struct ConversionContext {
 
     /// 根据回放包的 audio 与datetime receiver来自动计算时间戳
     auto_timestamp: bool,
+    /// 时间戳单调化, 去除重连造成的回跳
+    smooth_timestamps: bool,
     packetinfo_buffer: Vec<PacketInfo>,
+    include_object_ids: Option<HashSet<i32>>,
+    exclude_object_ids: Option<HashSet<i32>>,
+    reset_on_rejoin: bool,
+    embed_full_state_in_segments: bool,
+    swap_camera_order: bool,
+    keepalive_filter: KeepaliveFilter,
+    keepalive_dropped: usize,
+    room_id_override: Option<Vec<u8>>,
 }
 
 impl ConversionContext {
     fn new(
-        timeshift: i64,
-        split_write_mode: bool,
-        start_time: Option<String>,
-        data_start_time: Option<String>,
-        data_end_time: Option<String>,
-        metadata_path: Option<String>,
+        config: &AlsConverterConfig,
         output_dir: Option<String>,
         use_audio_processing: bool,
-        auto_timestamp: bool,
     ) -> Self {
         let mut st: Option<DateTime<Utc>> = None;
         let mut dst: Option<DateTime<Utc>> = None;
         let mut det: Option<DateTime<Utc>> = None;
-        if let Some(start_time) = start_time {
+        if let Some(start_time) = &config.start_time {
             st = Some(
-                DateTime::parse_from_rfc3339(&start_time)
+                DateTime::parse_from_rfc3339(start_time)
                     .unwrap()
                     .with_timezone(&Utc),
             )
         }
-        if let Some(data_start_time) = data_start_time {
+        if let Some(data_start_time) = &config.data_start_time {
             dst = Some(
-                DateTime::parse_from_rfc3339(&data_start_time)
+                DateTime::parse_from_rfc3339(data_start_time)
                     .unwrap()
                     .with_timezone(&Utc),
             )
         }
-        if let Some(data_end_time) = data_end_time {
+        if let Some(data_end_time) = &config.data_end_time {
             det = Some(
-                DateTime::parse_from_rfc3339(&data_end_time)
+                DateTime::parse_from_rfc3339(data_end_time)
                     .unwrap()
                     .with_timezone(&Utc),
             )
         }
+        let room_id_override = config
+            .room_id_override
+            .as_ref()
+            .map(|id| id.as_bytes().to_vec());
         Self {
             state: AlsConverterStateMachine::Initial,
             data_room: Room {
-                id: vec![0],
+                id: room_id_override.clone().unwrap_or_else(|| vec![0]),
                 started_at: 0,
                 ended_at: 0,
             },
             initial_timestamp: DateTime::<Utc>::from_timestamp_micros(0).unwrap(),
-            segment_builder: SegmentBuilder::new(metadata_path, output_dir.clone(), timeshift),
+            segment_builder: SegmentBuilder {
+                force_output: config.force_output,
+                output_target: config.output_target,
+                write_events_vtt: config.write_events_vtt,
+                segment_base_url: config.segment_base_url.clone(),
+                adjustments: Adjustments::default()
+                    .with(Adjustments::TIMESHIFT, config.timeshift != 0)
+                    .with(Adjustments::AUTO_TIMESTAMP, config.auto_timestamp)
+                    .with(Adjustments::SMOOTH_TIMESTAMPS, config.smooth_timestamps),
+                ..SegmentBuilder::new(
+                    config.metadata_path.clone(),
+                    output_dir.clone(),
+                    config.timeshift,
+                )
+            },
             initial_dataframes: Vec::new(),
-            split_write_mode,
+            split_write_mode: config.split,
             start_time: st,
             data_start_time: dst,
             data_end_time: det,
             use_audio_processing,
-            auto_timestamp,
+            auto_timestamp: config.auto_timestamp,
+            smooth_timestamps: config.smooth_timestamps,
             packetinfo_buffer: Vec::new(),
+            include_object_ids: config.include_object_ids.clone(),
+            exclude_object_ids: config.exclude_object_ids.clone(),
+            reset_on_rejoin: config.reset_on_rejoin,
+            embed_full_state_in_segments: config.embed_full_state_in_segments,
+            swap_camera_order: config.swap_camera_order,
+            keepalive_filter: config.keepalive_filter,
+            keepalive_dropped: 0,
+            room_id_override,
             #[cfg(feature = "audio")]
             audio_builder: AudioBuilder::new(output_dir),
         }
@@ -506,7 +1389,7 @@ impl ConversionContext {
             }
         }
 
-        // If both are found and FixedCamera comes before Cameraman, swap them
+        // If both are found and FixedCamera comes after Cameraman, swap them
         if let (Some(fixed_idx), Some(cameraman_idx)) = (fixed_camera_index, cameraman_index) {
             if fixed_idx > cameraman_idx {
                 dataframes.swap(fixed_idx, cameraman_idx);
@@ -559,7 +1442,7 @@ impl ConversionContext {
                     #[cfg(not(feature = "audio"))]
                     unreachable!("Audio feature is not enabled");
                 } else {
-                    if self.auto_timestamp {
+                    if self.buffer_packets() {
                         self.handle_packetinfo_buffer()?;
                     }
                     self.segment_builder
@@ -593,9 +1476,7 @@ impl ConversionContext {
                 self.state = AlsConverterStateMachine::FirstDataframes;
             }
             data_frame::Message::Room(msg) => {
-                self.data_room.clone_from(&msg);
-                // self.data_room.id = "default-114514".as_bytes().to_vec();
-                // self.data_room.id = "".as_bytes().to_vec();
+                self.set_data_room(msg);
             }
             _ => {}
         }
@@ -603,8 +1484,54 @@ impl ConversionContext {
         Ok(())
     }
 
+    /// 记录数据房间, 指定了 room id 时替换捕获的 id
+    fn set_data_room(&mut self, room: &Room) {
+        self.data_room.clone_from(room);
+        if let Some(id) = &self.room_id_override {
+            self.data_room.id.clone_from(id);
+        }
+        record_room_id(&String::from_utf8_lossy(&self.data_room.id));
+    }
+
+    /// 是否先缓冲所有包, 修正时间戳后再分段
+    fn buffer_packets(&self) -> bool {
+        self.auto_timestamp || self.smooth_timestamps
+    }
+
+    /// 处理中途重新加入房间的 JoinRoomResponse, 返回是否包含该消息
+    fn process_rejoin(&mut self, packet_info: &PacketInfo) -> bool {
+        let Some(response) = packet_info
+            .data_pack
+            .frames
+            .iter()
+            .find_map(|f| match &f.message {
+                Some(data_frame::Message::JoinRoomResponse(response)) => Some(response),
+                _ => None,
+            })
+        else {
+            return false;
+        };
+
+        tracing::warn!(
+            "JoinRoomResponse received in state {:?} at timestamp: {}",
+            self.state,
+            packet_info.timestamp
+        );
+        if self.reset_on_rejoin
+            && let Some(room) = &response.room
+        {
+            let previous_room_id = String::from_utf8_lossy(&self.data_room.id).to_string();
+            self.set_data_room(room);
+            tracing::info!(previous_room_id, "Data room reset");
+        }
+        true
+    }
+
     /// data packet 应该是 DataFrames(InstantiateObject|UpdateObject)
     fn process_first_dataframes_state(&mut self, mut packet_info: PacketInfo) -> Result<()> {
+        if self.process_rejoin(&packet_info) {
+            return Ok(());
+        }
         // control message 判断必须是Data
         if !packet_info
             .data_pack
@@ -633,7 +1560,7 @@ impl ConversionContext {
         let timestamp = packet_info.timestamp;
         self.initial_timestamp = timestamp;
 
-        if !self.auto_timestamp {
+        if !self.buffer_packets() {
             self.segment_builder
                 .start()
                 .add(PacketInfo::create_segment_started_packet(timestamp))
@@ -675,16 +1602,19 @@ impl ConversionContext {
             // do nothing
             #[cfg(not(feature = "audio"))]
             unreachable!("Audio processing is disabled");
-        } else if self.auto_timestamp {
+        } else if self.buffer_packets() {
             self.packetinfo_buffer.push(packet_info);
         } else {
-            self.segment_builder.add(packet_info);
+            self.segment_builder.add_data(packet_info);
         }
         self.state = AlsConverterStateMachine::UpdateObjects;
         Ok(())
     }
 
     fn process_update_objects_state(&mut self, mut packet_info: PacketInfo) -> Result<()> {
+        if self.process_rejoin(&packet_info) {
+            return Ok(());
+        }
         // control message 判断必须是Data
         if let Some(control) = &packet_info.data_pack.control {
             match control {
@@ -709,7 +1639,7 @@ impl ConversionContext {
 
         let timestamp = packet_info.timestamp;
         let mut use_custom_data_start_time = false;
-        if !self.auto_timestamp {
+        if !self.buffer_packets() {
             // 保留初始initial_dataframe, 但是跳过指定时间之前的包
             if let Some(data_start_time) = &self.data_start_time {
                 if timestamp < *data_start_time {
@@ -750,25 +1680,7 @@ impl ConversionContext {
                 self.initial_timestamp += DURATION;
                 if !use_custom_data_start_time {
                     // 处理新分片的头
-                    self.segment_builder
-                        .set_current_segment_duration(DURATION.as_seconds_f64())
-                        .next()
-                        .add(PacketInfo::create_segment_started_packet(
-                            self.initial_timestamp,
-                        ))
-                        .add(PacketInfo::create_room_frame(
-                            timestamp,
-                            self.data_room.clone(),
-                        ))
-                        .add(PacketInfo {
-                            timestamp,
-                            data_pack: DataPack {
-                                control: Some(data_pack::Control::Data(true)),
-                                frames: self.initial_dataframes.clone(),
-                            },
-                            raw_data: Vec::new(),
-                        })
-                        .add(PacketInfo::create_cache_end(timestamp));
+                    self.start_next_segment(timestamp)?;
                 }
             }
         }
@@ -786,6 +1698,24 @@ impl ConversionContext {
                 false
             }
         });
+        // 按 object id 过滤
+        if self.include_object_ids.is_some() || self.exclude_object_ids.is_some() {
+            packet_info.data_pack.frames.retain(|frame| {
+                let object_id = match &frame.message {
+                    Some(data_frame::Message::UpdateObject(obj)) => obj.object_id,
+                    Some(data_frame::Message::InstantiateObject(obj)) => obj.object_id,
+                    Some(data_frame::Message::DestroyObject(obj)) => obj.object_id,
+                    _ => return false,
+                };
+                self.include_object_ids
+                    .as_ref()
+                    .is_none_or(|ids| ids.contains(&object_id))
+                    && !self
+                        .exclude_object_ids
+                        .as_ref()
+                        .is_some_and(|ids| ids.contains(&object_id))
+            });
+        }
 
         for frame in &mut packet_info.data_pack.frames {
             match &mut frame.message {
@@ -863,10 +1793,10 @@ impl ConversionContext {
             self.audio_builder.handle_update_audio(&packet_info);
             #[cfg(not(feature = "audio"))]
             unreachable!("Audio feature is not enabled");
-        } else if self.auto_timestamp {
+        } else if self.buffer_packets() {
             self.packetinfo_buffer.push(packet_info);
         } else {
-            self.segment_builder.add(packet_info);
+            self.segment_builder.add_data(packet_info);
         }
         Ok(())
     }
@@ -930,7 +1860,9 @@ impl ConversionContext {
         }
         self.initial_dataframes.push(dataframe);
         // swap camera order first
-        Self::swap_order(&mut self.initial_dataframes);
+        if self.swap_camera_order {
+            Self::swap_order(&mut self.initial_dataframes);
+        }
         // then sort InitialObject is first
         self.initial_dataframes.sort_by(Self::compare_dataframes);
     }
@@ -1007,9 +1939,13 @@ impl ConversionContext {
                 self.packetinfo_buffer[end_index].timestamp = cur_timestamp;
             } else {
                 // 均匀分布时间
-                let time_step = total_delta / (total_packets as i32 - 1);
                 for (local_idx, info) in packet_infos.iter().enumerate() {
-                    let new_timestamp = last_timestamp + time_step * (local_idx as i32);
+                    let new_timestamp = interpolate_timestamp(
+                        last_timestamp,
+                        total_delta,
+                        local_idx,
+                        total_packets - 1,
+                    );
                     self.packetinfo_buffer[info.index].timestamp = new_timestamp;
                     tracing::trace!("Packet {} (uniform): {}", info.index, new_timestamp);
                 }
@@ -1027,8 +1963,7 @@ impl ConversionContext {
             total_delta
         );
 
-        // 第二步: 将总时间按音频包数量均分
-        let time_per_music_segment = total_delta / music_packet_count;
+        // 第二步: 将总时间按音频包数量均分, 第 i 个音频包落在 total_delta * (i + 1) / count
 
         // 第三步: 计算每个包的时间戳
         let mut current_time = last_timestamp;
@@ -1039,15 +1974,25 @@ impl ConversionContext {
             if packet_info.is_music {
                 // 这是一个音频包
                 // 先处理上一个音频段到当前音频包之间的其他包
+                let segment_end = interpolate_timestamp(
+                    last_timestamp,
+                    total_delta,
+                    music_segment_index + 1,
+                    music_packet_count,
+                );
                 let packets_before_this_music = local_idx - music_segment_start_packet_idx;
                 if packets_before_this_music > 0 {
                     // 有其他包需要插值（包括第一个音频包之前的包）
-                    let time_step = time_per_music_segment / (packets_before_this_music as i32 + 1);
                     for (step, info) in packet_infos[music_segment_start_packet_idx..local_idx]
                         .iter()
                         .enumerate()
                     {
-                        let new_timestamp = current_time + time_step * ((step + 1) as i32);
+                        let new_timestamp = interpolate_timestamp(
+                            current_time,
+                            segment_end - current_time,
+                            step + 1,
+                            packets_before_this_music + 1,
+                        );
                         self.packetinfo_buffer[info.index].timestamp = new_timestamp;
                         tracing::trace!(
                             "Packet {} (before music {}): {}",
@@ -1059,7 +2004,7 @@ impl ConversionContext {
                 }
 
                 // 更新当前音频段的时间
-                current_time = current_time + time_per_music_segment;
+                current_time = segment_end;
                 self.packetinfo_buffer[packet_info.index].timestamp = current_time;
                 tracing::debug!(
                     "Music packet {} at index {}: {}",
@@ -1078,12 +2023,16 @@ impl ConversionContext {
             let remaining_packets = packet_infos.len() - music_segment_start_packet_idx;
             if remaining_packets > 0 {
                 let remaining_time = cur_timestamp - current_time;
-                let time_step = remaining_time / (remaining_packets as i32 + 1);
                 for (step, info) in packet_infos[music_segment_start_packet_idx..]
                     .iter()
                     .enumerate()
                 {
-                    let new_timestamp = current_time + time_step * ((step + 1) as i32);
+                    let new_timestamp = interpolate_timestamp(
+                        current_time,
+                        remaining_time,
+                        step + 1,
+                        remaining_packets + 1,
+                    );
                     self.packetinfo_buffer[info.index].timestamp = new_timestamp;
                     tracing::trace!(
                         "Packet {} (after last music): {}",
@@ -1100,16 +2049,11 @@ impl ConversionContext {
 
         Ok(())
     }
-    ///
-    ///
-    fn handle_packetinfo_buffer(&mut self) -> Result<()> {
-        if self.packetinfo_buffer.is_empty() {
-            return Ok(());
-        }
+    /// 根据 DateTimeReceiver 与 MusicBroadcaster 重新计算缓冲包的时间戳
+    fn apply_music_timestamps(&mut self) -> Result<()> {
         tracing::warn!(
             "This is experimental auto timestamp feature, please report issues if any bugs found."
         );
-        self.initial_dataframes.clear(); // clear initial dataframes first
         let mut last_confirmed_timestamp: Option<DateTime<Utc>> = None;
         let mut last_confirmed_packet_index: usize = 0;
         let mut music_broadcasters: HashSet<i32> = HashSet::new();
@@ -1220,6 +2164,53 @@ impl ConversionContext {
                 }
             }
         }
+        Ok(())
+    }
+
+    /// 开始新的时间分片: SegmentStartedAt, Room, 当前全部对象状态 (可选), CacheEnded
+    fn start_next_segment(&mut self, timestamp: DateTime<Utc>) -> Result<()> {
+        self.segment_builder
+            .set_current_segment_duration(DURATION)
+            .next();
+        self.segment_builder
+            .flush_finished_segments(&self.data_room.id)?;
+        self.segment_builder
+            .add(PacketInfo::create_segment_started_packet(
+                self.initial_timestamp,
+            ))
+            .add(PacketInfo::create_room_frame(
+                timestamp,
+                self.data_room.clone(),
+            ));
+        // 新加入的观众需要全部对象状态, 这样每个分片都可以单独播放
+        if self.embed_full_state_in_segments {
+            self.segment_builder.add(PacketInfo {
+                timestamp,
+                data_pack: DataPack {
+                    control: Some(data_pack::Control::Data(true)),
+                    frames: self.initial_dataframes.clone(),
+                },
+                raw_data: Vec::new(),
+                source: None,
+            });
+        }
+        self.segment_builder
+            .add(PacketInfo::create_cache_end(timestamp));
+        Ok(())
+    }
+
+    /// 缓冲包在 Split/结束时统一修正时间戳后再写入分段
+    fn handle_packetinfo_buffer(&mut self) -> Result<()> {
+        if self.packetinfo_buffer.is_empty() {
+            return Ok(());
+        }
+        self.initial_dataframes.clear(); // clear initial dataframes first
+        if self.auto_timestamp {
+            self.apply_music_timestamps()?;
+        }
+        if self.smooth_timestamps {
+            smooth_timestamps(&mut self.packetinfo_buffer, MAX_TIMESTAMP_JITTER);
+        }
         // after timestamp confirmed, we can use segment_builder then.
         for packet_info in std::mem::take(&mut self.packetinfo_buffer) {
             let timestamp = packet_info.timestamp;
@@ -1239,25 +2230,7 @@ impl ConversionContext {
             // timestamp segment
             if timestamp - self.initial_timestamp > DURATION {
                 self.initial_timestamp += DURATION;
-                self.segment_builder
-                    .set_current_segment_duration(DURATION.as_seconds_f64())
-                    .next()
-                    .add(PacketInfo::create_segment_started_packet(
-                        self.initial_timestamp,
-                    ))
-                    .add(PacketInfo::create_room_frame(
-                        timestamp,
-                        self.data_room.clone(),
-                    ))
-                    .add(PacketInfo {
-                        timestamp,
-                        data_pack: DataPack {
-                            control: Some(data_pack::Control::Data(true)),
-                            frames: self.initial_dataframes.clone(),
-                        },
-                        raw_data: Vec::new(),
-                    })
-                    .add(PacketInfo::create_cache_end(timestamp));
+                self.start_next_segment(timestamp)?;
             }
             // update initial frames
             for frame in &packet_info.data_pack.frames {
@@ -1288,8 +2261,677 @@ impl ConversionContext {
                     }
                 }
             }
-            self.segment_builder.add(packet_info);
+            self.segment_builder.add_data(packet_info);
         }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn fixture_start() -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000, 0).unwrap()
+    }
+
+    // Helper: a Data packet `millis` after the fixture start carrying `message`
+    fn data_packet(millis: i64, message: data_frame::Message) -> PacketInfo {
+        PacketInfo {
+            timestamp: fixture_start() + TimeDelta::milliseconds(millis),
+            data_pack: DataPack {
+                control: Some(data_pack::Control::Data(true)),
+                frames: vec![DataFrame {
+                    message: Some(message),
+                }],
+            },
+            raw_data: vec![],
+            source: None,
+        }
+    }
+
+    // Helper: Room, JoinRoomResponse and the instantiation of object 7
+    fn opening_messages() -> [data_frame::Message; 3] {
+        [
+            data_frame::Message::Room(Room::default()),
+            data_frame::Message::JoinRoomResponse(Default::default()),
+            data_frame::Message::InstantiateObject(crate::als::proto::define::InstantiateObject {
+                object_id: 7,
+                ..Default::default()
+            }),
+        ]
+    }
+
+    fn update_message() -> data_frame::Message {
+        data_frame::Message::UpdateObject(crate::als::proto::define::UpdateObject {
+            object_id: 7,
+            ..Default::default()
+        })
+    }
+
+    // Helper: the opening messages at the start, then an update of object 7 at each of `updates`
+    fn replay_packets(updates: impl IntoIterator<Item = i64>) -> Vec<PacketInfo> {
+        let opening = opening_messages()
+            .into_iter()
+            .map(|message| data_packet(0, message));
+        let updates = updates
+            .into_iter()
+            .map(|millis| data_packet(millis, update_message()));
+        opening.chain(updates).collect()
+    }
+
+    // Helper: write `files` as data_1.bin, data_2.bin, ... into `input_dir`
+    fn write_capture(input_dir: &Path, files: &[&[PacketInfo]]) {
+        std::fs::create_dir_all(input_dir).unwrap();
+        for (index, packets) in files.iter().enumerate() {
+            let bytes: Vec<u8> = packets.iter().flat_map(PacketInfo::to_vec).collect();
+            std::fs::write(input_dir.join(format!("data_{}.bin", index + 1)), bytes).unwrap();
+        }
+    }
+
+    fn standard_config() -> AlsConverterConfig {
+        AlsConverterConfig {
+            input_format: InputFormat::Standard(None),
+            ..Default::default()
+        }
+    }
+
+    fn convert_fixture(
+        input_dir: &Path,
+        output_dir: &Path,
+        config: &AlsConverterConfig,
+    ) -> Result<()> {
+        AlsConverter::default().convert_mixed_to_standard(input_dir, output_dir, config)
+    }
+
+    #[test]
+    fn test_sanitize_path_component() {
+        assert_eq!(sanitize_path_component("room-1_abc"), "room-1_abc");
+        assert_eq!(
+            sanitize_path_component("../../etc/passwd"),
+            ".._.._etc_passwd"
+        );
+        assert_eq!(
+            sanitize_path_component("a\\b:c*d?e\"f<g>h|i"),
+            "a_b_c_d_e_f_g_h_i"
+        );
+        assert_eq!(
+            sanitize_path_component("null\0byte\n\t\x1b"),
+            "null_byte___"
+        );
+        assert_eq!(sanitize_path_component(".."), "_");
+        assert_eq!(sanitize_path_component("."), "_");
+        assert_eq!(sanitize_path_component(""), "_");
+        assert_eq!(sanitize_path_component("ライブ,1"), "ライブ,1");
+    }
+
+    #[test]
+    fn test_default_output_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let timestamp = fixture_start();
+        let room = Room {
+            id: b"../room/\0id".to_vec(),
+            started_at: timestamp.timestamp_micros(),
+            ended_at: 0,
+        };
+        write_capture(dir, &[&[PacketInfo::create_room_frame(timestamp, room)]]);
+
+        let output_dir =
+            AlsConverter::default_output_dir(dir, InputFormat::Standard(None), None).unwrap();
+        assert_eq!(output_dir, PathBuf::from(".._room__id_20231115_071320"));
+        assert_eq!(output_dir.components().count(), 1);
+        let output_dir =
+            AlsConverter::default_output_dir(dir, InputFormat::Standard(None), Some("anon"))
+                .unwrap();
+        assert_eq!(output_dir, PathBuf::from("anon_20231115_071320"));
+    }
+
+    #[test]
+    fn test_fill_live_id() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        write_capture(dir, &[&[PacketInfo::create_cache_end(fixture_start())]]);
+        let err = AlsConverter::fill_live_id("out/{live_id}", dir, InputFormat::Standard(None))
+            .unwrap_err();
+        assert!(err.to_string().contains("{live_id}"));
+        assert!(AlsConverter::fill_live_id("-", "-", InputFormat::Standard(None)).is_ok());
+        assert!(AlsConverter::fill_live_id("{live_id}", "-", InputFormat::Standard(None)).is_err());
+
+        let room = Room {
+            id: b"live/1".to_vec(),
+            started_at: 0,
+            ended_at: 0,
+        };
+        write_capture(
+            dir,
+            &[&[PacketInfo::create_room_frame(fixture_start(), room)]],
+        );
+        assert_eq!(
+            AlsConverter::fill_live_id("out/{live_id}_{part}", dir, InputFormat::Standard(None))
+                .unwrap(),
+            "out/live_1_{part}"
+        );
+        assert_eq!(
+            AlsConverter::fill_live_id("out/{{x}}_{room_id}", dir, InputFormat::Standard(None))
+                .unwrap(),
+            "out/{{x}}_{room_id}"
+        );
+    }
+
+    #[test]
+    fn test_templated_part_dirs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let template = format!("{}/{{room_id}}_{{part}}", dir.display());
+        let timestamp = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let mut builder = SegmentBuilder::new(None, None, 0);
+        let write_part = |builder: &mut SegmentBuilder| {
+            builder.start().add(PacketInfo::create_cache_end(timestamp));
+            builder.write_to_file(&template, 0, b"room/1").unwrap();
+        };
+        write_part(&mut builder);
+        write_part(&mut builder);
+        assert!(dir.join("room_1_000/index.m3u8").exists());
+        assert!(dir.join("room_1_001/index.m3u8").exists());
+
+        // A later conversion does not overwrite existing parts unless forced
+        let mut builder = SegmentBuilder::new(None, None, 0);
+        write_part(&mut builder);
+        assert!(dir.join("room_1_000-1/index.m3u8").exists());
+        builder.force_output = true;
+        builder.part_count = 0;
+        write_part(&mut builder);
+        assert!(!dir.join("room_1_000-2").exists());
+    }
+
+    #[test]
+    fn test_handshake_only_capture() {
+        let tmp = tempfile::tempdir().unwrap();
+        let input_dir = tmp.path().join("input");
+        let output_dir = tmp.path().join("output");
+        let [room, join, _] = opening_messages();
+        write_capture(&input_dir, &[&[data_packet(0, room), data_packet(0, join)]]);
+
+        let error = convert_fixture(&input_dir, &output_dir, &standard_config()).unwrap_err();
+        assert!(error.to_string().contains("No playable data frames"));
+        assert!(!output_dir.exists());
+    }
+
+    #[test]
+    fn test_room_id_override() {
+        let config = AlsConverterConfig {
+            room_id_override: Some("anon".to_string()),
+            ..Default::default()
+        };
+        let mut context = ConversionContext::new(&config, None, false);
+        let [_, join, object] = opening_messages();
+        let room = data_frame::Message::Room(Room {
+            id: b"captured".to_vec(),
+            started_at: 1,
+            ended_at: 2,
+        });
+        for message in [room, join, object] {
+            context.process_packet(data_packet(0, message)).unwrap();
+        }
+
+        assert_eq!(context.data_room.id, b"anon");
+        assert_eq!(context.data_room.started_at, 1);
+        let packets = &context.segment_builder.segments[0].packets;
+        assert_eq!(
+            packets[1].data_pack.frames[0].message,
+            Some(data_frame::Message::Room(context.data_room.clone()))
+        );
+        match &packets[3].data_pack.frames[0].message {
+            Some(data_frame::Message::InstantiateObject(obj)) => assert_eq!(
+                obj.target,
+                Some(instantiate_object::Target::RoomAll(RoomAll {
+                    room_id: b"anon".to_vec()
+                }))
+            ),
+            other => panic!("unexpected frame: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_convert_with_provenance() {
+        let tmp = tempfile::tempdir().unwrap();
+        let input_dir = tmp.path().join("input");
+        let output_dir = tmp.path().join("output");
+        let [room, join, object] = opening_messages();
+        let first = [
+            data_packet(0, room),
+            data_packet(0, join),
+            data_packet(1000, object),
+        ];
+        let second = [
+            data_packet(2000, update_message()),
+            data_packet(3000, update_message()),
+        ];
+        write_capture(&input_dir, &[&first, &second]);
+
+        let config = AlsConverterConfig {
+            timeshift: 500_000,
+            provenance: true,
+            ..standard_config()
+        };
+        convert_fixture(&input_dir, &output_dir, &config).unwrap();
+
+        let provenance = Provenance::read(&output_dir.join(PROVENANCE_FILE)).unwrap();
+        assert_eq!(provenance.files.len(), 2);
+        // SegmentStartedAt, Room, CacheEnded and 3 captured packets
+        assert_eq!(provenance.records.len(), 6);
+        assert!(provenance.lookup(0, 1).unwrap().source.is_none());
+
+        let record = provenance.lookup(0, 3).unwrap();
+        let source = record.source.unwrap();
+        assert!(
+            provenance
+                .source_file(record)
+                .unwrap()
+                .ends_with("data_1.bin")
+        );
+        assert_eq!(
+            source.offset,
+            (first[0].to_vec().len() + first[1].to_vec().len()) as u64
+        );
+        assert_eq!(source.timestamp, first[2].timestamp);
+        assert_eq!(record.delta, TimeDelta::milliseconds(500));
+        assert_eq!(record.adjustments.names(), vec!["timeshift"]);
+
+        let record = provenance.lookup(0, 5).unwrap();
+        assert!(
+            provenance
+                .source_file(record)
+                .unwrap()
+                .ends_with("data_2.bin")
+        );
+        assert_eq!(
+            record.source.unwrap().offset,
+            second[0].to_vec().len() as u64
+        );
+    }
+
+    #[test]
+    fn test_convert_to_tar() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let input_dir = dir.join("input");
+        write_capture(&input_dir, &[&replay_packets([])]);
+
+        let config = AlsConverterConfig {
+            output_target: OutputTarget::Tar,
+            ..standard_config()
+        };
+        convert_fixture(&input_dir, &dir.join("room.tar"), &config).unwrap();
+
+        assert!(!dir.join("room").exists());
+        let tar = std::fs::read(dir.join("room.tar")).unwrap();
+        let mut names = Vec::new();
+        let mut at = 0;
+        while tar[at] != 0 {
+            let name = &tar[at..at + 100];
+            let name = &name[..name.iter().position(|b| *b == 0).unwrap()];
+            names.push(String::from_utf8(name.to_vec()).unwrap());
+            let size = std::str::from_utf8(&tar[at + 124..at + 135]).unwrap();
+            let size = usize::from_str_radix(size, 8).unwrap();
+            at += 512 + size.div_ceil(512) * 512;
+        }
+        assert_eq!(names, vec!["segment_00000.ts", "index.m3u8", "index.md"]);
+    }
+
+    #[test]
+    fn test_streamed_segments() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let input_dir = dir.join("input");
+        write_capture(
+            &input_dir,
+            &[&replay_packets((1..=25).map(|second| second * 1000))],
+        );
+
+        let config = AlsConverterConfig {
+            provenance: true,
+            ..standard_config()
+        };
+        let streamed_dir = dir.join("streamed");
+        let reported = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let observer = {
+            let reported = reported.clone();
+            let playlist = streamed_dir.join("index.m3u8");
+            SegmentObserver::new(move |path| {
+                let name = path.file_name().unwrap().to_string_lossy().to_string();
+                assert!(path.is_file(), "{} reported before it was written", name);
+                reported.lock().unwrap().push((name, playlist.exists()));
+            })
+        };
+        AlsConverter::default()
+            .with_segment_observer(observer)
+            .convert_mixed_to_standard(&input_dir, &streamed_dir, &config)
+            .unwrap();
+        let buffered_dir = dir.join("buffered");
+        convert_fixture(&input_dir, &buffered_dir, &config).unwrap();
+
+        let reported = reported.lock().unwrap().clone();
+        let names = reported
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "segment_00000.ts",
+                "segment_00001.ts",
+                "segment_00002.ts",
+                "provenance.bin",
+                "index.md",
+                "index.m3u8"
+            ]
+        );
+        // finished segments are out before the conversion ends
+        assert!(!reported[0].1 && !reported[1].1);
+        for name in names {
+            assert_eq!(
+                std::fs::read(streamed_dir.join(name)).unwrap(),
+                std::fs::read(buffered_dir.join(name)).unwrap(),
+                "{} differs from the buffered conversion",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_cancelled_conversion() {
+        let tmp = tempfile::tempdir().unwrap();
+        let input_dir = tmp.path().join("input");
+        let output_dir = tmp.path().join("output");
+        // an hour of updates, ten per second
+        write_capture(
+            &input_dir,
+            &[&replay_packets((1..=36_000).map(|tick| tick * 100))],
+        );
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        // cancelled while the third segment is being filled
+        let observer = {
+            let cancel = cancel.clone();
+            SegmentObserver::new(move |path| {
+                if path.ends_with("segment_00001.ts") {
+                    cancel.store(true, AtomicOrdering::Relaxed);
+                }
+            })
+        };
+        let error = AlsConverter::default()
+            .with_segment_observer(observer)
+            .with_cancel(cancel)
+            .convert_mixed_to_standard(&input_dir, &output_dir, &standard_config())
+            .unwrap_err();
+        assert!(error.is::<ConversionCancelledError>(), "{:#}", error);
+
+        let metadata: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(output_dir.join("index.md")).unwrap())
+                .unwrap();
+        assert_eq!(metadata["partial"], true);
+        let playlist = std::fs::read_to_string(output_dir.join("index.m3u8")).unwrap();
+        assert!(playlist.ends_with("#EXT-X-ENDLIST\n"));
+        let report = crate::als::verify::run_verify(&crate::als::verify::VerifyConfig {
+            input_dir: output_dir.clone(),
+            strict_state: true,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(report.is_ok(), "{:?}", report);
+        assert_eq!(report.segments, 3);
+        assert!(report.packets < 36_000);
+    }
+
+    #[test]
+    fn test_out_of_order_packets() {
+        use crate::als::proto::reader::StandardPacketReader;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let input_dir = dir.join("input");
+        // 25 seconds of updates, every 40th stamped 500ms backwards, one at a segment end
+        let updates = (1..=250).map(|tick| {
+            if tick % 40 == 0 {
+                tick * 100 - 500
+            } else {
+                tick * 100
+            }
+        });
+        write_capture(&input_dir, &[&replay_packets(updates)]);
+
+        let convert = |out_of_order| {
+            let config = AlsConverterConfig {
+                out_of_order,
+                ..standard_config()
+            };
+            let output_dir = dir.join(format!("{:?}", out_of_order));
+            convert_fixture(&input_dir, &output_dir, &config).map(|_| output_dir)
+        };
+        // update timestamps of every segment and the playlist durations
+        let read_output = |output_dir: &Path| {
+            let mut updates = Vec::new();
+            for index in 0..3 {
+                let path = output_dir.join(format!("segment_{:05}.ts", index));
+                let mut reader = StandardPacketReader::new(File::open(path).unwrap());
+                updates.push(
+                    reader
+                        .read_packets()
+                        .unwrap()
+                        .into_iter()
+                        // the segment header repeats the object state
+                        .skip_while(|p| {
+                            !matches!(p.data_pack.control, Some(data_pack::Control::CacheEnded(_)))
+                        })
+                        .skip(1)
+                        .filter(|p| {
+                            p.data_pack.frames.iter().any(|f| {
+                                matches!(f.message, Some(data_frame::Message::UpdateObject(_)))
+                            })
+                        })
+                        .map(|p| (p.timestamp - fixture_start()).num_milliseconds())
+                        .collect::<Vec<_>>(),
+                );
+            }
+            let playlist = std::fs::read_to_string(output_dir.join("index.m3u8")).unwrap();
+            assert!(!playlist.contains("#EXTINF:-"), "{}", playlist);
+            updates
+        };
+
+        let reordered = read_output(&convert(OutOfOrderPolicy::Reorder).unwrap());
+        assert!(reordered.iter().all(|updates| updates.is_sorted()));
+        assert!(reordered[0].contains(&3500) && reordered[2].contains(&23500));
+        assert_eq!(reordered.iter().map(Vec::len).sum::<usize>(), 250);
+
+        let clamped = read_output(&convert(OutOfOrderPolicy::Clamp).unwrap());
+        assert!(clamped.iter().all(|updates| updates.is_sorted()));
+        // 3500 follows 3900 and is stamped like it
+        assert_eq!(clamped[0][37..40], [3800, 3900, 3900]);
+        assert_eq!(clamped.iter().map(Vec::len).sum::<usize>(), 250);
+
+        let error = convert(OutOfOrderPolicy::Error).unwrap_err();
+        assert!(error.to_string().contains("400ms earlier"), "{:#}", error);
+    }
+
+    #[test]
+    fn test_legacy_fixed_clock() {
+        use prost::Message;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let input_dir = dir.join("input");
+        std::fs::create_dir_all(&input_dir).unwrap();
+        // legacy packets: length, one unused byte and the protobuf, no timestamp
+        let packet = |message| {
+            let protobuf = data_packet(0, message).data_pack.encode_to_vec();
+            let mut bytes = ((protobuf.len() + 1) as u16).to_be_bytes().to_vec();
+            bytes.push(0x00);
+            bytes.extend(protobuf);
+            bytes
+        };
+        let mut first = opening_messages()
+            .into_iter()
+            .flat_map(packet)
+            .collect::<Vec<_>>();
+        first.extend((0..10).flat_map(|_| packet(update_message())));
+        std::fs::write(input_dir.join("data_1.bin"), first).unwrap();
+        let second = (0..15)
+            .flat_map(|_| packet(update_message()))
+            .collect::<Vec<_>>();
+        std::fs::write(input_dir.join("data_2.bin"), second).unwrap();
+
+        let start = fixture_start();
+        let convert = |name: &str, segment_base_url: Option<&str>| {
+            let config = AlsConverterConfig {
+                input_format: InputFormat::Legacy,
+                packet_clock: PacketClock::fixed(start, TimeDelta::seconds(1)),
+                segment_base_url: segment_base_url.map(String::from),
+                ..Default::default()
+            };
+            let output_dir = dir.join(name);
+            convert_fixture(&input_dir, &output_dir, &config).unwrap();
+            output_dir
+        };
+        let first_run = convert("first", None);
+        let second_run = convert("second", None);
+
+        // the clock continues across input files
+        assert_eq!(
+            std::fs::read_to_string(first_run.join("index.m3u8")).unwrap(),
+            "#EXTM3U8\n#EXT-X-VERSION:3\n#EXT-X-PLAYLIST-TYPE:VOD\n#EXT-X-MEDIA-SEQUENCE:0\n#EXT-X-TARGETDURATION:10\n\
+             #EXTINF:10.000000,\nsegment_00000.ts\n\
+             #EXTINF:10.000000,\nsegment_00001.ts\n\
+             #EXTINF:5.000000,\nsegment_00002.ts\n#EXT-X-ENDLIST\n"
+        );
+        let metadata: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(first_run.join("index.md")).unwrap())
+                .unwrap();
+        // Room and JoinRoomResponse come before the first replay packet
+        assert_eq!(
+            metadata["joined_room_at_micros"],
+            (start + TimeDelta::seconds(2)).timestamp_micros()
+        );
+        for entry in std::fs::read_dir(&first_run).unwrap() {
+            let name = entry.unwrap().file_name();
+            assert_eq!(
+                std::fs::read(first_run.join(&name)).unwrap(),
+                std::fs::read(second_run.join(&name)).unwrap(),
+                "{:?} differs between runs",
+                name
+            );
+        }
+
+        let hosted = convert("hosted", Some("https://cdn.example.com/room_1/"));
+        let playlist = std::fs::read_to_string(hosted.join("index.m3u8")).unwrap();
+        assert!(playlist.contains("\nhttps://cdn.example.com/room_1/segment_00000.ts\n"));
+        assert!(hosted.join("segment_00000.ts").is_file());
+    }
+
+    #[test]
+    fn test_segment_full_state() {
+        let timestamp = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let object = DataFrame {
+            message: Some(data_frame::Message::InstantiateObject(
+                crate::als::proto::define::InstantiateObject {
+                    object_id: 7,
+                    ..Default::default()
+                },
+            )),
+        };
+        let segment_packets = |embed_full_state_in_segments: bool| {
+            let config = AlsConverterConfig {
+                embed_full_state_in_segments,
+                ..Default::default()
+            };
+            let mut context = ConversionContext::new(&config, None, false);
+            context.initial_dataframes.push(object.clone());
+            context.segment_builder.start();
+            context.start_next_segment(timestamp).unwrap();
+            context.segment_builder.segments[1].packets.clone()
+        };
+
+        let packets = segment_packets(true);
+        assert_eq!(packets.len(), 4);
+        assert_eq!(packets[2].data_pack.frames, vec![object.clone()]);
+        assert_eq!(
+            packets[3].data_pack.control,
+            Some(data_pack::Control::CacheEnded(true))
+        );
+        assert_eq!(segment_packets(false).len(), 3);
+    }
+
+    #[test]
+    fn test_swap_camera_order() {
+        let instantiate = |object_id: i32, prefab_name: &str| DataFrame {
+            message: Some(data_frame::Message::InstantiateObject(
+                crate::als::proto::define::InstantiateObject {
+                    object_id,
+                    prefab_name: prefab_name.as_bytes().to_vec(),
+                    ..Default::default()
+                },
+            )),
+        };
+        let initial_order = |swap_camera_order: bool| {
+            let config = AlsConverterConfig {
+                swap_camera_order,
+                ..Default::default()
+            };
+            let mut context = ConversionContext::new(&config, None, false);
+            context.insert_initial_dataframes(instantiate(1, "Prefabs/Camera/Cameraman"));
+            context.insert_initial_dataframes(instantiate(2, "Prefabs/Camera/FixedCamera"));
+            context
+                .initial_dataframes
+                .iter()
+                .map(|frame| match &frame.message {
+                    Some(data_frame::Message::InstantiateObject(obj)) => obj.object_id,
+                    _ => unreachable!(),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(initial_order(true), vec![2, 1]);
+        assert_eq!(initial_order(false), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_interpolate_timestamp() {
+        let start = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let total = TimeDelta::minutes(10);
+        let steps = 10_000;
+        let exact_step = total.num_microseconds().unwrap() as f64 / steps as f64;
+
+        let mut previous = start;
+        for step in 0..=steps {
+            let timestamp = interpolate_timestamp(start, total, step, steps);
+            assert!(timestamp >= previous);
+            let error =
+                (timestamp - start).num_microseconds().unwrap() as f64 - exact_step * step as f64;
+            assert!(error.abs() < 1_000.0, "step {step} is off by {error}us");
+            previous = timestamp;
+        }
+        assert_eq!(
+            interpolate_timestamp(start, total, steps, steps),
+            start + total
+        );
+
+        // a step that does not divide evenly still ends on the anchor
+        let total = TimeDelta::microseconds(600_000_007);
+        assert_eq!(
+            interpolate_timestamp(start, total, 9_999, 9_999),
+            start + total
+        );
+    }
+
+    #[test]
+    fn test_format_extinf_duration() {
+        assert_eq!(format_extinf_duration(TimeDelta::seconds(10)), "10.000000");
+        assert_eq!(
+            format_extinf_duration(TimeDelta::microseconds(9_999_871)),
+            "9.999871"
+        );
+        assert_eq!(format_extinf_duration(TimeDelta::zero()), "0.000000");
+    }
+}
@@ -199,11 +199,18 @@ impl ExtractTarget for AudioExtractTarget {
     }
 
     fn finish(&mut self, ctx: &mut ExtractContext) -> Result<()> {
-        let generated_file_count = self.builder.audio_packets.len();
-        self.builder
+        for source in &self.builder.sources {
+            tracing::info!(
+                "Audio source: object id {} ({})",
+                source.object_id,
+                source.prefab_name
+            );
+        }
+        let files = self
+            .builder
             .write_to_file(&self.output_dir)
             .with_context(|| "failed to write extracted audio files")?;
-        ctx.stats.outputs_written += generated_file_count;
+        ctx.stats.outputs_written += files.len();
         Ok(())
     }
 }
@@ -360,7 +367,7 @@ fn build_target(
     }
 }
 
-fn collect_standard_entries(input_dir: &Path) -> Result<VecDeque<DirEntry>> {
+pub(crate) fn collect_standard_entries(input_dir: &Path) -> Result<VecDeque<DirEntry>> {
     if !input_dir.is_dir() {
         return Err(anyhow!(
             "input path is not a directory: {}",
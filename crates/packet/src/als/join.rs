@@ -0,0 +1,258 @@
+//! Joining the parts of one conversion back into a single playlist
+//!
+//! A capture that changes rooms is converted into one directory per room, `output`,
+//! `output_001`, ... Joining copies the segments of each part, in the given order, into
+//! one directory numbered on from the previous part, and writes an `index.m3u8` listing
+//! them all with `#EXT-X-DISCONTINUITY` where a new part starts. Segments are copied
+//! unchanged, each one already starts with the room state a player needs. Provenance and
+//! events files are not carried over.
+
+use anyhow::{Context, Result, anyhow};
+use chrono::TimeDelta;
+use std::path::{Path, PathBuf};
+
+use super::converter::{PlaylistEntry, playlist_m3u8};
+use super::retime::{METADATA_FILE, read_metadata};
+
+/// Playlist written next to the converted segments
+const PLAYLIST_FILE: &str = "index.m3u8";
+
+#[derive(Debug, Clone)]
+pub struct JoinConfig {
+    /// Converted part directories in playback order
+    pub part_dirs: Vec<PathBuf>,
+    pub output_dir: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+pub struct JoinSummary {
+    pub parts: usize,
+    pub segments: usize,
+    pub duration: TimeDelta,
+}
+
+pub fn run_join(config: &JoinConfig) -> Result<JoinSummary> {
+    if config.part_dirs.is_empty() {
+        return Err(anyhow!("No part directories to join"));
+    }
+    let output_playlist = config.output_dir.join(PLAYLIST_FILE);
+    if output_playlist.exists() {
+        return Err(anyhow!(
+            "Output already contains a playlist: {}",
+            output_playlist.display()
+        ));
+    }
+    // read every part first, nothing is written when one of them is broken
+    let parts = config
+        .part_dirs
+        .iter()
+        .map(|dir| {
+            let entries = read_playlist(dir)?;
+            for entry in &entries {
+                let path = segment_path(dir, &entry.uri);
+                if !path.is_file() {
+                    return Err(anyhow!("Missing segment: {}", path.display()));
+                }
+            }
+            Ok((dir, entries, read_metadata(dir)?))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    std::fs::create_dir_all(&config.output_dir).with_context(|| {
+        format!(
+            "Failed to create output directory: {}",
+            config.output_dir.display()
+        )
+    })?;
+
+    let mut joined = Vec::new();
+    let mut part_metadata = Vec::new();
+    for (part_index, (dir, entries, metadata)) in parts.iter().enumerate() {
+        part_metadata.push(serde_json::json!({
+            "room_id": metadata.get("room_id"),
+            "joined_room_at": metadata.get("joined_room_at"),
+            "first_segment": joined.len(),
+        }));
+        for (index, entry) in entries.iter().enumerate() {
+            let uri = format!("segment_{:05}.ts", joined.len());
+            let (from, to) = (segment_path(dir, &entry.uri), config.output_dir.join(&uri));
+            std::fs::copy(&from, &to).with_context(|| {
+                format!("Failed to copy {} to {}", from.display(), to.display())
+            })?;
+            joined.push(PlaylistEntry {
+                uri,
+                duration: entry.duration,
+                discontinuity: entry.discontinuity || (part_index > 0 && index == 0),
+            });
+        }
+    }
+
+    // the first part describes where the joined replay starts
+    let mut metadata = parts[0].2.clone();
+    metadata["parts"] = part_metadata.into();
+    if parts
+        .iter()
+        .any(|(_, _, metadata)| metadata["partial"].as_bool() == Some(true))
+    {
+        metadata["partial"] = true.into();
+    }
+    let metadata_path = config.output_dir.join(METADATA_FILE);
+    std::fs::write(&metadata_path, format!("{}\n", metadata))
+        .with_context(|| format!("Failed to write metadata: {}", metadata_path.display()))?;
+    // the playlist is written last, everything it lists is already there
+    std::fs::write(&output_playlist, playlist_m3u8(&joined))
+        .with_context(|| format!("Failed to write playlist: {}", output_playlist.display()))?;
+
+    Ok(JoinSummary {
+        parts: parts.len(),
+        segments: joined.len(),
+        duration: joined.iter().map(|entry| entry.duration).sum(),
+    })
+}
+
+/// Local file of a listed segment, a URI with a segment base URL names it by its last
+/// path component
+fn segment_path(dir: &Path, uri: &str) -> PathBuf {
+    dir.join(uri.rsplit('/').next().unwrap_or(uri))
+}
+
+/// Segments listed in the `index.m3u8` of a converted directory
+fn read_playlist(dir: &Path) -> Result<Vec<PlaylistEntry>> {
+    let playlist_path = dir.join(PLAYLIST_FILE);
+    let content = std::fs::read_to_string(&playlist_path)
+        .with_context(|| format!("Failed to read playlist: {}", playlist_path.display()))?;
+    let mut entries = Vec::new();
+    let mut duration = None;
+    let mut discontinuity = false;
+    for (line_number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line == "#EXT-X-DISCONTINUITY" {
+            discontinuity = true;
+        } else if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+            let seconds = extinf.split(',').next().unwrap_or_default();
+            duration = Some(parse_extinf_duration(seconds).ok_or_else(|| {
+                anyhow!(
+                    "{}:{}: invalid segment duration {:?}",
+                    playlist_path.display(),
+                    line_number + 1,
+                    seconds
+                )
+            })?);
+        } else if !line.is_empty() && !line.starts_with('#') {
+            entries.push(PlaylistEntry {
+                uri: line.to_string(),
+                duration: duration.take().ok_or_else(|| {
+                    anyhow!(
+                        "{}:{}: segment without #EXTINF",
+                        playlist_path.display(),
+                        line_number + 1
+                    )
+                })?,
+                discontinuity: std::mem::take(&mut discontinuity),
+            });
+        }
+    }
+    if entries.is_empty() {
+        return Err(anyhow!("No segments listed in {}", playlist_path.display()));
+    }
+    Ok(entries)
+}
+
+/// `10.5` or `10.500000` seconds, exact to the microsecond
+fn parse_extinf_duration(seconds: &str) -> Option<TimeDelta> {
+    let (whole, fraction) = seconds
+        .trim()
+        .split_once('.')
+        .unwrap_or((seconds.trim(), ""));
+    if fraction.len() > 6 || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let micros = format!("{:0<6}", fraction).parse::<i64>().ok()?;
+    Some(TimeDelta::seconds(whole.parse().ok()?) + TimeDelta::microseconds(micros))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_part(dir: &Path, room_id: &str, durations: &[&str]) {
+        std::fs::create_dir_all(dir).unwrap();
+        let mut playlist = String::from("#EXTM3U8\n#EXT-X-MEDIA-SEQUENCE:0\n");
+        for (index, duration) in durations.iter().enumerate() {
+            let name = format!("segment_{:05}.ts", index);
+            std::fs::write(dir.join(&name), format!("{} {}", room_id, index)).unwrap();
+            playlist.push_str(&format!("#EXTINF:{},\n{}\n", duration, name));
+        }
+        playlist.push_str("#EXT-X-ENDLIST\n");
+        std::fs::write(dir.join(PLAYLIST_FILE), playlist).unwrap();
+        let metadata = serde_json::json!({
+            "room_id": room_id,
+            "joined_room_at": format!("{} joined", room_id),
+            "playlist_file": "index.m3u8",
+        });
+        std::fs::write(dir.join(METADATA_FILE), metadata.to_string()).unwrap();
+    }
+
+    #[test]
+    fn test_run_join() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        write_part(&dir.join("output"), "room_a", &["10.000000", "4.250000"]);
+        write_part(
+            &dir.join("output_001"),
+            "room_b",
+            &["10.000000", "10", "0.5"],
+        );
+        let config = JoinConfig {
+            part_dirs: vec![dir.join("output"), dir.join("output_001")],
+            output_dir: dir.join("joined"),
+        };
+
+        let summary = run_join(&config).unwrap();
+        assert_eq!(summary.parts, 2);
+        assert_eq!(summary.segments, 5);
+        assert_eq!(summary.duration, TimeDelta::milliseconds(34_750));
+        assert_eq!(
+            std::fs::read_to_string(dir.join("joined/index.m3u8")).unwrap(),
+            "#EXTM3U8\n#EXT-X-VERSION:3\n#EXT-X-PLAYLIST-TYPE:VOD\n#EXT-X-MEDIA-SEQUENCE:0\n#EXT-X-TARGETDURATION:10\n\
+             #EXTINF:10.000000,\nsegment_00000.ts\n\
+             #EXTINF:4.250000,\nsegment_00001.ts\n\
+             #EXT-X-DISCONTINUITY\n#EXTINF:10.000000,\nsegment_00002.ts\n\
+             #EXTINF:10.000000,\nsegment_00003.ts\n\
+             #EXTINF:0.500000,\nsegment_00004.ts\n\
+             #EXT-X-ENDLIST\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("joined/segment_00002.ts")).unwrap(),
+            "room_b 0"
+        );
+        let metadata = read_metadata(&dir.join("joined")).unwrap();
+        assert_eq!(metadata["room_id"], "room_a");
+        assert_eq!(metadata["parts"][1]["room_id"], "room_b");
+        assert_eq!(metadata["parts"][1]["first_segment"], 2);
+
+        // a joined playlist keeps its discontinuities when joined again
+        assert_eq!(
+            read_playlist(&dir.join("joined"))
+                .unwrap()
+                .iter()
+                .map(|entry| entry.discontinuity)
+                .collect::<Vec<_>>(),
+            vec![false, false, true, false, false]
+        );
+        let error = run_join(&config).unwrap_err();
+        assert!(error.to_string().contains("already contains"), "{}", error);
+        assert_eq!(
+            segment_path(dir, "https://cdn.example.com/room/segment_00001.ts"),
+            dir.join("segment_00001.ts")
+        );
+
+        std::fs::remove_file(dir.join("output_001/segment_00001.ts")).unwrap();
+        let config = JoinConfig {
+            output_dir: dir.join("joined_2"),
+            ..config
+        };
+        let error = run_join(&config).unwrap_err();
+        assert!(error.to_string().contains("Missing segment"), "{}", error);
+        assert!(!config.output_dir.exists());
+    }
+}
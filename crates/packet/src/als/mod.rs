@@ -1,6 +1,17 @@
+pub mod anonymize;
+pub mod archive;
 pub mod converter;
 pub mod extract;
+pub mod join;
+pub mod options;
 pub mod proto;
+pub mod provenance;
+pub mod retime;
+pub mod split;
+pub mod sync;
+pub mod template;
+pub mod transform;
+pub mod verify;
 
 #[cfg(feature = "audio")]
 mod audio;
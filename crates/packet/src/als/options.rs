@@ -0,0 +1,308 @@
+//! Conversion options file for `convert --config`
+//!
+//! Every key is optional and named like the matching `convert` flag in snake case:
+//!
+//! ```toml
+//! input_format = "mixed"
+//! segment_duration = 6
+//! auto_timestamp = true
+//! drop_keepalive = "pong"
+//! exclude_object_ids = [3, 4]
+//! ```
+//!
+//! Unknown keys are an error. Values given on the command line win over the file.
+
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use super::converter::{AlsConverterConfig, parse_segment_base_url};
+use super::proto::reader::PacketClock;
+
+/// Default `segment_duration`, seconds
+pub const DEFAULT_SEGMENT_DURATION: u64 = 10;
+
+/// Default `legacy_packet_interval`, milliseconds
+pub const DEFAULT_LEGACY_PACKET_INTERVAL: i64 = 100;
+
+/// Conversion options, `None` keeps the default or the value of a lower layer
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConversionOptions {
+    /// 'standard', 'mixed', 'legacy' or 'auto'
+    pub input_format: Option<String>,
+    /// Output directory template
+    pub output: Option<String>,
+    /// Segment duration in seconds
+    pub segment_duration: Option<u64>,
+    pub timeshift: Option<i64>,
+    pub split: Option<bool>,
+    pub start_time: Option<String>,
+    pub data_start_time: Option<String>,
+    pub data_end_time: Option<String>,
+    pub metadata_path: Option<String>,
+    pub auto_timestamp: Option<bool>,
+    pub smooth_timestamps: Option<bool>,
+    pub events_vtt: Option<bool>,
+    pub include_object_ids: Option<Vec<i32>>,
+    pub exclude_object_ids: Option<Vec<i32>>,
+    pub reset_on_rejoin: Option<bool>,
+    pub force: Option<bool>,
+    /// `false` is the `--no-segment-state` flag
+    pub segment_state: Option<bool>,
+    /// `false` is the `--no-camera-swap` flag
+    pub camera_swap: Option<bool>,
+    /// Dedup window in milliseconds
+    pub dedup: Option<i64>,
+    pub timing_log: Option<PathBuf>,
+    /// 'none', 'pong' or 'all'
+    pub drop_keepalive: Option<String>,
+    pub room_id_override: Option<String>,
+    pub provenance: Option<bool>,
+    /// 'dir', 'zip' or 'tar'
+    pub output_target: Option<String>,
+    /// Timestamp of the first packet of a legacy capture, rfc3339. Legacy captures are
+    /// stamped with the time they are read when unset.
+    pub legacy_start_time: Option<String>,
+    /// Milliseconds between legacy packets after `legacy_start_time`
+    pub legacy_packet_interval: Option<i64>,
+    /// Absolute http(s) URL the segments are served from, prepended to their names in
+    /// `index.m3u8`
+    pub segment_base_url: Option<String>,
+    /// 'reorder', 'clamp' or 'error'
+    pub out_of_order: Option<String>,
+    /// Reorder window in milliseconds
+    pub reorder_window: Option<i64>,
+}
+
+impl ConversionOptions {
+    /// Read a `.toml` or `.json` options file
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+        let extension = path.extension().and_then(|ext| ext.to_str());
+        match extension.map(str::to_ascii_lowercase).as_deref() {
+            Some("toml") => Self::from_toml(&content),
+            Some("json") => Self::from_json(&content),
+            _ => Err(anyhow!(
+                "Unsupported config file extension, expected .toml or .json: {}",
+                path.display()
+            )),
+        }
+        .with_context(|| format!("Invalid config file: {}", path.display()))
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self> {
+        toml::from_str(content).map_err(|e| anyhow!("{}", e))
+    }
+
+    pub fn from_json(content: &str) -> Result<Self> {
+        serde_json::from_str(content).map_err(|e| anyhow!("{}", e))
+    }
+
+    /// Layer `overrides` on top, its `Some` values win
+    pub fn overridden_by(self, overrides: Self) -> Self {
+        Self {
+            input_format: overrides.input_format.or(self.input_format),
+            output: overrides.output.or(self.output),
+            segment_duration: overrides.segment_duration.or(self.segment_duration),
+            timeshift: overrides.timeshift.or(self.timeshift),
+            split: overrides.split.or(self.split),
+            start_time: overrides.start_time.or(self.start_time),
+            data_start_time: overrides.data_start_time.or(self.data_start_time),
+            data_end_time: overrides.data_end_time.or(self.data_end_time),
+            metadata_path: overrides.metadata_path.or(self.metadata_path),
+            auto_timestamp: overrides.auto_timestamp.or(self.auto_timestamp),
+            smooth_timestamps: overrides.smooth_timestamps.or(self.smooth_timestamps),
+            events_vtt: overrides.events_vtt.or(self.events_vtt),
+            include_object_ids: overrides.include_object_ids.or(self.include_object_ids),
+            exclude_object_ids: overrides.exclude_object_ids.or(self.exclude_object_ids),
+            reset_on_rejoin: overrides.reset_on_rejoin.or(self.reset_on_rejoin),
+            force: overrides.force.or(self.force),
+            segment_state: overrides.segment_state.or(self.segment_state),
+            camera_swap: overrides.camera_swap.or(self.camera_swap),
+            dedup: overrides.dedup.or(self.dedup),
+            timing_log: overrides.timing_log.or(self.timing_log),
+            drop_keepalive: overrides.drop_keepalive.or(self.drop_keepalive),
+            room_id_override: overrides.room_id_override.or(self.room_id_override),
+            provenance: overrides.provenance.or(self.provenance),
+            output_target: overrides.output_target.or(self.output_target),
+            legacy_start_time: overrides.legacy_start_time.or(self.legacy_start_time),
+            legacy_packet_interval: overrides
+                .legacy_packet_interval
+                .or(self.legacy_packet_interval),
+            segment_base_url: overrides.segment_base_url.or(self.segment_base_url),
+            out_of_order: overrides.out_of_order.or(self.out_of_order),
+            reorder_window: overrides.reorder_window.or(self.reorder_window),
+        }
+    }
+
+    /// Segment duration in seconds, 10 when unset
+    pub fn segment_duration(&self) -> u64 {
+        self.segment_duration.unwrap_or(DEFAULT_SEGMENT_DURATION)
+    }
+
+    /// Converter config with unset options at their defaults
+    pub fn to_config(&self) -> Result<AlsConverterConfig> {
+        let default = AlsConverterConfig::default();
+        if self.include_object_ids.is_some() && self.exclude_object_ids.is_some() {
+            return Err(anyhow!(
+                "include_object_ids and exclude_object_ids cannot be used together"
+            ));
+        }
+        let packet_clock = match &self.legacy_start_time {
+            Some(start) => PacketClock::fixed(
+                DateTime::parse_from_rfc3339(start)
+                    .with_context(|| format!("Invalid legacy_start_time: {}", start))?
+                    .with_timezone(&Utc),
+                TimeDelta::milliseconds(
+                    self.legacy_packet_interval
+                        .unwrap_or(DEFAULT_LEGACY_PACKET_INTERVAL),
+                ),
+            ),
+            None if self.legacy_packet_interval.is_some() => {
+                return Err(anyhow!("legacy_packet_interval needs legacy_start_time"));
+            }
+            None => default.packet_clock,
+        };
+        Ok(AlsConverterConfig {
+            input_format: match &self.input_format {
+                Some(format) => format.parse()?,
+                None => default.input_format,
+            },
+            timeshift: self.timeshift.unwrap_or(default.timeshift),
+            split: self.split.unwrap_or(default.split),
+            start_time: self.start_time.clone(),
+            data_start_time: self.data_start_time.clone(),
+            data_end_time: self.data_end_time.clone(),
+            metadata_path: self.metadata_path.clone(),
+            auto_timestamp: self.auto_timestamp.unwrap_or(default.auto_timestamp),
+            smooth_timestamps: self.smooth_timestamps.unwrap_or(default.smooth_timestamps),
+            write_events_vtt: self.events_vtt.unwrap_or(default.write_events_vtt),
+            include_object_ids: self
+                .include_object_ids
+                .as_ref()
+                .map(|ids| ids.iter().copied().collect()),
+            exclude_object_ids: self
+                .exclude_object_ids
+                .as_ref()
+                .map(|ids| ids.iter().copied().collect()),
+            reset_on_rejoin: self.reset_on_rejoin.unwrap_or(default.reset_on_rejoin),
+            force_output: self.force.unwrap_or(default.force_output),
+            embed_full_state_in_segments: self
+                .segment_state
+                .unwrap_or(default.embed_full_state_in_segments),
+            swap_camera_order: self.camera_swap.unwrap_or(default.swap_camera_order),
+            dedup_window: self.dedup.map(TimeDelta::milliseconds),
+            timing_log_path: self.timing_log.clone(),
+            keepalive_filter: match &self.drop_keepalive {
+                Some(filter) => filter.parse()?,
+                None => default.keepalive_filter,
+            },
+            room_id_override: self.room_id_override.clone(),
+            provenance: self.provenance.unwrap_or(default.provenance),
+            output_target: match &self.output_target {
+                Some(target) => target.parse()?,
+                None => default.output_target,
+            },
+            packet_clock,
+            segment_base_url: self
+                .segment_base_url
+                .as_deref()
+                .map(parse_segment_base_url)
+                .transpose()?,
+            out_of_order: match &self.out_of_order {
+                Some(policy) => policy.parse()?,
+                None => default.out_of_order,
+            },
+            reorder_window: self
+                .reorder_window
+                .map_or(default.reorder_window, TimeDelta::milliseconds),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::als::proto::reader::InputFormat;
+
+    #[test]
+    fn test_conversion_options() {
+        let file = ConversionOptions::from_toml(
+            r#"
+            input_format = "mixed"
+            segment_duration = 6
+            auto_timestamp = true
+            dedup = 2000
+            exclude_object_ids = [3, 4]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            file,
+            ConversionOptions::from_json(
+                r#"{"input_format": "mixed", "segment_duration": 6, "auto_timestamp": true,
+                    "dedup": 2000, "exclude_object_ids": [3, 4]}"#
+            )
+            .unwrap()
+        );
+
+        let cli = ConversionOptions {
+            segment_duration: Some(4),
+            split: Some(true),
+            ..Default::default()
+        };
+        let options = file.overridden_by(cli);
+        assert_eq!(options.segment_duration(), 4);
+        let config = options.to_config().unwrap();
+        assert_eq!(config.input_format, InputFormat::Mixed);
+        assert!(config.auto_timestamp && config.split);
+        assert_eq!(config.dedup_window, Some(TimeDelta::seconds(2)));
+        assert_eq!(config.exclude_object_ids, Some([3, 4].into_iter().collect()));
+        assert!(config.embed_full_state_in_segments);
+        assert_eq!(config.segment_base_url, None);
+
+        let error = ConversionOptions::from_toml("auto_timestmap = true").unwrap_err();
+        assert!(error.to_string().contains("unknown field `auto_timestmap`"));
+        assert!(
+            ConversionOptions::from_toml("input_format = \"zip\"")
+                .unwrap()
+                .to_config()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_segment_base_url() {
+        let base_url = |url: &str| {
+            ConversionOptions {
+                segment_base_url: Some(url.to_string()),
+                ..Default::default()
+            }
+            .to_config()
+            .map(|config| config.segment_base_url.unwrap())
+        };
+        assert_eq!(
+            base_url("https://cdn.example.com/archive/room_1").unwrap(),
+            "https://cdn.example.com/archive/room_1/"
+        );
+        assert_eq!(
+            base_url("https://cdn.example.com/archive/room_1/").unwrap(),
+            "https://cdn.example.com/archive/room_1/"
+        );
+        assert_eq!(
+            base_url("http://cdn.example.com").unwrap(),
+            "http://cdn.example.com/"
+        );
+        for invalid in [
+            "archive/room_1",
+            "ftp://cdn.example.com/archive",
+            "https://cdn.example.com/archive?token=1",
+            "https://cdn.example.com/archive#top",
+        ] {
+            assert!(base_url(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -1,16 +1,30 @@
 //! Analysis layer - responsible for statistics and data processing
 //! No I/O operations, pure business logic
 
-use chrono::{DateTime, Utc};
-use std::collections::HashMap;
+use chrono::{DateTime, TimeDelta, Utc};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 use super::define::{DataFrame, data_frame, data_pack};
-use crate::als::proto::PacketInfo;
+use super::extension::InstantiateObjectExt;
+use crate::als::proto::{PacketInfo, PacketSource, calculate_digest};
+
+/// Upper bound for a single unknown field sample
+pub const MAX_UNKNOWN_SAMPLE_BYTES: usize = 1024;
+
+/// Flagged timestamp gaps kept in `PacketStats`, later ones are only counted
+pub const MAX_TIMESTAMP_GAPS: usize = 1000;
+
+/// Drifted packets kept in `EncodeDriftCheck`, later ones are only counted
+pub const MAX_ENCODE_DRIFTS: usize = 1000;
 
 /// Main analyzer for packet statistics
 #[derive(Debug, Default, Clone)]
 pub struct PacketAnalyzer {
     stats: PacketStats,
+    /// Bytes kept from the first occurrence of each unknown field, 0 = disabled
+    unknown_sample_bytes: usize,
+    /// Timestamp of the previously analyzed packet, for gap detection
+    previous_timestamp: Option<DateTime<Utc>>,
 }
 
 impl PacketAnalyzer {
@@ -18,9 +32,24 @@ impl PacketAnalyzer {
         Self::default()
     }
 
+    /// Keep a truncated raw sample of each unknown field's first occurrence
+    pub fn with_unknown_samples(mut self, max_bytes: usize) -> Self {
+        self.unknown_sample_bytes = max_bytes.min(MAX_UNKNOWN_SAMPLE_BYTES);
+        self
+    }
+
+    /// Flag consecutive packets whose timestamps are further apart than `threshold`,
+    /// in either direction. `None` disables the check.
+    pub fn with_gap_threshold(mut self, threshold: Option<TimeDelta>) -> Self {
+        self.stats.gap_threshold = threshold;
+        self
+    }
+
     /// Analyze a single packet
     pub fn analyze_packet(&mut self, packet: &PacketInfo) {
         self.stats.total_packets += 1;
+        self.stats.track_timestamp(packet.timestamp);
+        self.check_gap(packet.timestamp);
 
         // Analyze data pack
         if let Some(control) = &packet.data_pack.control {
@@ -47,6 +76,11 @@ impl PacketAnalyzer {
         }
     }
 
+    /// Count a packet removed by `KeepaliveFilter` instead of analyzing it
+    pub fn record_keepalive_dropped(&mut self) {
+        self.stats.control.keepalive_dropped += 1;
+    }
+
     /// Get current statistics
     pub fn stats(&self) -> &PacketStats {
         &self.stats
@@ -57,6 +91,21 @@ impl PacketAnalyzer {
         self.stats.merge(&other.stats);
     }
 
+    fn check_gap(&mut self, timestamp: DateTime<Utc>) {
+        let previous = self.previous_timestamp.replace(timestamp);
+        let (Some(threshold), Some(previous)) = (self.stats.gap_threshold, previous) else {
+            return;
+        };
+        let gap = timestamp - previous;
+        if gap.abs() > threshold {
+            self.stats.push_gap(TimestampGap {
+                previous,
+                timestamp,
+                gap,
+            });
+        }
+    }
+
     fn analyze_control(&mut self, control: &data_pack::Control) {
         match control {
             data_pack::Control::Data(_) => self.stats.control.data_count += 1,
@@ -75,11 +124,24 @@ impl PacketAnalyzer {
                 data_frame::Message::InstantiateObject(_) => {
                     self.stats.frames.instantiate_object_count += 1
                 }
-                data_frame::Message::UpdateObject(_) => self.stats.frames.update_object_count += 1,
+                data_frame::Message::UpdateObject(obj) => {
+                    self.stats.frames.update_object_count += 1;
+                    *self
+                        .stats
+                        .frames
+                        .update_object_method_counts
+                        .entry(obj.method)
+                        .or_insert(0) += 1;
+                }
                 data_frame::Message::DestroyObject(_) => {
                     self.stats.frames.destroy_object_count += 1
                 }
-                data_frame::Message::Room(_) => self.stats.frames.room_count += 1,
+                data_frame::Message::Room(room) => {
+                    self.stats.frames.room_count += 1;
+                    if self.stats.room_id.is_none() {
+                        self.stats.room_id = Some(String::from_utf8_lossy(&room.id).to_string());
+                    }
+                }
                 data_frame::Message::AuthorizeResponse(_) => {
                     self.stats.frames.authorize_response_count += 1
                 }
@@ -100,6 +162,20 @@ impl PacketAnalyzer {
                     .unknown_fields
                     .entry(field.field_number)
                     .or_insert(0) += 1;
+                self.stats
+                    .unknown_field_wire_types
+                    .entry(field.field_number)
+                    .or_default()
+                    .insert(field.wire_type);
+                if self.unknown_sample_bytes > 0 {
+                    self.stats
+                        .unknown_field_samples
+                        .entry(field.field_number)
+                        .or_insert_with(|| {
+                            let len = field.raw_bytes.len().min(self.unknown_sample_bytes);
+                            field.raw_bytes[..len].to_vec()
+                        });
+                }
             }
         }
     }
@@ -115,9 +191,56 @@ pub struct PacketStats {
     pub control: ControlStats,
     pub frames: FrameStats,
     pub unknown_fields: HashMap<u32, u32>,
+    /// Truncated raw bytes of the first occurrence of each unknown field
+    pub unknown_field_samples: HashMap<u32, Vec<u8>>,
+    /// Wire types observed for each unknown field
+    pub unknown_field_wire_types: HashMap<u32, BTreeSet<u8>>,
+    pub first_timestamp: Option<DateTime<Utc>>,
+    pub last_timestamp: Option<DateTime<Utc>>,
+    /// Id of the first `Room` frame seen
+    pub room_id: Option<String>,
+    /// Intervals beyond this are flagged in `timestamp_gaps`, `None` = not checked
+    pub gap_threshold: Option<TimeDelta>,
+    /// First `MAX_TIMESTAMP_GAPS` flagged intervals in packet order
+    pub timestamp_gaps: Vec<TimestampGap>,
+    /// All flagged intervals, including those not kept in `timestamp_gaps`
+    pub timestamp_gap_count: u32,
+}
+
+/// Interval between consecutive packets beyond the gap threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampGap {
+    pub previous: DateTime<Utc>,
+    pub timestamp: DateTime<Utc>,
+    /// `timestamp - previous`, negative when the clock jumped back
+    pub gap: TimeDelta,
 }
 
 impl PacketStats {
+    /// Total occurrences of unknown fields
+    pub fn unknown_field_count(&self) -> u32 {
+        self.unknown_fields.values().sum()
+    }
+
+    fn track_timestamp(&mut self, timestamp: DateTime<Utc>) {
+        self.first_timestamp = Some(
+            self.first_timestamp
+                .map_or(timestamp, |first| first.min(timestamp)),
+        );
+        self.last_timestamp = Some(
+            self.last_timestamp
+                .map_or(timestamp, |last| last.max(timestamp)),
+        );
+    }
+
+    fn push_gap(&mut self, gap: TimestampGap) {
+        self.timestamp_gap_count += 1;
+        if self.timestamp_gaps.len() < MAX_TIMESTAMP_GAPS {
+            self.timestamp_gaps.push(gap);
+        }
+    }
+
+    /// Gaps between files are not checked, only those within each side
     pub fn merge(&mut self, other: &PacketStats) {
         self.total_packets += other.total_packets;
         self.packets_with_control += other.packets_with_control;
@@ -129,6 +252,32 @@ impl PacketStats {
         for (field_num, count) in &other.unknown_fields {
             *self.unknown_fields.entry(*field_num).or_insert(0) += count;
         }
+        for (field_num, sample) in &other.unknown_field_samples {
+            self.unknown_field_samples
+                .entry(*field_num)
+                .or_insert_with(|| sample.clone());
+        }
+        for (field_num, wire_types) in &other.unknown_field_wire_types {
+            self.unknown_field_wire_types
+                .entry(*field_num)
+                .or_default()
+                .extend(wire_types);
+        }
+
+        if let Some(first) = other.first_timestamp {
+            self.track_timestamp(first);
+        }
+        if let Some(last) = other.last_timestamp {
+            self.track_timestamp(last);
+        }
+        if self.room_id.is_none() {
+            self.room_id = other.room_id.clone();
+        }
+        self.gap_threshold = self.gap_threshold.or(other.gap_threshold);
+        let room = MAX_TIMESTAMP_GAPS.saturating_sub(self.timestamp_gaps.len());
+        self.timestamp_gaps
+            .extend(other.timestamp_gaps.iter().take(room).copied());
+        self.timestamp_gap_count += other.timestamp_gap_count;
     }
 }
 
@@ -139,6 +288,8 @@ pub struct ControlStats {
     pub segment_started_at_count: u32,
     pub cache_ended_count: u32,
     pub total: u32,
+    /// Packets removed by `KeepaliveFilter`, not part of the counts above
+    pub keepalive_dropped: u32,
 }
 
 impl ControlStats {
@@ -148,6 +299,7 @@ impl ControlStats {
         self.segment_started_at_count += other.segment_started_at_count;
         self.cache_ended_count += other.cache_ended_count;
         self.total += other.total;
+        self.keepalive_dropped += other.keepalive_dropped;
     }
 }
 
@@ -160,6 +312,8 @@ pub struct FrameStats {
     pub authorize_response_count: u32,
     pub join_room_response_count: u32,
     pub total: u32,
+    /// `UpdateObject` occurrences per method id
+    pub update_object_method_counts: HashMap<i32, u32>,
 }
 
 impl FrameStats {
@@ -171,6 +325,21 @@ impl FrameStats {
         self.authorize_response_count += other.authorize_response_count;
         self.join_room_response_count += other.join_room_response_count;
         self.total += other.total;
+        for (method, count) in &other.update_object_method_counts {
+            *self.update_object_method_counts.entry(*method).or_insert(0) += count;
+        }
+    }
+
+    /// Most frequent `UpdateObject` method ids, ties ordered by method id
+    pub fn top_update_object_methods(&self, limit: usize) -> Vec<(i32, u32)> {
+        let mut methods: Vec<_> = self
+            .update_object_method_counts
+            .iter()
+            .map(|(method, count)| (*method, *count))
+            .collect();
+        methods.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        methods.truncate(limit);
+        methods
     }
 }
 
@@ -178,6 +347,7 @@ impl FrameStats {
 pub struct PacketFilter {
     pub start_time: Option<DateTime<Utc>>,
     pub end_time: Option<DateTime<Utc>>,
+    pub keepalive: KeepaliveFilter,
 }
 
 impl PacketFilter {
@@ -193,9 +363,16 @@ impl PacketFilter {
                     .ok()
                     .map(|dt| dt.with_timezone(&Utc))
             }),
+            keepalive: KeepaliveFilter::default(),
         }
     }
 
+    /// Set which keepalive packets to drop (Builder pattern)
+    pub fn with_keepalive(mut self, keepalive: KeepaliveFilter) -> Self {
+        self.keepalive = keepalive;
+        self
+    }
+
     /// Check if a packet should be included
     pub fn should_include(&self, timestamp: &DateTime<Utc>) -> bool {
         if let Some(start) = &self.start_time {
@@ -221,12 +398,49 @@ impl PacketFilter {
     }
 }
 
+/// Which keepalive control packets to drop before analysis or conversion
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeepaliveFilter {
+    #[default]
+    Keep,
+    /// Drop `Control::Pong` packets
+    Pong,
+    /// Drop `Control::Pong` and `Control::Data(false)` packets
+    PongAndIdleData,
+}
+
+impl KeepaliveFilter {
+    /// Whether `packet` is a keepalive this filter drops
+    pub fn drops(&self, packet: &PacketInfo) -> bool {
+        match (self, &packet.data_pack.control) {
+            (Self::Keep, _) => false,
+            (_, Some(data_pack::Control::Pong(_))) => true,
+            (Self::PongAndIdleData, Some(data_pack::Control::Data(false))) => true,
+            _ => false,
+        }
+    }
+}
+
+impl std::str::FromStr for KeepaliveFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "none" => Ok(Self::Keep),
+            "pong" => Ok(Self::Pong),
+            "all" => Ok(Self::PongAndIdleData),
+            _ => Err(anyhow::anyhow!("Unsupported keepalive filter: {}", s)),
+        }
+    }
+}
+
 // Helper structures for protobuf field parsing
 #[derive(Debug)]
 struct ProtobufField {
     field_number: u32,
-    #[allow(unused)]
     wire_type: u8,
+    /// Field value bytes (length prefix excluded for length-delimited fields)
+    raw_bytes: Vec<u8>,
 }
 
 fn parse_protobuf_fields(data: &[u8]) -> Vec<ProtobufField> {
@@ -252,6 +466,7 @@ fn parse_field(cursor: &mut std::io::Cursor<&[u8]>) -> anyhow::Result<ProtobufFi
     let wire_type = (tag & 0x7) as u8;
 
     // Skip field data based on wire type
+    let mut start = cursor.position() as usize;
     match wire_type {
         0 => {
             read_varint(cursor)?;
@@ -267,6 +482,7 @@ fn parse_field(cursor: &mut std::io::Cursor<&[u8]>) -> anyhow::Result<ProtobufFi
             if cursor.remaining() < len as usize {
                 return Err(anyhow::anyhow!("Not enough bytes"));
             }
+            start = cursor.position() as usize;
             cursor.advance(len as usize);
         }
         5 => {
@@ -277,10 +493,12 @@ fn parse_field(cursor: &mut std::io::Cursor<&[u8]>) -> anyhow::Result<ProtobufFi
         }
         _ => return Err(anyhow::anyhow!("Unsupported wire type: {}", wire_type)),
     }
+    let end = cursor.position() as usize;
 
     Ok(ProtobufField {
         field_number,
         wire_type,
+        raw_bytes: cursor.get_ref()[start..end].to_vec(),
     })
 }
 
@@ -311,15 +529,459 @@ fn read_varint(cursor: &mut std::io::Cursor<&[u8]>) -> anyhow::Result<u64> {
     Ok(result)
 }
 
+/// Protobuf wire type name
+pub fn wire_type_name(wire_type: u8) -> &'static str {
+    match wire_type {
+        0 => "varint",
+        1 => "fixed64",
+        2 => "length-delimited",
+        5 => "fixed32",
+        _ => "unknown",
+    }
+}
+
+/// Best guess of an unknown field's type from its wire types and raw sample
+pub fn unknown_field_hypothesis(wire_types: &BTreeSet<u8>, sample: Option<&[u8]>) -> String {
+    let wire_type = match wire_types.iter().collect::<Vec<_>>().as_slice() {
+        [wire_type] => **wire_type,
+        [] => return "no wire type recorded".to_string(),
+        _ => return "mixed wire types, possibly several messages reusing the number".to_string(),
+    };
+
+    match (wire_type, sample) {
+        (0, Some(sample)) => {
+            let value = read_varint(&mut std::io::Cursor::new(sample)).ok();
+            match value {
+                Some(0 | 1) => "bool or enum".to_string(),
+                Some(value) => format!("int or enum (first value {})", value),
+                None => "int, enum or bool".to_string(),
+            }
+        }
+        (0, None) => "int, enum or bool".to_string(),
+        (1, Some(sample)) if sample.len() == 8 => {
+            let bytes: [u8; 8] = sample.try_into().unwrap_or_default();
+            format!(
+                "fixed64 or double (as double: {})",
+                f64::from_le_bytes(bytes)
+            )
+        }
+        (1, _) => "fixed64 or double".to_string(),
+        (5, Some(sample)) if sample.len() == 4 => {
+            let bytes: [u8; 4] = sample.try_into().unwrap_or_default();
+            format!("fixed32 or float (as float: {})", f32::from_le_bytes(bytes))
+        }
+        (5, _) => "fixed32 or float".to_string(),
+        (2, Some([])) => "empty string, bytes or message".to_string(),
+        (2, Some(sample)) => match std::str::from_utf8(sample) {
+            Ok(text) if !text.chars().any(char::is_control) => "string".to_string(),
+            _ if is_complete_message(sample) => "nested message".to_string(),
+            _ => "bytes or packed repeated".to_string(),
+        },
+        (2, None) => "string, bytes or nested message".to_string(),
+        _ => "unsupported wire type".to_string(),
+    }
+}
+
+/// Whether the bytes parse as protobuf fields without leftovers
+fn is_complete_message(data: &[u8]) -> bool {
+    let mut cursor = std::io::Cursor::new(data);
+    while (cursor.position() as usize) < data.len() {
+        match parse_field(&mut cursor) {
+            Ok(field) if field.field_number > 0 => {}
+            _ => return false,
+        }
+    }
+    !data.is_empty()
+}
+
 fn is_known_field_number(field_number: u32) -> bool {
-    matches!(
-        field_number,
-        1 | 2 | 3 | 4 | 6 | 7 | 8 | 9 | 10 | 11 | 14 | 15 | 16 | 128 | 129 | 130 | 143 | 144 | 147
-    )
+    KNOWN_FIELDS.iter().any(|f| f.number == field_number)
+}
+
+/// A field declared in `datapack.proto`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownField {
+    pub message: &'static str,
+    pub number: u32,
+    pub name: &'static str,
+    /// Message type of the field, `None` for scalars and bytes
+    pub nested: Option<&'static str>,
+}
+
+const fn known(
+    message: &'static str,
+    number: u32,
+    name: &'static str,
+    nested: Option<&'static str>,
+) -> KnownField {
+    KnownField {
+        message,
+        number,
+        name,
+        nested,
+    }
+}
+
+/// Fields of `datapack.proto`, in declaration order
+pub const KNOWN_FIELDS: &[KnownField] = &[
+    known("DataPack", 2, "data", None),
+    known("DataPack", 10, "pong", None),
+    known("DataPack", 14, "segment_started_at", None),
+    known("DataPack", 15, "cache_ended", None),
+    known("DataPack", 16, "frames", Some("DataFrame")),
+    known(
+        "DataFrame",
+        128,
+        "instantiate_object",
+        Some("InstantiateObject"),
+    ),
+    known("DataFrame", 129, "update_object", Some("UpdateObject")),
+    known("DataFrame", 130, "destroy_object", Some("DestroyObject")),
+    known("DataFrame", 143, "room", Some("Room")),
+    known(
+        "DataFrame",
+        144,
+        "authorize_response",
+        Some("AuthorizeResponse"),
+    ),
+    known(
+        "DataFrame",
+        147,
+        "join_room_response",
+        Some("JoinRoomResponse"),
+    ),
+    known("RoomAll", 1, "room_id", None),
+    known("Room", 1, "id", None),
+    known("Room", 2, "started_at", None),
+    known("Room", 3, "ended_at", None),
+    known(
+        "InstantiateObject",
+        3,
+        "current_player",
+        Some("CurrentPlayer"),
+    ),
+    known("InstantiateObject", 4, "room_all", Some("RoomAll")),
+    known("InstantiateObject", 7, "player_id", None),
+    known("InstantiateObject", 8, "object_id", None),
+    known("InstantiateObject", 9, "owner_id", None),
+    known("InstantiateObject", 10, "prefab_name", None),
+    known("InstantiateObject", 11, "init_data", None),
+    known("UpdateObject", 2, "current_player", Some("CurrentPlayer")),
+    known("UpdateObject", 3, "room_all", Some("RoomAll")),
+    known("UpdateObject", 6, "player_id", None),
+    known("UpdateObject", 8, "object_id", None),
+    known("UpdateObject", 9, "method", None),
+    known("UpdateObject", 10, "payload", None),
+    known("DestroyObject", 2, "current_player", Some("CurrentPlayer")),
+    known("DestroyObject", 3, "room_all", Some("RoomAll")),
+    known("DestroyObject", 6, "player_id", None),
+    known("DestroyObject", 8, "object_id", None),
+    known("AuthorizeResponse", 1, "player_id", None),
+    known("AuthorizeResponse", 2, "role", None),
+    known("AuthorizeResponse", 3, "allowed_room_ids", None),
+    known("JoinRoomResponse", 1, "room", Some("Room")),
+    known("JoinRoomResponse", 2, "joined_at", None),
+];
+
+/// Which `KNOWN_FIELDS` a capture exercises, and which undeclared fields it has
+#[derive(Debug, Default, Clone)]
+pub struct SchemaCoverage {
+    pub total_packets: u32,
+    /// Occurrences per `(message, field number)` of known fields
+    pub known: HashMap<(&'static str, u32), u32>,
+    /// Occurrences per `(message, field number)` of fields missing from the schema
+    pub unknown: BTreeMap<(&'static str, u32), u32>,
+    pub unknown_wire_types: BTreeMap<(&'static str, u32), BTreeSet<u8>>,
+}
+
+impl SchemaCoverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Walk the raw DataPack bytes of a packet, descending into known nested messages
+    pub fn record_packet(&mut self, packet: &PacketInfo) {
+        self.total_packets += 1;
+        self.record_message("DataPack", &packet.raw_data);
+    }
+
+    fn record_message(&mut self, message: &'static str, data: &[u8]) {
+        for field in parse_protobuf_fields(data) {
+            let known = KNOWN_FIELDS
+                .iter()
+                .find(|f| f.message == message && f.number == field.field_number);
+            match known {
+                Some(known) => {
+                    *self.known.entry((message, known.number)).or_insert(0) += 1;
+                    if let (Some(nested), 2) = (known.nested, field.wire_type) {
+                        self.record_message(nested, &field.raw_bytes);
+                    }
+                }
+                None => {
+                    let key = (message, field.field_number);
+                    *self.unknown.entry(key).or_insert(0) += 1;
+                    self.unknown_wire_types
+                        .entry(key)
+                        .or_default()
+                        .insert(field.wire_type);
+                }
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &SchemaCoverage) {
+        self.total_packets += other.total_packets;
+        for (key, count) in &other.known {
+            *self.known.entry(*key).or_insert(0) += count;
+        }
+        for (key, count) in &other.unknown {
+            *self.unknown.entry(*key).or_insert(0) += count;
+        }
+        for (key, wire_types) in &other.unknown_wire_types {
+            self.unknown_wire_types
+                .entry(*key)
+                .or_default()
+                .extend(wire_types);
+        }
+    }
+
+    /// Occurrences of a known field, 0 when it never appeared
+    pub fn count(&self, field: &KnownField) -> u32 {
+        self.known
+            .get(&(field.message, field.number))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Known fields that never appeared
+    pub fn missing(&self) -> Vec<&'static KnownField> {
+        KNOWN_FIELDS.iter().filter(|f| self.count(f) == 0).collect()
+    }
+}
+
+/// Occurrences of one prefab in a capture
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PrefabUsage {
+    /// `InstantiateObject` frames naming the prefab
+    pub instances: u32,
+    /// `UpdateObject` frames sent to objects of the prefab
+    pub updates: u32,
+}
+
+/// Unique prefab names of a capture, from its `InstantiateObject` frames
+#[derive(Debug, Default, Clone)]
+pub struct PrefabCatalog {
+    /// object_id to prefab_name mapping, updates of unknown objects are not counted
+    objects: HashMap<i32, String>,
+    pub prefabs: BTreeMap<String, PrefabUsage>,
+}
+
+impl PrefabCatalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_packet(&mut self, packet: &PacketInfo) {
+        for frame in &packet.data_pack.frames {
+            match &frame.message {
+                Some(data_frame::Message::InstantiateObject(obj)) => {
+                    let prefab_name = obj.prefab_name_lossy();
+                    self.prefabs
+                        .entry(prefab_name.clone())
+                        .or_default()
+                        .instances += 1;
+                    self.objects.insert(obj.object_id, prefab_name);
+                }
+                Some(data_frame::Message::UpdateObject(obj)) => {
+                    if let Some(usage) = self
+                        .objects
+                        .get(&obj.object_id)
+                        .and_then(|prefab_name| self.prefabs.get_mut(prefab_name))
+                    {
+                        usage.updates += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Interval between two consecutive packets, ending at packet `index` of the capture
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexedGap {
+    /// Zero based packet index in read order
+    pub index: u64,
+    pub gap: TimestampGap,
+}
+
+/// Timestamp monotonicity and gaps of a capture in read order, what segmenting it sees
+#[derive(Debug, Default, Clone)]
+pub struct TimestampCheck {
+    pub total_packets: u64,
+    pub first_timestamp: Option<DateTime<Utc>>,
+    pub last_timestamp: Option<DateTime<Utc>>,
+    /// Forward intervals longer than this count as gaps
+    pub gap_threshold: TimeDelta,
+    /// First `MAX_TIMESTAMP_GAPS` packets stamped earlier than the packet before them
+    pub regressions: Vec<IndexedGap>,
+    /// All packets stamped earlier than the packet before them
+    pub regression_count: u64,
+    pub largest_regression: Option<IndexedGap>,
+    pub largest_gap: Option<IndexedGap>,
+    pub gap_count: u64,
+    /// Sum of the intervals longer than `gap_threshold`
+    pub gap_time: TimeDelta,
+    previous_timestamp: Option<DateTime<Utc>>,
+}
+
+impl TimestampCheck {
+    pub fn new(gap_threshold: TimeDelta) -> Self {
+        Self {
+            gap_threshold,
+            ..Default::default()
+        }
+    }
+
+    pub fn record_packet(&mut self, packet: &PacketInfo) {
+        let timestamp = packet.timestamp;
+        let index = self.total_packets;
+        self.total_packets += 1;
+        self.first_timestamp.get_or_insert(timestamp);
+        self.last_timestamp = Some(timestamp);
+        let Some(previous) = self.previous_timestamp.replace(timestamp) else {
+            return;
+        };
+        let step = IndexedGap {
+            index,
+            gap: TimestampGap {
+                previous,
+                timestamp,
+                gap: timestamp - previous,
+            },
+        };
+        if step.gap.gap < TimeDelta::zero() {
+            self.regression_count += 1;
+            if self.regressions.len() < MAX_TIMESTAMP_GAPS {
+                self.regressions.push(step);
+            }
+            if self
+                .largest_regression
+                .is_none_or(|largest| step.gap.gap < largest.gap.gap)
+            {
+                self.largest_regression = Some(step);
+            }
+            return;
+        }
+        if self
+            .largest_gap
+            .is_none_or(|largest| step.gap.gap > largest.gap.gap)
+        {
+            self.largest_gap = Some(step);
+        }
+        if step.gap.gap > self.gap_threshold {
+            self.gap_count += 1;
+            self.gap_time += step.gap.gap;
+        }
+    }
+
+    pub fn is_monotonic(&self) -> bool {
+        self.regression_count == 0
+    }
+}
+
+/// A packet whose protobuf, encoded again from its decoded `DataPack`, differs from the
+/// bytes it was read with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodeDrift {
+    /// Zero based packet index in read order
+    pub index: u64,
+    pub source: Option<PacketSource>,
+    pub timestamp: DateTime<Utc>,
+    pub original_digest: String,
+    pub reencoded_digest: String,
+    pub original_len: usize,
+    pub reencoded_len: usize,
+    /// Offset of the first byte that differs, the shorter length when one is a prefix
+    pub first_difference: usize,
+    /// Indexes of the frames whose bytes differ, empty when only the frame order or
+    /// the control field differs
+    pub frames: Vec<usize>,
+}
+
+/// Packets the custom order encoder does not reproduce byte for byte
+#[derive(Debug, Default, Clone)]
+pub struct EncodeDriftCheck {
+    pub total_packets: u64,
+    pub total_frames: u64,
+    /// First `MAX_ENCODE_DRIFTS` drifted packets
+    pub drifts: Vec<EncodeDrift>,
+    pub drift_count: u64,
+    pub drifted_frames: u64,
+}
+
+impl EncodeDriftCheck {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_packet(&mut self, packet: &PacketInfo) {
+        let index = self.total_packets;
+        self.total_packets += 1;
+        self.total_frames += packet.data_pack.frames.len() as u64;
+        let reencoded = packet.protobuf_to_vec();
+        if reencoded == packet.raw_data {
+            return;
+        }
+
+        // Frames as read are the field 16 values of the packet, the decoder keeps them
+        // in the same order
+        let original_frames: Vec<_> = parse_protobuf_fields(&packet.raw_data)
+            .into_iter()
+            .filter(|field| field.field_number == 16 && field.wire_type == 2)
+            .map(|field| calculate_digest(&field.raw_bytes))
+            .collect();
+        let reencoded_frames: Vec<_> = packet
+            .frame_digests()
+            .into_iter()
+            .map(|(_, digest)| digest)
+            .collect();
+        let frames: Vec<_> = (0..original_frames.len().max(reencoded_frames.len()))
+            .filter(|&i| original_frames.get(i) != reencoded_frames.get(i))
+            .collect();
+        let first_difference = packet
+            .raw_data
+            .iter()
+            .zip(&reencoded)
+            .position(|(a, b)| a != b)
+            .unwrap_or(packet.raw_data.len().min(reencoded.len()));
+
+        self.drift_count += 1;
+        self.drifted_frames += frames.len() as u64;
+        if self.drifts.len() < MAX_ENCODE_DRIFTS {
+            self.drifts.push(EncodeDrift {
+                index,
+                source: packet.source,
+                timestamp: packet.timestamp,
+                original_digest: packet.protobuf_digest(),
+                reencoded_digest: calculate_digest(&reencoded),
+                original_len: packet.raw_data.len(),
+                reencoded_len: reencoded.len(),
+                first_difference,
+                frames,
+            });
+        }
+    }
+
+    /// Every packet encodes back to the bytes it was read with
+    pub fn is_exact(&self) -> bool {
+        self.drift_count == 0
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::super::define::{DataPack, InstantiateObject, RoomAll, UpdateObject, update_object};
     use super::*;
 
     #[test]
@@ -346,4 +1008,366 @@ mod tests {
         stats1.merge(&stats2);
         assert_eq!(stats1.total_packets, 30);
     }
+
+    #[test]
+    fn test_keepalive_filter() {
+        let packet = |control| PacketInfo {
+            timestamp: Utc::now(),
+            data_pack: DataPack {
+                control: Some(control),
+                ..Default::default()
+            },
+            raw_data: Vec::new(),
+            source: None,
+        };
+        let pong = packet(data_pack::Control::Pong(true));
+        let idle = packet(data_pack::Control::Data(false));
+        let data = packet(data_pack::Control::Data(true));
+
+        let keep = KeepaliveFilter::default();
+        assert!(!keep.drops(&pong) && !keep.drops(&idle) && !keep.drops(&data));
+        let filter: KeepaliveFilter = "pong".parse().unwrap();
+        assert!(filter.drops(&pong) && !filter.drops(&idle) && !filter.drops(&data));
+        let filter: KeepaliveFilter = "all".parse().unwrap();
+        assert!(filter.drops(&pong) && filter.drops(&idle) && !filter.drops(&data));
+        assert!("ping".parse::<KeepaliveFilter>().is_err());
+
+        let mut analyzer = PacketAnalyzer::new();
+        analyzer.record_keepalive_dropped();
+        let mut stats = analyzer.stats().clone();
+        stats.merge(analyzer.stats());
+        assert_eq!(stats.control.keepalive_dropped, 2);
+        assert_eq!(stats.control.total, 0);
+    }
+
+    #[test]
+    fn test_update_object_method_counts() {
+        let update = |method| DataFrame {
+            message: Some(data_frame::Message::UpdateObject(UpdateObject {
+                method,
+                ..Default::default()
+            })),
+        };
+        let packet = PacketInfo {
+            timestamp: Utc::now(),
+            data_pack: DataPack {
+                frames: vec![update(3), update(1), update(3), update(2), update(1)],
+                ..Default::default()
+            },
+            raw_data: Vec::new(),
+            source: None,
+        };
+
+        let mut analyzer = PacketAnalyzer::new();
+        analyzer.analyze_packet(&packet);
+        let mut stats = analyzer.stats().clone();
+        stats.merge(&analyzer.stats().clone());
+        assert_eq!(stats.frames.update_object_count, 10);
+        assert_eq!(
+            stats.frames.top_update_object_methods(2),
+            vec![(1, 4), (3, 4)]
+        );
+        assert_eq!(stats.frames.top_update_object_methods(10).len(), 3);
+    }
+
+    #[test]
+    fn test_schema_coverage() {
+        let mut packet = PacketInfo {
+            timestamp: Utc::now(),
+            data_pack: DataPack {
+                control: Some(data_pack::Control::Data(true)),
+                frames: vec![DataFrame {
+                    message: Some(data_frame::Message::UpdateObject(UpdateObject {
+                        target: Some(update_object::Target::RoomAll(RoomAll {
+                            room_id: b"room".to_vec(),
+                        })),
+                        object_id: 5,
+                        method: 3,
+                        ..Default::default()
+                    })),
+                }],
+            },
+            raw_data: Vec::new(),
+            source: None,
+        };
+        packet.raw_data = packet.protobuf_to_vec();
+        // field 200 (varint) next to the declared ones
+        packet.raw_data.extend_from_slice(&[0xc0, 0x0c, 0x01]);
+
+        let mut coverage = SchemaCoverage::new();
+        coverage.record_packet(&packet);
+        let mut merged = SchemaCoverage::new();
+        merged.merge(&coverage);
+        merged.merge(&coverage);
+
+        let count = |message, number| {
+            merged.count(
+                KNOWN_FIELDS
+                    .iter()
+                    .find(|f| f.message == message && f.number == number)
+                    .unwrap(),
+            )
+        };
+        assert_eq!(merged.total_packets, 2);
+        assert_eq!(count("DataPack", 2), 2);
+        assert_eq!(count("DataFrame", 129), 2);
+        assert_eq!(count("UpdateObject", 8), 2);
+        assert_eq!(count("UpdateObject", 9), 2);
+        assert_eq!(count("RoomAll", 1), 2);
+        assert_eq!(count("UpdateObject", 10), 0);
+        assert!(
+            merged
+                .missing()
+                .iter()
+                .any(|f| f.message == "Room" && f.name == "id")
+        );
+        assert_eq!(merged.unknown[&("DataPack", 200)], 2);
+        assert_eq!(
+            merged.unknown_wire_types[&("DataPack", 200)],
+            BTreeSet::from([0])
+        );
+    }
+
+    #[test]
+    fn test_unknown_field_samples() {
+        // field 200 (length-delimited) with 4 bytes, then field 201 (varint)
+        let raw_data = vec![0xc2, 0x0c, 0x04, 0xde, 0xad, 0xbe, 0xef, 0xc8, 0x0c, 0x2a];
+        let packet = PacketInfo {
+            timestamp: Utc::now(),
+            data_pack: Default::default(),
+            raw_data,
+            source: None,
+        };
+
+        let mut analyzer = PacketAnalyzer::new().with_unknown_samples(2);
+        analyzer.analyze_packet(&packet);
+        analyzer.analyze_packet(&packet);
+        let stats = analyzer.stats();
+        assert_eq!(stats.unknown_fields[&200], 2);
+        assert_eq!(stats.unknown_field_samples[&200], vec![0xde, 0xad]);
+        assert_eq!(stats.unknown_field_samples[&201], vec![0x2a]);
+
+        let mut analyzer = PacketAnalyzer::new();
+        analyzer.analyze_packet(&packet);
+        assert!(analyzer.stats().unknown_field_samples.is_empty());
+    }
+
+    #[test]
+    fn test_timestamp_gaps() {
+        let start = Utc::now();
+        let packet = |ms| PacketInfo::create_cache_end(start + TimeDelta::milliseconds(ms));
+        let threshold = Some(TimeDelta::seconds(1));
+
+        let mut analyzer = PacketAnalyzer::new().with_gap_threshold(threshold);
+        for ms in [0, 500, 1000, 6000, 6500, 5000] {
+            analyzer.analyze_packet(&packet(ms));
+        }
+        let stats = analyzer.stats();
+        assert_eq!(stats.timestamp_gap_count, 2);
+        assert_eq!(
+            stats.timestamp_gaps[0],
+            TimestampGap {
+                previous: start + TimeDelta::seconds(1),
+                timestamp: start + TimeDelta::seconds(6),
+                gap: TimeDelta::seconds(5),
+            }
+        );
+        assert_eq!(stats.timestamp_gaps[1].gap, TimeDelta::milliseconds(-1500));
+
+        let mut other = PacketAnalyzer::new().with_gap_threshold(threshold);
+        other.analyze_packet(&packet(20_000));
+        other.analyze_packet(&packet(30_000));
+        let mut merged = PacketAnalyzer::new();
+        merged.merge(&analyzer);
+        merged.merge(&other);
+        assert_eq!(merged.stats().gap_threshold, threshold);
+        assert_eq!(merged.stats().timestamp_gap_count, 3);
+
+        let mut unchecked = PacketAnalyzer::new();
+        unchecked.analyze_packet(&packet(0));
+        unchecked.analyze_packet(&packet(60_000));
+        assert!(unchecked.stats().timestamp_gaps.is_empty());
+    }
+
+    #[test]
+    fn test_timestamp_check() {
+        let start = Utc::now();
+        let at = |ms| start + TimeDelta::milliseconds(ms);
+        let mut check = TimestampCheck::new(TimeDelta::seconds(1));
+        for ms in [0, 500, 3000, 2500, 3500, 3400, 9000, 9100] {
+            check.record_packet(&PacketInfo::create_cache_end(at(ms)));
+        }
+        assert_eq!(check.total_packets, 8);
+        assert!(!check.is_monotonic());
+        assert_eq!(check.regression_count, 2);
+        assert_eq!(
+            check
+                .regressions
+                .iter()
+                .map(|r| r.index)
+                .collect::<Vec<_>>(),
+            vec![3, 5]
+        );
+        let largest = check.largest_regression.unwrap();
+        assert_eq!(
+            (largest.index, largest.gap.gap),
+            (3, TimeDelta::milliseconds(-500))
+        );
+        let largest = check.largest_gap.unwrap();
+        assert_eq!(
+            (largest.index, largest.gap.previous, largest.gap.timestamp),
+            (6, at(3400), at(9000))
+        );
+        // 500 -> 3000 and 3400 -> 9000
+        assert_eq!(check.gap_count, 2);
+        assert_eq!(check.gap_time, TimeDelta::milliseconds(8100));
+        assert_eq!(
+            (check.first_timestamp, check.last_timestamp),
+            (Some(at(0)), Some(at(9100)))
+        );
+    }
+
+    #[test]
+    fn test_encode_drift_check() {
+        use super::super::define::Room;
+        use prost::Message;
+        use prost::encoding::{WireType, encode_key, encode_varint};
+
+        let timestamp = Utc::now();
+        let read = |raw_data: Vec<u8>| PacketInfo {
+            timestamp,
+            data_pack: DataPack::decode(raw_data.as_slice()).unwrap(),
+            raw_data,
+            source: None,
+        };
+        let room = Room {
+            id: b"room".to_vec(),
+            started_at: 0,
+            ended_at: 0,
+        };
+        let generated = PacketInfo::create_room_frame(timestamp, room);
+        let mut check = EncodeDriftCheck::new();
+
+        // Bytes in the custom order come back unchanged
+        check.record_packet(&read(generated.protobuf_to_vec()));
+        assert!(check.is_exact());
+
+        // Control field before the frames, the frame itself is identical
+        let control_first = generated.data_pack.encode_to_vec();
+        check.record_packet(&read(control_first.clone()));
+        // A frame with a field the decoder drops
+        let mut frame = generated.data_pack.frames[0].encode_to_vec();
+        encode_key(1000, WireType::Varint, &mut frame);
+        encode_varint(1, &mut frame);
+        let mut unknown_field = Vec::new();
+        encode_key(16, WireType::LengthDelimited, &mut unknown_field);
+        encode_varint(frame.len() as u64, &mut unknown_field);
+        unknown_field.extend(&frame);
+        check.record_packet(&read(unknown_field.clone()));
+
+        assert_eq!((check.total_packets, check.total_frames), (3, 3));
+        assert_eq!((check.drift_count, check.drifted_frames), (2, 1));
+        let reordered = &check.drifts[0];
+        assert_eq!(reordered.index, 1);
+        assert!(reordered.frames.is_empty());
+        assert_eq!(reordered.first_difference, 0);
+        assert_eq!(reordered.original_len, reordered.reencoded_len);
+        assert_eq!(reordered.original_digest, calculate_digest(&control_first));
+        let dropped = &check.drifts[1];
+        assert_eq!(dropped.frames, vec![0]);
+        assert_eq!(dropped.original_len, dropped.reencoded_len + 3);
+        // Right after the two byte field 16 key, at the length of the frame
+        assert_eq!(dropped.first_difference, 2);
+        assert_ne!(dropped.original_digest, dropped.reencoded_digest);
+    }
+
+    #[test]
+    fn test_unknown_field_hypothesis() {
+        // field 200 holds a nested message (field 1 = 5), field 201 a varint
+        let raw_data = vec![0xc2, 0x0c, 0x02, 0x08, 0x05, 0xc8, 0x0c, 0x01];
+        let packet = PacketInfo {
+            timestamp: Utc::now(),
+            data_pack: Default::default(),
+            raw_data,
+            source: None,
+        };
+
+        let mut analyzer = PacketAnalyzer::new().with_unknown_samples(16);
+        analyzer.analyze_packet(&packet);
+        let stats = analyzer.stats();
+        assert_eq!(stats.unknown_field_wire_types[&200], BTreeSet::from([2]));
+        assert_eq!(stats.unknown_field_wire_types[&201], BTreeSet::from([0]));
+        assert_eq!(
+            unknown_field_hypothesis(
+                &stats.unknown_field_wire_types[&200],
+                stats.unknown_field_samples.get(&200).map(Vec::as_slice)
+            ),
+            "nested message"
+        );
+        assert_eq!(
+            unknown_field_hypothesis(&stats.unknown_field_wire_types[&201], None),
+            "int, enum or bool"
+        );
+        assert_eq!(
+            unknown_field_hypothesis(&BTreeSet::from([2]), Some(b"hello")),
+            "string"
+        );
+        assert!(unknown_field_hypothesis(&BTreeSet::from([0, 2]), None).starts_with("mixed"));
+    }
+
+    #[test]
+    fn test_prefab_catalog() {
+        let instantiate = |object_id, prefab_name: &[u8]| DataFrame {
+            message: Some(data_frame::Message::InstantiateObject(InstantiateObject {
+                object_id,
+                prefab_name: prefab_name.to_vec(),
+                ..Default::default()
+            })),
+        };
+        let update = |object_id| DataFrame {
+            message: Some(data_frame::Message::UpdateObject(UpdateObject {
+                object_id,
+                ..Default::default()
+            })),
+        };
+        let packet = PacketInfo {
+            timestamp: Utc::now(),
+            data_pack: DataPack {
+                control: Some(data_pack::Control::Data(true)),
+                frames: vec![
+                    instantiate(1, b"Character"),
+                    instantiate(2, b"Character"),
+                    instantiate(3, b"Stage\xff"),
+                    update(1),
+                    update(2),
+                    update(3),
+                    update(9),
+                ],
+            },
+            raw_data: Vec::new(),
+            source: None,
+        };
+
+        let mut catalog = PrefabCatalog::new();
+        catalog.record_packet(&packet);
+        assert_eq!(
+            catalog.prefabs.into_iter().collect::<Vec<_>>(),
+            vec![
+                (
+                    "Character".to_string(),
+                    PrefabUsage {
+                        instances: 2,
+                        updates: 2
+                    }
+                ),
+                (
+                    "Stage\u{fffd}".to_string(),
+                    PrefabUsage {
+                        instances: 1,
+                        updates: 1
+                    }
+                ),
+            ]
+        );
+    }
 }
@@ -2,47 +2,305 @@
 //! This shows how to use the refactored architecture
 
 use anyhow::{Context, Result};
+use chrono::{DateTime, TimeDelta, Utc};
 use std::fs::File;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
 
-use super::analyzer::{PacketAnalyzer, PacketFilter};
-use super::formatter::{OutputWriter, PacketFormatter, StatsFormatter};
-use super::reader::{LegacyPacketReader, MixedPacketReader, PacketReader, PacketReaderTrait};
+use super::analyzer::{
+    EncodeDriftCheck, KeepaliveFilter, PacketAnalyzer, PacketFilter, PrefabCatalog, SchemaCoverage,
+    TimestampCheck,
+};
+use super::define::data_frame;
+use super::follow::CaptureFollower;
+use super::formatter::{CsvSummaryFormatter, OutputWriter, PacketFilesSink, StatsFormatter};
+use super::reader::{
+    CaptureFormat, InputFormat, PacketReader, PacketReaderTrait, ParallelDecodeReader, is_stdin,
+    is_truncated, open_input, open_stream,
+};
+use super::sink::{AnalysisEvent, AnalysisSink};
+use super::{PacketDigests, PacketInfo};
+use crate::als::converter::AlsConverter;
+
+/// Packets queued to the digest thread ahead of formatting
+const DIGEST_QUEUE_DEPTH: usize = 256;
+
+/// Reader factory shared across analysis worker threads
+pub type SharedReaderFactory = dyn Fn(File) -> Box<dyn PacketReaderTrait> + Sync;
+
+/// Order in which directory files are analyzed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FileSortKey {
+    Created,
+    #[default]
+    Modified,
+    /// Numeric suffix after the last '_' in the file name (e.g. `data_xxx_12.bin`)
+    FilenameNumeric,
+}
+
+impl std::str::FromStr for FileSortKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "created" => Ok(Self::Created),
+            "modified" => Ok(Self::Modified),
+            "filename" => Ok(Self::FilenameNumeric),
+            _ => Err(anyhow::anyhow!("Unsupported sort key: {}", s)),
+        }
+    }
+}
+
+/// Options for file and directory analysis
+#[derive(Debug, Clone)]
+pub struct AnalyzeOptions {
+    /// Number of files analyzed in parallel (directory input)
+    pub jobs: usize,
+    /// File order (directory input)
+    pub sort_key: FileSortKey,
+    /// Write a per-file CSV summary to this path (directory input)
+    pub csv_summary: Option<PathBuf>,
+    /// Write the final statistics as JSON to this path
+    pub stats_json: Option<PathBuf>,
+    /// Write each packet to its own file in this directory, the output gets the rest
+    /// (file input)
+    pub packet_files: Option<PathBuf>,
+    /// Raw bytes sampled from each unknown field, 0 = disabled
+    pub unknown_sample_bytes: usize,
+    /// Hash packets on a separate thread while formatting (file input)
+    pub parallel_digest: bool,
+    /// Decode protobuf payloads on the rayon pool, readers without raw packet
+    /// support decode serially
+    pub parallel_decode: bool,
+    /// Keepalive packets dropped before analysis, kept by default
+    pub keepalive_filter: KeepaliveFilter,
+    /// Flag packet intervals longer than this, `None` = not checked
+    pub gap_threshold: Option<TimeDelta>,
+    /// Stop at the first file that fails instead of reporting it and going on (directory input)
+    pub fail_fast: bool,
+}
+
+impl Default for AnalyzeOptions {
+    fn default() -> Self {
+        Self {
+            jobs: 1,
+            sort_key: FileSortKey::default(),
+            csv_summary: None,
+            stats_json: None,
+            packet_files: None,
+            unknown_sample_bytes: 0,
+            parallel_digest: false,
+            parallel_decode: false,
+            keepalive_filter: KeepaliveFilter::default(),
+            gap_threshold: None,
+            fail_fast: false,
+        }
+    }
+}
+
+impl AnalyzeOptions {
+    /// Set number of parallel jobs
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs.max(1);
+        self
+    }
+
+    /// Set file sort key
+    pub fn with_sort_key(mut self, sort_key: FileSortKey) -> Self {
+        self.sort_key = sort_key;
+        self
+    }
+
+    /// Set CSV summary output path
+    pub fn with_csv_summary(mut self, path: Option<PathBuf>) -> Self {
+        self.csv_summary = path;
+        self
+    }
+
+    /// Set JSON statistics output path
+    pub fn with_stats_json(mut self, path: Option<PathBuf>) -> Self {
+        self.stats_json = path;
+        self
+    }
+
+    /// Set the directory for one file per packet, `None` keeps packets in the output
+    pub fn with_packet_files(mut self, dir: Option<PathBuf>) -> Self {
+        self.packet_files = dir;
+        self
+    }
+
+    /// Set unknown field sample size in bytes
+    pub fn with_unknown_samples(mut self, max_bytes: usize) -> Self {
+        self.unknown_sample_bytes = max_bytes;
+        self
+    }
+
+    /// Enable digest precomputation on a separate thread
+    pub fn with_parallel_digest(mut self, enabled: bool) -> Self {
+        self.parallel_digest = enabled;
+        self
+    }
+
+    /// Enable protobuf decoding on the rayon pool
+    pub fn with_parallel_decode(mut self, enabled: bool) -> Self {
+        self.parallel_decode = enabled;
+        self
+    }
+
+    /// Set which keepalive packets to drop
+    pub fn with_keepalive_filter(mut self, keepalive_filter: KeepaliveFilter) -> Self {
+        self.keepalive_filter = keepalive_filter;
+        self
+    }
+
+    /// Flag intervals between consecutive packets longer than `threshold`
+    pub fn with_gap_threshold(mut self, threshold: Option<TimeDelta>) -> Self {
+        self.gap_threshold = threshold;
+        self
+    }
+
+    /// Fail the whole directory analysis on the first file that fails
+    pub fn with_fail_fast(mut self, enabled: bool) -> Self {
+        self.fail_fast = enabled;
+        self
+    }
+}
+
+/// Limits applied while analyzing, shared by file and directory input
+///
+/// Every limit defaults to unlimited (`usize::MAX` / `u64::MAX` / no time range).
+#[derive(Debug, Clone)]
+pub struct AnalyzeLimits {
+    /// Packets analyzed across all files
+    pub max_packets: usize,
+    /// Packets analyzed from each file
+    pub max_packets_per_file: usize,
+    /// Files analyzed from a directory
+    pub max_files: usize,
+    /// Packet bytes read from each file
+    pub max_bytes_per_file: u64,
+    /// Data bytes of a single packet, a larger packet fails the file
+    pub max_packet_size: usize,
+    /// Skip packets before this rfc3339 time
+    pub start_time: Option<String>,
+    /// Stop at the first packet after this rfc3339 time
+    pub end_time: Option<String>,
+}
+
+impl Default for AnalyzeLimits {
+    fn default() -> Self {
+        Self {
+            max_packets: usize::MAX,
+            max_packets_per_file: usize::MAX,
+            max_files: usize::MAX,
+            max_bytes_per_file: u64::MAX,
+            max_packet_size: usize::MAX,
+            start_time: None,
+            end_time: None,
+        }
+    }
+}
+
+impl AnalyzeLimits {
+    /// No limits at all
+    pub fn unlimited() -> Self {
+        Self::default()
+    }
+
+    /// Set maximum packets across all files
+    pub fn with_max_packets(mut self, max: usize) -> Self {
+        self.max_packets = max;
+        self
+    }
+
+    /// Set maximum packets per file
+    pub fn with_max_packets_per_file(mut self, max: usize) -> Self {
+        self.max_packets_per_file = max;
+        self
+    }
+
+    /// Set maximum files
+    pub fn with_max_files(mut self, max: usize) -> Self {
+        self.max_files = max;
+        self
+    }
+
+    /// Set maximum packet bytes per file
+    pub fn with_max_bytes_per_file(mut self, max: u64) -> Self {
+        self.max_bytes_per_file = max;
+        self
+    }
+
+    /// Set maximum data bytes of a single packet
+    pub fn with_max_packet_size(mut self, max: usize) -> Self {
+        self.max_packet_size = max;
+        self
+    }
+
+    /// Set time range in rfc3339 format
+    pub fn with_time_range(mut self, start: Option<String>, end: Option<String>) -> Self {
+        self.start_time = start;
+        self.end_time = end;
+        self
+    }
+}
+
+/// The limit that stopped reading
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitReached {
+    MaxPackets(usize),
+    MaxPacketsPerFile(usize),
+    MaxFiles(usize),
+    MaxBytesPerFile(u64),
+    EndTime,
+}
+
+impl std::fmt::Display for LimitReached {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MaxPackets(max) => write!(f, "max packets ({})", max),
+            Self::MaxPacketsPerFile(max) => write!(f, "max packets per file ({})", max),
+            Self::MaxFiles(max) => write!(f, "max files ({})", max),
+            Self::MaxBytesPerFile(max) => write!(f, "max bytes per file ({})", max),
+            Self::EndTime => write!(f, "end time"),
+        }
+    }
+}
 
 pub fn analyze(
     input_path: &str,
     output_path: Option<&str>,
-    packet_type: &str,
-    max_packets: usize,
-    start_time: Option<String>,
-    end_time: Option<String>,
+    input_format: InputFormat,
+    limits: AnalyzeLimits,
+    options: AnalyzeOptions,
 ) -> Result<()> {
     let path = Path::new(input_path);
-    let reader_factory: Box<dyn Fn(File) -> Box<dyn PacketReaderTrait>> = match packet_type {
-        "standard" => Box::new(|file| Box::new(PacketReader::new(file))),
-        "mixed" => Box::new(|file| Box::new(MixedPacketReader::new(file))),
-        "mixed-legacy" => Box::new(|file| Box::new(LegacyPacketReader::new(file))),
-        // Future types can be added here
-        _ => return Err(anyhow::anyhow!("Unsupported packet type: {}", packet_type)),
+    if is_stdin(path) {
+        let (_, reader) = open_stream(std::io::stdin(), input_format)?;
+        let mut sink = open_sink(output_path, &options)?;
+        return analyze_reader_with_sink(input_path, reader, sink.as_mut(), &limits, &options);
+    }
+    // Directory input is sniffed from its first file
+    let sample_path = if path.is_dir() {
+        collect_files(path, options.sort_key)?.into_iter().next()
+    } else {
+        Some(path.to_path_buf())
+    };
+    let input_format = match sample_path {
+        Some(sample_path) => input_format.resolve(&sample_path)?,
+        None => input_format,
+    };
+    let reader_factory: Box<SharedReaderFactory> = match input_format {
+        // Empty directory, nothing will be read
+        InputFormat::Auto => Box::new(PacketReader::boxed),
+        input_format => Box::new(input_format.reader_factory()?),
     };
     if path.is_file() {
-        analyze_file(
-            input_path,
-            output_path,
-            max_packets,
-            start_time,
-            end_time,
-            &reader_factory,
-        )
+        analyze_file(input_path, output_path, &limits, &reader_factory, &options)
     } else if path.is_dir() {
-        analyze_directory(
-            input_path,
-            output_path,
-            max_packets,
-            start_time,
-            end_time,
-            &reader_factory,
-        )
+        analyze_directory(input_path, output_path, &limits, &reader_factory, &options).map(|_| ())
     } else {
         Err(anyhow::anyhow!("Input path is neither file nor directory"))
     }
@@ -52,164 +310,792 @@ pub fn analyze(
 pub fn analyze_file(
     file_path: &str,
     output_path: Option<&str>,
-    max_packets: usize,
-    start_time: Option<String>,
-    end_time: Option<String>,
+    limits: &AnalyzeLimits,
     reader_factory: &dyn Fn(File) -> Box<dyn PacketReaderTrait>,
+    options: &AnalyzeOptions,
+) -> Result<()> {
+    let mut sink = open_sink(output_path, options)?;
+    analyze_file_with_sink(file_path, sink.as_mut(), limits, reader_factory, options)
+}
+
+// Helper: the output writer, or the per-packet files around it
+fn open_sink(output_path: Option<&str>, options: &AnalyzeOptions) -> Result<Box<dyn AnalysisSink>> {
+    let writer = OutputWriter::new(output_path)?;
+    Ok(match &options.packet_files {
+        Some(dir) => Box::new(PacketFilesSink::new(dir, writer)?),
+        None => Box::new(writer),
+    })
+}
+
+/// Analyze a single file, emitting events to `sink`
+pub fn analyze_file_with_sink(
+    file_path: &str,
+    sink: &mut dyn AnalysisSink,
+    limits: &AnalyzeLimits,
+    reader_factory: &dyn Fn(File) -> Box<dyn PacketReaderTrait>,
+    options: &AnalyzeOptions,
 ) -> Result<()> {
-    let mut writer = OutputWriter::new(output_path)?;
     let file =
         File::open(file_path).with_context(|| format!("Failed to open file: {}", file_path))?;
+    analyze_reader_with_sink(file_path, reader_factory(file), sink, limits, options)
+}
 
-    writer.writeln(&format!("=== Analyzing: {} ===", file_path))?;
-    writer.writeln(&format!("Max packets: {}", max_packets))?;
-    writer.writeln("")?;
+/// Analyze the packets of an opened reader, `name` labels the input in the output
+pub fn analyze_reader_with_sink(
+    name: &str,
+    reader: Box<dyn PacketReaderTrait>,
+    sink: &mut dyn AnalysisSink,
+    limits: &AnalyzeLimits,
+    options: &AnalyzeOptions,
+) -> Result<()> {
+    sink.on_event(AnalysisEvent::FileStarted {
+        path: name,
+        max_packets: limits.max_packets.min(limits.max_packets_per_file),
+        format: reader.kind(),
+    })?;
 
     // Create components
-    let mut reader = reader_factory(file);
-    let mut analyzer = PacketAnalyzer::new();
-    let filter = PacketFilter::new(start_time, end_time);
-
-    // Process packets
-    let mut packet_count = 0;
-    let mut processed_count = 0;
-    let mut objects_map = std::collections::HashMap::<i32, String>::new();
-
-    for packet in reader.read_packets()? {
-        packet_count += 1;
+    let mut reader = prepare_reader(reader, limits, options.parallel_decode);
+    let mut analyzer = PacketAnalyzer::new()
+        .with_unknown_samples(options.unknown_sample_bytes)
+        .with_gap_threshold(options.gap_threshold);
+    let filter = PacketFilter::new(limits.start_time.clone(), limits.end_time.clone())
+        .with_keepalive(options.keepalive_filter);
 
-        // Apply time filter
-        if !filter.should_include(&packet.timestamp) {
-            continue;
-        }
-
-        // Check if we should stop
-        if filter.is_past_end(&packet.timestamp) {
-            writer.writeln(&format!(
-                "Reached end time filter at packet #{}",
-                packet_count
-            ))?;
-            break;
-        }
-
-        // Analyze packet
-        analyzer.analyze_packet(&packet);
-        processed_count += 1;
-
-        // Format each packet
-        PacketFormatter::new(&mut objects_map).format_packet(
-            &mut writer,
-            processed_count,
-            &packet,
+    let outcome = if options.parallel_digest {
+        read_with_digests(reader.as_mut(), limits, &filter, &mut analyzer, sink)?
+    } else {
+        let mut sink_result = Ok(());
+        let outcome = read_with_limits(
+            reader.as_mut(),
+            limits,
+            limits.max_packets,
+            &filter,
+            &mut analyzer,
+            |number, packet| {
+                sink_result = sink.on_event(AnalysisEvent::Packet {
+                    number,
+                    packet,
+                    digests: None,
+                });
+                sink_result.is_ok()
+            },
         )?;
-
-        // Check limit
-        if processed_count >= max_packets {
-            writer.writeln(&format!("Reached packet limit: {}", max_packets))?;
-            break;
-        }
+        sink_result?;
+        outcome
+    };
+    if let Some(limit) = outcome.limit_reached {
+        sink.on_event(AnalysisEvent::Notice(format!(
+            "Reached requested packet limit: {} at packet #{}",
+            limit, outcome.packets_read
+        )))?;
     }
 
     // Show statistics
-    writer.writeln(&format!("Total packets read: {}", packet_count))?;
-    writer.writeln(&format!("Packets processed: {}", processed_count))?;
-    StatsFormatter::format_stats(&mut writer, analyzer.stats())?;
+    sink.on_event(AnalysisEvent::FileFinished {
+        packets_read: outcome.packets_read,
+        packets_processed: outcome.packets_processed,
+        stats: analyzer.stats(),
+    })?;
+    if let Some(json_path) = &options.stats_json {
+        StatsFormatter::write_json(json_path, analyzer.stats())?;
+    }
 
-    writer.flush()?;
+    sink.flush()?;
     Ok(())
 }
 
+/// How much of a file in a directory could be read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    /// Read to the end, or to a limit
+    Complete,
+    /// Zero bytes, skipped without reading
+    Empty,
+    /// Ends inside a packet, the packets before it are analyzed
+    Truncated,
+    /// Could not be opened or parsed, see `error`
+    Failed,
+}
+
+impl std::fmt::Display for FileStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Complete => "complete",
+            Self::Empty => "empty",
+            Self::Truncated => "truncated",
+            Self::Failed => "failed",
+        })
+    }
+}
+
+/// Outcome of analyzing one file in a directory
+#[derive(Debug, Clone)]
+pub struct FileAnalysisResult {
+    pub path: PathBuf,
+    /// Packets analyzed from this file
+    pub packets: u32,
+    pub error: Option<String>,
+    /// Limit that stopped reading this file early
+    pub limit_reached: Option<LimitReached>,
+    pub status: FileStatus,
+}
+
 /// Analyze multiple files in a directory
 pub fn analyze_directory(
     dir_path: &str,
     output_path: Option<&str>,
-    max_packets_per_file: usize,
-    start_time: Option<String>,
-    end_time: Option<String>,
-    reader_factory: &dyn Fn(File) -> Box<dyn PacketReaderTrait>,
-) -> Result<()> {
+    limits: &AnalyzeLimits,
+    reader_factory: &SharedReaderFactory,
+    options: &AnalyzeOptions,
+) -> Result<Vec<FileAnalysisResult>> {
     let mut writer = OutputWriter::new(output_path)?;
+    analyze_directory_with_sink(dir_path, &mut writer, limits, reader_factory, options)
+}
+
+/// Analyze multiple files in a directory, emitting events to `sink`
+pub fn analyze_directory_with_sink(
+    dir_path: &str,
+    sink: &mut dyn AnalysisSink,
+    limits: &AnalyzeLimits,
+    reader_factory: &SharedReaderFactory,
+    options: &AnalyzeOptions,
+) -> Result<Vec<FileAnalysisResult>> {
     let path = Path::new(dir_path);
 
     // Collect and sort files
-    let files = collect_files(path)?;
+    let mut files = collect_files(path, options.sort_key)?;
+    let files_truncated = files.len() > limits.max_files;
+    files.truncate(limits.max_files);
 
-    writer.writeln(&format!("=== Batch Analysis: {} ===", dir_path))?;
-    writer.writeln(&format!("Total files: {}", files.len()))?;
-    writer.writeln(&format!("Max packets per file: {}", max_packets_per_file))?;
-    writer.writeln("")?;
+    sink.on_event(AnalysisEvent::BatchStarted {
+        dir: dir_path,
+        total_files: files.len(),
+        max_packets_per_file: limits.max_packets_per_file,
+    })?;
+    if files_truncated {
+        sink.on_event(AnalysisEvent::Notice(format!(
+            "Reached requested file limit: {}",
+            LimitReached::MaxFiles(limits.max_files)
+        )))?;
+    }
 
     // Combined analyzer for all files
     let mut combined_analyzer = PacketAnalyzer::new();
-    let filter = PacketFilter::new(start_time.clone(), end_time.clone());
-
-    // Process each file
-    for (index, file_path) in files.iter().enumerate() {
-        writer.writeln(&format!(
-            "--- File {}/{}: {} ---",
-            index + 1,
-            files.len(),
-            file_path.display()
-        ))?;
-
-        match analyze_single_file(file_path, max_packets_per_file, &filter, &reader_factory) {
-            Ok(file_analyzer) => {
-                let stats = file_analyzer.stats();
-                writer.writeln(&format!("  Packets analyzed: {}", stats.total_packets))?;
+    let filter = PacketFilter::new(limits.start_time.clone(), limits.end_time.clone())
+        .with_keepalive(options.keepalive_filter);
+
+    let results = analyze_files(&files, limits, &filter, reader_factory, options);
+
+    let mut csv_writer = match &options.csv_summary {
+        Some(csv_path) => {
+            let mut csv_writer = OutputWriter::new(csv_path.to_str())?;
+            CsvSummaryFormatter::format_header(&mut csv_writer)?;
+            Some(csv_writer)
+        }
+        None => None,
+    };
+
+    let mut file_results = Vec::with_capacity(files.len());
+    let mut first_error = None;
+
+    // Report each file in sorted order
+    for (index, (file_path, result)) in files.iter().zip(results).enumerate() {
+        if let Some(csv_writer) = &mut csv_writer {
+            let size_bytes = file_path.metadata().ok().map(|metadata| metadata.len());
+            match &result {
+                Ok((file_analyzer, _, status)) => CsvSummaryFormatter::format_row(
+                    csv_writer,
+                    file_path,
+                    size_bytes,
+                    Some(file_analyzer.stats()),
+                    None,
+                    *status,
+                )?,
+                Err(e) => CsvSummaryFormatter::format_row(
+                    csv_writer,
+                    file_path,
+                    size_bytes,
+                    None,
+                    Some(&format!("{:#}", e)),
+                    FileStatus::Failed,
+                )?,
+            }
+        }
+
+        match result {
+            Ok((file_analyzer, limit_reached, status)) => {
+                file_results.push(FileAnalysisResult {
+                    path: file_path.clone(),
+                    packets: file_analyzer.stats().total_packets,
+                    error: None,
+                    limit_reached,
+                    status,
+                });
                 combined_analyzer.merge(&file_analyzer);
             }
             Err(e) => {
-                writer.writeln(&format!("  Error: {}", e))?;
+                file_results.push(FileAnalysisResult {
+                    path: file_path.clone(),
+                    packets: 0,
+                    error: Some(format!("{:#}", e)),
+                    limit_reached: None,
+                    status: FileStatus::Failed,
+                });
+                if options.fail_fast {
+                    first_error =
+                        Some(e.context(format!("Failed to analyze file: {}", file_path.display())));
+                }
             }
         }
 
-        writer.writeln("")?;
+        sink.on_event(AnalysisEvent::BatchFile {
+            index,
+            total: files.len(),
+            result: &file_results[index],
+        })?;
+        if first_error.is_some() {
+            break;
+        }
+    }
+    if let Some(e) = first_error {
+        if let Some(csv_writer) = &mut csv_writer {
+            csv_writer.flush()?;
+        }
+        sink.flush()?;
+        return Err(e);
+    }
+    if file_results.len() < files.len() {
+        sink.on_event(AnalysisEvent::Notice(format!(
+            "Reached requested packet limit: {}, {} files skipped",
+            LimitReached::MaxPackets(limits.max_packets),
+            files.len() - file_results.len()
+        )))?;
+    }
+
+    if let Some(csv_writer) = &mut csv_writer {
+        csv_writer.flush()?;
+    }
+
+    // Show summary and combined statistics
+    sink.on_event(AnalysisEvent::BatchFinished {
+        results: &file_results,
+        stats: combined_analyzer.stats(),
+    })?;
+    if let Some(json_path) = &options.stats_json {
+        StatsFormatter::write_json(json_path, combined_analyzer.stats())?;
+    }
+
+    sink.flush()?;
+    Ok(file_results)
+}
+
+/// Polling of `follow_with_sink`
+#[derive(Debug, Clone)]
+pub struct FollowOptions {
+    /// Wait between polls once all written packets are read
+    pub poll_interval: Duration,
+    /// Statistics are emitted this often while following, and once more on stop
+    pub stats_interval: Duration,
+}
+
+impl Default for FollowOptions {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_millis(500),
+            stats_interval: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Analyze the capture being written to `path` until `stop` is set, like `tail -f`
+///
+/// `path` is a capture file or a directory holding a capture session. Packets are emitted
+/// as complete packets are appended, for a directory newly created files of the session
+/// are picked up. Only the time range of `limits` applies, packet and file limits do not.
+/// Returns the packets processed.
+pub fn follow_with_sink(
+    path: &str,
+    sink: &mut dyn AnalysisSink,
+    input_format: InputFormat,
+    limits: &AnalyzeLimits,
+    options: &AnalyzeOptions,
+    follow: &FollowOptions,
+    stop: &AtomicBool,
+) -> Result<usize> {
+    let mut follower = if Path::new(path).is_dir() {
+        CaptureFollower::new(path, input_format)
+    } else {
+        CaptureFollower::file(path, input_format)
+    };
+    let mut analyzer = PacketAnalyzer::new()
+        .with_unknown_samples(options.unknown_sample_bytes)
+        .with_gap_threshold(options.gap_threshold);
+    let filter = PacketFilter::new(limits.start_time.clone(), limits.end_time.clone())
+        .with_keepalive(options.keepalive_filter);
+    // `Auto` is resolved on the first poll, the format is not known yet
+    sink.on_event(AnalysisEvent::FileStarted {
+        path,
+        max_packets: usize::MAX,
+        format: match input_format {
+            InputFormat::Standard(_) => CaptureFormat::Standard,
+            InputFormat::Mixed => CaptureFormat::Mixed,
+            InputFormat::Legacy => CaptureFormat::Legacy,
+            InputFormat::Auto => CaptureFormat::Unknown,
+        },
+    })?;
+
+    let mut packets_read = 0;
+    let mut packets_processed = 0;
+    let mut stats_emitted_at = Instant::now();
+    loop {
+        // Read what was written before the stop once more
+        let stopping = stop.load(Ordering::Relaxed);
+        let packets = follower.poll()?;
+        let caught_up = packets.is_empty();
+        for packet in packets {
+            packets_read += 1;
+            if !filter.should_include(&packet.timestamp) {
+                continue;
+            }
+            if filter.keepalive.drops(&packet) {
+                analyzer.record_keepalive_dropped();
+                continue;
+            }
+            analyzer.analyze_packet(&packet);
+            packets_processed += 1;
+            sink.on_event(AnalysisEvent::Packet {
+                number: packets_processed,
+                packet: &packet,
+                digests: None,
+            })?;
+        }
+        if stopping {
+            break;
+        }
+        if stats_emitted_at.elapsed() >= follow.stats_interval {
+            sink.on_event(AnalysisEvent::FileFinished {
+                packets_read,
+                packets_processed,
+                stats: analyzer.stats(),
+            })?;
+            stats_emitted_at = Instant::now();
+        }
+        sink.flush()?;
+        if caught_up {
+            std::thread::sleep(follow.poll_interval);
+        }
+    }
+
+    sink.on_event(AnalysisEvent::FileFinished {
+        packets_read,
+        packets_processed,
+        stats: analyzer.stats(),
+    })?;
+    if let Some(json_path) = &options.stats_json {
+        StatsFormatter::write_json(json_path, analyzer.stats())?;
     }
+    sink.flush()?;
+    Ok(packets_processed)
+}
+
+/// Room id and live timing of a capture
+#[derive(Debug, Clone)]
+pub struct RoomInfo {
+    pub room_id: String,
+    pub started_at: i64,
+    pub ended_at: i64,
+    /// Timestamp of the first packet in the file
+    pub first_timestamp: DateTime<Utc>,
+    /// Timestamp of the last packet read, the Room packet for brief scans
+    pub last_timestamp: DateTime<Utc>,
+}
 
-    // Show combined statistics
-    writer.writeln("=== COMBINED STATISTICS ===")?;
-    StatsFormatter::format_stats(&mut writer, combined_analyzer.stats())?;
+/// Read packets until the first Room frame, returns None if the file has none
+pub fn extract_room_info(path: &Path, input_format: InputFormat) -> Result<Option<RoomInfo>> {
+    read_room_info(path, input_format, true)
+}
 
-    writer.flush()?;
+/// Like `extract_room_info` but reads to EOF, so `last_timestamp` is the final packet
+pub fn scan_room_info(path: &Path, input_format: InputFormat) -> Result<Option<RoomInfo>> {
+    read_room_info(path, input_format, false)
+}
+
+/// Schema coverage of a capture file, or of every file in a capture directory
+pub fn scan_schema_coverage(path: &Path, input_format: InputFormat) -> Result<SchemaCoverage> {
+    let mut coverage = SchemaCoverage::new();
+    scan_packets(path, input_format, |_, packet| {
+        coverage.record_packet(packet)
+    })?;
+    Ok(coverage)
+}
+
+/// Collect the prefab names instantiated in a capture file or directory
+pub fn scan_prefab_catalog(path: &Path, input_format: InputFormat) -> Result<PrefabCatalog> {
+    let mut catalog = PrefabCatalog::new();
+    scan_packets(path, input_format, |_, packet| {
+        catalog.record_packet(packet)
+    })?;
+    Ok(catalog)
+}
+
+/// Check that the timestamps of a capture file or directory never go backwards, and find
+/// its gaps, in the order the converter reads it
+pub fn scan_timestamps(
+    path: &Path,
+    input_format: InputFormat,
+    gap_threshold: TimeDelta,
+) -> Result<TimestampCheck> {
+    let mut check = TimestampCheck::new(gap_threshold);
+    scan_packets(path, input_format, |_, packet| check.record_packet(packet))?;
+    Ok(check)
+}
+
+/// Encode every packet of a capture file or directory again and compare it with the bytes
+/// it was read with, packets are numbered in the order the converter reads them
+pub fn scan_encode_drift(path: &Path, input_format: InputFormat) -> Result<EncodeDriftCheck> {
+    let mut check = EncodeDriftCheck::new();
+    scan_packets(path, input_format, |index, packet| {
+        if let Some(source) = &mut packet.source {
+            source.file_index = index as u32;
+        }
+        check.record_packet(packet);
+    })?;
+    Ok(check)
+}
+
+// Helper: read every packet of a capture file or directory in the order the converter
+// reads them, `record` gets the index of the file each packet came from
+fn scan_packets(
+    path: &Path,
+    input_format: InputFormat,
+    mut record: impl FnMut(usize, &mut PacketInfo),
+) -> Result<()> {
+    for (index, file_path) in capture_files(path)?.iter().enumerate() {
+        let mut reader = open_input(file_path, input_format)?;
+        while let Some(mut packet) = reader.read_packet()? {
+            record(index, &mut packet);
+        }
+    }
     Ok(())
 }
 
-// Helper: analyze single file without output
+// Helper: the file itself, or the `.bin` files of a directory in the order the converter reads them
+fn capture_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    Ok(AlsConverter::get_file_entries(path, None)?
+        .into_iter()
+        .map(|entry| entry.path())
+        .collect())
+}
+
+// Helper: scan packets for the first Room frame
+fn read_room_info(
+    path: &Path,
+    input_format: InputFormat,
+    stop_at_room: bool,
+) -> Result<Option<RoomInfo>> {
+    let mut reader = open_input(path, input_format)?;
+
+    let mut info: Option<RoomInfo> = None;
+    let mut first_timestamp = None;
+    while let Some(packet) = reader.read_packet()? {
+        let first_timestamp = *first_timestamp.get_or_insert(packet.timestamp);
+        if let Some(info) = &mut info {
+            info.last_timestamp = packet.timestamp;
+            continue;
+        }
+
+        let room = packet
+            .data_pack
+            .frames
+            .iter()
+            .find_map(|f| match &f.message {
+                Some(data_frame::Message::Room(room)) => Some(room),
+                _ => None,
+            });
+        if let Some(room) = room {
+            info = Some(RoomInfo {
+                room_id: String::from_utf8_lossy(&room.id).to_string(),
+                started_at: room.started_at,
+                ended_at: room.ended_at,
+                first_timestamp,
+                last_timestamp: packet.timestamp,
+            });
+            if stop_at_room {
+                break;
+            }
+        }
+    }
+
+    Ok(info)
+}
+
+// Helper: analyze files across `jobs` threads, results keep the input order
+// A total packet limit needs the count of earlier files, so it runs sequentially
+// and stops once the limit is used up (the result may be shorter than `files`)
+fn analyze_files(
+    files: &[PathBuf],
+    limits: &AnalyzeLimits,
+    filter: &PacketFilter,
+    reader_factory: &SharedReaderFactory,
+    options: &AnalyzeOptions,
+) -> Vec<Result<(PacketAnalyzer, Option<LimitReached>, FileStatus)>> {
+    if options.jobs <= 1 || files.len() <= 1 || limits.max_packets != usize::MAX {
+        let mut remaining = limits.max_packets;
+        let mut results = Vec::with_capacity(files.len());
+        for file_path in files {
+            if remaining == 0 {
+                break;
+            }
+            let result = analyze_single_file(
+                file_path,
+                limits,
+                remaining,
+                filter,
+                reader_factory,
+                options,
+            );
+            if let Ok((analyzer, _, _)) = &result
+                && limits.max_packets != usize::MAX
+            {
+                remaining -= analyzer.stats().total_packets as usize;
+            }
+            let failed = result.is_err();
+            results.push(result);
+            if failed && options.fail_fast {
+                break;
+            }
+        }
+        return results;
+    }
+
+    let chunk_size = files.len().div_ceil(options.jobs);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = files
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|file_path| {
+                            analyze_single_file(
+                                file_path,
+                                limits,
+                                usize::MAX,
+                                filter,
+                                reader_factory,
+                                options,
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .flat_map(|handle| match handle.join() {
+                Ok(results) => results,
+                Err(_) => vec![Err(anyhow::anyhow!("Analysis worker panicked"))],
+            })
+            .collect()
+    })
+}
+
+// Helper: analyze single file without output, `remaining` is the total packet budget left.
+// Empty files are not opened, a file ending inside a packet keeps the packets before it
 fn analyze_single_file(
     file_path: &Path,
-    max_packets: usize,
+    limits: &AnalyzeLimits,
+    remaining: usize,
     filter: &PacketFilter,
     reader_factory: &dyn Fn(File) -> Box<dyn PacketReaderTrait>,
-) -> Result<PacketAnalyzer> {
+    options: &AnalyzeOptions,
+) -> Result<(PacketAnalyzer, Option<LimitReached>, FileStatus)> {
+    let mut analyzer = PacketAnalyzer::new()
+        .with_unknown_samples(options.unknown_sample_bytes)
+        .with_gap_threshold(options.gap_threshold);
+    let metadata = file_path
+        .metadata()
+        .with_context(|| format!("Failed to read metadata: {}", file_path.display()))?;
+    if metadata.len() == 0 {
+        return Ok((analyzer, None, FileStatus::Empty));
+    }
     let file = File::open(file_path)
         .with_context(|| format!("Failed to open file: {}", file_path.display()))?;
 
-    let mut reader = reader_factory(file);
-    let mut analyzer = PacketAnalyzer::new();
+    let mut reader = prepare_reader(reader_factory(file), limits, options.parallel_decode);
+    match read_with_limits(
+        reader.as_mut(),
+        limits,
+        remaining,
+        filter,
+        &mut analyzer,
+        |_, _| true,
+    ) {
+        Ok(outcome) => Ok((analyzer, outcome.limit_reached, FileStatus::Complete)),
+        Err(e) if is_truncated(&e) => Ok((analyzer, None, FileStatus::Truncated)),
+        Err(e) => Err(e),
+    }
+}
 
-    let mut count = 0;
-    for packet in reader.read_packets()? {
-        if !filter.should_include(&packet.timestamp) {
-            continue;
+// Helper: apply the packet size limit to `reader`, decoding on the rayon pool when
+// `parallel_decode` is set and the reader can return raw packets, serially otherwise
+fn prepare_reader(
+    mut reader: Box<dyn PacketReaderTrait>,
+    limits: &AnalyzeLimits,
+    parallel_decode: bool,
+) -> Box<dyn PacketReaderTrait> {
+    reader.set_max_packet_size(limits.max_packet_size);
+    if parallel_decode && reader.supports_raw_packets() {
+        Box::new(ParallelDecodeReader::new(reader))
+    } else {
+        reader
+    }
+}
+
+// Counters of one `read_with_limits` run
+struct ReadOutcome {
+    packets_read: usize,
+    packets_processed: usize,
+    limit_reached: Option<LimitReached>,
+}
+
+// Helper: feed packets to `analyzer` until EOF or a limit fires,
+// `on_packet` returning false stops reading
+fn read_with_limits(
+    reader: &mut dyn PacketReaderTrait,
+    limits: &AnalyzeLimits,
+    remaining: usize,
+    filter: &PacketFilter,
+    analyzer: &mut PacketAnalyzer,
+    mut on_packet: impl FnMut(usize, &PacketInfo) -> bool,
+) -> Result<ReadOutcome> {
+    let mut outcome = ReadOutcome {
+        packets_read: 0,
+        packets_processed: 0,
+        limit_reached: None,
+    };
+    let mut bytes_read = 0u64;
+
+    while let Some(packet) = reader.read_packet()? {
+        outcome.packets_read += 1;
+        bytes_read += packet.len() as u64;
+        if bytes_read > limits.max_bytes_per_file {
+            outcome.limit_reached = Some(LimitReached::MaxBytesPerFile(limits.max_bytes_per_file));
+            break;
         }
 
         if filter.is_past_end(&packet.timestamp) {
+            outcome.limit_reached = Some(LimitReached::EndTime);
             break;
         }
+        if !filter.should_include(&packet.timestamp) {
+            continue;
+        }
+        if filter.keepalive.drops(&packet) {
+            analyzer.record_keepalive_dropped();
+            continue;
+        }
 
         analyzer.analyze_packet(&packet);
-        count += 1;
+        outcome.packets_processed += 1;
+        if !on_packet(outcome.packets_processed, &packet) {
+            break;
+        }
 
-        if count >= max_packets {
+        if outcome.packets_processed >= remaining {
+            outcome.limit_reached = Some(LimitReached::MaxPackets(limits.max_packets));
+            break;
+        }
+        if outcome.packets_processed >= limits.max_packets_per_file {
+            outcome.limit_reached =
+                Some(LimitReached::MaxPacketsPerFile(limits.max_packets_per_file));
             break;
         }
     }
 
-    Ok(analyzer)
+    Ok(outcome)
+}
+
+// Helper: `read_with_limits` for a single file, digests are hashed on a scoped thread
+// while this thread keeps reading and formatting. Packets come back in FIFO order,
+// at most `DIGEST_QUEUE_DEPTH` of them are in flight
+fn read_with_digests(
+    reader: &mut dyn PacketReaderTrait,
+    limits: &AnalyzeLimits,
+    filter: &PacketFilter,
+    analyzer: &mut PacketAnalyzer,
+    sink: &mut dyn AnalysisSink,
+) -> Result<ReadOutcome> {
+    std::thread::scope(|scope| {
+        let (packet_tx, packet_rx) = mpsc::sync_channel::<(usize, PacketInfo)>(DIGEST_QUEUE_DEPTH);
+        let (digest_tx, digest_rx) = mpsc::sync_channel(DIGEST_QUEUE_DEPTH);
+        scope.spawn(move || {
+            for (number, packet) in packet_rx {
+                let digests = packet.digests();
+                if digest_tx.send((number, packet, digests)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut sink_result = Ok(());
+        // Returns false once the sink failed
+        let mut emit = |(number, packet, digests): (usize, PacketInfo, PacketDigests)| {
+            if sink_result.is_ok() {
+                sink_result = sink.on_event(AnalysisEvent::Packet {
+                    number,
+                    packet: &packet,
+                    digests: Some(&digests),
+                });
+            }
+            sink_result.is_ok()
+        };
+        let mut in_flight = 0;
+        let outcome = read_with_limits(
+            reader,
+            limits,
+            limits.max_packets,
+            filter,
+            analyzer,
+            |number, packet| {
+                if in_flight == DIGEST_QUEUE_DEPTH {
+                    let Ok(hashed) = digest_rx.recv() else {
+                        return false;
+                    };
+                    in_flight -= 1;
+                    if !emit(hashed) {
+                        return false;
+                    }
+                }
+                while let Ok(hashed) = digest_rx.try_recv() {
+                    in_flight -= 1;
+                    if !emit(hashed) {
+                        return false;
+                    }
+                }
+                in_flight += 1;
+                packet_tx.send((number, packet.clone())).is_ok()
+            },
+        );
+        drop(packet_tx);
+        for hashed in digest_rx {
+            if !emit(hashed) {
+                break;
+            }
+        }
+        let outcome = outcome?;
+        sink_result?;
+        Ok(outcome)
+    })
 }
 
 // Helper: collect files from directory
-fn collect_files(dir: &Path) -> Result<Vec<std::path::PathBuf>> {
+pub(crate) fn collect_files(dir: &Path, sort_key: FileSortKey) -> Result<Vec<PathBuf>> {
     use std::fs;
 
     let mut entries: Vec<_> = fs::read_dir(dir)
@@ -221,17 +1107,39 @@ fn collect_files(dir: &Path) -> Result<Vec<std::path::PathBuf>> {
                 return None;
             }
             let metadata = path.metadata().ok()?;
-            let modified = metadata.modified().ok()?;
-            Some((path, modified))
+            let time = match sort_key {
+                // Fall back to modification time where creation time is unsupported
+                FileSortKey::Created => metadata.created().or_else(|_| metadata.modified()).ok()?,
+                _ => metadata.modified().ok()?,
+            };
+            Some((path, time))
         })
         .collect();
 
-    // Sort by modification time
-    entries.sort_by_key(|(_, time)| *time);
+    match sort_key {
+        FileSortKey::Created | FileSortKey::Modified => {
+            entries.sort_by_key(|(_, time)| *time);
+        }
+        FileSortKey::FilenameNumeric => {
+            entries.sort_by_key(|(path, _)| (file_number(path).unwrap_or(0), path.clone()));
+        }
+    }
 
     Ok(entries.into_iter().map(|(path, _)| path).collect())
 }
 
+// Helper: numeric suffix after the last '_' of the file stem
+fn file_number(path: &Path) -> Option<u64> {
+    path.file_name()?
+        .to_str()?
+        .rsplit('_')
+        .next()?
+        .split('.')
+        .next()?
+        .parse::<u64>()
+        .ok()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -240,4 +1148,500 @@ mod tests {
     fn test_analyze_file_architecture() {
         // Test the new architecture with a sample file
     }
+
+    #[test]
+    fn test_file_number_sort_key() {
+        assert_eq!(file_number(Path::new("data_1700000000_12.bin")), Some(12));
+        assert_eq!(file_number(Path::new("data.bin")), None);
+        assert_eq!(
+            "filename".parse::<FileSortKey>().unwrap(),
+            FileSortKey::FilenameNumeric
+        );
+        assert!("size".parse::<FileSortKey>().is_err());
+    }
+
+    #[test]
+    fn test_csv_summary() {
+        use crate::als::proto::PacketInfo;
+        use crate::als::proto::define::Room;
+        use chrono::{TimeZone, Utc};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let input_dir = dir.join("input");
+        std::fs::create_dir_all(&input_dir).unwrap();
+        let timestamp = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let room = Room {
+            id: b"room,1".to_vec(),
+            started_at: 0,
+            ended_at: 0,
+        };
+        let mut bytes = PacketInfo::create_room_frame(timestamp, room).to_vec();
+        bytes.extend(
+            PacketInfo::create_cache_end(timestamp + chrono::Duration::seconds(1)).to_vec(),
+        );
+        std::fs::write(input_dir.join("data_1.bin"), bytes).unwrap();
+        std::fs::write(
+            input_dir.join("data_2.bin"),
+            PacketInfo::create_cache_end(timestamp).to_vec(),
+        )
+        .unwrap();
+        // Valid length header but wrong marker byte
+        std::fs::write(input_dir.join("data_3.bin"), [0x00, 0x09, 0xff]).unwrap();
+        std::fs::write(input_dir.join("data_4.bin"), []).unwrap();
+        // Two packets and the first bytes of a third
+        let packet = PacketInfo::create_cache_end(timestamp).to_vec();
+        let truncated = [packet.clone(), packet.clone(), packet[..5].to_vec()].concat();
+        std::fs::write(input_dir.join("data_5.bin"), truncated).unwrap();
+
+        let csv_path = dir.join("summary.csv");
+        let report_path = dir.join("report.txt");
+        let factory = |file| PacketReader::boxed(file);
+        let file_results = analyze_directory(
+            input_dir.to_str().unwrap(),
+            report_path.to_str(),
+            &AnalyzeLimits::unlimited(),
+            &factory,
+            &AnalyzeOptions::default()
+                .with_sort_key(FileSortKey::FilenameNumeric)
+                .with_csv_summary(Some(csv_path.clone())),
+        )
+        .unwrap();
+
+        assert_eq!(file_results.len(), 5);
+        assert_eq!(file_results[0].packets, 2);
+        assert!(file_results[0].error.is_none());
+        assert!(file_results[2].error.is_some());
+        let statuses: Vec<_> = file_results.iter().map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            [
+                FileStatus::Complete,
+                FileStatus::Complete,
+                FileStatus::Failed,
+                FileStatus::Empty,
+                FileStatus::Truncated,
+            ]
+        );
+        assert!(file_results[3].error.is_none());
+        assert_eq!(file_results[4].packets, 2);
+        assert!(file_results[4].error.is_none());
+
+        let csv = std::fs::read_to_string(&csv_path).unwrap();
+        let rows: Vec<&str> = csv.split("\r\n").filter(|row| !row.is_empty()).collect();
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows[0], CsvSummaryFormatter::HEADER);
+        assert!(rows[1].contains(
+            ",2,1,2023-11-14T22:13:20.000000Z,2023-11-14T22:13:21.000000Z,\"room,1\",,0,complete"
+        ));
+        assert!(rows[2].contains(",1,0,"));
+        // The error text has a comma, so it is quoted as one field
+        assert!(rows[3].ends_with(
+            ",3,,,,,,\"Invalid marker byte: expected one of [0x01], got 0xff\",,failed"
+        ));
+        assert!(rows[4].contains(",0,0,"));
+        assert!(rows[4].ends_with(",empty"));
+        assert!(rows[5].contains(",2,0,"));
+        assert!(rows[5].ends_with(",truncated"));
+
+        let report = std::fs::read_to_string(&report_path).unwrap();
+        assert!(report.contains("  Empty file, skipped"));
+        assert!(report.contains("  Truncated after 2 packets"));
+        assert!(report.contains("empty: 1, truncated: 1, failed: 1"));
+    }
+
+    #[test]
+    fn test_fail_fast() {
+        use crate::als::proto::PacketInfo;
+        use crate::als::proto::sink::CallbackSink;
+        use chrono::TimeZone;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let packet = PacketInfo::create_cache_end(Utc.timestamp_opt(1_700_000_000, 0).unwrap());
+        std::fs::write(dir.join("data_1.bin"), packet.to_vec()).unwrap();
+        // Valid length header but wrong marker byte
+        std::fs::write(dir.join("data_2.bin"), [0x00, 0x09, 0xff]).unwrap();
+        std::fs::write(dir.join("data_3.bin"), packet.to_vec()).unwrap();
+
+        let factory = |file| PacketReader::boxed(file);
+        let run = |options: AnalyzeOptions| {
+            let mut reported = 0;
+            let result = analyze_directory_with_sink(
+                dir.to_str().unwrap(),
+                &mut CallbackSink::new(|event: &AnalysisEvent<'_>| {
+                    if let AnalysisEvent::BatchFile { .. } = event {
+                        reported += 1;
+                    }
+                    Ok(())
+                }),
+                &AnalyzeLimits::unlimited(),
+                &factory,
+                &options.with_sort_key(FileSortKey::FilenameNumeric),
+            );
+            (result, reported)
+        };
+
+        let (result, reported) = run(AnalyzeOptions::default());
+        assert_eq!(result.unwrap().len(), 3);
+        assert_eq!(reported, 3);
+
+        for jobs in [1, 3] {
+            let (result, reported) = run(AnalyzeOptions::default()
+                .with_jobs(jobs)
+                .with_fail_fast(true));
+            let error = format!("{:#}", result.unwrap_err());
+            assert!(error.contains("data_2.bin"), "{}", error);
+            assert!(error.contains("Invalid marker byte"), "{}", error);
+            assert_eq!(reported, 2);
+        }
+    }
+
+    #[test]
+    fn test_callback_sink() {
+        use crate::als::proto::PacketInfo;
+        use crate::als::proto::sink::CallbackSink;
+        use chrono::{TimeZone, Utc};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let timestamp = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let mut bytes = PacketInfo::create_cache_end(timestamp).to_vec();
+        bytes.extend(PacketInfo::create_cache_end(timestamp).to_vec());
+        let file_path = dir.join("data_1.bin");
+        std::fs::write(&file_path, bytes).unwrap();
+
+        let mut packets = 0;
+        let mut finished = None;
+        let mut format = None;
+        let mut sink = CallbackSink::new(|event: &AnalysisEvent<'_>| {
+            match event {
+                AnalysisEvent::FileStarted { format: kind, .. } => format = Some(*kind),
+                AnalysisEvent::Packet { .. } => packets += 1,
+                AnalysisEvent::FileFinished { stats, .. } => finished = Some(stats.total_packets),
+                _ => {}
+            }
+            Ok(())
+        });
+        let factory = |file| PacketReader::boxed(file);
+        analyze_file_with_sink(
+            file_path.to_str().unwrap(),
+            &mut sink,
+            &AnalyzeLimits::unlimited(),
+            &factory,
+            &AnalyzeOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(packets, 2);
+        assert_eq!(finished, Some(2));
+        assert_eq!(format, Some(CaptureFormat::Standard));
+    }
+
+    #[test]
+    fn test_packet_files() {
+        use crate::als::proto::PacketInfo;
+        use crate::als::proto::formatter::PACKET_INDEX_FILE;
+        use chrono::{TimeDelta, TimeZone, Utc};
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let timestamp = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let mut bytes = PacketInfo::create_cache_end(timestamp).to_vec();
+        bytes.extend(PacketInfo::create_cache_end(timestamp + TimeDelta::seconds(1)).to_vec());
+        let file_path = dir.join("data_1.bin");
+        std::fs::write(&file_path, bytes).unwrap();
+        let (output_path, packets_dir) = (dir.join("report.txt"), dir.join("packets"));
+        let options = AnalyzeOptions::default().with_packet_files(Some(packets_dir.clone()));
+        let run = || {
+            analyze(
+                file_path.to_str().unwrap(),
+                output_path.to_str(),
+                InputFormat::Auto,
+                AnalyzeLimits::unlimited(),
+                options.clone(),
+            )
+        };
+
+        run().unwrap();
+        let report = std::fs::read_to_string(&output_path).unwrap();
+        assert!(report.contains("Total packets read: 2"), "{}", report);
+        assert!(!report.contains("=== Packet #"), "{}", report);
+        let second = std::fs::read_to_string(packets_dir.join("packet_00002.txt")).unwrap();
+        assert!(second.starts_with("=== Packet #2:"), "{}", second);
+        assert!(
+            second.contains("2023-11-14 22:13:21.000000 UTC"),
+            "{}",
+            second
+        );
+        assert_eq!(
+            std::fs::read_to_string(packets_dir.join(PACKET_INDEX_FILE)).unwrap(),
+            "file\tpacket\ttimestamp\n\
+             packet_00001.txt\t1\t2023-11-14T22:13:20.000000Z\n\
+             packet_00002.txt\t2\t2023-11-14T22:13:21.000000Z\n"
+        );
+
+        // Files of an earlier run are not mixed with new ones
+        let error = run().unwrap_err();
+        assert!(error.to_string().contains("already contains"), "{}", error);
+    }
+
+    #[test]
+    fn test_follow_growing_capture() {
+        use crate::als::proto::PacketInfo;
+        use crate::als::proto::sink::CallbackSink;
+        use chrono::{TimeDelta, TimeZone, Utc};
+        use std::io::Write;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let start = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let capture = move |first: i64| -> Vec<u8> {
+            (first..first + 20)
+                .flat_map(move |i| {
+                    PacketInfo::create_cache_end(start + TimeDelta::seconds(i)).to_vec()
+                })
+                .collect()
+        };
+        std::fs::write(dir.join("data_1700000000_1.bin"), capture(0)).unwrap();
+
+        // Appends the second file in chunks that split packets
+        let writer_dir = dir.to_path_buf();
+        let writer = std::thread::spawn(move || {
+            let bytes = capture(20);
+            let mut file = std::fs::File::create(writer_dir.join("data_1700000000_2.bin")).unwrap();
+            for chunk in bytes.chunks(7) {
+                file.write_all(chunk).unwrap();
+                file.flush().unwrap();
+                std::thread::sleep(Duration::from_millis(2));
+            }
+        });
+
+        let stop = AtomicBool::new(false);
+        let deadline = Instant::now() + Duration::from_secs(30);
+        let mut timestamps = Vec::new();
+        let mut sink = CallbackSink::new(|event: &AnalysisEvent<'_>| {
+            if let AnalysisEvent::Packet { packet, .. } = event {
+                timestamps.push(packet.timestamp);
+            }
+            if timestamps.len() >= 40 || Instant::now() > deadline {
+                stop.store(true, Ordering::Relaxed);
+            }
+            Ok(())
+        });
+        let follow = FollowOptions {
+            poll_interval: Duration::from_millis(5),
+            stats_interval: Duration::from_millis(50),
+        };
+        let processed = follow_with_sink(
+            dir.to_str().unwrap(),
+            &mut sink,
+            InputFormat::Auto,
+            &AnalyzeLimits::unlimited(),
+            &AnalyzeOptions::default(),
+            &follow,
+            &stop,
+        )
+        .unwrap();
+        writer.join().unwrap();
+
+        let expected: Vec<_> = (0..40).map(|i| start + TimeDelta::seconds(i)).collect();
+        assert_eq!(processed, 40);
+        assert_eq!(timestamps, expected);
+    }
+
+    #[test]
+    fn test_extract_room_info() {
+        use crate::als::proto::PacketInfo;
+        use crate::als::proto::define::Room;
+        use chrono::TimeZone;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let timestamp = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let room = Room {
+            id: b"room-1".to_vec(),
+            started_at: 1_700_000_000,
+            ended_at: 1_700_003_600,
+        };
+        let mut bytes = PacketInfo::create_cache_end(timestamp).to_vec();
+        bytes.extend(
+            PacketInfo::create_room_frame(timestamp + chrono::Duration::seconds(1), room).to_vec(),
+        );
+        bytes.extend(
+            PacketInfo::create_cache_end(timestamp + chrono::Duration::seconds(5)).to_vec(),
+        );
+        let file_path = dir.join("data_1.bin");
+        std::fs::write(&file_path, bytes).unwrap();
+
+        let brief = extract_room_info(&file_path, InputFormat::Auto)
+            .unwrap()
+            .unwrap();
+        assert_eq!(brief.room_id, "room-1");
+        assert_eq!(brief.ended_at, 1_700_003_600);
+        assert_eq!(brief.first_timestamp, timestamp);
+        assert_eq!(
+            brief.last_timestamp,
+            timestamp + chrono::Duration::seconds(1)
+        );
+
+        let full = scan_room_info(&file_path, InputFormat::Standard(None))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            full.last_timestamp,
+            timestamp + chrono::Duration::seconds(5)
+        );
+    }
+
+    #[test]
+    fn test_scan_timestamps_converter_order() {
+        use crate::als::proto::PacketInfo;
+        use chrono::TimeZone;
+        use std::time::SystemTime;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let timestamp = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let packets = |from: i64| {
+            [
+                PacketInfo::create_cache_end(timestamp + chrono::Duration::seconds(from)).to_vec(),
+                PacketInfo::create_cache_end(timestamp + chrono::Duration::seconds(from + 1))
+                    .to_vec(),
+            ]
+            .concat()
+        };
+        std::fs::write(dir.join("data_1.bin"), packets(0)).unwrap();
+        std::fs::write(dir.join("data_2.bin"), packets(2)).unwrap();
+        // Not a capture file, and would fail to parse
+        std::fs::write(dir.join("notes.txt"), b"not a capture").unwrap();
+        // Modification order is the reverse of the filename order
+        let older = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let newer = older + Duration::from_secs(60);
+        for (name, time) in [("data_1.bin", newer), ("data_2.bin", older)] {
+            File::options()
+                .write(true)
+                .open(dir.join(name))
+                .unwrap()
+                .set_modified(time)
+                .unwrap();
+        }
+
+        let check =
+            scan_timestamps(dir, InputFormat::Standard(None), TimeDelta::seconds(5)).unwrap();
+        assert_eq!(check.total_packets, 4);
+        assert_eq!(check.regression_count, 0);
+        assert_eq!(check.first_timestamp, Some(timestamp));
+        assert_eq!(
+            check.last_timestamp,
+            Some(timestamp + chrono::Duration::seconds(3))
+        );
+    }
+
+    #[test]
+    fn test_analyze_limits() {
+        use crate::als::proto::sink::CallbackSink;
+        use chrono::TimeZone;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let timestamp = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let packet_len = PacketInfo::create_cache_end(timestamp).to_vec().len() as u64;
+        for file_index in 0..3 {
+            let bytes: Vec<u8> = (0..3)
+                .flat_map(|i| {
+                    let seconds = file_index * 3 + i;
+                    PacketInfo::create_cache_end(timestamp + chrono::Duration::seconds(seconds))
+                        .to_vec()
+                })
+                .collect();
+            std::fs::write(dir.join(format!("data_{}.bin", file_index + 1)), bytes).unwrap();
+        }
+        let factory = |file| PacketReader::boxed(file);
+        let options = AnalyzeOptions::default().with_sort_key(FileSortKey::FilenameNumeric);
+        let run = |limits: AnalyzeLimits| {
+            let results = analyze_directory_with_sink(
+                dir.to_str().unwrap(),
+                &mut CallbackSink::new(|_: &AnalysisEvent<'_>| Ok(())),
+                &limits,
+                &factory,
+                &options,
+            )
+            .unwrap();
+            results
+                .into_iter()
+                .map(|r| (r.packets, r.limit_reached))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            run(AnalyzeLimits::unlimited()),
+            vec![(3, None), (3, None), (3, None)]
+        );
+        assert_eq!(
+            run(AnalyzeLimits::default().with_max_packets(4)),
+            vec![(3, None), (1, Some(LimitReached::MaxPackets(4)))]
+        );
+        assert_eq!(
+            run(AnalyzeLimits::default().with_max_packets_per_file(2)),
+            vec![(2, Some(LimitReached::MaxPacketsPerFile(2))); 3]
+        );
+        assert_eq!(run(AnalyzeLimits::default().with_max_files(1)).len(), 1);
+        assert_eq!(
+            run(AnalyzeLimits::default().with_max_bytes_per_file(packet_len * 2))[0],
+            (2, Some(LimitReached::MaxBytesPerFile(packet_len * 2)))
+        );
+        let end_time = (timestamp + chrono::Duration::seconds(4)).to_rfc3339();
+        assert_eq!(
+            run(AnalyzeLimits::default().with_time_range(None, Some(end_time))),
+            vec![
+                (3, None),
+                (2, Some(LimitReached::EndTime)),
+                (0, Some(LimitReached::EndTime))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parallel_digest_output() {
+        use crate::als::proto::define::Room;
+        use chrono::TimeZone;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let timestamp = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        // More packets than DIGEST_QUEUE_DEPTH so the queue fills up
+        let bytes: Vec<u8> = (0..DIGEST_QUEUE_DEPTH as i64 * 2 + 3)
+            .flat_map(|i| {
+                let room = Room {
+                    id: format!("room-{}", i).into_bytes(),
+                    started_at: i,
+                    ended_at: 0,
+                };
+                PacketInfo::create_room_frame(timestamp + chrono::Duration::seconds(i), room)
+                    .to_vec()
+            })
+            .collect();
+        let file_path = dir.join("data_1.bin");
+        std::fs::write(&file_path, bytes).unwrap();
+
+        let factory = |file| PacketReader::boxed(file);
+        let run = |parallel_digest: bool, limits: AnalyzeLimits| {
+            let output_path = dir.join(format!("report_{}.txt", parallel_digest));
+            analyze_file(
+                file_path.to_str().unwrap(),
+                output_path.to_str(),
+                &limits,
+                &factory,
+                &AnalyzeOptions::default().with_parallel_digest(parallel_digest),
+            )
+            .unwrap();
+            std::fs::read_to_string(output_path).unwrap()
+        };
+        let sequential = run(false, AnalyzeLimits::unlimited());
+        assert!(sequential.contains("SHA-256"));
+        assert_eq!(run(true, AnalyzeLimits::unlimited()), sequential);
+
+        let limits = AnalyzeLimits::default().with_max_packets(DIGEST_QUEUE_DEPTH + 1);
+        assert_eq!(run(true, limits.clone()), run(false, limits));
+    }
 }
@@ -63,6 +63,8 @@ pub trait UpdateObjectExt {
 }
 
 pub trait InstantiateObjectExt {
+    /// Prefab name as text, invalid UTF-8 replaced with U+FFFD
+    fn prefab_name_lossy(&self) -> String;
     fn try_parse_init_data(
         &self,
         prefab_name: &str,
@@ -159,6 +161,10 @@ impl UpdateObjectExt for UpdateObject {
 }
 
 impl InstantiateObjectExt for InstantiateObject {
+    fn prefab_name_lossy(&self) -> String {
+        String::from_utf8_lossy(&self.prefab_name).into_owned()
+    }
+
     fn try_parse_init_data(
         &self,
         prefab_name: &str,
@@ -0,0 +1,272 @@
+//! Following a capture file or directory that is still being written, like `tail -f`
+//!
+//! The follower remembers how many bytes of each file it has read, up to the end of the
+//! last complete packet. A packet cut off at the end of a file is read again on the next
+//! poll, once the rest of it has been written. Checkpoints are kept in memory only.
+
+use anyhow::{Context, Result, anyhow};
+use std::fs::File;
+use std::io::{Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use super::PacketInfo;
+use super::application::{FileSortKey, collect_files};
+use super::reader::{
+    DetectConfidence, InputFormat, LegacyPacketReader, MixedPacketReader, PacketReaderTrait,
+    StandardPacketReader, detect_format, is_truncated,
+};
+
+/// Reads the packets appended to the files of one capture session
+#[derive(Debug, Clone)]
+pub struct CaptureFollower {
+    dir: PathBuf,
+    input_format: InputFormat,
+    /// File name prefix shared by the session's files, up to the last '_'
+    session_prefix: Option<String>,
+    /// Followed files in reading order, with the bytes read from each
+    checkpoints: Vec<(PathBuf, u64)>,
+    /// Only the file given to `file` is followed, new files are not picked up
+    single_file: bool,
+}
+
+impl CaptureFollower {
+    /// `Auto` is resolved from the first file once two packet headers can be checked
+    pub fn new(dir: impl Into<PathBuf>, input_format: InputFormat) -> Self {
+        Self {
+            dir: dir.into(),
+            input_format,
+            session_prefix: None,
+            checkpoints: Vec::new(),
+            single_file: false,
+        }
+    }
+
+    /// Follow a single capture file instead of a session directory
+    pub fn file(path: impl Into<PathBuf>, input_format: InputFormat) -> Self {
+        let path = path.into();
+        Self {
+            dir: path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            input_format,
+            session_prefix: None,
+            checkpoints: vec![(path, 0)],
+            single_file: true,
+        }
+    }
+
+    /// Only follow files whose name starts with `prefix` (Builder pattern).
+    /// Defaults to the prefix of the most recently modified file.
+    pub fn with_session_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.session_prefix = Some(prefix.into());
+        self
+    }
+
+    pub fn session_prefix(&self) -> Option<&str> {
+        self.session_prefix.as_deref()
+    }
+
+    /// Followed files with the bytes read from each so far
+    pub fn checkpoints(&self) -> &[(PathBuf, u64)] {
+        &self.checkpoints
+    }
+
+    /// Read the complete packets appended since the last poll, in file order.
+    /// Files of the session created since then are picked up first.
+    pub fn poll(&mut self) -> Result<Vec<PacketInfo>> {
+        self.discover_files()?;
+        if self.checkpoints.is_empty() || !self.resolve_format()? {
+            return Ok(Vec::new());
+        }
+
+        let mut packets = Vec::new();
+        for (index, (path, offset)) in self.checkpoints.iter_mut().enumerate() {
+            let len = path
+                .metadata()
+                .with_context(|| format!("Failed to read metadata: {}", path.display()))?
+                .len();
+            if len < *offset {
+                return Err(anyhow!(
+                    "File shrank below its read offset {}: {}",
+                    offset,
+                    path.display()
+                ));
+            }
+            if len == *offset {
+                continue;
+            }
+
+            let mut reader = open_at(self.input_format, path, *offset)?;
+            loop {
+                match reader.read_packet() {
+                    Ok(Some(mut packet)) => {
+                        if let Some(source) = &mut packet.source {
+                            source.file_index = index as u32;
+                        }
+                        *offset = reader.offset().unwrap_or(*offset);
+                        packets.push(packet);
+                    }
+                    Ok(None) => break,
+                    // The rest of the packet is not written yet
+                    Err(e) if is_truncated(&e) => break,
+                    Err(e) => {
+                        return Err(e.context(format!(
+                            "Failed to read {} at offset {}",
+                            path.display(),
+                            offset
+                        )));
+                    }
+                }
+            }
+        }
+        Ok(packets)
+    }
+
+    // Helper: start following the session's files that are not followed yet
+    fn discover_files(&mut self) -> Result<()> {
+        if self.single_file {
+            return Ok(());
+        }
+        if self.session_prefix.is_none() {
+            let latest = collect_files(&self.dir, FileSortKey::Modified)?.pop();
+            self.session_prefix = latest.as_deref().and_then(session_prefix);
+        }
+        let Some(prefix) = &self.session_prefix else {
+            return Ok(());
+        };
+
+        for path in collect_files(&self.dir, FileSortKey::FilenameNumeric)? {
+            let matches = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(prefix.as_str()));
+            if matches
+                && !self
+                    .checkpoints
+                    .iter()
+                    .any(|(followed, _)| *followed == path)
+            {
+                tracing::info!("Following {}", path.display());
+                self.checkpoints.push((path, 0));
+            }
+        }
+        Ok(())
+    }
+
+    // Helper: resolve `Auto` from the first file, false while it is too short to tell
+    fn resolve_format(&mut self) -> Result<bool> {
+        if self.input_format != InputFormat::Auto {
+            return Ok(true);
+        }
+        // A file just created by the client has no packet header yet
+        let first = &self.checkpoints[0].0;
+        if first.metadata().is_ok_and(|metadata| metadata.len() < 3) {
+            return Ok(false);
+        }
+        let detection = detect_format(first)?;
+        if detection.confidence != DetectConfidence::High {
+            return Ok(false);
+        }
+        self.input_format = InputFormat::detected(detection.format)?;
+        tracing::info!("Input format: {:?}", self.input_format);
+        Ok(true)
+    }
+}
+
+/// File name up to and including its last '_', the whole name when it has none
+pub fn session_prefix(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    Some(match name.rfind('_') {
+        Some(index) => name[..=index].to_string(),
+        None => name.to_string(),
+    })
+}
+
+// Helper: reader for `path` resuming at byte `offset`
+fn open_at(
+    input_format: InputFormat,
+    path: &Path,
+    offset: u64,
+) -> Result<Box<dyn PacketReaderTrait>> {
+    let mut file =
+        File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
+    file.seek(SeekFrom::Start(offset))?;
+    Ok(match input_format {
+        InputFormat::Standard(marker) => Box::new(
+            StandardPacketReader::new(file)
+                .with_extra_marker(marker)
+                .with_offset(offset),
+        ),
+        InputFormat::Mixed => Box::new(MixedPacketReader::new(file).with_offset(offset)),
+        InputFormat::Legacy => Box::new(LegacyPacketReader::new(file).with_offset(offset)),
+        InputFormat::Auto => return Err(anyhow!("Input format must be resolved before reading")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeDelta, TimeZone, Utc};
+    use std::io::Write;
+
+    #[test]
+    fn test_follow_partial_packets() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let start = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let bytes: Vec<u8> = (0..4)
+            .flat_map(|i| PacketInfo::create_cache_end(start + TimeDelta::seconds(i)).to_vec())
+            .collect();
+        let packet_len = bytes.len() as u64 / 4;
+        let path = dir.join("data_1_1.bin");
+        std::fs::write(dir.join("other.bin"), &bytes).unwrap();
+        let mut file = File::create(&path).unwrap();
+
+        let mut follower =
+            CaptureFollower::new(dir, InputFormat::Standard(None)).with_session_prefix("data_1_");
+        // Two packets and the first bytes of the third
+        file.write_all(&bytes[..packet_len as usize * 2 + 5])
+            .unwrap();
+        let packets = follower.poll().unwrap();
+        assert_eq!(packets.len(), 2);
+        assert_eq!(follower.checkpoints(), &[(path.clone(), packet_len * 2)]);
+        assert!(follower.poll().unwrap().is_empty());
+
+        file.write_all(&bytes[packet_len as usize * 2 + 5..])
+            .unwrap();
+        let packets = follower.poll().unwrap();
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[0].timestamp, start + TimeDelta::seconds(2));
+        assert_eq!(packets[0].source.unwrap().offset, packet_len * 2);
+        assert_eq!(follower.checkpoints()[0].1, bytes.len() as u64);
+
+        assert_eq!(
+            session_prefix(Path::new("data_1700000000_12.bin")).as_deref(),
+            Some("data_1700000000_")
+        );
+    }
+
+    #[test]
+    fn test_follow_single_file() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let start = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let packet = |i| PacketInfo::create_cache_end(start + TimeDelta::seconds(i)).to_vec();
+        let path = dir.join("capture_1.bin");
+        let mut file = File::create(&path).unwrap();
+
+        let mut follower = CaptureFollower::file(&path, InputFormat::Auto);
+        assert!(follower.poll().unwrap().is_empty());
+        // Auto needs two packet headers to detect the format
+        file.write_all(&packet(0)).unwrap();
+        assert!(follower.poll().unwrap().is_empty());
+        file.write_all(&packet(1)).unwrap();
+        assert_eq!(follower.poll().unwrap().len(), 2);
+
+        // Other files of the session are not followed
+        std::fs::write(dir.join("capture_2.bin"), packet(2)).unwrap();
+        file.write_all(&packet(3)).unwrap();
+        let packets = follower.poll().unwrap();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].timestamp, start + TimeDelta::seconds(3));
+        assert_eq!(follower.checkpoints().len(), 1);
+    }
+}
@@ -2,17 +2,25 @@
 //! All formatting logic in one place
 
 use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, TimeDelta, Utc};
 use prost::Message;
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::fs::File;
-use std::io::Write;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 
-use super::analyzer::PacketStats;
+use super::analyzer::{
+    EncodeDriftCheck, IndexedGap, KNOWN_FIELDS, PacketStats, PrefabCatalog, SchemaCoverage,
+    TimestampCheck, unknown_field_hypothesis, wire_type_name,
+};
+use super::application::{FileAnalysisResult, FileStatus};
 use super::define::{DataPack, data_frame, data_pack, instantiate_object, update_object};
+use super::reader::CaptureFormat;
+use super::sink::{AnalysisEvent, AnalysisSink};
 use crate::als::proto::define::{InstantiateObject, UpdateObject};
 use crate::als::proto::extension::InstantiateObjectExt;
-use crate::als::proto::{PacketInfo, extension};
+use crate::als::proto::{PacketDigests, PacketInfo, extension};
 
 impl Display for instantiate_object::Target {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -48,22 +56,43 @@ impl Display for update_object::Target {
         }
     }
 }
-/// Output writer abstraction
+/// Output writer abstraction, the default text `AnalysisSink`
+///
+/// Output is buffered, it reaches the file or stdout on `flush` or when dropped.
 pub struct OutputWriter {
     writer: Box<dyn Write>,
+    objects_map: HashMap<i32, String>, // object_id to prefab_name mapping for packet events
 }
 
 impl OutputWriter {
     pub fn new(output_path: Option<&str>) -> Result<Self> {
         let writer: Box<dyn Write> = match output_path {
-            Some(path) => Box::new(
-                File::create(path)
-                    .with_context(|| format!("Failed to create output file: {}", path))?,
-            ),
-            None => Box::new(std::io::stdout()),
+            Some(path) => {
+                let file = File::create(path)
+                    .with_context(|| format!("Failed to create output file: {}", path))?;
+                Box::new(BufWriter::new(file))
+            }
+            None => Box::new(BufWriter::new(std::io::stdout())),
         };
 
-        Ok(Self { writer })
+        Ok(Self {
+            writer,
+            objects_map: HashMap::new(),
+        })
+    }
+
+    /// Append to an existing output file, creating it if missing
+    pub fn append(output_path: &str) -> Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(output_path)
+            .with_context(|| format!("Failed to open output file: {}", output_path))?;
+
+        Ok(Self {
+            writer: Box::new(BufWriter::new(file)),
+            objects_map: HashMap::new(),
+        })
     }
 
     pub fn writeln(&mut self, content: &str) -> Result<()> {
@@ -84,6 +113,171 @@ impl OutputWriter {
     }
 }
 
+impl AnalysisSink for OutputWriter {
+    fn on_event(&mut self, event: AnalysisEvent<'_>) -> Result<()> {
+        match event {
+            AnalysisEvent::FileStarted {
+                path,
+                max_packets,
+                format,
+            } => {
+                self.writeln(&format!("=== Analyzing: {} ===", path))?;
+                if format != CaptureFormat::Unknown {
+                    self.writeln(&format!("Format: {}", format))?;
+                }
+                self.writeln(&format!("Max packets: {}", max_packets))?;
+                self.writeln("")?;
+            }
+            AnalysisEvent::Packet {
+                number,
+                packet,
+                digests,
+            } => {
+                let mut objects_map = std::mem::take(&mut self.objects_map);
+                let result = PacketFormatter::new(&mut objects_map)
+                    .format_packet_with_digests(self, number, packet, digests);
+                self.objects_map = objects_map;
+                result?;
+            }
+            AnalysisEvent::Notice(message) => self.writeln(&message)?,
+            AnalysisEvent::FileFinished {
+                packets_read,
+                packets_processed,
+                stats,
+            } => {
+                self.writeln(&format!("Total packets read: {}", packets_read))?;
+                self.writeln(&format!("Packets processed: {}", packets_processed))?;
+                StatsFormatter::format_stats(self, stats)?;
+            }
+            AnalysisEvent::BatchStarted {
+                dir,
+                total_files,
+                max_packets_per_file,
+            } => {
+                self.writeln(&format!("=== Batch Analysis: {} ===", dir))?;
+                self.writeln(&format!("Total files: {}", total_files))?;
+                self.writeln(&format!("Max packets per file: {}", max_packets_per_file))?;
+                self.writeln("")?;
+            }
+            AnalysisEvent::BatchFile {
+                index,
+                total,
+                result,
+            } => {
+                self.writeln(&format!(
+                    "--- File {}/{}: {} ---",
+                    index + 1,
+                    total,
+                    result.path.display()
+                ))?;
+                match (result.status, &result.error) {
+                    (_, Some(error)) => self.writeln(&format!("  Error: {}", error))?,
+                    (FileStatus::Empty, None) => self.writeln("  Empty file, skipped")?,
+                    (FileStatus::Truncated, None) => {
+                        self.writeln(&format!("  Truncated after {} packets", result.packets))?
+                    }
+                    _ => self.writeln(&format!("  Packets analyzed: {}", result.packets))?,
+                }
+                if let Some(limit) = &result.limit_reached {
+                    self.writeln(&format!("  Reached requested packet limit: {}", limit))?;
+                }
+                self.writeln("")?;
+            }
+            AnalysisEvent::BatchFinished { results, stats } => {
+                FileSummaryFormatter::format_results(self, results)?;
+                self.writeln("=== COMBINED STATISTICS ===")?;
+                StatsFormatter::format_stats(self, stats)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        OutputWriter::flush(self)
+    }
+}
+
+/// Index of the packet files written by `PacketFilesSink`
+pub const PACKET_INDEX_FILE: &str = "index.tsv";
+
+/// Sink writing the details of each packet to its own file, `packet_00001.txt`, ...
+///
+/// Files are numbered in the order packets arrive, the packet number of the analysis is
+/// kept inside the file. Every other event goes to `summary`. `index.tsv` lists the
+/// files with the packet number and timestamp of each.
+pub struct PacketFilesSink {
+    dir: PathBuf,
+    summary: OutputWriter,
+    index: OutputWriter,
+    files: usize,
+    objects_map: HashMap<i32, String>,
+}
+
+impl PacketFilesSink {
+    /// Write the packet files into `dir`, which must not hold an index yet
+    pub fn new(dir: &Path, summary: OutputWriter) -> Result<Self> {
+        let index_path = dir.join(PACKET_INDEX_FILE);
+        if index_path.exists() {
+            return Err(anyhow::anyhow!(
+                "Packet directory already contains an index: {}",
+                index_path.display()
+            ));
+        }
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create packet directory: {}", dir.display()))?;
+        let mut index = OutputWriter::new(Some(&index_path.to_string_lossy()))?;
+        index.writeln("file\tpacket\ttimestamp")?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            summary,
+            index,
+            files: 0,
+            objects_map: HashMap::new(),
+        })
+    }
+
+    /// Packet files written so far
+    pub fn files(&self) -> usize {
+        self.files
+    }
+}
+
+impl AnalysisSink for PacketFilesSink {
+    fn on_event(&mut self, event: AnalysisEvent<'_>) -> Result<()> {
+        let AnalysisEvent::Packet {
+            number,
+            packet,
+            digests,
+        } = event
+        else {
+            return self.summary.on_event(event);
+        };
+        self.files += 1;
+        let name = format!("packet_{:05}.txt", self.files);
+        let mut writer = OutputWriter::new(Some(&self.dir.join(&name).to_string_lossy()))?;
+        PacketFormatter::new(&mut self.objects_map).format_packet_with_digests(
+            &mut writer,
+            number,
+            packet,
+            digests,
+        )?;
+        writer.flush()?;
+        self.index.writeln(&format!(
+            "{}\t{}\t{}",
+            name,
+            number,
+            packet
+                .timestamp
+                .to_rfc3339_opts(SecondsFormat::Micros, true)
+        ))
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.index.flush()?;
+        self.summary.flush()
+    }
+}
+
 /// Packet formatter
 pub struct PacketFormatter<'a> {
     objects_map: &'a mut HashMap<i32, String>, // object_id to prefab_name mapping
@@ -101,6 +295,17 @@ impl<'a> PacketFormatter<'a> {
         writer: &mut OutputWriter,
         packet_number: usize,
         packet: &PacketInfo,
+    ) -> Result<()> {
+        self.format_packet_with_digests(writer, packet_number, packet, None)
+    }
+
+    /// Format a single packet, digests missing from `digests` are calculated inline
+    pub fn format_packet_with_digests(
+        &mut self,
+        writer: &mut OutputWriter,
+        packet_number: usize,
+        packet: &PacketInfo,
+        digests: Option<&PacketDigests>,
     ) -> Result<()> {
         writer.writeln(&format!(
             "=== Packet #{}: {} bytes ===",
@@ -117,7 +322,10 @@ impl<'a> PacketFormatter<'a> {
         ))?;
 
         // Show protobuf digest
-        let protobuf_digest = super::calculate_digest(&packet.raw_data);
+        let protobuf_digest = match digests {
+            Some(digests) => digests.protobuf.clone(),
+            None => super::calculate_digest(&packet.raw_data),
+        };
         writer.writeln(&format!("  Protobuf SHA-256: {}", protobuf_digest))?;
 
         // Show first bytes in hex
@@ -128,14 +336,20 @@ impl<'a> PacketFormatter<'a> {
             hex_string(&packet.raw_data[..debug_len])
         ))?;
 
-        self.format_data_pack(writer, &packet.data_pack)?;
+        let frame_digests = digests.map(|digests| digests.frames.as_slice());
+        self.format_data_pack(writer, &packet.data_pack, frame_digests)?;
         writer.writeln("")?;
 
         Ok(())
     }
 
     /// Format DataPack details
-    fn format_data_pack(&mut self, writer: &mut OutputWriter, data_pack: &DataPack) -> Result<()> {
+    fn format_data_pack(
+        &mut self,
+        writer: &mut OutputWriter,
+        data_pack: &DataPack,
+        frame_digests: Option<&[String]>,
+    ) -> Result<()> {
         // Control message
         if let Some(control) = &data_pack.control {
             writer.writeln("  Control message:")?;
@@ -163,7 +377,9 @@ impl<'a> PacketFormatter<'a> {
             for (i, frame) in data_pack.frames.iter().enumerate() {
                 writer.writeln(&format!("    Frame #{}:", i + 1))?;
                 // print sha-256 for frame
-                let frame_digest = super::calculate_digest(&frame.encode_to_vec());
+                let frame_digest = frame_digests
+                    .and_then(|digests| digests.get(i).cloned())
+                    .unwrap_or_else(|| super::calculate_digest(&frame.encode_to_vec()));
                 writer.writeln(&format!("      SHA-256: {}", frame_digest))?;
                 if let Some(message) = &frame.message {
                     self.format_frame_message(writer, message)?;
@@ -189,7 +405,7 @@ impl<'a> PacketFormatter<'a> {
         match message {
             Message::InstantiateObject(obj) => {
                 let object_id = obj.object_id;
-                let prefab_name = String::from_utf8_lossy(&obj.prefab_name).to_string();
+                let prefab_name = obj.prefab_name_lossy();
                 self.objects_map.insert(object_id, prefab_name.clone());
                 writer.writeln("      Type: InstantiateObject")?;
                 writer.writeln(&format!("        Object ID: {}", object_id))?;
@@ -274,6 +490,9 @@ impl<'a> PacketFormatter<'a> {
     }
 }
 
+/// Number of `UpdateObject` methods listed in the statistics
+const TOP_UPDATE_OBJECT_METHODS: usize = 10;
+
 /// Statistics formatter
 pub struct StatsFormatter;
 
@@ -329,6 +548,14 @@ impl StatsFormatter {
             writer.writeln("")?;
         }
 
+        if stats.control.keepalive_dropped > 0 {
+            writer.writeln(&format!(
+                "Dropped keepalive packets: {}",
+                stats.control.keepalive_dropped
+            ))?;
+            writer.writeln("")?;
+        }
+
         // Frame stats
         if stats.frames.total > 0 {
             writer.writeln("Frame Messages:")?;
@@ -378,6 +605,48 @@ impl StatsFormatter {
             writer.writeln("")?;
         }
 
+        // UpdateObject methods
+        let methods = stats
+            .frames
+            .top_update_object_methods(TOP_UPDATE_OBJECT_METHODS);
+        if !methods.is_empty() {
+            writer.writeln(&format!(
+                "Top UpdateObject Methods ({} distinct):",
+                stats.frames.update_object_method_counts.len()
+            ))?;
+            for (method, count) in methods {
+                writer.writeln(&format!(
+                    "  Method {}: {} ({:.1}%)",
+                    method,
+                    count,
+                    percentage(count, stats.frames.update_object_count)
+                ))?;
+            }
+            writer.writeln("")?;
+        }
+
+        // Timestamp gaps
+        if let Some(threshold) = stats.gap_threshold {
+            writer.writeln(&format!(
+                "Timestamp Gaps (over {:.3}s): {}",
+                seconds(threshold),
+                stats.timestamp_gap_count
+            ))?;
+            for gap in &stats.timestamp_gaps {
+                writer.writeln(&format!(
+                    "  {}: {:+.3}s after {}",
+                    gap.timestamp.to_rfc3339_opts(SecondsFormat::Micros, true),
+                    seconds(gap.gap),
+                    gap.previous.to_rfc3339_opts(SecondsFormat::Micros, true)
+                ))?;
+            }
+            let omitted = stats.timestamp_gap_count as usize - stats.timestamp_gaps.len();
+            if omitted > 0 {
+                writer.writeln(&format!("  ... {} more", omitted))?;
+            }
+            writer.writeln("")?;
+        }
+
         // Unknown fields
         if !stats.unknown_fields.is_empty() {
             writer.writeln("Unknown Fields:")?;
@@ -385,6 +654,23 @@ impl StatsFormatter {
             fields.sort_by_key(|(num, _)| *num);
             for (field_num, count) in fields {
                 writer.writeln(&format!("  Field #{}: {} occurrences", field_num, count))?;
+                let wire_types = stats
+                    .unknown_field_wire_types
+                    .get(field_num)
+                    .cloned()
+                    .unwrap_or_default();
+                if !wire_types.is_empty() {
+                    let names: Vec<_> = wire_types.iter().map(|t| wire_type_name(*t)).collect();
+                    writer.writeln(&format!("    Wire types: {}", names.join(", ")))?;
+                }
+                let sample = stats.unknown_field_samples.get(field_num);
+                if let Some(sample) = sample {
+                    writer.writeln(&format!("    Sample: {}", hex::encode(sample)))?;
+                }
+                writer.writeln(&format!(
+                    "    Hypothesis: {}",
+                    unknown_field_hypothesis(&wire_types, sample.map(Vec::as_slice))
+                ))?;
             }
             writer.writeln("")?;
         }
@@ -394,6 +680,391 @@ impl StatsFormatter {
 
         Ok(())
     }
+
+    /// Statistics as JSON, unknown field samples are included when collected
+    pub fn to_json(stats: &PacketStats) -> serde_json::Value {
+        let mut unknown_fields: Vec<_> = stats.unknown_fields.iter().collect();
+        unknown_fields.sort_by_key(|(num, _)| *num);
+        let unknown_fields: Vec<_> = unknown_fields
+            .into_iter()
+            .map(|(field_num, count)| {
+                let mut field = serde_json::json!({
+                    "field_number": field_num,
+                    "count": count,
+                });
+                if let Some(wire_types) = stats.unknown_field_wire_types.get(field_num) {
+                    field["wire_types"] = wire_types
+                        .iter()
+                        .map(|t| wire_type_name(*t))
+                        .collect::<Vec<_>>()
+                        .into();
+                }
+                if let Some(sample) = stats.unknown_field_samples.get(field_num) {
+                    field["sample_hex"] = serde_json::Value::from(hex::encode(sample));
+                }
+                field
+            })
+            .collect();
+
+        let mut json = serde_json::json!({
+            "total_packets": stats.total_packets,
+            "packets_with_control": stats.packets_with_control,
+            "packets_with_frames": stats.packets_with_frames,
+            "total_frames": stats.total_frames,
+            "first_timestamp": stats.first_timestamp.map(|t| t.to_rfc3339()),
+            "last_timestamp": stats.last_timestamp.map(|t| t.to_rfc3339()),
+            "room_id": stats.room_id,
+            "control": {
+                "data": stats.control.data_count,
+                "pong": stats.control.pong_count,
+                "segment_started_at": stats.control.segment_started_at_count,
+                "cache_ended": stats.control.cache_ended_count,
+                "total": stats.control.total,
+                "keepalive_dropped": stats.control.keepalive_dropped,
+            },
+            "frames": {
+                "instantiate_object": stats.frames.instantiate_object_count,
+                "update_object": stats.frames.update_object_count,
+                "destroy_object": stats.frames.destroy_object_count,
+                "room": stats.frames.room_count,
+                "authorize_response": stats.frames.authorize_response_count,
+                "join_room_response": stats.frames.join_room_response_count,
+                "total": stats.frames.total,
+                "update_object_methods": stats
+                    .frames
+                    .top_update_object_methods(usize::MAX)
+                    .into_iter()
+                    .map(|(method, count)| serde_json::json!({ "method": method, "count": count }))
+                    .collect::<Vec<_>>(),
+            },
+            "unknown_fields": unknown_fields,
+        });
+        if let Some(threshold) = stats.gap_threshold {
+            json["timestamp_gaps"] = serde_json::json!({
+                "threshold_ms": threshold.num_milliseconds(),
+                "count": stats.timestamp_gap_count,
+                "gaps": stats
+                    .timestamp_gaps
+                    .iter()
+                    .map(|gap| serde_json::json!({
+                        "previous": gap.previous.to_rfc3339(),
+                        "timestamp": gap.timestamp.to_rfc3339(),
+                        "gap_ms": gap.gap.num_milliseconds(),
+                    }))
+                    .collect::<Vec<_>>(),
+            });
+        }
+        json
+    }
+
+    pub fn write_json(path: &Path, stats: &PacketStats) -> Result<()> {
+        let content = serde_json::to_string_pretty(&Self::to_json(stats))?;
+        std::fs::write(path, content)
+            .with_context(|| format!("Failed to write stats json: {}", path.display()))
+    }
+}
+
+/// Per-file table at the end of a batch analysis
+pub struct FileSummaryFormatter;
+
+impl FileSummaryFormatter {
+    pub fn format_results(writer: &mut OutputWriter, results: &[FileAnalysisResult]) -> Result<()> {
+        let count = |status| results.iter().filter(|r| r.status == status).count();
+        let with_packets = results
+            .iter()
+            .filter(|r| r.error.is_none() && r.packets > 0)
+            .count();
+
+        writer.writeln("=== FILE SUMMARY ===")?;
+        writer.writeln(&format!("{:<10} {:>10}  {}", "Status", "Packets", "File"))?;
+        for result in results {
+            let status = match result.status {
+                FileStatus::Complete => "OK",
+                FileStatus::Empty => "EMPTY",
+                FileStatus::Truncated => "TRUNCATED",
+                FileStatus::Failed => "FAILED",
+            };
+            writer.writeln(&format!(
+                "{:<10} {:>10}  {}",
+                status,
+                result.packets,
+                result.path.display()
+            ))?;
+            if let Some(error) = &result.error {
+                writer.writeln(&format!("{:<10} {:>10}  -> {}", "", "", error))?;
+            }
+        }
+        writer.writeln(&format!(
+            "Files processed: {}, with valid packets: {}, empty: {}, truncated: {}, failed: {}",
+            results.len(),
+            with_packets,
+            count(FileStatus::Empty),
+            count(FileStatus::Truncated),
+            count(FileStatus::Failed)
+        ))?;
+        writer.writeln("")?;
+        Ok(())
+    }
+}
+
+/// Known schema fields with their occurrence counts, then undeclared fields
+pub struct SchemaCoverageFormatter;
+
+impl SchemaCoverageFormatter {
+    pub fn format_table(writer: &mut OutputWriter, coverage: &SchemaCoverage) -> Result<()> {
+        let seen = KNOWN_FIELDS.len() - coverage.missing().len();
+
+        writer.writeln("=== SCHEMA COVERAGE ===")?;
+        writer.writeln(&format!("Packets: {}", coverage.total_packets))?;
+        writer.writeln(&format!(
+            "{:<18} {:<20} {:>5} {:>10}  {}",
+            "Message", "Field", "No.", "Count", "Status"
+        ))?;
+        for field in KNOWN_FIELDS {
+            let count = coverage.count(field);
+            writer.writeln(&format!(
+                "{:<18} {:<20} {:>5} {:>10}  {}",
+                field.message,
+                field.name,
+                field.number,
+                count,
+                if count > 0 { "seen" } else { "never" }
+            ))?;
+        }
+        writer.writeln(&format!(
+            "Known fields seen: {}/{}",
+            seen,
+            KNOWN_FIELDS.len()
+        ))?;
+        writer.writeln("")?;
+
+        if coverage.unknown.is_empty() {
+            writer.writeln("No unknown fields")?;
+        } else {
+            writer.writeln("Unknown fields:")?;
+            writer.writeln(&format!(
+                "{:<18} {:>5} {:>10}  {}",
+                "Message", "No.", "Count", "Wire types"
+            ))?;
+            for ((message, number), count) in &coverage.unknown {
+                let wire_types = coverage
+                    .unknown_wire_types
+                    .get(&(*message, *number))
+                    .map(|types| {
+                        types
+                            .iter()
+                            .map(|t| wire_type_name(*t))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    })
+                    .unwrap_or_default();
+                writer.writeln(&format!(
+                    "{:<18} {:>5} {:>10}  {}",
+                    message, number, count, wire_types
+                ))?;
+            }
+        }
+        writer.writeln("")?;
+        Ok(())
+    }
+}
+
+/// Prefab names of a capture with their occurrence counts
+pub struct PrefabCatalogFormatter;
+
+impl PrefabCatalogFormatter {
+    pub fn format_table(writer: &mut OutputWriter, catalog: &PrefabCatalog) -> Result<()> {
+        writer.writeln(&format!(
+            "{:>10} {:>10}  {}",
+            "Instances", "Updates", "Prefab"
+        ))?;
+        for (prefab_name, usage) in &catalog.prefabs {
+            writer.writeln(&format!(
+                "{:>10} {:>10}  {}",
+                usage.instances, usage.updates, prefab_name
+            ))?;
+        }
+        writer.writeln(&format!("Prefabs: {}", catalog.prefabs.len()))?;
+        Ok(())
+    }
+
+    pub fn to_json(catalog: &PrefabCatalog) -> serde_json::Value {
+        catalog
+            .prefabs
+            .iter()
+            .map(|(prefab_name, usage)| {
+                serde_json::json!({
+                    "prefab_name": prefab_name,
+                    "instances": usage.instances,
+                    "updates": usage.updates,
+                })
+            })
+            .collect()
+    }
+
+    pub fn format_json(writer: &mut OutputWriter, catalog: &PrefabCatalog) -> Result<()> {
+        writer.writeln(&serde_json::to_string_pretty(&Self::to_json(catalog))?)
+    }
+}
+
+pub struct TimestampCheckFormatter;
+
+impl TimestampCheckFormatter {
+    /// Summary with the first `max_listed` backward jumps
+    pub fn format_table(
+        writer: &mut OutputWriter,
+        check: &TimestampCheck,
+        max_listed: usize,
+    ) -> Result<()> {
+        let (Some(first), Some(last)) = (check.first_timestamp, check.last_timestamp) else {
+            return writer.writeln("Packets: 0");
+        };
+        let step = |step: &IndexedGap| {
+            format!(
+                "#{} at {}: {:+.3}s after {}",
+                step.index,
+                step.gap
+                    .timestamp
+                    .to_rfc3339_opts(SecondsFormat::Micros, true),
+                seconds(step.gap.gap),
+                step.gap
+                    .previous
+                    .to_rfc3339_opts(SecondsFormat::Micros, true)
+            )
+        };
+
+        writer.writeln("=== TIMESTAMP CHECK ===")?;
+        writer.writeln(&format!(
+            "Packets: {}, {} to {}",
+            check.total_packets,
+            first.to_rfc3339_opts(SecondsFormat::Micros, true),
+            last.to_rfc3339_opts(SecondsFormat::Micros, true)
+        ))?;
+        writer.writeln(&format!("Backward jumps: {}", check.regression_count))?;
+        for regression in check.regressions.iter().take(max_listed) {
+            writer.writeln(&format!("  {}", step(regression)))?;
+        }
+        let listed = check.regressions.len().min(max_listed) as u64;
+        if check.regression_count > listed {
+            writer.writeln(&format!("  ... {} more", check.regression_count - listed))?;
+        }
+        if let Some(largest) = &check.largest_regression {
+            writer.writeln(&format!("Largest backward jump: {}", step(largest)))?;
+        }
+        if let Some(largest) = &check.largest_gap {
+            writer.writeln(&format!("Largest gap: {}", step(largest)))?;
+        }
+        writer.writeln(&format!(
+            "Gaps over {:.3}s: {}, {:.3}s in total",
+            seconds(check.gap_threshold),
+            check.gap_count,
+            seconds(check.gap_time)
+        ))?;
+        Ok(())
+    }
+}
+
+pub struct EncodeDriftFormatter;
+
+impl EncodeDriftFormatter {
+    /// Summary with the first `max_listed` drifted packets and both of their digests
+    pub fn format_table(
+        writer: &mut OutputWriter,
+        check: &EncodeDriftCheck,
+        max_listed: usize,
+    ) -> Result<()> {
+        writer.writeln("=== ENCODE DRIFT ===")?;
+        writer.writeln(&format!(
+            "Packets: {}, {} frames",
+            check.total_packets, check.total_frames
+        ))?;
+        writer.writeln(&format!(
+            "Drifted packets: {}, {} frames",
+            check.drift_count, check.drifted_frames
+        ))?;
+        for drift in check.drifts.iter().take(max_listed) {
+            let position = match drift.source {
+                Some(source) => format!(" file {} offset {}", source.file_index, source.offset),
+                None => String::new(),
+            };
+            let frames = if drift.frames.is_empty() {
+                "frames identical".to_string()
+            } else {
+                let indexes: Vec<_> = drift.frames.iter().map(usize::to_string).collect();
+                format!("frames {}", indexes.join(", "))
+            };
+            writer.writeln(&format!(
+                "  #{}{} at {}: {} -> {} bytes, first difference at byte {}, {}",
+                drift.index,
+                position,
+                drift.timestamp.to_rfc3339_opts(SecondsFormat::Micros, true),
+                drift.original_len,
+                drift.reencoded_len,
+                drift.first_difference,
+                frames
+            ))?;
+            writer.writeln(&format!("    Original:   {}", drift.original_digest))?;
+            writer.writeln(&format!("    Re-encoded: {}", drift.reencoded_digest))?;
+        }
+        let listed = check.drifts.len().min(max_listed) as u64;
+        if check.drift_count > listed {
+            writer.writeln(&format!("  ... {} more", check.drift_count - listed))?;
+        }
+        Ok(())
+    }
+}
+
+/// One row per analyzed file, RFC 4180 style CSV
+pub struct CsvSummaryFormatter;
+
+impl CsvSummaryFormatter {
+    pub const HEADER: &'static str = "file,size_bytes,packets,frames,first_timestamp,last_timestamp,room_id,error,unknown_fields,status";
+
+    pub fn format_header(writer: &mut OutputWriter) -> Result<()> {
+        writer.write(Self::HEADER)?;
+        writer.write("\r\n")
+    }
+
+    pub fn format_row(
+        writer: &mut OutputWriter,
+        file_path: &Path,
+        size_bytes: Option<u64>,
+        stats: Option<&PacketStats>,
+        error: Option<&str>,
+        status: FileStatus,
+    ) -> Result<()> {
+        let timestamp = |t: Option<DateTime<Utc>>| {
+            t.map(|t| t.to_rfc3339_opts(SecondsFormat::Micros, true))
+                .unwrap_or_default()
+        };
+        let fields = [
+            file_path.display().to_string(),
+            size_bytes.map(|size| size.to_string()).unwrap_or_default(),
+            stats
+                .map(|stats| stats.total_packets.to_string())
+                .unwrap_or_default(),
+            stats
+                .map(|stats| stats.total_frames.to_string())
+                .unwrap_or_default(),
+            timestamp(stats.and_then(|stats| stats.first_timestamp)),
+            timestamp(stats.and_then(|stats| stats.last_timestamp)),
+            stats
+                .and_then(|stats| stats.room_id.clone())
+                .unwrap_or_default(),
+            error.unwrap_or_default().to_string(),
+            stats
+                .map(|stats| stats.unknown_field_count().to_string())
+                .unwrap_or_default(),
+            status.to_string(),
+        ];
+        let row = fields
+            .iter()
+            .map(|field| csv_escape(field))
+            .collect::<Vec<_>>()
+            .join(",");
+        writer.write(&row)?;
+        writer.write("\r\n")
+    }
 }
 
 struct UpdateObjectPayloadAnalyzer<'a> {
@@ -462,6 +1133,10 @@ fn parse_update_payload(
 }
 
 // Helper functions
+fn seconds(delta: TimeDelta) -> f64 {
+    delta.num_milliseconds() as f64 / 1000.0
+}
+
 fn percentage(count: u32, total: u32) -> f64 {
     if total > 0 {
         count as f64 / total as f64 * 100.0
@@ -470,6 +1145,14 @@ fn percentage(count: u32, total: u32) -> f64 {
     }
 }
 
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
 fn hex_string(data: &[u8]) -> String {
     data.iter()
         .map(|b| format!("{:02x}", b))
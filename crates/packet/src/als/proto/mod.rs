@@ -1,8 +1,10 @@
 pub mod analyzer;
 pub mod application;
 pub mod extension;
+pub mod follow;
 pub mod formatter;
 pub mod reader;
+pub mod sink;
 
 use chrono::{DateTime, Utc};
 use prost::Message;
@@ -16,6 +18,12 @@ pub mod define {
 
 use define::DataPack;
 
+/// Live marker byte of the standard format, the only one written by default
+pub const LIVE_MARKER: u8 = 0x01;
+
+/// Live marker bytes the standard reader accepts unless told otherwise
+pub const KNOWN_LIVE_MARKERS: &[u8] = &[LIVE_MARKER];
+
 use crate::als::proto::define::{DataFrame, Room, data_frame};
 
 fn encode_frame(frame: &DataFrame, buf: &mut Vec<u8>) {
@@ -73,11 +81,31 @@ pub fn calculate_digest(data: &[u8]) -> String {
     hex::encode(hasher.finalize())
 }
 
+/// Precomputed SHA-256 digests of a packet and each of its frames
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PacketDigests {
+    pub protobuf: String,
+    pub frames: Vec<String>,
+}
+
+/// Where a packet was read from, carried through conversion for provenance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketSource {
+    /// Index of the input file in reading order, set by `PacketsBufferReader`
+    pub file_index: u32,
+    /// Byte offset of the packet's length header in its file
+    pub offset: u64,
+    /// Timestamp as read, before any adjustment
+    pub timestamp: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone)]
 pub struct PacketInfo {
     pub timestamp: DateTime<Utc>,
     pub data_pack: DataPack,
     pub raw_data: Vec<u8>,
+    /// Capture position of a packet read from a file, `None` for generated packets
+    pub source: Option<PacketSource>,
 }
 
 impl PacketInfo {
@@ -103,6 +131,18 @@ impl PacketInfo {
             .collect()
     }
 
+    /// Calculate protobuf and frame digests at once
+    pub fn digests(&self) -> PacketDigests {
+        PacketDigests {
+            protobuf: self.protobuf_digest(),
+            frames: self
+                .frame_digests()
+                .into_iter()
+                .map(|(_, digest)| digest)
+                .collect(),
+        }
+    }
+
     pub fn create_segment_started_packet(timestamp: DateTime<Utc>) -> Self {
         Self {
             timestamp: timestamp,
@@ -113,6 +153,7 @@ impl PacketInfo {
                 frames: vec![],
             },
             raw_data: vec![],
+            source: None,
         }
     }
 
@@ -126,6 +167,7 @@ impl PacketInfo {
                 }],
             },
             raw_data: vec![],
+            source: None,
         }
     }
 
@@ -137,16 +179,22 @@ impl PacketInfo {
                 frames: vec![],
             },
             raw_data: vec![],
+            source: None,
         }
     }
 
     pub fn to_vec(&self) -> Vec<u8> {
+        self.to_vec_with_marker(LIVE_MARKER)
+    }
+
+    /// Standard format bytes with `marker` in place of the live marker
+    pub fn to_vec_with_marker(&self, marker: u8) -> Vec<u8> {
         let mut buf = Vec::new();
         let data_pack_bytes = self.protobuf_to_vec(); // keep bytes order
         // let data_pack_bytes = self.data_pack.encode_to_vec(); // do not keep the bytes order but workable for replay
         let len = 9 + data_pack_bytes.len() as u16;
         buf.extend_from_slice(&len.to_be_bytes());
-        buf.push(0x01); // live mark
+        buf.push(marker);
         buf.extend_from_slice(&self.timestamp.timestamp_micros().to_be_bytes());
         buf.extend_from_slice(&data_pack_bytes);
         buf
@@ -4,15 +4,20 @@
 //! This module provides a trait-based abstraction for reading different packet formats.
 
 use anyhow::{Context, Result, anyhow};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
 use prost::Message;
-use std::collections::VecDeque;
+use rayon::prelude::*;
+use std::collections::{HashSet, VecDeque};
 use std::fs::{DirEntry, File};
 use std::io::{BufReader, Read};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, mpsc};
+use std::time::{Duration, Instant};
 use std::usize;
 
 use super::define::DataPack;
-use crate::als::proto::PacketInfo;
+use crate::als::proto::{KNOWN_LIVE_MARKERS, PacketInfo, PacketSource};
 
 /// Trait for reading packets from different formats
 ///
@@ -22,6 +27,87 @@ pub trait PacketReaderTrait {
     /// Read the next packet, returns None on EOF
     fn read_packet(&mut self) -> Result<Option<PacketInfo>>;
     fn read_packets(&mut self) -> Result<Vec<PacketInfo>>;
+    /// Reject packets with more than `max` data bytes, wrapping readers pass it on
+    fn set_max_packet_size(&mut self, _max: usize) {}
+    /// Timestamps for formats that record none, wrapping readers pass it on
+    fn set_clock(&mut self, _clock: PacketClock) {}
+    /// Whether `read_raw_packet` is implemented
+    fn supports_raw_packets(&self) -> bool {
+        false
+    }
+    /// Read the next packet without decoding its protobuf payload, returns None on EOF
+    fn read_raw_packet(&mut self) -> Result<Option<RawPacket>> {
+        Err(anyhow!("Reader does not support reading raw packets"))
+    }
+    /// Input bytes consumed by the packets read so far, for readers that track it
+    fn offset(&self) -> Option<u64> {
+        None
+    }
+    /// Framing the packets are read with, wrapping readers pass it on.
+    /// `Unknown` for readers that don't parse framing themselves
+    fn kind(&self) -> CaptureFormat {
+        CaptureFormat::Unknown
+    }
+}
+
+/// Framed packet whose protobuf payload is not decoded yet
+#[derive(Debug, Clone)]
+pub struct RawPacket {
+    pub timestamp: DateTime<Utc>,
+    pub raw_data: Vec<u8>,
+    pub source: Option<PacketSource>,
+}
+
+impl RawPacket {
+    pub fn decode(self) -> Result<PacketInfo> {
+        let data_pack = DataPack::decode(self.raw_data.as_slice()).map_err(|e| {
+            anyhow!(
+                "Failed to decode protobuf data (length: {}): {}",
+                self.raw_data.len(),
+                e
+            )
+        })?;
+        Ok(PacketInfo {
+            timestamp: self.timestamp,
+            data_pack,
+            raw_data: self.raw_data,
+            source: self.source,
+        })
+    }
+}
+
+impl<R: PacketReaderTrait + ?Sized> PacketReaderTrait for Box<R> {
+    fn read_packet(&mut self) -> Result<Option<PacketInfo>> {
+        (**self).read_packet()
+    }
+
+    fn read_packets(&mut self) -> Result<Vec<PacketInfo>> {
+        (**self).read_packets()
+    }
+
+    fn set_max_packet_size(&mut self, max: usize) {
+        (**self).set_max_packet_size(max)
+    }
+
+    fn set_clock(&mut self, clock: PacketClock) {
+        (**self).set_clock(clock)
+    }
+
+    fn supports_raw_packets(&self) -> bool {
+        (**self).supports_raw_packets()
+    }
+
+    fn read_raw_packet(&mut self) -> Result<Option<RawPacket>> {
+        (**self).read_raw_packet()
+    }
+
+    fn offset(&self) -> Option<u64> {
+        (**self).offset()
+    }
+
+    fn kind(&self) -> CaptureFormat {
+        (**self).kind()
+    }
 }
 
 /// Iterator wrapper for any PacketReaderTrait
@@ -52,43 +138,116 @@ impl<'a> Iterator for PacketIterator<'a> {
 // ============================================================================
 
 /// Reader for standard packet format (length + marker + timestamp + protobuf)
-pub struct StandardPacketReader {
-    reader: BufReader<File>,
+pub struct StandardPacketReader<R: Read = File> {
+    reader: BufReader<R>,
+    /// Bytes consumed so far
+    offset: u64,
+    max_packet_size: usize,
+    /// Live marker bytes accepted after the length
+    markers: Vec<u8>,
+    /// Markers logged so far, each one is logged once
+    seen_markers: Vec<u8>,
 }
 
-impl StandardPacketReader {
-    pub fn new(file: File) -> Self {
+impl<R: Read> StandardPacketReader<R> {
+    pub fn new(input: R) -> Self {
         Self {
-            reader: BufReader::new(file),
+            reader: BufReader::new(input),
+            offset: 0,
+            max_packet_size: usize::MAX,
+            markers: KNOWN_LIVE_MARKERS.to_vec(),
+            seen_markers: Vec::new(),
+        }
+    }
+
+    /// Reject packets with more than `max` data bytes (Builder pattern)
+    pub fn with_max_packet_size(mut self, max: usize) -> Self {
+        self.max_packet_size = max;
+        self
+    }
+
+    /// Accept `markers` as live marker bytes instead of the known ones (Builder pattern)
+    pub fn with_markers(mut self, markers: impl Into<Vec<u8>>) -> Self {
+        self.markers = markers.into();
+        self
+    }
+
+    /// Also accept `marker` as a live marker byte, if any (Builder pattern)
+    pub fn with_extra_marker(mut self, marker: Option<u8>) -> Self {
+        if let Some(marker) = marker.filter(|m| !self.markers.contains(m)) {
+            self.markers.push(marker);
         }
+        self
+    }
+
+    /// Resume at byte `offset` of the capture, `input` must already be positioned there
+    /// (Builder pattern)
+    pub fn with_offset(mut self, offset: u64) -> Self {
+        self.offset = offset;
+        self
     }
 
     /// Create a boxed trait object for polymorphic use
-    pub fn boxed(file: File) -> Box<dyn PacketReaderTrait> {
-        Box::new(Self::new(file))
+    pub fn boxed(input: R) -> Box<dyn PacketReaderTrait>
+    where
+        R: 'static,
+    {
+        Box::new(Self::new(input))
     }
 
     fn read_u16_be(&mut self) -> Result<u16> {
         let mut buf = [0u8; 2];
         self.reader.read_exact(&mut buf)?;
+        self.offset += 2;
         Ok(u16::from_be_bytes(buf))
     }
 
     fn read_u8(&mut self) -> Result<u8> {
         let mut buf = [0u8; 1];
         self.reader.read_exact(&mut buf)?;
+        self.offset += 1;
         Ok(buf[0])
     }
 
     fn read_u64_be(&mut self) -> Result<u64> {
         let mut buf = [0u8; 8];
         self.reader.read_exact(&mut buf)?;
+        self.offset += 8;
         Ok(u64::from_be_bytes(buf))
     }
 }
 
-impl PacketReaderTrait for StandardPacketReader {
+impl<R: Read> PacketReaderTrait for StandardPacketReader<R> {
     fn read_packet(&mut self) -> Result<Option<PacketInfo>> {
+        self.read_raw_packet()?.map(RawPacket::decode).transpose()
+    }
+
+    fn read_packets(&mut self) -> Result<Vec<PacketInfo>> {
+        let mut packets = Vec::new();
+        while let Some(packet) = self.read_packet()? {
+            packets.push(packet);
+        }
+        Ok(packets)
+    }
+
+    fn set_max_packet_size(&mut self, max: usize) {
+        self.max_packet_size = max;
+    }
+
+    fn offset(&self) -> Option<u64> {
+        Some(self.offset)
+    }
+
+    fn kind(&self) -> CaptureFormat {
+        CaptureFormat::Standard
+    }
+
+    fn supports_raw_packets(&self) -> bool {
+        true
+    }
+
+    fn read_raw_packet(&mut self) -> Result<Option<RawPacket>> {
+        let offset = self.offset;
         // Try to read length, return None on EOF
         let length = match self.read_u16_be() {
             Ok(len) => len,
@@ -106,12 +265,26 @@ impl PacketReaderTrait for StandardPacketReader {
         let marker = self
             .read_u8()
             .with_context(|| "Failed to read marker byte")?;
-        if marker != 0x01 {
+        if !self.markers.contains(&marker) {
+            let expected: Vec<_> = self
+                .markers
+                .iter()
+                .map(|m| format!("0x{:02x}", m))
+                .collect();
             return Err(anyhow!(
-                "Invalid marker byte: expected 0x01, got 0x{:02x}",
+                "Invalid marker byte: expected one of [{}], got 0x{:02x}",
+                expected.join(", "),
                 marker
             ));
         }
+        if !self.seen_markers.contains(&marker) {
+            self.seen_markers.push(marker);
+            if KNOWN_LIVE_MARKERS.contains(&marker) {
+                tracing::debug!(offset, "Live marker 0x{:02x}", marker);
+            } else {
+                tracing::warn!(offset, "Unknown live marker 0x{:02x}", marker);
+            }
+        }
 
         let timestamp_micros = self
             .read_u64_be()
@@ -126,33 +299,23 @@ impl PacketReaderTrait for StandardPacketReader {
             })?;
 
         let data_length = length - 9;
+        check_packet_size(data_length as usize, self.max_packet_size, offset)?;
         let mut data = vec![0u8; data_length as usize];
         self.reader
             .read_exact(&mut data)
             .with_context(|| format!("Failed to read protobuf data of length {}", data_length))?;
+        self.offset += data_length as u64;
 
-        let data_pack = DataPack::decode(data.as_slice()).map_err(|e| {
-            anyhow!(
-                "Failed to decode protobuf data (length: {}): {}",
-                data_length,
-                e
-            )
-        })?;
-
-        Ok(Some(PacketInfo {
+        Ok(Some(RawPacket {
             timestamp,
-            data_pack,
             raw_data: data,
+            source: Some(PacketSource {
+                file_index: 0,
+                offset,
+                timestamp,
+            }),
         }))
     }
-
-    fn read_packets(&mut self) -> Result<Vec<PacketInfo>> {
-        let mut packets = Vec::new();
-        while let Some(packet) = self.read_packet()? {
-            packets.push(packet);
-        }
-        Ok(packets)
-    }
 }
 
 // ============================================================================
@@ -167,49 +330,103 @@ enum MixedReaderState {
 }
 
 /// Reader for mixed packet format (alternating protobuf and timestamp packets)
-pub struct MixedPacketReader {
-    reader: BufReader<File>,
+pub struct MixedPacketReader<R: Read = File> {
+    reader: BufReader<R>,
     state: MixedReaderState,
-    pending_protobuf: Option<(DataPack, Vec<u8>)>,
+    /// Protobuf packet waiting for its timestamp, with its offset
+    pending_protobuf: Option<(Vec<u8>, u64)>,
+    /// Bytes consumed so far
+    offset: u64,
+    max_packet_size: usize,
 }
 
-impl MixedPacketReader {
-    pub fn new(file: File) -> Self {
+impl<R: Read> MixedPacketReader<R> {
+    pub fn new(input: R) -> Self {
         Self {
-            reader: BufReader::new(file),
+            reader: BufReader::new(input),
             state: MixedReaderState::ExpectProtobuf,
             pending_protobuf: None,
+            offset: 0,
+            max_packet_size: usize::MAX,
         }
     }
 
+    /// Reject packets with more than `max` data bytes (Builder pattern)
+    pub fn with_max_packet_size(mut self, max: usize) -> Self {
+        self.max_packet_size = max;
+        self
+    }
+
+    /// Resume at byte `offset` of the capture, `input` must already be positioned there
+    /// (Builder pattern)
+    pub fn with_offset(mut self, offset: u64) -> Self {
+        self.offset = offset;
+        self
+    }
+
     /// Create a boxed trait object for polymorphic use
-    pub fn boxed(file: File) -> Box<dyn PacketReaderTrait> {
-        Box::new(Self::new(file))
+    pub fn boxed(input: R) -> Box<dyn PacketReaderTrait>
+    where
+        R: 'static,
+    {
+        Box::new(Self::new(input))
     }
 
     fn read_u16_be(&mut self) -> Result<u16> {
         let mut buf = [0u8; 2];
         self.reader.read_exact(&mut buf)?;
+        self.offset += 2;
         Ok(u16::from_be_bytes(buf))
     }
 
     fn read_u8(&mut self) -> Result<u8> {
         let mut buf = [0u8; 1];
         self.reader.read_exact(&mut buf)?;
+        self.offset += 1;
         Ok(buf[0])
     }
 
     fn read_u64_be(&mut self) -> Result<u64> {
         let mut buf = [0u8; 8];
         self.reader.read_exact(&mut buf)?;
+        self.offset += 8;
         Ok(u64::from_be_bytes(buf))
     }
 }
 
-impl PacketReaderTrait for MixedPacketReader {
-    // read two packets each time, convert it to one PacketInfo
+impl<R: Read> PacketReaderTrait for MixedPacketReader<R> {
     fn read_packet(&mut self) -> Result<Option<PacketInfo>> {
+        self.read_raw_packet()?.map(RawPacket::decode).transpose()
+    }
+
+    fn read_packets(&mut self) -> Result<Vec<PacketInfo>> {
+        let mut packets = Vec::new();
+        while let Some(packet) = self.read_packet()? {
+            packets.push(packet);
+        }
+        Ok(packets)
+    }
+
+    fn set_max_packet_size(&mut self, max: usize) {
+        self.max_packet_size = max;
+    }
+
+    fn offset(&self) -> Option<u64> {
+        Some(self.offset)
+    }
+
+    fn kind(&self) -> CaptureFormat {
+        CaptureFormat::Mixed
+    }
+
+    fn supports_raw_packets(&self) -> bool {
+        true
+    }
+
+    // read two packets each time, convert it to one RawPacket
+    fn read_raw_packet(&mut self) -> Result<Option<RawPacket>> {
         loop {
+            let offset = self.offset;
             // Read length header
             let length = match self.read_u16_be() {
                 Ok(len) => len,
@@ -226,14 +443,13 @@ impl PacketReaderTrait for MixedPacketReader {
 
                     let _unused = self.read_u8()?;
                     let data_length = length - 1;
+                    check_packet_size(data_length as usize, self.max_packet_size, offset)?;
                     let mut data = vec![0u8; data_length as usize];
                     self.reader.read_exact(&mut data)?;
-
-                    let data_pack = DataPack::decode(data.as_slice())
-                        .with_context(|| "Failed to decode protobuf")?;
+                    self.offset += data_length as u64;
 
                     // Store protobuf data, wait for timestamp
-                    self.pending_protobuf = Some((data_pack, data));
+                    self.pending_protobuf = Some((data, offset));
                     self.state = MixedReaderState::ExpectTimestamp;
                     // Continue to read timestamp
                     continue;
@@ -249,58 +465,122 @@ impl PacketReaderTrait for MixedPacketReader {
                         .ok_or_else(|| anyhow!("Invalid timestamp: {}", timestamp_micros))?;
 
                     // Combine with pending protobuf
-                    let (data_pack, raw_data) = self
+                    let (raw_data, offset) = self
                         .pending_protobuf
                         .take()
                         .ok_or_else(|| anyhow!("Missing protobuf packet"))?;
 
                     self.state = MixedReaderState::ExpectProtobuf;
 
-                    return Ok(Some(PacketInfo {
+                    return Ok(Some(RawPacket {
                         timestamp,
-                        data_pack,
                         raw_data,
+                        source: Some(PacketSource {
+                            file_index: 0,
+                            offset,
+                            timestamp,
+                        }),
                     }));
                 }
             }
         }
     }
+}
 
-    fn read_packets(&mut self) -> Result<Vec<PacketInfo>> {
-        let mut packets = Vec::new();
-        while let Some(packet) = self.read_packet()? {
-            packets.push(packet);
+/// Timestamps given to packets of formats that record none
+#[derive(Debug, Clone, Default)]
+pub enum PacketClock {
+    /// Time the packet is read
+    #[default]
+    WallClock,
+    /// The first packet at a start time, every following one `step` later.
+    /// Clones share the position, so the files of one capture continue each other.
+    Fixed {
+        next_micros: Arc<AtomicI64>,
+        step: TimeDelta,
+    },
+}
+
+impl PacketClock {
+    /// Reproducible timestamps starting at `start`, for tests and deterministic output
+    pub fn fixed(start: DateTime<Utc>, step: TimeDelta) -> Self {
+        Self::Fixed {
+            next_micros: Arc::new(AtomicI64::new(start.timestamp_micros())),
+            step,
+        }
+    }
+
+    /// Timestamp of the next packet
+    pub fn tick(&self) -> DateTime<Utc> {
+        match self {
+            Self::WallClock => Utc::now(),
+            Self::Fixed { next_micros, step } => {
+                let step = step.num_microseconds().unwrap_or(i64::MAX);
+                let micros = next_micros.fetch_add(step, Ordering::Relaxed);
+                DateTime::from_timestamp_micros(micros).unwrap_or_default()
+            }
         }
-        Ok(packets)
     }
 }
 
 /// Reader for legacy mixed packet format, no timestamp packet
-pub struct LegacyPacketReader {
-    reader: BufReader<File>,
+pub struct LegacyPacketReader<R: Read = File> {
+    reader: BufReader<R>,
+    /// Bytes consumed so far
+    offset: u64,
+    max_packet_size: usize,
+    /// Stamps the packets, the capture has no timestamps
+    clock: PacketClock,
 }
 
-impl LegacyPacketReader {
-    pub fn new(file: File) -> Self {
+impl<R: Read> LegacyPacketReader<R> {
+    pub fn new(input: R) -> Self {
         Self {
-            reader: BufReader::new(file),
+            reader: BufReader::new(input),
+            offset: 0,
+            max_packet_size: usize::MAX,
+            clock: PacketClock::WallClock,
         }
     }
 
+    /// Reject packets with more than `max` data bytes (Builder pattern)
+    pub fn with_max_packet_size(mut self, max: usize) -> Self {
+        self.max_packet_size = max;
+        self
+    }
+
+    /// Stamp packets with `clock` instead of the wall clock (Builder pattern)
+    pub fn with_clock(mut self, clock: PacketClock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Resume at byte `offset` of the capture, `input` must already be positioned there
+    /// (Builder pattern)
+    pub fn with_offset(mut self, offset: u64) -> Self {
+        self.offset = offset;
+        self
+    }
+
     /// Create a boxed trait object for polymorphic use
-    pub fn boxed(file: File) -> Box<dyn PacketReaderTrait> {
-        Box::new(Self::new(file))
+    pub fn boxed(input: R) -> Box<dyn PacketReaderTrait>
+    where
+        R: 'static,
+    {
+        Box::new(Self::new(input))
     }
 
     fn read_u16_be(&mut self) -> Result<u16> {
         let mut buf = [0u8; 2];
         self.reader.read_exact(&mut buf)?;
+        self.offset += 2;
         Ok(u16::from_be_bytes(buf))
     }
 
     fn read_u8(&mut self) -> Result<u8> {
         let mut buf = [0u8; 1];
         self.reader.read_exact(&mut buf)?;
+        self.offset += 1;
         Ok(buf[0])
     }
 
@@ -311,9 +591,10 @@ impl LegacyPacketReader {
     // }
 }
 
-impl PacketReaderTrait for LegacyPacketReader {
+impl<R: Read> PacketReaderTrait for LegacyPacketReader<R> {
     // read two packets each time, convert it to one PacketInfo
     fn read_packet(&mut self) -> Result<Option<PacketInfo>> {
+        let offset = self.offset;
         // Read length header
         let length = match self.read_u16_be() {
             Ok(len) => len,
@@ -327,14 +608,22 @@ impl PacketReaderTrait for LegacyPacketReader {
         }
         let _unused = self.read_u8()?;
         let data_length = length - 1;
+        check_packet_size(data_length as usize, self.max_packet_size, offset)?;
         let mut data = vec![0u8; data_length as usize];
         self.reader.read_exact(&mut data)?;
+        self.offset += data_length as u64;
         let data_pack =
             DataPack::decode(data.as_slice()).with_context(|| "Failed to decode protobuf")?;
+        let timestamp = self.clock.tick();
         return Ok(Some(PacketInfo {
-            timestamp: Utc::now(),
+            timestamp,
             raw_data: data_pack.encode_to_vec(),
             data_pack: data_pack,
+            source: Some(PacketSource {
+                file_index: 0,
+                offset,
+                timestamp,
+            }),
         }));
     }
     fn read_packets(&mut self) -> Result<Vec<PacketInfo>> {
@@ -344,12 +633,317 @@ impl PacketReaderTrait for LegacyPacketReader {
         }
         Ok(packets)
     }
+
+    fn set_max_packet_size(&mut self, max: usize) {
+        self.max_packet_size = max;
+    }
+
+    fn set_clock(&mut self, clock: PacketClock) {
+        self.clock = clock;
+    }
+
+    fn offset(&self) -> Option<u64> {
+        Some(self.offset)
+    }
+
+    fn kind(&self) -> CaptureFormat {
+        CaptureFormat::Legacy
+    }
+}
+
+// ============================================================================
+// Input Format Selection
+// ============================================================================
+
+/// Packet file format, `Auto` is resolved by sniffing the first packet
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InputFormat {
+    /// Standard framing, accepting an extra live marker byte besides the known ones
+    Standard(Option<u8>),
+    Mixed,
+    Legacy,
+    #[default]
+    Auto,
+}
+
+impl std::str::FromStr for InputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "standard" => Ok(Self::Standard(None)),
+            // "als" is the former converter type name
+            "mixed" | "als" => Ok(Self::Mixed),
+            "legacy" | "mixed-legacy" | "als-legacy" => Ok(Self::Legacy),
+            "auto" => Ok(Self::Auto),
+            _ => Err(anyhow!("Unsupported input format: {}", s)),
+        }
+    }
+}
+
+impl InputFormat {
+    /// Resolve `Auto` by sniffing `path`, other formats are returned as is
+    pub fn resolve(self, path: &std::path::Path) -> Result<Self> {
+        match self {
+            Self::Auto => Self::detected(detect_format(path)?.format)
+                .with_context(|| format!("Unrecognized packet format: {}", path.display())),
+            format => Ok(format),
+        }
+    }
+
+    /// Also accept `marker` as a live marker byte, which only standard captures carry.
+    /// `Auto` becomes `Standard`, since sniffing does not know the marker
+    pub fn with_live_marker(self, marker: Option<u8>) -> Result<Self> {
+        match (self, marker) {
+            (format, None) => Ok(format),
+            (Self::Standard(_) | Self::Auto, marker) => Ok(Self::Standard(marker)),
+            (format, Some(_)) => Err(anyhow!(
+                "Live markers only apply to the standard format, not {:?}",
+                format
+            )),
+        }
+    }
+
+    // Helper: input format of a sniffed capture format
+    pub(crate) fn detected(format: CaptureFormat) -> Result<Self> {
+        match format {
+            CaptureFormat::Standard => Ok(Self::Standard(None)),
+            CaptureFormat::Mixed => Ok(Self::Mixed),
+            CaptureFormat::Legacy => Ok(Self::Legacy),
+            CaptureFormat::Unknown => Err(anyhow!("Unrecognized packet format")),
+        }
+    }
+
+    /// Reader constructor for a resolved format
+    pub fn reader_factory(
+        self,
+    ) -> Result<impl Fn(File) -> Box<dyn PacketReaderTrait> + Copy + Send + Sync + 'static> {
+        type Open = fn(File, Option<u8>) -> Box<dyn PacketReaderTrait>;
+        let (open, marker): (Open, _) = match self {
+            Self::Standard(marker) => (
+                |file, marker| Box::new(StandardPacketReader::new(file).with_extra_marker(marker)),
+                marker,
+            ),
+            Self::Mixed => (|file, _| MixedPacketReader::boxed(file), None),
+            Self::Legacy => (|file, _| LegacyPacketReader::boxed(file), None),
+            Self::Auto => return Err(anyhow!("Input format must be resolved before reading")),
+        };
+        Ok(move |file| open(file, marker))
+    }
+
+    /// Reader of a resolved format over any byte stream
+    pub fn open_stream<R: Read + 'static>(self, input: R) -> Result<Box<dyn PacketReaderTrait>> {
+        match self {
+            Self::Standard(marker) => Ok(Box::new(
+                StandardPacketReader::new(input).with_extra_marker(marker),
+            )),
+            Self::Mixed => Ok(MixedPacketReader::boxed(input)),
+            Self::Legacy => Ok(LegacyPacketReader::boxed(input)),
+            Self::Auto => Err(anyhow!("Input format must be resolved before reading")),
+        }
+    }
+}
+
+/// Path argument that reads a capture from stdin
+pub const STDIN_PATH: &str = "-";
+
+/// Whether `path` is the stdin argument `-`
+pub fn is_stdin(path: &std::path::Path) -> bool {
+    path.as_os_str() == STDIN_PATH
+}
+
+/// Packet reader of a capture file, or of stdin when `path` is `-`
+pub fn open_input(
+    path: &std::path::Path,
+    format: InputFormat,
+) -> Result<Box<dyn PacketReaderTrait>> {
+    if is_stdin(path) {
+        return Ok(open_stream(std::io::stdin(), format)?.1);
+    }
+    let reader_factory = format.resolve(path)?.reader_factory()?;
+    let file =
+        File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
+    Ok(reader_factory(file))
+}
+
+/// Packet reader over a forward-only stream, `Auto` is resolved by sniffing the head of
+/// the stream, which is replayed to the reader afterwards. Returns the resolved format
+pub fn open_stream<R: Read + 'static>(
+    mut input: R,
+    format: InputFormat,
+) -> Result<(InputFormat, Box<dyn PacketReaderTrait>)> {
+    let (format, head) = match format {
+        InputFormat::Auto => {
+            let (detection, head) = detect_stream_format(&mut input)?;
+            let format = InputFormat::detected(detection.format)
+                .with_context(|| format!("Unrecognized packet format: {}", detection.reason))?;
+            (format, head)
+        }
+        format => (format, Vec::new()),
+    };
+    let reader = format.open_stream(std::io::Cursor::new(head).chain(input))?;
+    Ok((format, reader))
+}
+
+/// Capture format classified from file framing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureFormat {
+    Standard,
+    Mixed,
+    Legacy,
+    Unknown,
+}
+
+impl std::fmt::Display for CaptureFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Standard => write!(f, "standard"),
+            Self::Mixed => write!(f, "mixed"),
+            Self::Legacy => write!(f, "legacy"),
+            Self::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+/// How sure `detect_format` is about its answer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectConfidence {
+    /// Two consecutive headers agree with the format
+    High,
+    /// Only the first packet could be checked
+    Low,
+}
+
+/// Result of `detect_format`
+#[derive(Debug, Clone)]
+pub struct FormatDetection {
+    pub format: CaptureFormat,
+    pub confidence: DetectConfidence,
+    /// Short explanation of the decision
+    pub reason: String,
+}
+
+impl FormatDetection {
+    fn new(format: CaptureFormat, confidence: DetectConfidence, reason: &str) -> Self {
+        Self {
+            format,
+            confidence,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+/// Bytes read from the start of the file by `detect_format`
+const DETECT_HEAD_BYTES: usize = 64;
+
+/// Classify a capture file by its framing:
+/// - standard: `[len u16][marker u8][timestamp u64][protobuf]`, with a known live marker
+/// - mixed: `[len u16][unused u8][protobuf]` followed by `[0x0008][timestamp u64]`
+/// - legacy: `[len u16][unused u8][protobuf]` only
+///
+/// Reads the first 64 bytes plus the header of the second packet.
+pub fn detect_format(path: &std::path::Path) -> Result<FormatDetection> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open file: {}", path.display()))?;
+    Ok(detect_stream_format(file)?.0)
+}
+
+/// `detect_format` for a stream, also returns the bytes it consumed
+pub fn detect_stream_format<R: Read>(mut input: R) -> Result<(FormatDetection, Vec<u8>)> {
+    let mut prefix = Vec::with_capacity(DETECT_HEAD_BYTES);
+    (&mut input).take(2).read_to_end(&mut prefix)?;
+    if prefix.len() == 2 {
+        // Rest of the head, or the first packet and the header of the second one
+        let length = u16::from_be_bytes([prefix[0], prefix[1]]) as usize;
+        let wanted = DETECT_HEAD_BYTES.max(2 + length + 11) - 2;
+        (&mut input).take(wanted as u64).read_to_end(&mut prefix)?;
+    }
+    Ok((classify_prefix(&prefix), prefix))
+}
+
+// Helper: classify the bytes read by `detect_stream_format`
+fn classify_prefix(prefix: &[u8]) -> FormatDetection {
+    use CaptureFormat::*;
+    use DetectConfidence::*;
+
+    let head = &prefix[..prefix.len().min(DETECT_HEAD_BYTES)];
+    if head.len() < 3 {
+        return FormatDetection::new(Unknown, High, "file shorter than a packet header");
+    }
+
+    let length = u16::from_be_bytes([head[0], head[1]]) as usize;
+    let next_offset = 2 + length;
+    if next_offset > prefix.len() {
+        return FormatDetection::new(Unknown, Low, "first packet length exceeds file size");
+    }
+
+    // Header of the second packet: length and the next 9 bytes
+    let next = &prefix[next_offset..prefix.len().min(next_offset + 11)];
+    let next_length = (next.len() >= 2).then(|| u16::from_be_bytes([next[0], next[1]]));
+
+    let standard_first =
+        length >= 9 && KNOWN_LIVE_MARKERS.contains(&head[2]) && plausible_timestamp(&head[3..11]);
+    if standard_first {
+        return match next_length {
+            None => FormatDetection::new(Standard, Low, "single standard packet"),
+            Some(next_length)
+                if next_length >= 9
+                    && next.len() >= 11
+                    && KNOWN_LIVE_MARKERS.contains(&next[2])
+                    && plausible_timestamp(&next[3..11]) =>
+            {
+                FormatDetection::new(Standard, High, "live marker and timestamps in two packets")
+            }
+            Some(_) => FormatDetection::new(
+                Standard,
+                Low,
+                "first packet is standard, second packet does not match",
+            ),
+        };
+    }
+
+    if length < 3 {
+        return FormatDetection::new(Unknown, High, "first packet too short for any format");
+    }
+    match next_length {
+        Some(8) if next.len() >= 10 && plausible_timestamp(&next[2..10]) => {
+            FormatDetection::new(Mixed, High, "protobuf packet followed by 8-byte timestamp")
+        }
+        Some(next_length) if next_length >= 3 => FormatDetection::new(
+            Legacy,
+            High,
+            "consecutive protobuf packets without timestamps",
+        ),
+        Some(_) => FormatDetection::new(Unknown, Low, "second packet length is invalid"),
+        // A mixed capture cut after its first protobuf packet looks the same
+        None => FormatDetection::new(Legacy, Low, "single protobuf packet, could be mixed"),
+    }
+}
+
+// Timestamp between 2020-01-01 and 2100-01-01 in microseconds
+fn plausible_timestamp(bytes: &[u8]) -> bool {
+    bytes.len() >= 8
+        && (1_577_836_800_000_000..=4_102_444_800_000_000)
+            .contains(&u64::from_be_bytes(bytes[..8].try_into().unwrap()))
 }
 
 // ============================================================================
 // Helper Functions
 // ============================================================================
 
+/// Fail before allocating a buffer for a packet larger than `max` data bytes
+fn check_packet_size(data_length: usize, max: usize, offset: u64) -> Result<()> {
+    if data_length > max {
+        return Err(anyhow!(
+            "Packet at offset {} has {} data bytes, exceeding the maximum packet size of {}",
+            offset,
+            data_length,
+            max
+        ));
+    }
+    Ok(())
+}
+
 fn is_eof_error(e: &anyhow::Error) -> bool {
     let error_msg = e.to_string().to_lowercase();
     error_msg.contains("eof")
@@ -357,6 +951,15 @@ fn is_eof_error(e: &anyhow::Error) -> bool {
         || error_msg.contains("failed to fill whole buffer")
 }
 
+/// Whether reading stopped because the input ended inside a packet
+pub(crate) fn is_truncated(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| {
+        cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|e| e.kind() == std::io::ErrorKind::UnexpectedEof)
+    })
+}
+
 // ============================================================================
 // Convenience Type Aliases
 // ============================================================================
@@ -376,6 +979,8 @@ pub struct ReaderLimits {
     pub max_files: usize,
     /// Maximum number of packets per file (None = unlimited)
     pub max_packets_per_file: usize,
+    /// Maximum data bytes of a single packet, larger packets are an error (None = unlimited)
+    pub max_packet_size: usize,
 }
 
 impl Default for ReaderLimits {
@@ -384,6 +989,7 @@ impl Default for ReaderLimits {
             max_packets: usize::MAX,
             max_files: usize::MAX,
             max_packets_per_file: usize::MAX,
+            max_packet_size: usize::MAX,
         }
     }
 }
@@ -411,13 +1017,34 @@ impl ReaderLimits {
         self.max_packets_per_file = max;
         self
     }
+
+    /// Set maximum data bytes of a single packet
+    pub fn with_max_packet_size(mut self, max: usize) -> Self {
+        self.max_packet_size = max;
+        self
+    }
+}
+
+/// A file `PacketsBufferReader` is done with
+#[derive(Debug, Clone)]
+pub struct FileReadSummary {
+    pub path: PathBuf,
+    pub packets: usize,
+    /// From opening the file until the packet after its last one was requested,
+    /// so it covers whatever the caller did with the file's packets
+    pub elapsed: Duration,
 }
 
+pub type FileObserver = Box<dyn FnMut(&FileReadSummary)>;
+
 pub struct PacketsBufferReader {
     current_reader: Option<Box<dyn PacketReaderTrait>>,
     file_entries: VecDeque<DirEntry>,
     reader_factory: ReaderFactory,
     limits: ReaderLimits,
+    clock: PacketClock,
+    file_observer: Option<FileObserver>,
+    current_file: Option<(PathBuf, Instant)>,
     // Tracking counters
     total_packets_read: usize,
     files_processed: usize,
@@ -435,6 +1062,9 @@ impl PacketsBufferReader {
             file_entries,
             reader_factory: Box::new(reader_factory),
             limits: ReaderLimits::default(),
+            clock: PacketClock::WallClock,
+            file_observer: None,
+            current_file: None,
             total_packets_read: 0,
             files_processed: 0,
             current_file_packets: 0,
@@ -461,6 +1091,15 @@ impl PacketsBufferReader {
         self
     }
 
+    /// Call `observer` each time a file is finished (Builder pattern)
+    pub fn with_file_observer<F>(mut self, observer: F) -> Self
+    where
+        F: FnMut(&FileReadSummary) + 'static,
+    {
+        self.file_observer = Some(Box::new(observer));
+        self
+    }
+
     /// Create a boxed trait object for polymorphic use
     pub fn boxed<F>(
         file_entries: VecDeque<DirEntry>,
@@ -497,15 +1136,34 @@ impl PacketsBufferReader {
         false
     }
 
+    /// Drop the current reader and report the file to the observer
+    fn close_current_file(&mut self) {
+        self.current_reader = None;
+        if let Some((path, opened_at)) = self.current_file.take()
+            && let Some(observer) = &mut self.file_observer
+        {
+            observer(&FileReadSummary {
+                path,
+                packets: self.current_file_packets,
+                elapsed: opened_at.elapsed(),
+            });
+        }
+    }
+
     /// Try to open the next file and create a new reader
     fn open_next_file(&mut self) -> Result<bool> {
+        self.close_current_file();
         // Reset per-file counter
         self.current_file_packets = 0;
 
         if let Some(entry) = self.file_entries.pop_front() {
             let file = File::open(entry.path())
                 .with_context(|| format!("Failed to open file: {:?}", entry.path()))?;
-            self.current_reader = Some((self.reader_factory)(file));
+            let mut reader = (self.reader_factory)(file);
+            reader.set_max_packet_size(self.limits.max_packet_size);
+            reader.set_clock(self.clock.clone());
+            self.current_reader = Some(reader);
+            self.current_file = Some((entry.path(), Instant::now()));
             self.files_processed += 1;
             Ok(true)
         } else {
@@ -514,6 +1172,13 @@ impl PacketsBufferReader {
     }
 }
 
+impl Drop for PacketsBufferReader {
+    // Report a file the caller stopped reading early
+    fn drop(&mut self) {
+        self.close_current_file();
+    }
+}
+
 /// Statistics about reader progress
 #[derive(Debug, Clone, Copy)]
 pub struct ReaderStats {
@@ -534,10 +1199,11 @@ impl PacketReaderTrait for PacketsBufferReader {
             if self.check_limits() {
                 // Per-file limit reached, move to next file
                 if self.current_file_packets >= self.limits.max_packets_per_file {
-                    self.current_reader = None; // Close current file
+                    self.close_current_file();
                 // Try next file (will be opened below)
                 } else {
                     // Total packets limit reached, stop completely
+                    self.close_current_file();
                     return Ok(None);
                 }
             }
@@ -545,15 +1211,18 @@ impl PacketReaderTrait for PacketsBufferReader {
             // Try to read from current reader
             if let Some(reader) = &mut self.current_reader {
                 match reader.read_packet()? {
-                    Some(packet) => {
+                    Some(mut packet) => {
                         // Update counters
                         self.total_packets_read += 1;
                         self.current_file_packets += 1;
+                        if let Some(source) = &mut packet.source {
+                            source.file_index = self.files_processed.saturating_sub(1) as u32;
+                        }
                         return Ok(Some(packet));
                     }
                     None => {
                         // Current file exhausted, try next file
-                        self.current_reader = None;
+                        self.close_current_file();
                     }
                 }
             }
@@ -574,17 +1243,1015 @@ impl PacketReaderTrait for PacketsBufferReader {
         }
         Ok(packets)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    #[test]
-    fn test_packet_reader_eof() {
-        // Test that EOF is handled gracefully
+    fn set_max_packet_size(&mut self, max: usize) {
+        self.limits.max_packet_size = max;
+        if let Some(reader) = &mut self.current_reader {
+            reader.set_max_packet_size(max);
+        }
     }
 
-    #[test]
-    fn test_trait_object() {
-        // Test that we can use readers polymorphically
+    fn set_clock(&mut self, clock: PacketClock) {
+        if let Some(reader) = &mut self.current_reader {
+            reader.set_clock(clock.clone());
+        }
+        self.clock = clock;
+    }
+    /// Kind of the file being read, `Unknown` before the first file is opened
+    fn kind(&self) -> CaptureFormat {
+        self.current_reader
+            .as_ref()
+            .map_or(CaptureFormat::Unknown, |reader| reader.kind())
+    }
+}
+
+// ============================================================================
+// Indexed Reader
+// ============================================================================
+
+/// Pre-scanned file list with the packet count of each file
+pub struct PacketFileIndex {
+    file_entries: VecDeque<DirEntry>,
+    packet_counts: Vec<usize>,
+}
+
+impl PacketFileIndex {
+    /// Scan every file once with the given reader factory to count packets
+    pub fn build(
+        file_entries: VecDeque<DirEntry>,
+        reader_factory: &dyn Fn(File) -> Box<dyn PacketReaderTrait>,
+    ) -> Result<Self> {
+        let mut packet_counts = Vec::with_capacity(file_entries.len());
+        for entry in &file_entries {
+            let file = File::open(entry.path())
+                .with_context(|| format!("Failed to open file: {:?}", entry.path()))?;
+            let mut reader = reader_factory(file);
+            let mut count = 0;
+            while reader.read_packet()?.is_some() {
+                count += 1;
+            }
+            packet_counts.push(count);
+        }
+        Ok(Self {
+            file_entries,
+            packet_counts,
+        })
+    }
+
+    /// Packet count of each file, in reading order
+    pub fn packet_counts(&self) -> &[usize] {
+        &self.packet_counts
+    }
+
+    /// Total packets across all indexed files
+    pub fn total_packets(&self) -> usize {
+        self.packet_counts.iter().sum()
+    }
+
+    /// Number of packets a reader will yield under the given limits
+    pub fn expected_packets(&self, limits: &ReaderLimits) -> usize {
+        count_within_limits(&self.packet_counts, limits)
+    }
+}
+
+impl PacketsBufferReader {
+    /// Create a reader whose total packet count is known from an index
+    pub fn new_indexed<F>(index: PacketFileIndex, reader_factory: F) -> IndexedPacketsBufferReader
+    where
+        F: Fn(File) -> Box<dyn PacketReaderTrait> + 'static,
+    {
+        let total_packets = index.expected_packets(&ReaderLimits::default());
+        IndexedPacketsBufferReader {
+            inner: Self::new(index.file_entries, reader_factory),
+            packet_counts: index.packet_counts,
+            total_packets,
+        }
+    }
+}
+
+fn count_within_limits(packet_counts: &[usize], limits: &ReaderLimits) -> usize {
+    let total: usize = packet_counts
+        .iter()
+        .take(limits.max_files)
+        .map(|count| (*count).min(limits.max_packets_per_file))
+        .sum();
+    total.min(limits.max_packets)
+}
+
+/// Multi-file reader with a pre-known packet count, usable as an `ExactSizeIterator`
+pub struct IndexedPacketsBufferReader {
+    inner: PacketsBufferReader,
+    packet_counts: Vec<usize>,
+    total_packets: usize,
+}
+
+impl IndexedPacketsBufferReader {
+    /// Set limits (Builder pattern), the expected packet count follows them
+    pub fn with_limits(mut self, limits: ReaderLimits) -> Self {
+        self.inner = self.inner.with_limits(limits);
+        self.total_packets = count_within_limits(&self.packet_counts, &limits);
+        self
+    }
+
+    /// Total packets this reader will yield
+    pub fn total_packets(&self) -> usize {
+        self.total_packets
+    }
+
+    /// Get statistics about reading progress
+    pub fn stats(&self) -> ReaderStats {
+        self.inner.stats()
+    }
+}
+
+impl PacketReaderTrait for IndexedPacketsBufferReader {
+    fn read_packet(&mut self) -> Result<Option<PacketInfo>> {
+        self.inner.read_packet()
+    }
+
+    fn read_packets(&mut self) -> Result<Vec<PacketInfo>> {
+        self.inner.read_packets()
+    }
+
+    fn set_max_packet_size(&mut self, max: usize) {
+        self.inner.set_max_packet_size(max);
+    }
+
+    fn set_clock(&mut self, clock: PacketClock) {
+        self.inner.set_clock(clock);
+    }
+    fn kind(&self) -> CaptureFormat {
+        self.inner.kind()
+    }
+}
+
+impl Iterator for IndexedPacketsBufferReader {
+    type Item = Result<PacketInfo>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.inner.read_packet() {
+            Ok(Some(packet)) => Some(Ok(packet)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self
+            .total_packets
+            .saturating_sub(self.inner.total_packets_read);
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for IndexedPacketsBufferReader {}
+
+/// Reader dropping packets already seen with the same timestamp and digests,
+/// e.g. overlapping shards of a capture that reconnected
+///
+/// Only packets within `window` of the newest timestamp are remembered, so repeats
+/// at different times (keepalives) are never collapsed.
+pub struct DedupPacketReader<R: PacketReaderTrait> {
+    inner: R,
+    window: TimeDelta,
+    recent: VecDeque<(DateTime<Utc>, String, Vec<String>)>,
+    seen: HashSet<(DateTime<Utc>, String, Vec<String>)>,
+    newest: Option<DateTime<Utc>>,
+    duplicates: usize,
+}
+
+impl<R: PacketReaderTrait> DedupPacketReader<R> {
+    pub fn new(inner: R, window: TimeDelta) -> Self {
+        Self {
+            inner,
+            window,
+            recent: VecDeque::new(),
+            seen: HashSet::new(),
+            newest: None,
+            duplicates: 0,
+        }
+    }
+
+    /// Packets dropped so far
+    pub fn duplicates(&self) -> usize {
+        self.duplicates
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    // Forget packets that fell out of the window
+    fn evict(&mut self, newest: DateTime<Utc>) {
+        while let Some((timestamp, ..)) = self.recent.front()
+            && newest - *timestamp > self.window
+        {
+            if let Some(key) = self.recent.pop_front() {
+                self.seen.remove(&key);
+            }
+        }
+    }
+}
+
+impl<R: PacketReaderTrait> PacketReaderTrait for DedupPacketReader<R> {
+    fn read_packet(&mut self) -> Result<Option<PacketInfo>> {
+        while let Some(packet) = self.inner.read_packet()? {
+            let key = (
+                packet.timestamp,
+                packet.protobuf_digest(),
+                packet
+                    .frame_digests()
+                    .into_iter()
+                    .map(|(_, digest)| digest)
+                    .collect::<Vec<_>>(),
+            );
+            if self.seen.contains(&key) {
+                self.duplicates += 1;
+                continue;
+            }
+            let newest = self
+                .newest
+                .map_or(packet.timestamp, |n| n.max(packet.timestamp));
+            self.newest = Some(newest);
+            if newest - packet.timestamp <= self.window {
+                self.seen.insert(key.clone());
+                self.recent.push_back(key);
+            }
+            self.evict(newest);
+            return Ok(Some(packet));
+        }
+        Ok(None)
+    }
+
+    fn read_packets(&mut self) -> Result<Vec<PacketInfo>> {
+        let mut packets = Vec::new();
+        while let Some(packet) = self.read_packet()? {
+            packets.push(packet);
+        }
+        Ok(packets)
+    }
+
+    fn set_max_packet_size(&mut self, max: usize) {
+        self.inner.set_max_packet_size(max);
+    }
+
+    fn set_clock(&mut self, clock: PacketClock) {
+        self.inner.set_clock(clock);
+    }
+    fn kind(&self) -> CaptureFormat {
+        self.inner.kind()
+    }
+}
+
+/// Packets `OrderedPacketReader` holds at most, the oldest is released early beyond it
+const MAX_HELD_PACKETS: usize = 65_536;
+
+/// What `OrderedPacketReader` does with a packet stamped earlier than one read before it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutOfOrderPolicy {
+    /// Hold packets for the window and release them sorted by timestamp
+    #[default]
+    Reorder,
+    /// Stamp the packet with the timestamp of the packet before it
+    Clamp,
+    /// Fail at the packet
+    Error,
+}
+
+impl std::str::FromStr for OutOfOrderPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "reorder" => Ok(Self::Reorder),
+            "clamp" => Ok(Self::Clamp),
+            "error" => Ok(Self::Error),
+            _ => Err(anyhow!("Unsupported out-of-order policy: {}", s)),
+        }
+    }
+}
+
+/// Reader keeping packet timestamps from going backwards, e.g. in merged captures
+///
+/// A packet stamped up to `window` earlier than the newest packet read is out of order
+/// and handled by the policy. Jumps further back are reconnect seams, they pass through
+/// unchanged once every held packet is released and are left to `smooth_timestamps`.
+/// `Reorder` holds at most the packets of one window, and never more than 65536.
+pub struct OrderedPacketReader<R: PacketReaderTrait> {
+    inner: R,
+    policy: OutOfOrderPolicy,
+    window: TimeDelta,
+    /// Held packets sorted by timestamp, in read order among equal timestamps
+    held: VecDeque<PacketInfo>,
+    /// Packets released before the seam that follows them
+    ready: VecDeque<PacketInfo>,
+    /// Newest timestamp read since the last seam
+    newest: Option<DateTime<Utc>>,
+    /// Timestamp of the last packet returned
+    last: Option<DateTime<Utc>>,
+    out_of_order: usize,
+    seams: usize,
+    exhausted: bool,
+}
+
+impl<R: PacketReaderTrait> OrderedPacketReader<R> {
+    pub fn new(inner: R, policy: OutOfOrderPolicy, window: TimeDelta) -> Self {
+        Self {
+            inner,
+            policy,
+            window,
+            held: VecDeque::new(),
+            ready: VecDeque::new(),
+            newest: None,
+            last: None,
+            out_of_order: 0,
+            seams: 0,
+            exhausted: false,
+        }
+    }
+
+    /// Packets read earlier than the packet before them, reordered or clamped
+    pub fn out_of_order(&self) -> usize {
+        self.out_of_order
+    }
+
+    /// Backward jumps larger than the window, passed through unchanged
+    pub fn seams(&self) -> usize {
+        self.seams
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    // Track `packet` against the newest timestamp, false for a seam
+    fn check(&mut self, packet: &PacketInfo) -> Result<bool> {
+        let Some(newest) = self.newest else {
+            self.newest = Some(packet.timestamp);
+            return Ok(true);
+        };
+        if newest - packet.timestamp > self.window {
+            tracing::debug!(
+                "Timestamp seam of {}ms at {}",
+                (newest - packet.timestamp).num_milliseconds(),
+                packet.timestamp
+            );
+            self.seams += 1;
+            self.newest = Some(packet.timestamp);
+            return Ok(false);
+        }
+        if packet.timestamp < newest {
+            if self.policy == OutOfOrderPolicy::Error {
+                return Err(anyhow!(
+                    "Packet at {} is {}ms earlier than a packet before it{}",
+                    packet.timestamp,
+                    (newest - packet.timestamp).num_milliseconds(),
+                    packet
+                        .source
+                        .map(|source| format!(", offset {}", source.offset))
+                        .unwrap_or_default()
+                ));
+            }
+            self.out_of_order += 1;
+        }
+        self.newest = Some(newest.max(packet.timestamp));
+        Ok(true)
+    }
+
+    // Oldest held packet once no packet within the window can come before it
+    fn release(&mut self) -> Option<PacketInfo> {
+        if let Some(packet) = self.ready.pop_front() {
+            return Some(packet);
+        }
+        let front = self.held.front()?;
+        let expired = self
+            .newest
+            .is_some_and(|newest| newest - front.timestamp > self.window);
+        if self.exhausted || expired || self.held.len() > MAX_HELD_PACKETS {
+            return self.held.pop_front();
+        }
+        None
+    }
+
+    // Stamp `packet` no earlier than the last packet returned, unless it starts a seam
+    fn returned(&mut self, mut packet: PacketInfo, seam: bool) -> PacketInfo {
+        if !seam && let Some(last) = self.last {
+            packet.timestamp = packet.timestamp.max(last);
+        }
+        self.last = Some(packet.timestamp);
+        packet
+    }
+}
+
+impl<R: PacketReaderTrait> PacketReaderTrait for OrderedPacketReader<R> {
+    fn read_packet(&mut self) -> Result<Option<PacketInfo>> {
+        loop {
+            if self.policy == OutOfOrderPolicy::Reorder
+                && let Some(packet) = self.release()
+            {
+                // only packets released early over the limit can be behind the last one
+                let seam = self
+                    .last
+                    .is_some_and(|last| last - packet.timestamp > self.window);
+                return Ok(Some(self.returned(packet, seam)));
+            }
+            if self.exhausted {
+                return Ok(None);
+            }
+            let Some(packet) = self.inner.read_packet()? else {
+                self.exhausted = true;
+                continue;
+            };
+            let in_order = self.check(&packet)?;
+            if self.policy != OutOfOrderPolicy::Reorder {
+                return Ok(Some(self.returned(packet, !in_order)));
+            }
+            if !in_order {
+                self.ready.extend(self.held.drain(..));
+            }
+            let index = self
+                .held
+                .partition_point(|held| held.timestamp <= packet.timestamp);
+            self.held.insert(index, packet);
+        }
+    }
+
+    fn read_packets(&mut self) -> Result<Vec<PacketInfo>> {
+        let mut packets = Vec::new();
+        while let Some(packet) = self.read_packet()? {
+            packets.push(packet);
+        }
+        Ok(packets)
+    }
+
+    fn set_max_packet_size(&mut self, max: usize) {
+        self.inner.set_max_packet_size(max);
+    }
+
+    fn set_clock(&mut self, clock: PacketClock) {
+        self.inner.set_clock(clock);
+    }
+    fn kind(&self) -> CaptureFormat {
+        self.inner.kind()
+    }
+}
+
+/// Batches of raw packets decoded on the rayon pool, oldest first
+type DecodedBatch = mpsc::Receiver<Vec<(usize, Result<PacketInfo>)>>;
+
+/// Reader decoding protobuf payloads on the rayon pool
+///
+/// Framing is read on the calling thread in batches of `batch_size` packets, up to
+/// `max_in_flight` batches are decoded ahead. Every packet carries its read index, so
+/// packets come out in input order; a read error is returned after the packets read
+/// before it. The inner reader must support `read_raw_packet`.
+pub struct ParallelDecodeReader {
+    inner: Box<dyn PacketReaderTrait>,
+    batch_size: usize,
+    max_in_flight: usize,
+    in_flight: VecDeque<DecodedBatch>,
+    ready: VecDeque<(usize, Result<PacketInfo>)>,
+    /// Index of the next packet read from `inner`
+    next_read: usize,
+    /// Index of the next packet returned
+    next_output: usize,
+    /// Read error or EOF of `inner`, reported once all earlier packets are returned
+    end: Option<Result<()>>,
+}
+
+impl ParallelDecodeReader {
+    pub fn new(inner: Box<dyn PacketReaderTrait>) -> Self {
+        Self {
+            inner,
+            batch_size: 256,
+            max_in_flight: rayon::current_num_threads().max(1) * 2,
+            in_flight: VecDeque::new(),
+            ready: VecDeque::new(),
+            next_read: 0,
+            next_output: 0,
+            end: None,
+        }
+    }
+
+    /// Packets decoded per pool task (Builder pattern)
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    // Read batches until `max_in_flight` are decoding or the inner reader ended
+    fn fill(&mut self) {
+        while self.end.is_none() && self.in_flight.len() < self.max_in_flight {
+            let mut batch = Vec::with_capacity(self.batch_size);
+            while batch.len() < self.batch_size {
+                match self.inner.read_raw_packet() {
+                    Ok(Some(raw)) => {
+                        batch.push((self.next_read, raw));
+                        self.next_read += 1;
+                    }
+                    Ok(None) => {
+                        self.end = Some(Ok(()));
+                        break;
+                    }
+                    Err(e) => {
+                        self.end = Some(Err(e));
+                        break;
+                    }
+                }
+            }
+            if batch.is_empty() {
+                break;
+            }
+
+            let (sender, receiver) = mpsc::sync_channel(1);
+            rayon::spawn(move || {
+                let decoded = batch
+                    .into_par_iter()
+                    .map(|(index, raw)| (index, raw.decode()))
+                    .collect();
+                let _ = sender.send(decoded);
+            });
+            self.in_flight.push_back(receiver);
+        }
+    }
+}
+
+impl PacketReaderTrait for ParallelDecodeReader {
+    fn read_packet(&mut self) -> Result<Option<PacketInfo>> {
+        loop {
+            if let Some((index, packet)) = self.ready.pop_front() {
+                if index != self.next_output {
+                    return Err(anyhow!(
+                        "Decoded packet #{} out of order, expected #{}",
+                        index,
+                        self.next_output
+                    ));
+                }
+                self.next_output += 1;
+                return packet.map(Some);
+            }
+
+            self.fill();
+            match self.in_flight.pop_front() {
+                Some(batch) => {
+                    self.ready = batch
+                        .recv()
+                        .map_err(|_| anyhow!("Packet decode worker panicked"))?
+                        .into();
+                }
+                None => {
+                    return match self.end.replace(Ok(())) {
+                        Some(Err(e)) => Err(e),
+                        _ => Ok(None),
+                    };
+                }
+            }
+        }
+    }
+
+    fn read_packets(&mut self) -> Result<Vec<PacketInfo>> {
+        let mut packets = Vec::new();
+        while let Some(packet) = self.read_packet()? {
+            packets.push(packet);
+        }
+        Ok(packets)
+    }
+
+    fn set_max_packet_size(&mut self, max: usize) {
+        self.inner.set_max_packet_size(max);
+    }
+
+    fn set_clock(&mut self, clock: PacketClock) {
+        self.inner.set_clock(clock);
+    }
+    fn kind(&self) -> CaptureFormat {
+        self.inner.kind()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_packet_reader_eof() {
+        // Test that EOF is handled gracefully
+    }
+
+    #[test]
+    fn test_trait_object() {
+        // Test that we can use readers polymorphically
+    }
+
+    #[test]
+    fn test_indexed_reader_exact_size() {
+        use super::*;
+        use chrono::TimeZone;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let timestamp = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        for (name, count) in [("a.bin", 3), ("b.bin", 2)] {
+            let mut bytes = Vec::new();
+            for _ in 0..count {
+                bytes.extend(PacketInfo::create_cache_end(timestamp).to_vec());
+            }
+            std::fs::write(dir.join(name), bytes).unwrap();
+        }
+        let entries = || {
+            let mut entries: Vec<_> = std::fs::read_dir(dir)
+                .unwrap()
+                .map(|e| e.unwrap())
+                .collect();
+            entries.sort_by_key(|e| e.file_name());
+            entries.into_iter().collect::<VecDeque<_>>()
+        };
+
+        let index = PacketFileIndex::build(entries(), &StandardPacketReader::boxed).unwrap();
+        assert_eq!(index.packet_counts(), &[3, 2]);
+        assert_eq!(index.total_packets(), 5);
+
+        let mut reader = PacketsBufferReader::new_indexed(index, StandardPacketReader::boxed);
+        assert_eq!(reader.len(), 5);
+        reader.next().unwrap().unwrap();
+        assert_eq!(reader.len(), 4);
+        assert_eq!(reader.count(), 4);
+
+        let index = PacketFileIndex::build(entries(), &StandardPacketReader::boxed).unwrap();
+        let reader = PacketsBufferReader::new_indexed(index, StandardPacketReader::boxed)
+            .with_limits(ReaderLimits::default().with_max_packets_per_file(2));
+        assert_eq!(reader.len(), 4);
+        assert_eq!(reader.count(), 4);
+    }
+
+    #[test]
+    fn test_max_packet_size() {
+        use super::*;
+        use chrono::TimeZone;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let timestamp = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let packet = PacketInfo::create_cache_end(timestamp).to_vec();
+        let data_length = packet.len() - 11;
+        std::fs::write(dir.join("a.bin"), [packet.clone(), packet].concat()).unwrap();
+        let entries = || {
+            std::fs::read_dir(dir)
+                .unwrap()
+                .map(|e| e.unwrap())
+                .collect::<VecDeque<_>>()
+        };
+
+        let mut reader = StandardPacketReader::new(File::open(dir.join("a.bin")).unwrap())
+            .with_max_packet_size(data_length);
+        assert_eq!(reader.read_packets().unwrap().len(), 2);
+
+        let mut reader = PacketsBufferReader::new_standard(entries())
+            .with_limits(ReaderLimits::default().with_max_packet_size(data_length - 1));
+        let error = reader.read_packet().unwrap_err().to_string();
+        assert!(error.contains("exceeding the maximum packet size"));
+        assert!(error.contains("offset 0"));
+    }
+
+    #[test]
+    fn test_live_markers() {
+        use super::*;
+        use crate::als::proto::LIVE_MARKER;
+        use chrono::TimeZone;
+        use std::io::Cursor;
+
+        let timestamp = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let packet = PacketInfo::create_cache_end(timestamp);
+        assert_eq!(packet.to_vec(), packet.to_vec_with_marker(LIVE_MARKER));
+        assert_eq!(packet.to_vec()[2], 0x01);
+        let bytes = [packet.to_vec(), packet.to_vec_with_marker(0x02)].concat();
+
+        let error = StandardPacketReader::new(Cursor::new(bytes.clone()))
+            .read_packets()
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.contains("expected one of [0x01], got 0x02"),
+            "{}",
+            error
+        );
+
+        let mut reader =
+            StandardPacketReader::new(Cursor::new(bytes.clone())).with_markers([0x01, 0x02]);
+        let packets = reader.read_packets().unwrap();
+        assert_eq!(packets.len(), 2);
+        assert_eq!(packets[1].timestamp, timestamp);
+        assert_eq!(reader.seen_markers, [0x01, 0x02]);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.bin");
+        std::fs::write(&path, &bytes).unwrap();
+        let mut reader = open_input(&path, InputFormat::Auto).unwrap();
+        assert!(reader.read_packets().is_err());
+        let format = InputFormat::Auto.with_live_marker(Some(0x02)).unwrap();
+        assert_eq!(format, InputFormat::Standard(Some(0x02)));
+        let packets = open_input(&path, format).unwrap().read_packets().unwrap();
+        assert_eq!(packets.len(), 2);
+        assert!(InputFormat::Mixed.with_live_marker(Some(0x02)).is_err());
+    }
+
+    #[test]
+    fn test_file_observer() {
+        use super::*;
+        use chrono::TimeZone;
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let timestamp = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        for (name, count) in [("a.bin", 3), ("b.bin", 2)] {
+            let mut bytes = Vec::new();
+            for _ in 0..count {
+                bytes.extend(PacketInfo::create_cache_end(timestamp).to_vec());
+            }
+            std::fs::write(dir.join(name), bytes).unwrap();
+        }
+        let entries = || {
+            let mut entries: Vec<_> = std::fs::read_dir(dir)
+                .unwrap()
+                .map(|e| e.unwrap())
+                .collect();
+            entries.sort_by_key(|e| e.file_name());
+            entries.into_iter().collect::<VecDeque<_>>()
+        };
+        let observed = Rc::new(RefCell::new(Vec::new()));
+        let observer = |observed: &Rc<RefCell<Vec<(String, usize)>>>| {
+            let observed = observed.clone();
+            move |summary: &FileReadSummary| {
+                let name = summary.path.file_name().unwrap().to_string_lossy();
+                observed
+                    .borrow_mut()
+                    .push((name.into_owned(), summary.packets));
+            }
+        };
+
+        let mut reader =
+            PacketsBufferReader::new_standard(entries()).with_file_observer(observer(&observed));
+        assert_eq!(reader.read_packets().unwrap().len(), 5);
+        assert_eq!(
+            *observed.borrow(),
+            vec![("a.bin".to_string(), 3), ("b.bin".to_string(), 2)]
+        );
+
+        // A file left early is reported when the reader is dropped
+        observed.borrow_mut().clear();
+        let mut reader =
+            PacketsBufferReader::new_standard(entries()).with_file_observer(observer(&observed));
+        reader.read_packet().unwrap().unwrap();
+        drop(reader);
+        assert_eq!(*observed.borrow(), vec![("a.bin".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_input_format_resolve() {
+        use super::*;
+        use chrono::TimeZone;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let timestamp = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let packet = PacketInfo::create_cache_end(timestamp);
+        let protobuf = packet.data_pack.encode_to_vec();
+        let mut legacy = ((protobuf.len() + 1) as u16).to_be_bytes().to_vec();
+        legacy.push(0x00);
+        legacy.extend(&protobuf);
+        let mut mixed = legacy.clone();
+        mixed.extend(8u16.to_be_bytes());
+        mixed.extend((timestamp.timestamp_micros() as u64).to_be_bytes());
+
+        for (name, bytes, expected, kind) in [
+            (
+                "standard.bin",
+                packet.to_vec(),
+                InputFormat::Standard(None),
+                CaptureFormat::Standard,
+            ),
+            ("mixed.bin", mixed, InputFormat::Mixed, CaptureFormat::Mixed),
+            (
+                "legacy.bin",
+                legacy,
+                InputFormat::Legacy,
+                CaptureFormat::Legacy,
+            ),
+        ] {
+            let path = dir.join(name);
+            std::fs::write(&path, bytes).unwrap();
+            assert_eq!(InputFormat::Auto.resolve(&path).unwrap(), expected);
+            assert_eq!(
+                InputFormat::Legacy.resolve(&path).unwrap(),
+                InputFormat::Legacy
+            );
+            // the reader reports its own framing, through wrapping readers too
+            let reader = open_input(&path, InputFormat::Auto).unwrap();
+            assert_eq!(reader.kind(), kind);
+            let reader = DedupPacketReader::new(reader, TimeDelta::seconds(1));
+            assert_eq!(ParallelDecodeReader::new(Box::new(reader)).kind(), kind);
+        }
+        assert_eq!(
+            open_input(&dir.join("legacy.bin"), InputFormat::Standard(None))
+                .unwrap()
+                .kind(),
+            CaptureFormat::Standard
+        );
+        assert_eq!(
+            "mixed-legacy".parse::<InputFormat>().unwrap(),
+            InputFormat::Legacy
+        );
+        assert!(InputFormat::Auto.reader_factory().is_err());
+    }
+
+    #[test]
+    fn test_detect_format_confidence() {
+        use super::*;
+        use chrono::TimeZone;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let timestamp = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let packet = PacketInfo::create_cache_end(timestamp).to_vec();
+        let detect = |name: &str, bytes: Vec<u8>| {
+            let path = dir.join(name);
+            std::fs::write(&path, bytes).unwrap();
+            let detection = detect_format(&path).unwrap();
+            (detection.format, detection.confidence)
+        };
+
+        assert_eq!(
+            detect("one.bin", packet.clone()),
+            (CaptureFormat::Standard, DetectConfidence::Low)
+        );
+        assert_eq!(
+            detect("two.bin", packet.repeat(2)),
+            (CaptureFormat::Standard, DetectConfidence::High)
+        );
+        assert_eq!(
+            detect("empty.bin", Vec::new()),
+            (CaptureFormat::Unknown, DetectConfidence::High)
+        );
+        // Length header larger than the file
+        assert_eq!(
+            detect("truncated.bin", vec![0x01, 0x00, 0x00]),
+            (CaptureFormat::Unknown, DetectConfidence::Low)
+        );
+    }
+
+    #[test]
+    fn test_dedup_reader() {
+        use super::*;
+        use chrono::TimeZone;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("dedup.bin");
+        let timestamp = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let at = |seconds: i64| {
+            PacketInfo::create_cache_end(timestamp + TimeDelta::seconds(seconds)).to_vec()
+        };
+        // Shard 1: 0 1 2, shard 2 overlaps from 1: 1 2 3, then a keepalive-like repeat at 3
+        let bytes: Vec<u8> = [0, 1, 2, 1, 2, 3, 3, 20, 1]
+            .into_iter()
+            .flat_map(at)
+            .collect();
+        std::fs::write(&path, bytes).unwrap();
+        let inner = StandardPacketReader::new(File::open(&path).unwrap());
+        let mut reader = DedupPacketReader::new(inner, TimeDelta::seconds(5));
+        let seconds: Vec<i64> = reader
+            .read_packets()
+            .unwrap()
+            .iter()
+            .map(|p| (p.timestamp - timestamp).num_seconds())
+            .collect();
+        // 1 at the end is outside the window of 20, so it is kept
+        assert_eq!(seconds, vec![0, 1, 2, 3, 20, 1]);
+        assert_eq!(reader.duplicates(), 3);
+    }
+
+    #[test]
+    fn test_ordered_reader() {
+        use super::*;
+        use chrono::TimeZone;
+
+        let timestamp = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        // 500ms backwards at 1000 and 2500, a reconnect seam back to 100
+        let millis = [0, 500, 1500, 1000, 2000, 3000, 2500, 3500, 100, 200];
+        let bytes: Vec<u8> = millis
+            .into_iter()
+            .flat_map(|ms| {
+                PacketInfo::create_cache_end(timestamp + TimeDelta::milliseconds(ms)).to_vec()
+            })
+            .collect();
+        let read = |policy| {
+            let inner = StandardPacketReader::new(std::io::Cursor::new(bytes.clone()));
+            let mut reader = OrderedPacketReader::new(inner, policy, TimeDelta::seconds(2));
+            reader.read_packets().map(|packets| {
+                let millis: Vec<i64> = packets
+                    .iter()
+                    .map(|p| (p.timestamp - timestamp).num_milliseconds())
+                    .collect();
+                (millis, reader.out_of_order(), reader.seams())
+            })
+        };
+
+        assert_eq!(
+            read(OutOfOrderPolicy::Reorder).unwrap(),
+            (
+                vec![0, 500, 1000, 1500, 2000, 2500, 3000, 3500, 100, 200],
+                2,
+                1
+            )
+        );
+        assert_eq!(
+            read(OutOfOrderPolicy::Clamp).unwrap(),
+            (
+                vec![0, 500, 1500, 1500, 2000, 3000, 3000, 3500, 100, 200],
+                2,
+                1
+            )
+        );
+        let error = read(OutOfOrderPolicy::Error).unwrap_err();
+        assert!(error.to_string().contains("500ms earlier"), "{}", error);
+        assert!("shuffle".parse::<OutOfOrderPolicy>().is_err());
+    }
+
+    #[test]
+    fn test_parallel_decode_reader() {
+        use super::*;
+        use chrono::TimeZone;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("parallel.bin");
+        let timestamp = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let valid: Vec<u8> = (0..500)
+            .flat_map(|i| PacketInfo::create_cache_end(timestamp + TimeDelta::seconds(i)).to_vec())
+            .collect();
+        let read = |bytes: Vec<u8>, parallel: bool| {
+            std::fs::write(&path, bytes).unwrap();
+            let inner = StandardPacketReader::boxed(File::open(&path).unwrap());
+            let mut reader: Box<dyn PacketReaderTrait> = if parallel {
+                Box::new(ParallelDecodeReader::new(inner).with_batch_size(7))
+            } else {
+                inner
+            };
+            let mut seconds = Vec::new();
+            loop {
+                match reader.read_packet() {
+                    Ok(Some(packet)) => seconds.push((packet.timestamp - timestamp).num_seconds()),
+                    Ok(None) => return (seconds, None),
+                    Err(e) => return (seconds, Some(e.to_string())),
+                }
+            }
+        };
+
+        let (seconds, error) = read(valid.clone(), true);
+        assert_eq!(seconds, (0..500).collect::<Vec<_>>());
+        assert!(error.is_none());
+
+        // Undecodable payload in the middle, then a packet cut after its marker
+        let mut garbage = vec![0x00, 12, 0x01];
+        garbage.extend(timestamp.timestamp_micros().to_be_bytes());
+        garbage.extend([0xff, 0xff, 0xff]);
+        for bytes in [
+            [&valid[..], &garbage[..], &valid[..]].concat(),
+            [&valid[..], &[0x00, 0x20, 0x01][..]].concat(),
+        ] {
+            let parallel = read(bytes.clone(), true);
+            assert_eq!(parallel, read(bytes, false));
+            assert_eq!(parallel.0.len(), 500);
+            assert!(parallel.1.is_some());
+        }
+    }
+
+    #[test]
+    fn test_open_stream() {
+        use super::*;
+        use chrono::TimeZone;
+
+        let timestamp = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let bytes: Vec<u8> = (0..3)
+            .flat_map(|i| PacketInfo::create_cache_end(timestamp + TimeDelta::seconds(i)).to_vec())
+            .collect();
+
+        for format in [InputFormat::Auto, InputFormat::Standard(None)] {
+            let (format, mut reader) =
+                open_stream(std::io::Cursor::new(bytes.clone()), format).unwrap();
+            assert_eq!(format, InputFormat::Standard(None));
+            let timestamps: Vec<_> = reader
+                .read_packets()
+                .unwrap()
+                .iter()
+                .map(|packet| packet.timestamp)
+                .collect();
+            assert_eq!(
+                timestamps,
+                (0..3)
+                    .map(|i| timestamp + TimeDelta::seconds(i))
+                    .collect::<Vec<_>>()
+            );
+        }
+
+        assert!(open_stream(std::io::Cursor::new(vec![0x00]), InputFormat::Auto).is_err());
     }
 }
@@ -0,0 +1,184 @@
+//! Analysis sinks - where analysis events go
+//! `OutputWriter` is the default text sink, other sinks receive structured events
+
+use anyhow::Result;
+
+use super::analyzer::PacketStats;
+use super::application::{FileAnalysisResult, FileStatus};
+use super::reader::CaptureFormat;
+use super::{PacketDigests, PacketInfo};
+
+/// Structured event emitted while analyzing files
+#[derive(Debug)]
+pub enum AnalysisEvent<'a> {
+    /// Single file analysis started, `format` is the framing of the reader in use
+    FileStarted {
+        path: &'a str,
+        max_packets: usize,
+        format: CaptureFormat,
+    },
+    /// A packet passed the filters
+    Packet {
+        number: usize,
+        packet: &'a PacketInfo,
+        /// Digests hashed ahead of time, see `AnalyzeOptions::parallel_digest`
+        digests: Option<&'a PacketDigests>,
+    },
+    /// Informational notice, e.g. a limit was reached
+    Notice(String),
+    /// Single file analysis finished
+    FileFinished {
+        packets_read: usize,
+        packets_processed: usize,
+        stats: &'a PacketStats,
+    },
+    /// Directory analysis started
+    BatchStarted {
+        dir: &'a str,
+        total_files: usize,
+        max_packets_per_file: usize,
+    },
+    /// One file of a directory finished, `index` starts at 0
+    BatchFile {
+        index: usize,
+        total: usize,
+        result: &'a FileAnalysisResult,
+    },
+    /// Directory analysis finished with combined statistics
+    BatchFinished {
+        results: &'a [FileAnalysisResult],
+        stats: &'a PacketStats,
+    },
+}
+
+/// Receiver of analysis events
+pub trait AnalysisSink {
+    fn on_event(&mut self, event: AnalysisEvent<'_>) -> Result<()>;
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Sink forwarding every event to a callback
+pub struct CallbackSink<F>
+where
+    F: FnMut(&AnalysisEvent<'_>) -> Result<()>,
+{
+    callback: F,
+}
+
+impl<F> CallbackSink<F>
+where
+    F: FnMut(&AnalysisEvent<'_>) -> Result<()>,
+{
+    pub fn new(callback: F) -> Self {
+        Self { callback }
+    }
+}
+
+impl<F> AnalysisSink for CallbackSink<F>
+where
+    F: FnMut(&AnalysisEvent<'_>) -> Result<()>,
+{
+    fn on_event(&mut self, event: AnalysisEvent<'_>) -> Result<()> {
+        (self.callback)(&event)
+    }
+}
+
+/// Sink logging events through `tracing`, packets are logged at debug level
+#[derive(Debug, Default)]
+pub struct TracingSink;
+
+impl AnalysisSink for TracingSink {
+    fn on_event(&mut self, event: AnalysisEvent<'_>) -> Result<()> {
+        match event {
+            AnalysisEvent::FileStarted {
+                path,
+                max_packets,
+                format,
+            } => {
+                tracing::info!(path, max_packets, %format, "analysis started");
+            }
+            AnalysisEvent::Packet { number, packet, .. } => {
+                tracing::debug!(
+                    number,
+                    timestamp = %packet.timestamp,
+                    frames = packet.data_pack.frames.len(),
+                    "packet"
+                );
+            }
+            AnalysisEvent::Notice(message) => tracing::info!("{}", message),
+            AnalysisEvent::FileFinished {
+                packets_read,
+                packets_processed,
+                stats,
+            } => {
+                tracing::info!(
+                    packets_read,
+                    packets_processed,
+                    total_frames = stats.total_frames,
+                    "analysis finished"
+                );
+            }
+            AnalysisEvent::BatchStarted {
+                dir,
+                total_files,
+                max_packets_per_file,
+            } => {
+                tracing::info!(dir, total_files, max_packets_per_file, "batch started");
+            }
+            AnalysisEvent::BatchFile {
+                index,
+                total,
+                result,
+            } => match (result.status, &result.error) {
+                (_, Some(error)) => tracing::warn!(
+                    file = %result.path.display(),
+                    "file {}/{} failed: {}",
+                    index + 1,
+                    total,
+                    error
+                ),
+                (FileStatus::Empty, None) => tracing::warn!(
+                    file = %result.path.display(),
+                    "file {}/{} is empty, skipped",
+                    index + 1,
+                    total
+                ),
+                (FileStatus::Truncated, None) => tracing::warn!(
+                    file = %result.path.display(),
+                    packets = result.packets,
+                    "file {}/{} truncated after {} packets",
+                    index + 1,
+                    total,
+                    result.packets
+                ),
+                _ => tracing::info!(
+                    file = %result.path.display(),
+                    packets = result.packets,
+                    "file {}/{} analyzed",
+                    index + 1,
+                    total
+                ),
+            },
+            AnalysisEvent::BatchFinished { results, stats } => {
+                tracing::info!(
+                    files = results.len(),
+                    empty = results
+                        .iter()
+                        .filter(|r| r.status == FileStatus::Empty)
+                        .count(),
+                    truncated = results
+                        .iter()
+                        .filter(|r| r.status == FileStatus::Truncated)
+                        .count(),
+                    failed = results.iter().filter(|r| r.error.is_some()).count(),
+                    total_packets = stats.total_packets,
+                    "batch finished"
+                );
+            }
+        }
+        Ok(())
+    }
+}
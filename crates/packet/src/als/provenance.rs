@@ -0,0 +1,262 @@
+//! Provenance sidecar of a conversion
+//!
+//! With provenance enabled, every part directory gets a `provenance.bin` next to
+//! `index.m3u8` holding one fixed size record per output packet, so a packet of the
+//! converted replay can be traced back to its input file, byte offset and original
+//! timestamp. Layout, all integers little endian:
+//!
+//! - header: `LKPV`, version `u8`, input file count `u32`, then per file `u16` length + utf8 path
+//! - record (37 bytes): segment `u32`, packet `u32`, file index `u32` (`u32::MAX` for
+//!   generated packets), byte offset `u64`, original timestamp `i64` (micros),
+//!   timestamp delta `i64` (micros), adjustment flags `u8`
+
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, TimeDelta, Utc};
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+
+use super::proto::PacketSource;
+
+/// Sidecar file written next to `index.m3u8`
+pub const PROVENANCE_FILE: &str = "provenance.bin";
+
+const MAGIC: &[u8; 4] = b"LKPV";
+const VERSION: u8 = 1;
+const RECORD_SIZE: usize = 37;
+/// File index of packets generated by the converter (segment headers, full state)
+const NO_SOURCE: u32 = u32::MAX;
+
+/// Rewrites applied to a packet on its way to the output, as bit flags
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Adjustments(pub u8);
+
+impl Adjustments {
+    pub const TIMESHIFT: u8 = 1 << 0;
+    pub const AUTO_TIMESTAMP: u8 = 1 << 1;
+    pub const SMOOTH_TIMESTAMPS: u8 = 1 << 2;
+    /// Frames of an oversized packet were split over several output packets
+    pub const SPLIT: u8 = 1 << 3;
+
+    pub fn contains(&self, flag: u8) -> bool {
+        self.0 & flag != 0
+    }
+
+    pub fn with(self, flag: u8, enabled: bool) -> Self {
+        if enabled { Self(self.0 | flag) } else { self }
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        [
+            (Self::TIMESHIFT, "timeshift"),
+            (Self::AUTO_TIMESTAMP, "auto-timestamp"),
+            (Self::SMOOTH_TIMESTAMPS, "smooth-timestamps"),
+            (Self::SPLIT, "split"),
+        ]
+        .into_iter()
+        .filter(|(flag, _)| self.contains(*flag))
+        .map(|(_, name)| name)
+        .collect()
+    }
+}
+
+/// Lineage of one output packet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProvenanceRecord {
+    pub segment: u32,
+    /// Zero based packet index inside the segment
+    pub packet: u32,
+    /// Input position, `None` for packets generated by the converter
+    pub source: Option<PacketSource>,
+    /// Output timestamp minus the original one
+    pub delta: TimeDelta,
+    pub adjustments: Adjustments,
+}
+
+impl ProvenanceRecord {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend(self.segment.to_le_bytes());
+        buf.extend(self.packet.to_le_bytes());
+        let (file_index, offset, timestamp) = match &self.source {
+            Some(source) => (
+                source.file_index,
+                source.offset,
+                source.timestamp.timestamp_micros(),
+            ),
+            None => (NO_SOURCE, 0, 0),
+        };
+        buf.extend(file_index.to_le_bytes());
+        buf.extend(offset.to_le_bytes());
+        buf.extend(timestamp.to_le_bytes());
+        buf.extend(self.delta.num_microseconds().unwrap_or(0).to_le_bytes());
+        buf.push(self.adjustments.0);
+    }
+
+    fn decode(bytes: &[u8; RECORD_SIZE]) -> Result<Self> {
+        let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let u64_at = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+        let file_index = u32_at(8);
+        let source = if file_index == NO_SOURCE {
+            None
+        } else {
+            let micros = u64_at(20) as i64;
+            Some(PacketSource {
+                file_index,
+                offset: u64_at(12),
+                timestamp: DateTime::<Utc>::from_timestamp_micros(micros)
+                    .ok_or_else(|| anyhow!("Invalid timestamp in provenance record: {}", micros))?,
+            })
+        };
+        Ok(Self {
+            segment: u32_at(0),
+            packet: u32_at(4),
+            source,
+            delta: TimeDelta::microseconds(u64_at(28) as i64),
+            adjustments: Adjustments(bytes[36]),
+        })
+    }
+}
+
+/// Contents of a `provenance.bin`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Provenance {
+    /// Input files, indexed by `PacketSource::file_index`
+    pub files: Vec<String>,
+    pub records: Vec<ProvenanceRecord>,
+}
+
+impl Provenance {
+    pub fn read(path: &Path) -> Result<Self> {
+        let mut bytes = Vec::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .with_context(|| format!("Failed to read provenance file {}", path.display()))?;
+        Self::decode(&bytes).with_context(|| format!("Invalid provenance file {}", path.display()))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create provenance file {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(&self.to_bytes())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Record of packet `packet` in segment `segment`
+    pub fn lookup(&self, segment: u32, packet: u32) -> Option<&ProvenanceRecord> {
+        self.records
+            .iter()
+            .find(|record| record.segment == segment && record.packet == packet)
+    }
+
+    /// Input file a record was read from
+    pub fn source_file(&self, record: &ProvenanceRecord) -> Option<&str> {
+        let source = record.source.as_ref()?;
+        self.files
+            .get(source.file_index as usize)
+            .map(String::as_str)
+    }
+
+    /// Serialized `provenance.bin` contents
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.records.len() * RECORD_SIZE + 64);
+        buf.extend(MAGIC);
+        buf.push(VERSION);
+        buf.extend((self.files.len() as u32).to_le_bytes());
+        for file in &self.files {
+            buf.extend((file.len() as u16).to_le_bytes());
+            buf.extend(file.as_bytes());
+        }
+        for record in &self.records {
+            record.encode(&mut buf);
+        }
+        buf
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut rest = bytes;
+        if take_bytes(&mut rest, 4)? != MAGIC {
+            return Err(anyhow!("Not a provenance file"));
+        }
+        let version = take_bytes(&mut rest, 1)?[0];
+        if version != VERSION {
+            return Err(anyhow!("Unsupported provenance version: {}", version));
+        }
+        let file_count = u32::from_le_bytes(take_bytes(&mut rest, 4)?.try_into().unwrap());
+        let mut files = Vec::new();
+        for _ in 0..file_count {
+            let len = u16::from_le_bytes(take_bytes(&mut rest, 2)?.try_into().unwrap()) as usize;
+            files.push(String::from_utf8_lossy(take_bytes(&mut rest, len)?).to_string());
+        }
+        let records = rest
+            .chunks(RECORD_SIZE)
+            .map(|chunk| {
+                let chunk: &[u8; RECORD_SIZE] = chunk
+                    .try_into()
+                    .map_err(|_| anyhow!("Truncated provenance record"))?;
+                ProvenanceRecord::decode(chunk)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { files, records })
+    }
+}
+
+/// Split the first `n` bytes off `rest`
+fn take_bytes<'a>(rest: &mut &'a [u8], n: usize) -> Result<&'a [u8]> {
+    if rest.len() < n {
+        return Err(anyhow!("Unexpected end of provenance data"));
+    }
+    let (head, tail) = rest.split_at(n);
+    *rest = tail;
+    Ok(head)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_provenance_round_trip() {
+        let timestamp = Utc.timestamp_opt(1_700_000_000, 123_000).unwrap();
+        let provenance = Provenance {
+            files: vec!["data/capture_1.bin".to_string(), "データ.bin".to_string()],
+            records: vec![
+                ProvenanceRecord {
+                    segment: 0,
+                    packet: 0,
+                    source: None,
+                    delta: TimeDelta::zero(),
+                    adjustments: Adjustments::default(),
+                },
+                ProvenanceRecord {
+                    segment: 3,
+                    packet: 7,
+                    source: Some(PacketSource {
+                        file_index: 1,
+                        offset: 0x1_0000_0042,
+                        timestamp,
+                    }),
+                    delta: TimeDelta::milliseconds(-1500),
+                    adjustments: Adjustments(Adjustments::TIMESHIFT | Adjustments::SPLIT),
+                },
+            ],
+        };
+
+        let bytes = provenance.to_bytes();
+        let header_len = 4 + 1 + 4 + (2 + 18) + (2 + "データ.bin".len());
+        assert_eq!(bytes.len(), header_len + 2 * RECORD_SIZE);
+        let decoded = Provenance::decode(&bytes).unwrap();
+        assert_eq!(decoded, provenance);
+
+        let record = decoded.lookup(3, 7).unwrap();
+        assert_eq!(decoded.source_file(record), Some("データ.bin"));
+        assert_eq!(record.adjustments.names(), vec!["timeshift", "split"]);
+        assert_eq!(decoded.source_file(decoded.lookup(0, 0).unwrap()), None);
+        assert!(decoded.lookup(3, 8).is_none());
+
+        assert!(Provenance::decode(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Provenance::decode(b"LKPX").is_err());
+    }
+}
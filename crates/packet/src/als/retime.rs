@@ -0,0 +1,412 @@
+//! Piecewise-linear retiming of a converted replay
+//!
+//! A map file lists control points `replay_time -> target_time`, one per line. Each side
+//! is an RFC3339 date time or seconds relative to the first packet of the replay. Packet
+//! timestamps between two control points are interpolated linearly, outside of them the
+//! edge slopes are extended. The retimed packets are segmented again, so segment
+//! durations and `SegmentStartedAt` values follow the new timeline.
+
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, TimeDelta, Utc};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use super::converter::{AlsConverter, AlsConverterConfig};
+use super::extract::collect_standard_entries;
+use super::proto::PacketInfo;
+use super::proto::define::{Room, data_frame, data_pack};
+use super::proto::reader::{PacketReaderTrait, StandardPacketReader};
+
+/// Metadata file written next to the converted segments
+pub(super) const METADATA_FILE: &str = "index.md";
+/// Events file written next to the converted segments
+pub(super) const EVENTS_FILE: &str = "events.vtt";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ControlPoint {
+    pub replay_time: DateTime<Utc>,
+    pub target_time: DateTime<Utc>,
+}
+
+/// Control points strictly increasing on both axes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeMap {
+    points: Vec<ControlPoint>,
+}
+
+impl TimeMap {
+    pub fn new(points: Vec<ControlPoint>) -> Result<Self> {
+        if points.len() < 2 {
+            return Err(anyhow!(
+                "Time map needs at least two control points, got {}",
+                points.len()
+            ));
+        }
+        for (i, pair) in points.windows(2).enumerate() {
+            if pair[1].replay_time <= pair[0].replay_time {
+                return Err(anyhow!(
+                    "Replay times of control points {} and {} are not strictly increasing",
+                    i + 1,
+                    i + 2
+                ));
+            }
+            if pair[1].target_time <= pair[0].target_time {
+                return Err(anyhow!(
+                    "Target times of control points {} and {} are not strictly increasing",
+                    i + 1,
+                    i + 2
+                ));
+            }
+        }
+        Ok(Self { points })
+    }
+
+    /// Parse a map file, relative seconds count from `replay_zero`
+    pub fn parse(content: &str, replay_zero: DateTime<Utc>) -> Result<Self> {
+        let mut points = Vec::new();
+        for (line_number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (replay_time, target_time) = line
+                .split_once("->")
+                .ok_or_else(|| anyhow!("Line {}: expected `replay -> target`", line_number + 1))?;
+            let parse = |value: &str| {
+                parse_time(value.trim(), replay_zero)
+                    .with_context(|| format!("Line {}: invalid time {:?}", line_number + 1, value))
+            };
+            points.push(ControlPoint {
+                replay_time: parse(replay_time)?,
+                target_time: parse(target_time)?,
+            });
+        }
+        Self::new(points)
+    }
+
+    pub fn points(&self) -> &[ControlPoint] {
+        &self.points
+    }
+
+    /// Map a replay timestamp onto the target timeline
+    pub fn apply(&self, timestamp: DateTime<Utc>) -> DateTime<Utc> {
+        let upper = self
+            .points
+            .iter()
+            .position(|p| p.replay_time > timestamp)
+            .unwrap_or(self.points.len() - 1)
+            .max(1);
+        let (from, to) = (self.points[upper - 1], self.points[upper]);
+        let replay_span = micros(to.replay_time - from.replay_time);
+        let target_span = micros(to.target_time - from.target_time);
+        let offset = micros(timestamp - from.replay_time) * target_span / replay_span;
+        from.target_time + TimeDelta::microseconds(offset as i64)
+    }
+}
+
+fn micros(delta: TimeDelta) -> i128 {
+    delta.num_microseconds().unwrap_or(i64::MAX) as i128
+}
+
+fn parse_time(value: &str, replay_zero: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    if let Ok(seconds) = value.parse::<f64>() {
+        return Ok(replay_zero + TimeDelta::microseconds((seconds * 1_000_000.0).round() as i64));
+    }
+    Ok(DateTime::parse_from_rfc3339(value)?.with_timezone(&Utc))
+}
+
+#[derive(Debug, Clone)]
+pub struct RetimeConfig {
+    pub input_dir: PathBuf,
+    pub map_path: PathBuf,
+    pub output_dir: PathBuf,
+}
+
+#[derive(Debug, Clone)]
+pub struct RetimeSummary {
+    pub packets: usize,
+    pub first_timestamp: DateTime<Utc>,
+    pub last_timestamp: DateTime<Utc>,
+}
+
+/// Data packets of a converted replay with the segment headers removed
+pub(super) struct ReplayPackets {
+    pub room: Room,
+    pub packets: Vec<PacketInfo>,
+    /// Later segments repeat the full object state in their headers
+    pub embeds_full_state: bool,
+    pub replay_zero: DateTime<Utc>,
+}
+
+pub fn run_retime(config: &RetimeConfig) -> Result<RetimeSummary> {
+    let replay = read_replay_packets(&config.input_dir)?;
+    let map_content = std::fs::read_to_string(&config.map_path)
+        .with_context(|| format!("Failed to read time map: {}", config.map_path.display()))?;
+    let time_map = TimeMap::parse(&map_content, replay.replay_zero)
+        .with_context(|| format!("Invalid time map: {}", config.map_path.display()))?;
+    let metadata = read_metadata(&config.input_dir)?;
+
+    let mut packets = replay.packets;
+    for packet in &mut packets {
+        packet.timestamp = time_map.apply(packet.timestamp);
+    }
+    let summary = RetimeSummary {
+        packets: packets.len(),
+        first_timestamp: packets[0].timestamp,
+        last_timestamp: packets[packets.len() - 1].timestamp,
+    };
+
+    let converter_config = AlsConverterConfig {
+        metadata_path: metadata
+            .get("path")
+            .and_then(|v| v.as_str())
+            .map(String::from),
+        write_events_vtt: config.input_dir.join(EVENTS_FILE).is_file(),
+        embed_full_state_in_segments: replay.embeds_full_state,
+        ..Default::default()
+    };
+    AlsConverter::write_data_packets(&converter_config, replay.room, packets, &config.output_dir)?;
+    write_retime_map(&config.output_dir, &time_map)?;
+    Ok(summary)
+}
+
+pub(super) fn read_replay_packets(input_dir: &Path) -> Result<ReplayPackets> {
+    let mut room = None;
+    let mut packets = Vec::new();
+    let mut embeds_full_state = false;
+    let mut replay_zero = None;
+
+    for (index, entry) in collect_standard_entries(input_dir)?.into_iter().enumerate() {
+        let path = entry.path();
+        let file =
+            File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let mut reader = StandardPacketReader::new(file);
+        // SegmentStartedAt, Room, full object state (optional), CacheEnded
+        let mut in_header = true;
+        while let Some(packet) = reader.read_packet()? {
+            replay_zero.get_or_insert(packet.timestamp);
+            if !in_header {
+                packets.push(packet);
+                continue;
+            }
+            match &packet.data_pack.control {
+                Some(data_pack::Control::CacheEnded(_)) => in_header = false,
+                Some(data_pack::Control::SegmentStartedAt(_)) => {}
+                _ => {
+                    let room_frame =
+                        packet
+                            .data_pack
+                            .frames
+                            .iter()
+                            .find_map(|f| match &f.message {
+                                Some(data_frame::Message::Room(room)) => Some(room),
+                                _ => None,
+                            });
+                    match room_frame {
+                        Some(frame) => {
+                            room.get_or_insert_with(|| frame.clone());
+                        }
+                        None if index > 0 => embeds_full_state = true,
+                        None => {}
+                    }
+                }
+            }
+        }
+        if in_header {
+            return Err(anyhow!(
+                "Segment {} has no CacheEnded packet, not a converted replay",
+                path.display()
+            ));
+        }
+    }
+
+    if packets.is_empty() {
+        return Err(anyhow!("No data packets found in {}", input_dir.display()));
+    }
+    Ok(ReplayPackets {
+        room: room.ok_or_else(|| anyhow!("No Room frame found in {}", input_dir.display()))?,
+        packets,
+        embeds_full_state,
+        replay_zero: replay_zero.unwrap(),
+    })
+}
+
+pub(super) fn read_metadata(replay_dir: &Path) -> Result<serde_json::Value> {
+    let metadata_path = replay_dir.join(METADATA_FILE);
+    let content = std::fs::read_to_string(&metadata_path)
+        .with_context(|| format!("Failed to read metadata: {}", metadata_path.display()))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse metadata: {}", metadata_path.display()))
+}
+
+/// Store the applied control points as `retime_map` in the output metadata
+fn write_retime_map(output_dir: &Path, time_map: &TimeMap) -> Result<()> {
+    let mut metadata = read_metadata(output_dir)?;
+    metadata["retime_map"] = time_map
+        .points()
+        .iter()
+        .map(|p| {
+            serde_json::json!({
+                "replay_time": p.replay_time.to_rfc3339(),
+                "target_time": p.target_time.to_rfc3339(),
+            })
+        })
+        .collect();
+    let metadata_path = output_dir.join(METADATA_FILE);
+    std::fs::write(&metadata_path, format!("{}\n", metadata))
+        .with_context(|| format!("Failed to write metadata: {}", metadata_path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::als::proto::define::{DataFrame, DataPack, InstantiateObject, UpdateObject};
+    use chrono::TimeZone;
+
+    fn data_packet(timestamp: DateTime<Utc>, message: data_frame::Message) -> PacketInfo {
+        PacketInfo {
+            timestamp,
+            data_pack: DataPack {
+                control: Some(data_pack::Control::Data(true)),
+                frames: vec![DataFrame {
+                    message: Some(message),
+                }],
+            },
+            raw_data: vec![],
+            source: None,
+        }
+    }
+
+    fn read_all(dir: &Path) -> Vec<PacketInfo> {
+        let mut packets = Vec::new();
+        for entry in collect_standard_entries(dir).unwrap() {
+            let mut reader = StandardPacketReader::new(File::open(entry.path()).unwrap());
+            packets.extend(reader.read_packets().unwrap());
+        }
+        packets
+    }
+
+    #[test]
+    fn test_time_map_validation() {
+        let zero = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let map = TimeMap::parse("# stretch\n0 -> 0\n100 -> 101\n", zero).unwrap();
+        assert_eq!(
+            map.apply(zero + TimeDelta::seconds(50)),
+            zero + TimeDelta::milliseconds(50_500)
+        );
+        // edge slopes are extended
+        assert_eq!(
+            map.apply(zero + TimeDelta::seconds(200)),
+            zero + TimeDelta::seconds(202)
+        );
+        assert_eq!(
+            map.apply(zero - TimeDelta::seconds(100)),
+            zero - TimeDelta::seconds(101)
+        );
+
+        let map = TimeMap::parse(
+            "2023-11-14T22:13:20Z -> 5\n10 -> 2023-11-14T22:13:35Z",
+            zero,
+        )
+        .unwrap();
+        assert_eq!(map.points()[0].target_time, zero + TimeDelta::seconds(5));
+
+        assert!(TimeMap::parse("0 -> 0", zero).is_err());
+        assert!(TimeMap::parse("0 -> 0\n0 -> 1", zero).is_err());
+        assert!(TimeMap::parse("0 -> 1\n1 -> 1", zero).is_err());
+        assert!(TimeMap::parse("0 0\n1 1", zero).is_err());
+    }
+
+    #[test]
+    fn test_run_retime_stretch() {
+        let tmp = tempfile::tempdir().unwrap();
+        let base = tmp.path();
+        let input_dir = base.join("input");
+        let output_dir = base.join("output");
+        let zero = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+
+        let mut packets = vec![data_packet(
+            zero,
+            data_frame::Message::InstantiateObject(InstantiateObject {
+                object_id: 1,
+                prefab_name: b"Character".to_vec(),
+                ..Default::default()
+            }),
+        )];
+        for second in 1..=30 {
+            packets.push(data_packet(
+                zero + TimeDelta::seconds(second),
+                data_frame::Message::UpdateObject(UpdateObject {
+                    object_id: 1,
+                    ..Default::default()
+                }),
+            ));
+        }
+        let room = Room {
+            id: b"room".to_vec(),
+            started_at: zero.timestamp_micros(),
+            ended_at: 0,
+        };
+        AlsConverter::write_data_packets(&AlsConverterConfig::default(), room, packets, &input_dir)
+            .unwrap();
+
+        let map_path = base.join("map.txt");
+        std::fs::write(&map_path, "0 -> 0\n10 -> 10.1\n").unwrap();
+        let summary = run_retime(&RetimeConfig {
+            input_dir: input_dir.clone(),
+            map_path: map_path.clone(),
+            output_dir: output_dir.clone(),
+        })
+        .unwrap();
+        assert_eq!(summary.packets, 31);
+        assert_eq!(
+            summary.last_timestamp - summary.first_timestamp,
+            TimeDelta::milliseconds(30_300)
+        );
+
+        let data_packets = read_replay_packets(&output_dir).unwrap().packets;
+        assert_eq!(data_packets.len(), 31);
+        assert_eq!(data_packets[0].timestamp, zero);
+        assert_eq!(
+            data_packets[10].timestamp,
+            zero + TimeDelta::milliseconds(10_100)
+        );
+        assert_eq!(
+            data_packets[25].timestamp,
+            zero + TimeDelta::milliseconds(25_250)
+        );
+
+        // segment headers follow the new timeline
+        let segment_starts = read_all(&output_dir)
+            .iter()
+            .filter_map(|p| match p.data_pack.control {
+                Some(data_pack::Control::SegmentStartedAt(micros)) => Some(micros),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            segment_starts,
+            vec![
+                zero.timestamp_micros(),
+                (zero + TimeDelta::seconds(10)).timestamp_micros(),
+                (zero + TimeDelta::seconds(20)).timestamp_micros(),
+                (zero + TimeDelta::seconds(30)).timestamp_micros(),
+            ]
+        );
+        let metadata = read_metadata(&output_dir).unwrap();
+        assert_eq!(metadata["retime_map"].as_array().unwrap().len(), 2);
+
+        // invalid maps fail before anything is written
+        std::fs::write(&map_path, "0 -> 0\n10 -> 0\n").unwrap();
+        let rejected_dir = base.join("rejected");
+        assert!(
+            run_retime(&RetimeConfig {
+                input_dir,
+                map_path,
+                output_dir: rejected_dir.clone(),
+            })
+            .is_err()
+        );
+        assert!(!rejected_dir.exists());
+    }
+}
@@ -0,0 +1,420 @@
+//! Splitting one large capture file into numbered shards, or cutting out a time range
+//!
+//! Shards are cut at packet boundaries and copied byte for byte from the input, so they
+//! keep its format and timestamps, and concatenating them gives back the input. They are
+//! named `<stem>_<n>.<ext>` numbered from 1, the order `FileSortKey::FilenameNumeric` and
+//! the converter read a capture directory in.
+
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, TimeDelta, Utc};
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use super::proto::analyzer::{KeepaliveFilter, PacketFilter};
+use super::proto::reader::InputFormat;
+
+/// Where shards are cut
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitBy {
+    /// Each shard covers at most this much capture time
+    Duration(TimeDelta),
+    /// Each shard holds at most this many packets
+    Count(usize),
+    /// Each shard holds at most this many bytes, unless a single packet is larger
+    Size(u64),
+}
+
+impl SplitBy {
+    /// `kind` is `duration`, `count` or `size` and `every` is in seconds, packets or MiB
+    pub fn new(kind: &str, every: u64) -> Result<Self> {
+        if every == 0 {
+            return Err(anyhow!("Split interval must be greater than 0"));
+        }
+        match kind {
+            "duration" => Ok(Self::Duration(TimeDelta::seconds(every as i64))),
+            "count" => Ok(Self::Count(every as usize)),
+            "size" => Ok(Self::Size(every.saturating_mul(1024 * 1024))),
+            _ => Err(anyhow!("Unsupported split kind: {}", kind)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SplitConfig {
+    /// Capture file to split
+    pub input_path: PathBuf,
+    pub input_format: InputFormat,
+    /// Directory the shards are written to, created when missing
+    pub output_dir: PathBuf,
+    pub split_by: SplitBy,
+}
+
+/// One written shard
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitShard {
+    pub path: PathBuf,
+    pub packets: usize,
+    pub bytes: u64,
+    pub first_timestamp: DateTime<Utc>,
+    pub last_timestamp: DateTime<Utc>,
+}
+
+// Byte offset and timestamp of a packet in the input
+struct PacketPosition {
+    offset: u64,
+    timestamp: DateTime<Utc>,
+}
+
+// Packets `first..last` (exclusive) of the input, starting at byte `start`
+struct ShardRange {
+    first: usize,
+    last: usize,
+    start: u64,
+}
+
+pub fn run_split(config: &SplitConfig) -> Result<Vec<SplitShard>> {
+    let input = &config.input_path;
+    if !input.is_file() {
+        return Err(anyhow!("Input path is not a file: {}", input.display()));
+    }
+    let positions = read_positions(input, config.input_format)?;
+    if positions.is_empty() {
+        return Err(anyhow!("No packets found in {}", input.display()));
+    }
+    let file_len = input.metadata()?.len();
+    let ranges = shard_ranges(&positions, file_len, config.split_by);
+
+    std::fs::create_dir_all(&config.output_dir).with_context(|| {
+        format!(
+            "Failed to create output directory: {}",
+            config.output_dir.display()
+        )
+    })?;
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = input
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+
+    let mut file =
+        File::open(input).with_context(|| format!("Failed to open file: {}", input.display()))?;
+    let mut shards = Vec::with_capacity(ranges.len());
+    for (index, range) in ranges.iter().enumerate() {
+        // The last shard keeps any trailing bytes after the last packet
+        let end = positions
+            .get(range.last)
+            .map_or(file_len, |position| position.offset);
+        let path = config
+            .output_dir
+            .join(format!("{}_{}{}", stem, index + 1, extension));
+        copy_range(&mut file, range.start, end, &path)?;
+        shards.push(SplitShard {
+            path,
+            packets: range.last - range.first,
+            bytes: end - range.start,
+            first_timestamp: positions[range.first].timestamp,
+            last_timestamp: positions[range.last - 1].timestamp,
+        });
+    }
+    Ok(shards)
+}
+
+/// Copy the packets of `input_path` timestamped between `start` and `end` to `output_path`
+///
+/// Packets keep their framing and input order, so the output is a capture in the input's
+/// format. Either bound may be open; reading stops at the first packet after `end`, like
+/// `analyze --data-end-time`. Legacy captures have no timestamps and are rejected.
+pub fn extract_time_range(
+    input_path: &Path,
+    input_format: InputFormat,
+    output_path: &Path,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+) -> Result<SplitShard> {
+    let input_format = input_format.resolve(input_path)?;
+    if input_format == InputFormat::Legacy {
+        return Err(anyhow!(
+            "Legacy captures have no timestamps to extract a range from"
+        ));
+    }
+    let filter = PacketFilter {
+        start_time: start,
+        end_time: end,
+        keepalive: KeepaliveFilter::default(),
+    };
+    let open = || {
+        File::open(input_path)
+            .with_context(|| format!("Failed to open file: {}", input_path.display()))
+    };
+    let mut reader = input_format.reader_factory()?(open()?);
+    let mut input = open()?;
+    if let Some(parent) = output_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut writer = BufWriter::new(
+        File::create(output_path)
+            .with_context(|| format!("Failed to create file: {}", output_path.display()))?,
+    );
+
+    let mut packets = 0;
+    let mut bytes = 0;
+    let mut timestamps = None;
+    // Byte range of consecutive packets not copied yet
+    let mut pending: Option<(u64, u64)> = None;
+    while let Some(packet) = reader.read_packet()? {
+        if filter.is_past_end(&packet.timestamp) {
+            break;
+        }
+        if !filter.should_include(&packet.timestamp) {
+            continue;
+        }
+        let packet_start = packet
+            .source
+            .ok_or_else(|| anyhow!("Reader did not report packet offsets"))?
+            .offset;
+        let packet_end = reader
+            .offset()
+            .ok_or_else(|| anyhow!("Reader did not report packet offsets"))?;
+        pending = match pending {
+            Some((run_start, run_end)) if run_end == packet_start => Some((run_start, packet_end)),
+            previous => {
+                if let Some((run_start, run_end)) = previous {
+                    copy_bytes(&mut input, run_start, run_end, &mut writer, output_path)?;
+                }
+                Some((packet_start, packet_end))
+            }
+        };
+        packets += 1;
+        bytes += packet_end - packet_start;
+        let first = timestamps.map_or(packet.timestamp, |(first, _)| first);
+        timestamps = Some((first, packet.timestamp));
+    }
+    if let Some((run_start, run_end)) = pending {
+        copy_bytes(&mut input, run_start, run_end, &mut writer, output_path)?;
+    }
+    writer.flush()?;
+
+    let Some((first_timestamp, last_timestamp)) = timestamps else {
+        return Err(anyhow!(
+            "No packets in the requested time range in {}",
+            input_path.display()
+        ));
+    };
+    Ok(SplitShard {
+        path: output_path.to_path_buf(),
+        packets,
+        bytes,
+        first_timestamp,
+        last_timestamp,
+    })
+}
+
+// Helper: offsets and timestamps of every packet of the capture
+fn read_positions(input: &Path, input_format: InputFormat) -> Result<Vec<PacketPosition>> {
+    let reader_factory = input_format.resolve(input)?.reader_factory()?;
+    let file =
+        File::open(input).with_context(|| format!("Failed to open file: {}", input.display()))?;
+    let mut reader = reader_factory(file);
+    let mut positions = Vec::new();
+    while let Some(packet) = reader.read_packet()? {
+        let source = packet
+            .source
+            .ok_or_else(|| anyhow!("Reader did not report packet offsets"))?;
+        positions.push(PacketPosition {
+            offset: source.offset,
+            timestamp: packet.timestamp,
+        });
+    }
+    Ok(positions)
+}
+
+// Helper: group packets into shards, a packet starts a new shard when adding it to the
+// current one would cross the limit. The first shard starts at byte 0.
+fn shard_ranges(positions: &[PacketPosition], file_len: u64, split_by: SplitBy) -> Vec<ShardRange> {
+    let mut ranges = Vec::new();
+    let mut current = ShardRange {
+        first: 0,
+        last: 0,
+        start: 0,
+    };
+    for (index, position) in positions.iter().enumerate() {
+        let end = positions
+            .get(index + 1)
+            .map_or(file_len, |next| next.offset);
+        let exceeds = match split_by {
+            SplitBy::Duration(window) => {
+                position.timestamp - positions[current.first].timestamp >= window
+            }
+            SplitBy::Count(max) => current.last - current.first >= max,
+            SplitBy::Size(max) => end - current.start > max,
+        };
+        if exceeds && current.last > current.first {
+            let start = position.offset;
+            ranges.push(std::mem::replace(
+                &mut current,
+                ShardRange {
+                    first: index,
+                    last: index,
+                    start,
+                },
+            ));
+        }
+        current.last = index + 1;
+    }
+    ranges.push(current);
+    ranges
+}
+
+// Helper: copy bytes `start..end` of `input` to a new file at `path`
+fn copy_range(input: &mut File, start: u64, end: u64, path: &Path) -> Result<()> {
+    let mut writer = BufWriter::new(
+        File::create(path).with_context(|| format!("Failed to create file: {}", path.display()))?,
+    );
+    copy_bytes(input, start, end, &mut writer, path)?;
+    writer.flush()?;
+    Ok(())
+}
+
+// Helper: append bytes `start..end` of `input` to `writer`, which writes to `path`
+fn copy_bytes(
+    input: &mut File,
+    start: u64,
+    end: u64,
+    writer: &mut impl Write,
+    path: &Path,
+) -> Result<()> {
+    input.seek(SeekFrom::Start(start))?;
+    let copied = std::io::copy(&mut Read::by_ref(input).take(end - start), writer)?;
+    if copied != end - start {
+        return Err(anyhow!(
+            "Input ended early while writing {}",
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::als::proto::PacketInfo;
+    use crate::als::proto::reader::{PacketReaderTrait, StandardPacketReader};
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_run_split() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let start = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        let packets: Vec<Vec<u8>> = (0..10)
+            .map(|i| PacketInfo::create_cache_end(start + TimeDelta::seconds(i)).to_vec())
+            .collect();
+        let input = dir.join("capture.bin");
+        std::fs::write(&input, packets.concat()).unwrap();
+        let packet_len = packets[0].len() as u64;
+
+        let split = |split_by: SplitBy| {
+            let output_dir = dir.join("shards");
+            let _ = std::fs::remove_dir_all(&output_dir);
+            let shards = run_split(&SplitConfig {
+                input_path: input.clone(),
+                input_format: InputFormat::Auto,
+                output_dir,
+                split_by,
+            })
+            .unwrap();
+            let joined: Vec<u8> = shards
+                .iter()
+                .flat_map(|shard| std::fs::read(&shard.path).unwrap())
+                .collect();
+            assert_eq!(joined, packets.concat());
+            for shard in &shards {
+                let file = File::open(&shard.path).unwrap();
+                let read = StandardPacketReader::new(file).read_packets().unwrap();
+                assert_eq!(read.len(), shard.packets);
+                assert_eq!(read[0].timestamp, shard.first_timestamp);
+            }
+            shards.iter().map(|shard| shard.packets).collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            split(SplitBy::Duration(TimeDelta::seconds(3))),
+            vec![3, 3, 3, 1]
+        );
+        assert_eq!(split(SplitBy::Count(4)), vec![4, 4, 2]);
+        assert_eq!(split(SplitBy::Size(packet_len * 5 - 1)), vec![4, 4, 2]);
+        // A single packet larger than the limit still gets its own shard
+        assert_eq!(split(SplitBy::Size(1)), vec![1; 10]);
+
+        let names: Vec<_> = std::fs::read_dir(dir.join("shards"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert!(names.contains(&"capture_10.bin".to_string()));
+        assert!(SplitBy::new("lines", 1).is_err());
+        assert!(SplitBy::new("count", 0).is_err());
+    }
+
+    #[test]
+    fn test_extract_time_range() {
+        use crate::als::proto::reader::MixedPacketReader;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let start = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+        // Mixed framing: protobuf packet then 8-byte timestamp packet
+        let packets: Vec<Vec<u8>> = (0..10)
+            .map(|i| {
+                let packet = PacketInfo::create_cache_end(start + TimeDelta::seconds(i));
+                let data = packet.protobuf_to_vec();
+                let mut bytes = ((data.len() + 1) as u16).to_be_bytes().to_vec();
+                bytes.push(0);
+                bytes.extend(&data);
+                bytes.extend(8u16.to_be_bytes());
+                bytes.extend((packet.timestamp.timestamp_micros() as u64).to_be_bytes());
+                bytes
+            })
+            .collect();
+        let input = dir.join("capture.bin");
+        std::fs::write(&input, packets.concat()).unwrap();
+        let output = dir.join("slice").join("capture.bin");
+
+        let shard = extract_time_range(
+            &input,
+            InputFormat::Auto,
+            &output,
+            Some(start + TimeDelta::seconds(3)),
+            Some(start + TimeDelta::seconds(6)),
+        )
+        .unwrap();
+        assert_eq!(shard.packets, 4);
+        assert_eq!(shard.first_timestamp, start + TimeDelta::seconds(3));
+        assert_eq!(shard.last_timestamp, start + TimeDelta::seconds(6));
+        assert_eq!(std::fs::read(&output).unwrap(), packets[3..7].concat());
+        let read = MixedPacketReader::new(File::open(&output).unwrap())
+            .read_packets()
+            .unwrap();
+        assert_eq!(read.len(), 4);
+
+        let open_start = extract_time_range(
+            &input,
+            InputFormat::Mixed,
+            &output,
+            None,
+            Some(start + TimeDelta::seconds(1)),
+        )
+        .unwrap();
+        assert_eq!(open_start.packets, 2);
+        assert!(
+            extract_time_range(
+                &input,
+                InputFormat::Mixed,
+                &output,
+                Some(start + TimeDelta::seconds(20)),
+                None,
+            )
+            .is_err()
+        );
+    }
+}
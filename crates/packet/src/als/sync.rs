@@ -0,0 +1,183 @@
+//! Replay / official video sync offset estimation
+//!
+//! The replay timeline zero is the first packet of a converted replay, mapped to
+//! wall clock through the `DateTimeReceiver` object. The video timeline zero comes
+//! from the playlist's `EXT-X-PROGRAM-DATE-TIME`, or `live_started_at` as fallback.
+
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Utc};
+use std::collections::HashSet;
+use std::path::Path;
+
+use super::extract::collect_standard_entries;
+use super::proto::define::data_frame;
+use super::proto::extension::{UpdateObjectExt, prefab_name};
+use super::proto::reader::{PacketReaderTrait, PacketsBufferReader};
+
+/// Metadata file written next to the converted segments
+const METADATA_FILE: &str = "index.md";
+
+/// Where a timeline zero's wall clock came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockSource {
+    /// `DateTimeReceiver` update in the replay
+    DateTimeReceiver,
+    /// Packet timestamps of the replay
+    PacketTimestamp,
+    /// `EXT-X-PROGRAM-DATE-TIME` tag of the video playlist
+    ProgramDateTime,
+    /// `live_started_at` of the replay metadata
+    LiveStartedAt,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReplayClock {
+    /// Timestamp of the first replay packet
+    pub first_packet_at: DateTime<Utc>,
+    /// Wall clock at the first replay packet
+    pub wall_clock: DateTime<Utc>,
+    pub source: ClockSource,
+    /// `live_started_at` from the replay metadata
+    pub live_started_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SyncOffset {
+    /// Video zero minus replay zero, positive when the video starts later
+    pub offset_ms: i64,
+    pub replay_source: ClockSource,
+    pub video_source: ClockSource,
+}
+
+/// Find the wall clock at the start of a converted replay directory
+pub fn read_replay_clock(replay_dir: &Path) -> Result<ReplayClock> {
+    let metadata = read_metadata(replay_dir)?;
+    let live_started_at = metadata
+        .get("live_started_at_micros")
+        .and_then(|v| v.as_i64())
+        .and_then(DateTime::from_timestamp_micros)
+        .or_else(|| {
+            metadata
+                .get("live_started_at")
+                .and_then(|v| v.as_str())
+                .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+                .map(|dt| dt.with_timezone(&Utc))
+        });
+
+    let mut reader = PacketsBufferReader::new_standard(collect_standard_entries(replay_dir)?);
+    let mut first_packet_at = None;
+    let mut receiver_ids = HashSet::new();
+
+    while let Some(packet) = reader.read_packet()? {
+        let first = *first_packet_at.get_or_insert(packet.timestamp);
+        for frame in &packet.data_pack.frames {
+            match &frame.message {
+                Some(data_frame::Message::InstantiateObject(obj)) => {
+                    let name = String::from_utf8_lossy(&obj.prefab_name);
+                    if name.contains(prefab_name::DATE_TIME_RECEIVER) {
+                        receiver_ids.insert(obj.object_id);
+                    }
+                }
+                Some(data_frame::Message::UpdateObject(obj))
+                    if receiver_ids.contains(&obj.object_id) =>
+                {
+                    if let Ok(date_time) = obj.try_parse_date_time() {
+                        return Ok(ReplayClock {
+                            first_packet_at: first,
+                            wall_clock: date_time.date_time - (packet.timestamp - first),
+                            source: ClockSource::DateTimeReceiver,
+                            live_started_at,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let first_packet_at =
+        first_packet_at.ok_or_else(|| anyhow!("No packets found in {}", replay_dir.display()))?;
+    tracing::warn!("No DateTimeReceiver update found, using packet timestamps as wall clock");
+    Ok(ReplayClock {
+        first_packet_at,
+        wall_clock: first_packet_at,
+        source: ClockSource::PacketTimestamp,
+        live_started_at,
+    })
+}
+
+/// Compute the offset between replay zero and video zero
+///
+/// `video_started_at` is the playlist's program date time at its first segment,
+/// when missing the replay's `live_started_at` is used instead.
+pub fn compute_sync_offset(
+    replay: &ReplayClock,
+    video_started_at: Option<DateTime<Utc>>,
+) -> Result<SyncOffset> {
+    let (video_zero, video_source) = match video_started_at {
+        Some(started_at) => (started_at, ClockSource::ProgramDateTime),
+        None => (
+            replay.live_started_at.ok_or_else(|| {
+                anyhow!(
+                    "Playlist has no EXT-X-PROGRAM-DATE-TIME and metadata has no live_started_at"
+                )
+            })?,
+            ClockSource::LiveStartedAt,
+        ),
+    };
+    Ok(SyncOffset {
+        offset_ms: (video_zero - replay.wall_clock).num_milliseconds(),
+        replay_source: replay.source,
+        video_source,
+    })
+}
+
+/// Store the offset as `video_sync_offset_ms` in the replay metadata
+pub fn write_sync_offset(replay_dir: &Path, offset_ms: i64) -> Result<()> {
+    let mut metadata = read_metadata(replay_dir)?;
+    metadata["video_sync_offset_ms"] = serde_json::Value::from(offset_ms);
+    let metadata_path = replay_dir.join(METADATA_FILE);
+    std::fs::write(&metadata_path, format!("{}\n", metadata))
+        .with_context(|| format!("Failed to write metadata: {}", metadata_path.display()))
+}
+
+fn read_metadata(replay_dir: &Path) -> Result<serde_json::Value> {
+    let metadata_path = replay_dir.join(METADATA_FILE);
+    let content = std::fs::read_to_string(&metadata_path)
+        .with_context(|| format!("Failed to read metadata: {}", metadata_path.display()))?;
+    serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse metadata: {}", metadata_path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_compute_sync_offset() {
+        let replay = ReplayClock {
+            first_packet_at: utc("2025-08-21T10:00:00Z"),
+            wall_clock: utc("2025-08-21T10:00:01.500Z"),
+            source: ClockSource::DateTimeReceiver,
+            live_started_at: Some(utc("2025-08-21T09:59:00Z")),
+        };
+
+        let offset = compute_sync_offset(&replay, Some(utc("2025-08-21T10:00:04Z"))).unwrap();
+        assert_eq!(offset.offset_ms, 2500);
+        assert_eq!(offset.video_source, ClockSource::ProgramDateTime);
+
+        let offset = compute_sync_offset(&replay, None).unwrap();
+        assert_eq!(offset.offset_ms, -61500);
+        assert_eq!(offset.video_source, ClockSource::LiveStartedAt);
+
+        let replay = ReplayClock {
+            live_started_at: None,
+            ..replay
+        };
+        assert!(compute_sync_offset(&replay, None).is_err());
+    }
+}
@@ -0,0 +1,286 @@
+//! Output path templates, e.g. `out/{date}_{room_id}` expanded when the directory is created
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, FixedOffset};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::converter::sanitize_path_component;
+
+/// Placeholders supported in output path templates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placeholder {
+    /// `{date}`, `YYYYMMDD`
+    Date,
+    /// `{time}`, `HHMMSS`
+    Time,
+    /// `{live_id}`
+    LiveId,
+    /// `{room_id}`
+    RoomId,
+    /// `{part}`, zero padded part index starting at 0
+    Part,
+}
+
+impl Placeholder {
+    pub const ALL: [Placeholder; 5] = [
+        Placeholder::Date,
+        Placeholder::Time,
+        Placeholder::LiveId,
+        Placeholder::RoomId,
+        Placeholder::Part,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Placeholder::Date => "date",
+            Placeholder::Time => "time",
+            Placeholder::LiveId => "live_id",
+            Placeholder::RoomId => "room_id",
+            Placeholder::Part => "part",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+/// Parsed output path template, `{{` and `}}` are literal braces
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathTemplate {
+    tokens: Vec<Token>,
+}
+
+impl FromStr for PathTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut tokens = Vec::new();
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(anyhow!("Unclosed '{{' in '{}', use '{{{{'", s)),
+                        }
+                    }
+                    let placeholder = Placeholder::ALL
+                        .into_iter()
+                        .find(|p| p.name() == name)
+                        .ok_or_else(|| {
+                            anyhow!(
+                                "Unknown placeholder {{{}}} in '{}', supported: {}",
+                                name,
+                                s,
+                                Placeholder::ALL
+                                    .map(|p| format!("{{{}}}", p.name()))
+                                    .join(", ")
+                            )
+                        })?;
+                    if !literal.is_empty() {
+                        tokens.push(Token::Literal(std::mem::take(&mut literal)));
+                    }
+                    tokens.push(Token::Placeholder(placeholder));
+                }
+                '}' => return Err(anyhow!("Unmatched '}}' in '{}', use '}}}}'", s)),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            tokens.push(Token::Literal(literal));
+        }
+        Ok(Self { tokens })
+    }
+}
+
+/// Values substituted into a `PathTemplate`
+#[derive(Debug, Clone)]
+pub struct TemplateVars {
+    pub time: DateTime<FixedOffset>,
+    pub live_id: Option<String>,
+    pub room_id: Option<String>,
+    pub part: Option<u32>,
+}
+
+impl TemplateVars {
+    pub fn new(time: DateTime<FixedOffset>) -> Self {
+        Self {
+            time,
+            live_id: None,
+            room_id: None,
+            part: None,
+        }
+    }
+}
+
+impl PathTemplate {
+    /// Escape braces so `path` is used literally as a template
+    pub fn escape(path: &str) -> String {
+        path.replace('{', "{{").replace('}', "}}")
+    }
+
+    /// Whether the template contains `placeholder`
+    pub fn uses(&self, placeholder: Placeholder) -> bool {
+        self.tokens.contains(&Token::Placeholder(placeholder))
+    }
+
+    /// Whether the template contains any placeholder
+    pub fn is_templated(&self) -> bool {
+        self.tokens
+            .iter()
+            .any(|token| matches!(token, Token::Placeholder(_)))
+    }
+
+    /// The template with `placeholder` replaced by the sanitized `value`, other
+    /// placeholders are kept for a later `expand`
+    pub fn fill(&self, placeholder: Placeholder, value: &str) -> String {
+        self.tokens
+            .iter()
+            .map(|token| match token {
+                Token::Literal(literal) => Self::escape(literal),
+                Token::Placeholder(p) if *p == placeholder => {
+                    Self::escape(&sanitize_path_component(value))
+                }
+                Token::Placeholder(p) => format!("{{{}}}", p.name()),
+            })
+            .collect()
+    }
+
+    /// Expand placeholders, values from capture data are sanitized as path components
+    pub fn expand(&self, vars: &TemplateVars) -> Result<PathBuf> {
+        let mut path = String::new();
+        for token in &self.tokens {
+            match token {
+                Token::Literal(literal) => path.push_str(literal),
+                Token::Placeholder(placeholder) => {
+                    let missing = || anyhow!("No value for placeholder {{{}}}", placeholder.name());
+                    let value = match placeholder {
+                        Placeholder::Date => vars.time.format("%Y%m%d").to_string(),
+                        Placeholder::Time => vars.time.format("%H%M%S").to_string(),
+                        Placeholder::LiveId => {
+                            sanitize_path_component(vars.live_id.as_deref().ok_or_else(missing)?)
+                        }
+                        Placeholder::RoomId => {
+                            sanitize_path_component(vars.room_id.as_deref().ok_or_else(missing)?)
+                        }
+                        Placeholder::Part => format!("{:03}", vars.part.ok_or_else(missing)?),
+                    };
+                    path.push_str(&value);
+                }
+            }
+        }
+        Ok(PathBuf::from(path))
+    }
+}
+
+/// `path` when it does not exist yet, otherwise the first free `path-1`, `path-2`, ...
+pub fn unique_dir(path: &Path) -> PathBuf {
+    unique_dir_by(path, Path::exists)
+}
+
+/// Like `unique_dir`, with `taken` deciding whether a candidate is in use
+pub fn unique_dir_by(path: &Path, taken: impl Fn(&Path) -> bool) -> PathBuf {
+    if !taken(path) {
+        return path.to_path_buf();
+    }
+    (1..)
+        .map(|n| {
+            let mut name = path.as_os_str().to_os_string();
+            name.push(format!("-{}", n));
+            PathBuf::from(name)
+        })
+        .find(|candidate| !taken(candidate))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn vars() -> TemplateVars {
+        let time = FixedOffset::east_opt(9 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2025, 8, 21, 9, 30, 5)
+            .unwrap();
+        TemplateVars {
+            live_id: Some("live/1".to_string()),
+            room_id: Some("room,1".to_string()),
+            part: Some(2),
+            ..TemplateVars::new(time)
+        }
+    }
+
+    #[test]
+    fn test_expand_placeholders() {
+        let template: PathTemplate = "out/{date}_{time}/{live_id}-{room_id}_{part}"
+            .parse()
+            .unwrap();
+        assert!(template.is_templated());
+        assert!(template.uses(Placeholder::Part));
+        assert_eq!(
+            template.expand(&vars()).unwrap(),
+            PathBuf::from("out/20250821_093005/live_1-room,1_002")
+        );
+
+        let err = "{room_id}"
+            .parse::<PathTemplate>()
+            .unwrap()
+            .expand(&TemplateVars::new(vars().time));
+        assert!(err.unwrap_err().to_string().contains("{room_id}"));
+    }
+
+    #[test]
+    fn test_parse_errors_and_escaping() {
+        let err = "out/{day}".parse::<PathTemplate>().unwrap_err();
+        assert!(err.to_string().contains("Unknown placeholder {day}"));
+        assert!("out/}".parse::<PathTemplate>().is_err());
+        assert!("out/{date".parse::<PathTemplate>().is_err());
+
+        let template: PathTemplate = "out/{{date}}_{{{part}}}".parse().unwrap();
+        assert!(!template.uses(Placeholder::Date));
+        assert_eq!(
+            template.expand(&vars()).unwrap(),
+            PathBuf::from("out/{date}_{002}")
+        );
+
+        let escaped: PathTemplate = PathTemplate::escape("a{b}_{part}").parse().unwrap();
+        assert!(!escaped.is_templated());
+        assert_eq!(
+            escaped.expand(&vars()).unwrap(),
+            PathBuf::from("a{b}_{part}")
+        );
+
+        let plain: PathTemplate = "output".parse().unwrap();
+        assert!(!plain.is_templated());
+        assert_eq!(plain.expand(&vars()).unwrap(), PathBuf::from("output"));
+    }
+
+    #[test]
+    fn test_unique_dir() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let target = dir.join("out");
+        assert_eq!(unique_dir(&target), target);
+        std::fs::create_dir_all(&target).unwrap();
+        assert_eq!(unique_dir(&target), dir.join("out-1"));
+        std::fs::create_dir_all(dir.join("out-1")).unwrap();
+        assert_eq!(unique_dir(&target), dir.join("out-2"));
+    }
+}
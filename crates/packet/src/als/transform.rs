@@ -0,0 +1,650 @@
+//! Rule based rewriting of the frames of a converted replay
+//!
+//! A script is a YAML (or JSON) list of rules, each with a `match` and an `action`:
+//!
+//! ```yaml
+//! - match: { prefab: Character, message_type: update_object }
+//!   action: { replace_payload_bytes: { offset: 4, hex: "00000000" } }
+//! - match: { object_id: 12 }
+//!   action: drop_frame
+//! - match: { message_type: instantiate_object }
+//!   action: { set_owner: "player" }
+//! - match: { object_id: 3 }
+//!   action: { retarget: room_all }
+//! ```
+//!
+//! `match` needs at least one of `prefab` (substring of the prefab name of the frame's
+//! object, known from its `InstantiateObject`), `object_id` and `message_type`. Every
+//! frame is checked against all rules in order, a dropped frame is not checked further
+//! and packets left without frames are dropped. The rewritten packets are segmented
+//! again like in a retime.
+
+use anyhow::{Context, Result, anyhow};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use super::converter::{AlsConverter, AlsConverterConfig};
+use super::proto::PacketInfo;
+use super::proto::define::{
+    CurrentPlayer, DataFrame, RoomAll, data_frame, destroy_object, instantiate_object,
+    update_object,
+};
+use super::proto::extension::InstantiateObjectExt;
+use super::retime::{EVENTS_FILE, METADATA_FILE, read_metadata, read_replay_packets};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageType {
+    InstantiateObject,
+    UpdateObject,
+    DestroyObject,
+    Room,
+    AuthorizeResponse,
+    JoinRoomResponse,
+}
+
+impl MessageType {
+    pub fn of(message: &data_frame::Message) -> Self {
+        match message {
+            data_frame::Message::InstantiateObject(_) => Self::InstantiateObject,
+            data_frame::Message::UpdateObject(_) => Self::UpdateObject,
+            data_frame::Message::DestroyObject(_) => Self::DestroyObject,
+            data_frame::Message::Room(_) => Self::Room,
+            data_frame::Message::AuthorizeResponse(_) => Self::AuthorizeResponse,
+            data_frame::Message::JoinRoomResponse(_) => Self::JoinRoomResponse,
+        }
+    }
+
+    /// Frames of this type address an object
+    pub fn has_object(self) -> bool {
+        matches!(
+            self,
+            Self::InstantiateObject | Self::UpdateObject | Self::DestroyObject
+        )
+    }
+}
+
+impl fmt::Display for MessageType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::InstantiateObject => "instantiate_object",
+            Self::UpdateObject => "update_object",
+            Self::DestroyObject => "destroy_object",
+            Self::Room => "room",
+            Self::AuthorizeResponse => "authorize_response",
+            Self::JoinRoomResponse => "join_room_response",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleMatch {
+    pub prefab: Option<String>,
+    pub object_id: Option<i32>,
+    pub message_type: Option<MessageType>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Retarget {
+    RoomAll,
+    CurrentPlayer,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleAction {
+    DropFrame,
+    /// `owner_id` of `InstantiateObject` frames
+    SetOwner(String),
+    /// Overwrite bytes of the `UpdateObject` payload or the `InstantiateObject` init data
+    ReplacePayloadBytes {
+        offset: usize,
+        #[serde(deserialize_with = "deserialize_hex")]
+        hex: Vec<u8>,
+    },
+    /// Target of object frames, `room_all` uses the replay's room id
+    Retarget(Retarget),
+}
+
+impl RuleAction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::DropFrame => "drop_frame",
+            Self::SetOwner(_) => "set_owner",
+            Self::ReplacePayloadBytes { .. } => "replace_payload_bytes",
+            Self::Retarget(_) => "retarget",
+        }
+    }
+
+    /// Message types the action has an effect on
+    fn applies_to(&self, message_type: MessageType) -> bool {
+        match self {
+            Self::DropFrame => true,
+            Self::SetOwner(_) => message_type == MessageType::InstantiateObject,
+            Self::ReplacePayloadBytes { .. } => matches!(
+                message_type,
+                MessageType::InstantiateObject | MessageType::UpdateObject
+            ),
+            Self::Retarget(_) => message_type.has_object(),
+        }
+    }
+}
+
+fn deserialize_hex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    let bytes = hex::decode(value.replace(' ', "")).map_err(serde::de::Error::custom)?;
+    if bytes.is_empty() {
+        return Err(serde::de::Error::custom("hex must not be empty"));
+    }
+    Ok(bytes)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    #[serde(rename = "match")]
+    pub matcher: RuleMatch,
+    /// `drop_frame` or a single key map, not a YAML tag
+    #[serde(with = "serde_yaml::with::singleton_map")]
+    pub action: RuleAction,
+}
+
+/// Validated list of rules
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransformScript {
+    rules: Vec<Rule>,
+}
+
+impl TransformScript {
+    /// Parse a YAML or JSON script, syntax errors carry the line and column
+    pub fn parse(content: &str) -> Result<Self> {
+        let rules: Vec<Rule> = serde_yaml::from_str(content).map_err(|e| anyhow!("{}", e))?;
+        if rules.is_empty() {
+            return Err(anyhow!("Script has no rules"));
+        }
+        for (index, rule) in rules.iter().enumerate() {
+            Self::validate(rule).map_err(|e| anyhow!("Rule {}: {}", index + 1, e))?;
+        }
+        Ok(Self { rules })
+    }
+
+    fn validate(rule: &Rule) -> Result<()> {
+        let matcher = &rule.matcher;
+        if matcher.prefab.is_none() && matcher.object_id.is_none() && matcher.message_type.is_none()
+        {
+            return Err(anyhow!(
+                "match needs at least one of prefab, object_id, message_type"
+            ));
+        }
+        if let Some(message_type) = matcher.message_type {
+            if !message_type.has_object()
+                && (matcher.prefab.is_some() || matcher.object_id.is_some())
+            {
+                return Err(anyhow!(
+                    "{} frames have no object, prefab and object_id never match",
+                    message_type
+                ));
+            }
+            if !rule.action.applies_to(message_type) {
+                return Err(anyhow!(
+                    "{} has no effect on {} frames",
+                    rule.action.name(),
+                    message_type
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TransformStats {
+    /// Frames matched per rule, in script order
+    pub matches: Vec<usize>,
+    pub dropped_frames: usize,
+    pub dropped_packets: usize,
+}
+
+/// Applies a script to packets in timestamp order
+pub struct Transformer<'a> {
+    script: &'a TransformScript,
+    room_id: Vec<u8>,
+    /// object_id to prefab_name mapping
+    objects: HashMap<i32, String>,
+    stats: TransformStats,
+}
+
+impl<'a> Transformer<'a> {
+    pub fn new(script: &'a TransformScript, room_id: Vec<u8>) -> Self {
+        Self {
+            script,
+            room_id,
+            objects: HashMap::new(),
+            stats: TransformStats {
+                matches: vec![0; script.rules.len()],
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn stats(&self) -> &TransformStats {
+        &self.stats
+    }
+
+    /// Rewrite the frames of a packet, `false` when nothing is left of it
+    pub fn apply(&mut self, packet: &mut PacketInfo) -> Result<bool> {
+        if packet.data_pack.frames.is_empty() {
+            return Ok(true);
+        }
+        let mut frames = Vec::with_capacity(packet.data_pack.frames.len());
+        for mut frame in std::mem::take(&mut packet.data_pack.frames) {
+            if self.apply_frame(&mut frame)? {
+                frames.push(frame);
+            } else {
+                self.stats.dropped_frames += 1;
+            }
+        }
+        packet.data_pack.frames = frames;
+        if packet.data_pack.frames.is_empty() {
+            self.stats.dropped_packets += 1;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    fn apply_frame(&mut self, frame: &mut DataFrame) -> Result<bool> {
+        let Some(message) = &mut frame.message else {
+            return Ok(true);
+        };
+        if let data_frame::Message::InstantiateObject(obj) = message {
+            self.objects.insert(obj.object_id, obj.prefab_name_lossy());
+        }
+        for (index, rule) in self.script.rules.iter().enumerate() {
+            if !self.matches(&rule.matcher, message) {
+                continue;
+            }
+            self.stats.matches[index] += 1;
+            match &rule.action {
+                RuleAction::DropFrame => return Ok(false),
+                RuleAction::SetOwner(owner) => {
+                    if let data_frame::Message::InstantiateObject(obj) = message {
+                        obj.owner_id = owner.as_bytes().to_vec();
+                    }
+                }
+                RuleAction::ReplacePayloadBytes { offset, hex } => {
+                    let (object_id, payload) = match message {
+                        data_frame::Message::InstantiateObject(obj) => {
+                            (obj.object_id, &mut obj.init_data)
+                        }
+                        data_frame::Message::UpdateObject(obj) => (obj.object_id, &mut obj.payload),
+                        _ => continue,
+                    };
+                    let end = offset + hex.len();
+                    if end > payload.len() {
+                        return Err(anyhow!(
+                            "Rule {}: payload of object {} has {} bytes, cannot replace {} bytes at offset {}",
+                            index + 1,
+                            object_id,
+                            payload.len(),
+                            hex.len(),
+                            offset
+                        ));
+                    }
+                    payload[*offset..end].copy_from_slice(hex);
+                }
+                RuleAction::Retarget(target) => self.retarget(message, *target),
+            }
+        }
+        Ok(true)
+    }
+
+    fn matches(&self, matcher: &RuleMatch, message: &data_frame::Message) -> bool {
+        if matcher
+            .message_type
+            .is_some_and(|message_type| message_type != MessageType::of(message))
+        {
+            return false;
+        }
+        let object_id = object_id(message);
+        if matcher.object_id.is_some() && matcher.object_id != object_id {
+            return false;
+        }
+        if let Some(prefab) = &matcher.prefab {
+            return object_id
+                .and_then(|object_id| self.objects.get(&object_id))
+                .is_some_and(|prefab_name| prefab_name.contains(prefab.as_str()));
+        }
+        true
+    }
+
+    fn retarget(&self, message: &mut data_frame::Message, target: Retarget) {
+        let room_all = || RoomAll {
+            room_id: self.room_id.clone(),
+        };
+        match message {
+            data_frame::Message::InstantiateObject(obj) => {
+                obj.target = Some(match target {
+                    Retarget::RoomAll => instantiate_object::Target::RoomAll(room_all()),
+                    Retarget::CurrentPlayer => {
+                        instantiate_object::Target::CurrentPlayer(CurrentPlayer {})
+                    }
+                });
+            }
+            data_frame::Message::UpdateObject(obj) => {
+                obj.target = Some(match target {
+                    Retarget::RoomAll => update_object::Target::RoomAll(room_all()),
+                    Retarget::CurrentPlayer => {
+                        update_object::Target::CurrentPlayer(CurrentPlayer {})
+                    }
+                });
+            }
+            data_frame::Message::DestroyObject(obj) => {
+                obj.target = Some(match target {
+                    Retarget::RoomAll => destroy_object::Target::RoomAll(room_all()),
+                    Retarget::CurrentPlayer => {
+                        destroy_object::Target::CurrentPlayer(CurrentPlayer {})
+                    }
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+fn object_id(message: &data_frame::Message) -> Option<i32> {
+    match message {
+        data_frame::Message::InstantiateObject(obj) => Some(obj.object_id),
+        data_frame::Message::UpdateObject(obj) => Some(obj.object_id),
+        data_frame::Message::DestroyObject(obj) => Some(obj.object_id),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TransformConfig {
+    pub input_dir: PathBuf,
+    pub script_path: PathBuf,
+    pub output_dir: PathBuf,
+    /// Count matches only, nothing is written
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct TransformSummary {
+    pub rules: Vec<Rule>,
+    pub stats: TransformStats,
+    /// Data packets left after the transform
+    pub packets: usize,
+}
+
+pub fn run_transform(config: &TransformConfig) -> Result<TransformSummary> {
+    let content = std::fs::read_to_string(&config.script_path).with_context(|| {
+        format!(
+            "Failed to read transform script: {}",
+            config.script_path.display()
+        )
+    })?;
+    let script = TransformScript::parse(&content)
+        .with_context(|| format!("Invalid transform script: {}", config.script_path.display()))?;
+    let replay = read_replay_packets(&config.input_dir)?;
+
+    let mut transformer = Transformer::new(&script, replay.room.id.clone());
+    let mut packets = Vec::with_capacity(replay.packets.len());
+    for mut packet in replay.packets {
+        if transformer.apply(&mut packet)? {
+            packets.push(packet);
+        }
+    }
+    let summary = TransformSummary {
+        rules: script.rules.clone(),
+        stats: transformer.stats().clone(),
+        packets: packets.len(),
+    };
+    if config.dry_run {
+        return Ok(summary);
+    }
+
+    let metadata = read_metadata(&config.input_dir)?;
+    let converter_config = AlsConverterConfig {
+        metadata_path: metadata
+            .get("path")
+            .and_then(|v| v.as_str())
+            .map(String::from),
+        write_events_vtt: config.input_dir.join(EVENTS_FILE).is_file(),
+        embed_full_state_in_segments: replay.embeds_full_state,
+        ..Default::default()
+    };
+    AlsConverter::write_data_packets(&converter_config, replay.room, packets, &config.output_dir)?;
+
+    let mut metadata = read_metadata(&config.output_dir)?;
+    metadata["transform_script"] = config.script_path.display().to_string().into();
+    let metadata_path = config.output_dir.join(METADATA_FILE);
+    std::fs::write(&metadata_path, format!("{}\n", metadata))
+        .with_context(|| format!("Failed to write metadata: {}", metadata_path.display()))?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::als::proto::define::{
+        DataPack, DestroyObject, InstantiateObject, Room, UpdateObject, data_pack,
+    };
+    use chrono::{DateTime, TimeDelta, TimeZone, Utc};
+
+    fn data_packet(timestamp: DateTime<Utc>, messages: Vec<data_frame::Message>) -> PacketInfo {
+        PacketInfo {
+            timestamp,
+            data_pack: DataPack {
+                control: Some(data_pack::Control::Data(true)),
+                frames: messages
+                    .into_iter()
+                    .map(|message| DataFrame {
+                        message: Some(message),
+                    })
+                    .collect(),
+            },
+            raw_data: vec![],
+            source: None,
+        }
+    }
+
+    #[test]
+    fn test_script_validation() {
+        let script = TransformScript::parse(
+            r#"[{"match": {"object_id": 1}, "action": "drop_frame"},
+                {"match": {"prefab": "Stage"}, "action": {"retarget": "current_player"}}]"#,
+        )
+        .unwrap();
+        assert_eq!(script.rules().len(), 2);
+        assert_eq!(
+            script.rules()[1].action,
+            RuleAction::Retarget(Retarget::CurrentPlayer)
+        );
+
+        let error = |content: &str| format!("{:#}", TransformScript::parse(content).unwrap_err());
+        assert!(error("- match: { object_id: 1 }\n  action: explode\n").contains("line 2"));
+        assert!(
+            error("- match: { object_id: 1 }\n  action: { replace_payload_bytes: { offset: 0, hex: zz } }\n")
+                .contains("line 2")
+        );
+        assert!(error("- match: { object: 1 }\n  action: drop_frame\n").contains("line 1"));
+        assert!(error("- match: {}\n  action: drop_frame\n").starts_with("Rule 1:"));
+        assert!(
+            error("- match: { object_id: 1 }\n  action: drop_frame\n- match: { message_type: update_object }\n  action: { set_owner: x }\n")
+                .starts_with("Rule 2: set_owner has no effect on update_object frames")
+        );
+        assert!(
+            error("- match: { message_type: room, object_id: 1 }\n  action: drop_frame\n")
+                .starts_with("Rule 1:")
+        );
+        assert!(error("[]").contains("no rules"));
+    }
+
+    #[test]
+    fn test_run_transform() {
+        let tmp = tempfile::tempdir().unwrap();
+        let base = tmp.path();
+        let input_dir = base.join("input");
+        let output_dir = base.join("output");
+        let zero = Utc.timestamp_opt(1_700_000_000, 0).unwrap();
+
+        let instantiate = |object_id, prefab_name: &[u8]| {
+            data_frame::Message::InstantiateObject(InstantiateObject {
+                object_id,
+                owner_id: b"owner".to_vec(),
+                prefab_name: prefab_name.to_vec(),
+                init_data: vec![0xaa; 4],
+                ..Default::default()
+            })
+        };
+        let update = |object_id| {
+            data_frame::Message::UpdateObject(UpdateObject {
+                object_id,
+                method: 1,
+                payload: vec![1, 2, 3, 4],
+                ..Default::default()
+            })
+        };
+        let packets = vec![
+            data_packet(
+                zero,
+                vec![
+                    instantiate(1, b"Character"),
+                    instantiate(2, b"StageProp"),
+                    instantiate(3, b"Camera"),
+                ],
+            ),
+            data_packet(zero + TimeDelta::seconds(1), vec![update(1), update(2)]),
+            data_packet(zero + TimeDelta::seconds(2), vec![update(3)]),
+            data_packet(
+                zero + TimeDelta::seconds(3),
+                vec![data_frame::Message::DestroyObject(DestroyObject {
+                    object_id: 2,
+                    ..Default::default()
+                })],
+            ),
+        ];
+        let room = Room {
+            id: b"room".to_vec(),
+            started_at: zero.timestamp_micros(),
+            ended_at: 0,
+        };
+        AlsConverter::write_data_packets(&AlsConverterConfig::default(), room, packets, &input_dir)
+            .unwrap();
+
+        let script_path = base.join("rules.yaml");
+        std::fs::write(
+            &script_path,
+            r#"
+- match: { object_id: 3 }
+  action: drop_frame
+- match: { message_type: instantiate_object, prefab: Character }
+  action: { set_owner: "player" }
+- match: { prefab: Character, message_type: update_object }
+  action: { replace_payload_bytes: { offset: 1, hex: "ff ff" } }
+- match: { prefab: Stage }
+  action: { retarget: room_all }
+"#,
+        )
+        .unwrap();
+        let config = TransformConfig {
+            input_dir: input_dir.clone(),
+            script_path: script_path.clone(),
+            output_dir: output_dir.clone(),
+            dry_run: true,
+        };
+        let summary = run_transform(&config).unwrap();
+        assert_eq!(summary.stats.matches, vec![2, 1, 1, 3]);
+        assert_eq!(summary.stats.dropped_frames, 2);
+        assert_eq!(summary.stats.dropped_packets, 1);
+        assert_eq!(summary.packets, 3);
+        assert!(!output_dir.exists());
+
+        run_transform(&TransformConfig {
+            dry_run: false,
+            ..config.clone()
+        })
+        .unwrap();
+        let frames = read_replay_packets(&output_dir)
+            .unwrap()
+            .packets
+            .into_iter()
+            .flat_map(|p| p.data_pack.frames)
+            .filter_map(|f| f.message)
+            .collect::<Vec<_>>();
+        let room_all = || RoomAll {
+            room_id: b"room".to_vec(),
+        };
+        assert_eq!(frames.len(), 5);
+        match &frames[0] {
+            data_frame::Message::InstantiateObject(obj) => {
+                assert_eq!(obj.object_id, 1);
+                assert_eq!(obj.owner_id, b"player");
+                assert_eq!(obj.init_data, vec![0xaa; 4]);
+            }
+            other => panic!("unexpected frame {:?}", other),
+        }
+        match &frames[1] {
+            data_frame::Message::InstantiateObject(obj) => {
+                assert_eq!(obj.owner_id, b"owner");
+                assert_eq!(
+                    obj.target,
+                    Some(instantiate_object::Target::RoomAll(room_all()))
+                );
+            }
+            other => panic!("unexpected frame {:?}", other),
+        }
+        match &frames[2] {
+            data_frame::Message::UpdateObject(obj) => {
+                assert_eq!(obj.object_id, 1);
+                assert_eq!(obj.payload, vec![1, 0xff, 0xff, 4]);
+                assert_eq!(obj.target, None);
+            }
+            other => panic!("unexpected frame {:?}", other),
+        }
+        match &frames[3] {
+            data_frame::Message::UpdateObject(obj) => {
+                assert_eq!(obj.payload, vec![1, 2, 3, 4]);
+                assert_eq!(obj.target, Some(update_object::Target::RoomAll(room_all())));
+            }
+            other => panic!("unexpected frame {:?}", other),
+        }
+        match &frames[4] {
+            data_frame::Message::DestroyObject(obj) => {
+                assert_eq!(
+                    obj.target,
+                    Some(destroy_object::Target::RoomAll(room_all()))
+                );
+            }
+            other => panic!("unexpected frame {:?}", other),
+        }
+        let metadata = read_metadata(&output_dir).unwrap();
+        assert!(metadata["transform_script"].is_string());
+
+        // replacements past the end of a payload fail before anything is written
+        std::fs::write(
+            &script_path,
+            "- match: { object_id: 1 }\n  action: { replace_payload_bytes: { offset: 3, hex: \"0000\" } }\n",
+        )
+        .unwrap();
+        let rejected_dir = base.join("rejected");
+        let error = run_transform(&TransformConfig {
+            output_dir: rejected_dir.clone(),
+            dry_run: false,
+            ..config
+        })
+        .unwrap_err();
+        assert!(error.to_string().starts_with("Rule 1: payload of object 1"));
+        assert!(!rejected_dir.exists());
+    }
+}
@@ -0,0 +1,351 @@
+//! Verification of converted replay output
+//!
+//! The base check walks `index.m3u8` and makes sure every listed segment exists and
+//! parses as standard packets. Strict state mode additionally replays each segment
+//! the way a client joining at that segment would: starting from no live objects,
+//! applying instantiates, updates and destroys in order, and flagging any update or
+//! destroy that references an object which is not alive at that point.
+
+use anyhow::{Context, Result, anyhow};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use super::proto::PacketInfo;
+use super::proto::define::data_frame;
+use super::proto::extension::InstantiateObjectExt;
+use super::proto::reader::{PacketReaderTrait, StandardPacketReader};
+
+/// Playlist written next to the converted segments
+const PLAYLIST_FILE: &str = "index.m3u8";
+
+#[derive(Debug, Clone)]
+pub struct VerifyConfig {
+    pub input_dir: PathBuf,
+    pub strict_state: bool,
+    /// Number of state violations kept in the report, the rest are only counted
+    pub max_violations: usize,
+}
+
+impl Default for VerifyConfig {
+    fn default() -> Self {
+        Self {
+            input_dir: PathBuf::from("output"),
+            strict_state: false,
+            max_violations: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateViolationKind {
+    UpdateBeforeInstantiate,
+    DestroyBeforeInstantiate,
+}
+
+impl std::fmt::Display for StateViolationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StateViolationKind::UpdateBeforeInstantiate => write!(f, "UpdateObject"),
+            StateViolationKind::DestroyBeforeInstantiate => write!(f, "DestroyObject"),
+        }
+    }
+}
+
+/// An update or destroy referencing an object that is not alive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateViolation {
+    pub segment: String,
+    /// Zero based packet index inside the segment
+    pub packet_index: usize,
+    /// Zero based frame index inside the packet
+    pub frame_index: usize,
+    pub object_id: i32,
+    pub kind: StateViolationKind,
+    /// Prefab of the object, when it is instantiated anywhere in the replay
+    pub prefab_name: Option<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub segments: usize,
+    pub packets: usize,
+    pub missing_segments: Vec<String>,
+    pub unreadable_segments: Vec<(String, String)>,
+    /// First `max_violations` state violations in replay order
+    pub violations: Vec<StateViolation>,
+    pub total_violations: usize,
+}
+
+impl VerifyReport {
+    pub fn is_ok(&self) -> bool {
+        self.missing_segments.is_empty()
+            && self.unreadable_segments.is_empty()
+            && self.total_violations == 0
+    }
+}
+
+pub fn run_verify(config: &VerifyConfig) -> Result<VerifyReport> {
+    let segments = read_playlist_segments(&config.input_dir)?;
+    let mut report = VerifyReport {
+        segments: segments.len(),
+        ..Default::default()
+    };
+    let mut prefab_names: HashMap<i32, String> = HashMap::new();
+
+    for segment in &segments {
+        let path = config.input_dir.join(segment);
+        if !path.is_file() {
+            report.missing_segments.push(segment.clone());
+            continue;
+        }
+        let packets = match read_segment(&path) {
+            Ok(packets) => packets,
+            Err(e) => {
+                report
+                    .unreadable_segments
+                    .push((segment.clone(), format!("{:#}", e)));
+                continue;
+            }
+        };
+        report.packets += packets.len();
+
+        if config.strict_state {
+            collect_prefab_names(&packets, &mut prefab_names);
+            let violations = simulate_segment_state(segment, &packets);
+            report.total_violations += violations.len();
+            let room = config
+                .max_violations
+                .saturating_sub(report.violations.len());
+            report.violations.extend(violations.into_iter().take(room));
+        }
+    }
+
+    // objects may be instantiated in a later segment than the violation
+    for violation in &mut report.violations {
+        violation.prefab_name = prefab_names.get(&violation.object_id).cloned();
+    }
+
+    Ok(report)
+}
+
+/// Replay one segment from an empty object set and collect every update or destroy
+/// that references an object which is not alive at that point
+pub fn simulate_segment_state(segment: &str, packets: &[PacketInfo]) -> Vec<StateViolation> {
+    let mut alive: HashSet<i32> = HashSet::new();
+    let mut violations = Vec::new();
+
+    for (packet_index, packet) in packets.iter().enumerate() {
+        for (frame_index, frame) in packet.data_pack.frames.iter().enumerate() {
+            let (object_id, kind) = match &frame.message {
+                Some(data_frame::Message::InstantiateObject(obj)) => {
+                    alive.insert(obj.object_id);
+                    continue;
+                }
+                Some(data_frame::Message::UpdateObject(obj)) => {
+                    if alive.contains(&obj.object_id) {
+                        continue;
+                    }
+                    (obj.object_id, StateViolationKind::UpdateBeforeInstantiate)
+                }
+                Some(data_frame::Message::DestroyObject(obj)) => {
+                    if alive.remove(&obj.object_id) {
+                        continue;
+                    }
+                    (obj.object_id, StateViolationKind::DestroyBeforeInstantiate)
+                }
+                _ => continue,
+            };
+            violations.push(StateViolation {
+                segment: segment.to_string(),
+                packet_index,
+                frame_index,
+                object_id,
+                kind,
+                prefab_name: None,
+            });
+        }
+    }
+
+    violations
+}
+
+fn collect_prefab_names(packets: &[PacketInfo], prefab_names: &mut HashMap<i32, String>) {
+    for frame in packets.iter().flat_map(|p| &p.data_pack.frames) {
+        if let Some(data_frame::Message::InstantiateObject(obj)) = &frame.message {
+            prefab_names
+                .entry(obj.object_id)
+                .or_insert_with(|| obj.prefab_name_lossy());
+        }
+    }
+}
+
+fn read_playlist_segments(input_dir: &Path) -> Result<Vec<String>> {
+    let playlist_path = input_dir.join(PLAYLIST_FILE);
+    let content = std::fs::read_to_string(&playlist_path)
+        .with_context(|| format!("failed to read playlist: {}", playlist_path.display()))?;
+    let segments = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    if segments.is_empty() {
+        return Err(anyhow!("no segments listed in {}", playlist_path.display()));
+    }
+    Ok(segments)
+}
+
+fn read_segment(path: &Path) -> Result<Vec<PacketInfo>> {
+    let file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let mut reader = StandardPacketReader::new(file);
+    let mut packets = Vec::new();
+    while let Some(packet) = reader.read_packet()? {
+        packets.push(packet);
+    }
+    Ok(packets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::als::proto::define::{
+        DataFrame, DataPack, DestroyObject, InstantiateObject, UpdateObject, data_pack,
+    };
+    use chrono::{TimeZone, Utc};
+    use std::io::Write;
+
+    fn packet(seconds: i64, messages: Vec<data_frame::Message>) -> PacketInfo {
+        PacketInfo {
+            timestamp: Utc.timestamp_opt(1_700_000_000 + seconds, 0).unwrap(),
+            data_pack: DataPack {
+                control: Some(data_pack::Control::Data(true)),
+                frames: messages
+                    .into_iter()
+                    .map(|message| DataFrame {
+                        message: Some(message),
+                    })
+                    .collect(),
+            },
+            raw_data: vec![],
+            source: None,
+        }
+    }
+
+    fn instantiate(object_id: i32, prefab: &str) -> data_frame::Message {
+        data_frame::Message::InstantiateObject(InstantiateObject {
+            object_id,
+            prefab_name: prefab.as_bytes().to_vec(),
+            ..Default::default()
+        })
+    }
+
+    fn update(object_id: i32) -> data_frame::Message {
+        data_frame::Message::UpdateObject(UpdateObject {
+            object_id,
+            ..Default::default()
+        })
+    }
+
+    fn destroy(object_id: i32) -> data_frame::Message {
+        data_frame::Message::DestroyObject(DestroyObject {
+            object_id,
+            ..Default::default()
+        })
+    }
+
+    fn write_output(dir: &Path, segments: &[Vec<PacketInfo>]) {
+        std::fs::create_dir_all(dir).unwrap();
+        let mut playlist = String::from("#EXTM3U\n");
+        for (i, packets) in segments.iter().enumerate() {
+            let name = format!("segment_{:05}.ts", i);
+            let mut file = File::create(dir.join(&name)).unwrap();
+            for packet in packets {
+                file.write_all(&packet.to_vec()).unwrap();
+            }
+            playlist.push_str(&format!("#EXTINF:10.0,\n{}\n", name));
+        }
+        playlist.push_str("#EXT-X-ENDLIST\n");
+        std::fs::write(dir.join(PLAYLIST_FILE), playlist).unwrap();
+    }
+
+    #[test]
+    fn test_simulate_segment_state_reordered_frame() {
+        let packets = vec![
+            packet(0, vec![instantiate(1, "Character")]),
+            // update of object 2 moved ahead of its instantiate
+            packet(1, vec![update(1), update(2), instantiate(2, "Stage")]),
+            packet(2, vec![update(2), destroy(1), update(1)]),
+        ];
+
+        let violations = simulate_segment_state("segment_00000.ts", &packets);
+        assert_eq!(violations.len(), 2);
+        assert_eq!(violations[0].packet_index, 1);
+        assert_eq!(violations[0].frame_index, 1);
+        assert_eq!(violations[0].object_id, 2);
+        assert_eq!(
+            violations[0].kind,
+            StateViolationKind::UpdateBeforeInstantiate
+        );
+        assert_eq!(violations[1].packet_index, 2);
+        assert_eq!(violations[1].frame_index, 2);
+        assert_eq!(violations[1].object_id, 1);
+    }
+
+    #[test]
+    fn test_run_verify_strict_state() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        write_output(
+            dir,
+            &[
+                vec![
+                    packet(0, vec![instantiate(3, "Character")]),
+                    packet(1, vec![update(3)]),
+                ],
+                // segment without the full state, as a late joining client sees it
+                vec![packet(10, vec![update(3)]), packet(11, vec![destroy(4)])],
+            ],
+        );
+
+        let config = VerifyConfig {
+            input_dir: dir.to_path_buf(),
+            strict_state: false,
+            max_violations: 1,
+        };
+        let report = run_verify(&config).unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.segments, 2);
+        assert_eq!(report.packets, 4);
+
+        let report = run_verify(&VerifyConfig {
+            strict_state: true,
+            ..config
+        })
+        .unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(report.total_violations, 2);
+        assert_eq!(
+            report.violations,
+            vec![StateViolation {
+                segment: "segment_00001.ts".to_string(),
+                packet_index: 0,
+                frame_index: 0,
+                object_id: 3,
+                kind: StateViolationKind::UpdateBeforeInstantiate,
+                prefab_name: Some("Character".to_string()),
+            }]
+        );
+
+        std::fs::remove_file(dir.join("segment_00000.ts")).unwrap();
+        let report = run_verify(&VerifyConfig {
+            input_dir: dir.to_path_buf(),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            report.missing_segments,
+            vec!["segment_00000.ts".to_string()]
+        );
+    }
+}